@@ -0,0 +1,99 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use test::Bencher;
+
+use tikv::coprocessor::codec::chunk::{ArcChunk, Chunk};
+use tikv::coprocessor::codec::mysql::types;
+
+const ROWS: usize = 1024;
+
+fn new_chunk() -> Chunk {
+    let tps = &[types::LONG_LONG as i32, types::VARCHAR as i32];
+    let mut chunk = Chunk::new_chunk(tps);
+    for i in 0..ROWS {
+        if i % 7 == 0 {
+            chunk.append_null(0);
+        } else {
+            chunk.append_i64(0, i as i64).unwrap();
+        }
+        chunk.append_bytes(1, format!("{}", i).as_bytes()).unwrap();
+    }
+    chunk
+}
+
+#[bench]
+fn bench_chunk_append(b: &mut Bencher) {
+    let src = new_chunk();
+    let mut dst = new_chunk();
+    b.iter(|| {
+        dst.reset();
+        dst.append(&src, 0, ROWS).unwrap();
+    });
+}
+
+#[bench]
+fn bench_chunk_append_row(b: &mut Bencher) {
+    let src = ArcChunk::new(new_chunk());
+    let mut dst = new_chunk();
+    b.iter(|| {
+        dst.reset();
+        for row in &src {
+            dst.append_row(&row).unwrap();
+        }
+    });
+}
+
+const APPEND_ROWS: usize = 10_000;
+
+#[bench]
+fn bench_chunk_append_i64(b: &mut Bencher) {
+    let vs: Vec<i64> = (0..APPEND_ROWS as i64).collect();
+    let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+    b.iter(|| {
+        chunk.reset();
+        for &v in &vs {
+            chunk.append_i64(0, v).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn bench_chunk_append_i64_slice(b: &mut Bencher) {
+    let vs: Vec<i64> = (0..APPEND_ROWS as i64).collect();
+    let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+    b.iter(|| {
+        chunk.reset();
+        chunk.append_i64_slice(0, &vs).unwrap();
+    });
+}
+
+#[bench]
+fn bench_chunk_append_nulls(b: &mut Bencher) {
+    let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+    b.iter(|| {
+        chunk.reset();
+        for _ in 0..APPEND_ROWS {
+            chunk.append_null(0);
+        }
+    });
+}
+
+#[bench]
+fn bench_chunk_append_nulls_at_once(b: &mut Bencher) {
+    let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+    b.iter(|| {
+        chunk.reset();
+        chunk.append_nulls(0, APPEND_ROWS);
+    });
+}
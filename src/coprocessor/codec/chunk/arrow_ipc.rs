@@ -0,0 +1,621 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Chunks are written as Arrow IPC streams, so the intermediate results can be
+// read by the Arrow tools like pyarrow for debugging. A stream is a schema
+// message, a record batch message holding all the rows, and the end of stream
+// marker. Every message is the continuation marker, the length of the
+// metadata, the metadata as a flatbuffer of `Message.fbs`, and the body:
+//
+//     0xffffffff | metadata length: i32 | Message | padding | body
+//
+// There is no flatbuffers crate to depend on, so the few tables needed are
+// built by `FbBuilder`.
+
+use std::io::Write;
+use std::{i32, str};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use chrono::FixedOffset;
+use tipb::expression::FieldType;
+
+use coprocessor::codec::mysql::{charset, types};
+use coprocessor::codec::Result;
+use super::{Chunk, Column};
+
+const CONTINUATION: u32 = 0xffff_ffff;
+const METADATA_VERSION_V5: i16 = 4;
+
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+const TYPE_INT: u8 = 2;
+const TYPE_FLOATING_POINT: u8 = 3;
+const TYPE_BINARY: u8 = 4;
+const TYPE_UTF8: u8 = 5;
+
+const PRECISION_DOUBLE: i16 = 2;
+
+/// Write the chunk as an Arrow IPC stream of one record batch into `w`, `fts`
+/// are the types of its columns. The integers are written as Int64 or UInt64
+/// by their signedness, the floats as Float64, the var-length values as Utf8
+/// if they are valid UTF-8 strings of a non-binary charset, or Binary
+/// otherwise. The other values, e.g. decimals and times, are rendered as
+/// Utf8 strings, and the times are in UTC.
+///
+/// The chunk must be reconstructed if there is a selection vector.
+pub fn write_chunk_ipc<W: Write>(chunk: &Chunk, fts: &[FieldType], mut w: W) -> Result<()> {
+    chunk.check_no_sel()?;
+    if fts.len() != chunk.num_cols() {
+        return Err(box_err!(
+            "expect {} field types, but got {}",
+            chunk.num_cols(),
+            fts.len()
+        ));
+    }
+    let mut arrays = Vec::with_capacity(fts.len());
+    for (col_idx, (col, fp)) in chunk.columns.iter().zip(fts).enumerate() {
+        arrays.push(Array::new(col, col_idx, fp)?);
+    }
+    write_message(&mut w, &schema_message(&arrays), &[])?;
+    let (batch, body) = record_batch_message(chunk.num_rows(), &arrays);
+    write_message(&mut w, &batch, &body)?;
+    // the end of the stream.
+    w.write_u32::<LittleEndian>(CONTINUATION)?;
+    w.write_u32::<LittleEndian>(0)?;
+    Ok(())
+}
+
+fn write_message<W: Write>(w: &mut W, metadata: &[u8], body: &[u8]) -> Result<()> {
+    // the metadata is padded, so the body starts at a multiple of 8.
+    let padded = align(metadata.len(), 8);
+    w.write_u32::<LittleEndian>(CONTINUATION)?;
+    w.write_i32::<LittleEndian>(padded as i32)?;
+    w.write_all(metadata)?;
+    w.write_all(&[0; 8][..padded - metadata.len()])?;
+    w.write_all(body)?;
+    Ok(())
+}
+
+#[inline]
+fn align(len: usize, n: usize) -> usize {
+    (len + n - 1) / n * n
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ArrowType {
+    Int64,
+    UInt64,
+    Float64,
+    Binary,
+    Utf8,
+}
+
+// The buffers of a column in Arrow's layout.
+struct Array {
+    tp: ArrowType,
+    len: usize,
+    null_cnt: usize,
+    // it's empty if there are no nulls.
+    validity: Vec<u8>,
+    // the i32 offsets of the var-length values.
+    offsets: Option<Vec<u8>>,
+    data: Vec<u8>,
+}
+
+impl Array {
+    fn new(col: &Column, col_idx: usize, fp: &FieldType) -> Result<Array> {
+        col.check_kind(col_idx, fp)?;
+        let len = col.len();
+        // the null bits are the validity bits of Arrow, but the bits beyond
+        // the length must be unset.
+        let validity = if col.null_cnt > 0 {
+            let mut bitmap = col.null_bitmap[..(len + 7) >> 3].to_vec();
+            if len & 7 != 0 {
+                *bitmap.last_mut().unwrap() &= (1 << (len & 7)) - 1;
+            }
+            bitmap
+        } else {
+            vec![]
+        };
+        let mut array = Array {
+            tp: ArrowType::Int64,
+            len: len,
+            null_cnt: col.null_cnt,
+            validity: validity,
+            offsets: None,
+            data: vec![],
+        };
+        match fp.get_tp() as u8 {
+            types::TINY |
+            types::SHORT |
+            types::INT24 |
+            types::LONG |
+            types::LONG_LONG |
+            types::YEAR => {
+                if col.read_unsigned(col_idx, fp) {
+                    array.tp = ArrowType::UInt64;
+                }
+                array.data = col.data[..len * 8].to_vec();
+            }
+            types::DOUBLE => {
+                array.tp = ArrowType::Float64;
+                array.data = col.data[..len * 8].to_vec();
+            }
+            types::FLOAT => {
+                array.tp = ArrowType::Float64;
+                array.data = vec![0; len * 8];
+                for (idx, buf) in array.data.chunks_mut(8).enumerate() {
+                    LittleEndian::write_f64(buf, f64::from(col.get_f32(idx)));
+                }
+            }
+            types::VARCHAR |
+            types::VAR_STRING |
+            types::STRING |
+            types::BLOB |
+            types::TINY_BLOB |
+            types::MEDIUM_BLOB |
+            types::LONG_BLOB => {
+                let is_text = fp.get_charset() != charset::CHARSET_BIN &&
+                    (0..len).all(|idx| str::from_utf8(col.get_bytes(idx)).is_ok());
+                array.tp = if is_text {
+                    ArrowType::Utf8
+                } else {
+                    ArrowType::Binary
+                };
+                let values = (0..len).map(|idx| col.get_bytes(idx));
+                array.set_var_len_values(values)?;
+            }
+            _ => {
+                array.tp = ArrowType::Utf8;
+                let tz = FixedOffset::east(0);
+                let mut values = Vec::with_capacity(len);
+                for idx in 0..len {
+                    let s = if col.is_null(idx) {
+                        String::new()
+                    } else {
+                        col.get_datum(idx, col_idx, fp, &tz)?.to_string()?
+                    };
+                    values.push(s);
+                }
+                array.set_var_len_values(values.iter().map(|s| s.as_bytes()))?;
+            }
+        }
+        Ok(array)
+    }
+
+    fn set_var_len_values<'a, I: Iterator<Item = &'a [u8]>>(&mut self, values: I) -> Result<()> {
+        let mut offsets = Vec::with_capacity((self.len + 1) * 4);
+        offsets.write_i32::<LittleEndian>(0)?;
+        for v in values {
+            if self.data.len() + v.len() > i32::MAX as usize {
+                return Err(box_err!(
+                    "the values are more than {} bytes, which can't be offset by i32",
+                    i32::MAX
+                ));
+            }
+            self.data.extend_from_slice(v);
+            offsets.write_i32::<LittleEndian>(self.data.len() as i32)?;
+        }
+        self.offsets = Some(offsets);
+        Ok(())
+    }
+
+    fn type_table(&self) -> (u8, Table) {
+        match self.tp {
+            ArrowType::Int64 | ArrowType::UInt64 => {
+                let signed = self.tp == ArrowType::Int64;
+                let int = Table(vec![Some(Value::I32(64)), Some(Value::Bool(signed))]);
+                (TYPE_INT, int)
+            }
+            ArrowType::Float64 => {
+                let float = Table(vec![Some(Value::I16(PRECISION_DOUBLE))]);
+                (TYPE_FLOATING_POINT, float)
+            }
+            ArrowType::Binary => (TYPE_BINARY, Table(vec![])),
+            ArrowType::Utf8 => (TYPE_UTF8, Table(vec![])),
+        }
+    }
+
+    fn buffers(&self) -> Vec<&[u8]> {
+        let mut buffers = vec![self.validity.as_slice()];
+        if let Some(ref offsets) = self.offsets {
+            buffers.push(offsets);
+        }
+        buffers.push(&self.data);
+        buffers
+    }
+}
+
+// Build a `Message` with the header of type `header_type`.
+fn message(header_type: u8, header: Table, body_len: usize) -> Vec<u8> {
+    let message = Table(vec![
+        Some(Value::I16(METADATA_VERSION_V5)),
+        Some(Value::U8(header_type)),
+        Some(Value::Table(header)),
+        Some(Value::I64(body_len as i64)),
+    ]);
+    FbBuilder::finish(&message)
+}
+
+fn schema_message(arrays: &[Array]) -> Vec<u8> {
+    let fields = arrays
+        .iter()
+        .enumerate()
+        .map(|(i, array)| {
+            let (type_type, type_table) = array.type_table();
+            Table(vec![
+                Some(Value::Str(format!("c{}", i))),
+                Some(Value::Bool(true)),
+                Some(Value::U8(type_type)),
+                Some(Value::Table(type_table)),
+                None,
+                // the readers require the children even if there are none.
+                Some(Value::Tables(vec![])),
+            ])
+        })
+        .collect();
+    // the endianness is little.
+    let schema = Table(vec![Some(Value::I16(0)), Some(Value::Tables(fields))]);
+    message(HEADER_SCHEMA, schema, 0)
+}
+
+// Build the record batch message and its body, every buffer in the body is
+// padded to a multiple of 8 bytes.
+fn record_batch_message(rows: usize, arrays: &[Array]) -> (Vec<u8>, Vec<u8>) {
+    let mut nodes = vec![];
+    let mut buffers = vec![];
+    let mut body = vec![];
+    for array in arrays {
+        nodes.write_i64::<LittleEndian>(array.len as i64).unwrap();
+        nodes.write_i64::<LittleEndian>(array.null_cnt as i64).unwrap();
+        for buf in array.buffers() {
+            buffers.write_i64::<LittleEndian>(body.len() as i64).unwrap();
+            buffers.write_i64::<LittleEndian>(buf.len() as i64).unwrap();
+            body.extend_from_slice(buf);
+            let len = align(body.len(), 8);
+            body.resize(len, 0);
+        }
+    }
+    let batch = Table(vec![
+        Some(Value::I64(rows as i64)),
+        Some(Value::Structs(nodes)),
+        Some(Value::Structs(buffers)),
+    ]);
+    (message(HEADER_RECORD_BATCH, batch, body.len()), body)
+}
+
+// A flatbuffer table, the fields are indexed by their ids in the schema.
+struct Table(Vec<Option<Value>>);
+
+enum Value {
+    U8(u8),
+    Bool(bool),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Str(String),
+    Table(Table),
+    Tables(Vec<Table>),
+    // a vector of the structs of 16 bytes aligned to 8, which are encoded.
+    Structs(Vec<u8>),
+}
+
+impl Value {
+    // The size of the field in the table, the values which aren't scalars
+    // are stored out of the table and referred by a u32 offset.
+    fn inline_size(&self) -> usize {
+        match *self {
+            Value::U8(_) | Value::Bool(_) => 1,
+            Value::I16(_) => 2,
+            Value::I64(_) => 8,
+            _ => 4,
+        }
+    }
+}
+
+// `FbBuilder` lays a flatbuffer out from the front to the back, unlike the
+// builders of the flatbuffers library. A table is written as its vtable
+// followed by the table itself, which starts at a multiple of 8 so the fields
+// are aligned by aligning their positions in the table, and the objects it
+// refers to are written after it, since the offsets are unsigned.
+struct FbBuilder {
+    buf: Vec<u8>,
+}
+
+impl FbBuilder {
+    fn finish(root: &Table) -> Vec<u8> {
+        let mut builder = FbBuilder { buf: vec![0; 4] };
+        let pos = builder.write_table(root);
+        LittleEndian::write_u32(&mut builder.buf[..4], pos as u32);
+        builder.buf
+    }
+
+    // Pad the buffer, so `ahead` bytes later is a multiple of `n`.
+    fn pad(&mut self, n: usize, ahead: usize) {
+        let len = align(self.buf.len() + ahead, n) - ahead;
+        self.buf.resize(len, 0);
+    }
+
+    // Point the u32 offset at `pos` to `target`.
+    fn patch(&mut self, pos: usize, target: usize) {
+        LittleEndian::write_u32(&mut self.buf[pos..pos + 4], (target - pos) as u32);
+    }
+
+    fn write_table(&mut self, table: &Table) -> usize {
+        let mut field_offsets = Vec::with_capacity(table.0.len());
+        // the table begins with the offset to its vtable.
+        let mut size = 4;
+        for field in &table.0 {
+            match *field {
+                Some(ref v) => {
+                    let n = v.inline_size();
+                    size = align(size, n);
+                    field_offsets.push(size);
+                    size += n;
+                }
+                None => field_offsets.push(0),
+            }
+        }
+
+        let vtable_len = 4 + 2 * table.0.len();
+        self.pad(2, 0);
+        self.pad(8, vtable_len);
+        let vtable_pos = self.buf.len();
+        self.buf.write_u16::<LittleEndian>(vtable_len as u16).unwrap();
+        self.buf.write_u16::<LittleEndian>(size as u16).unwrap();
+        for &offset in &field_offsets {
+            self.buf.write_u16::<LittleEndian>(offset as u16).unwrap();
+        }
+
+        let table_pos = self.buf.len();
+        self.buf.resize(table_pos + size, 0);
+        LittleEndian::write_i32(&mut self.buf[table_pos..], (table_pos - vtable_pos) as i32);
+        let mut refs = vec![];
+        for (field, &offset) in table.0.iter().zip(&field_offsets) {
+            let v = match *field {
+                Some(ref v) => v,
+                None => continue,
+            };
+            let pos = table_pos + offset;
+            let buf = &mut self.buf[pos..];
+            match *v {
+                Value::U8(n) => buf[0] = n,
+                Value::Bool(b) => buf[0] = b as u8,
+                Value::I16(n) => LittleEndian::write_i16(buf, n),
+                Value::I32(n) => LittleEndian::write_i32(buf, n),
+                Value::I64(n) => LittleEndian::write_i64(buf, n),
+                _ => refs.push((pos, v)),
+            }
+        }
+        for (pos, v) in refs {
+            let target = self.write_object(v);
+            self.patch(pos, target);
+        }
+        table_pos
+    }
+
+    // Write the object out of the table, returns where it begins.
+    fn write_object(&mut self, v: &Value) -> usize {
+        match *v {
+            Value::Str(ref s) => {
+                self.pad(4, 0);
+                let pos = self.buf.len();
+                self.buf.write_u32::<LittleEndian>(s.len() as u32).unwrap();
+                self.buf.extend_from_slice(s.as_bytes());
+                // the strings are null-terminated.
+                self.buf.push(0);
+                pos
+            }
+            Value::Table(ref t) => self.write_table(t),
+            Value::Tables(ref tables) => {
+                self.pad(4, 0);
+                let pos = self.buf.len();
+                self.buf.write_u32::<LittleEndian>(tables.len() as u32).unwrap();
+                let len = self.buf.len() + 4 * tables.len();
+                self.buf.resize(len, 0);
+                for (i, t) in tables.iter().enumerate() {
+                    let target = self.write_table(t);
+                    self.patch(pos + 4 + 4 * i, target);
+                }
+                pos
+            }
+            Value::Structs(ref structs) => {
+                // the structs after the length are aligned to 8.
+                self.pad(8, 4);
+                let pos = self.buf.len();
+                self.buf.write_u32::<LittleEndian>((structs.len() / 16) as u32).unwrap();
+                self.buf.extend_from_slice(structs);
+                pos
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use byteorder::{ByteOrder, LittleEndian};
+    use tipb::expression::FieldType;
+
+    use coprocessor::codec::Datum;
+    use coprocessor::codec::mysql::{charset, types};
+    use super::*;
+    use super::super::Chunk;
+
+    fn field_type(tp: u8) -> FieldType {
+        let mut fp = FieldType::new();
+        fp.set_tp(tp as i32);
+        fp
+    }
+
+    fn u16_at(buf: &[u8], pos: usize) -> usize {
+        LittleEndian::read_u16(&buf[pos..]) as usize
+    }
+
+    fn u32_at(buf: &[u8], pos: usize) -> usize {
+        LittleEndian::read_u32(&buf[pos..]) as usize
+    }
+
+    fn i64_at(buf: &[u8], pos: usize) -> i64 {
+        LittleEndian::read_i64(&buf[pos..])
+    }
+
+    // Get the position of the field `id` of the table at `table`.
+    fn field(buf: &[u8], table: usize, id: usize) -> Option<usize> {
+        let vtable = table - LittleEndian::read_i32(&buf[table..]) as usize;
+        if 4 + 2 * id >= u16_at(buf, vtable) {
+            return None;
+        }
+        match u16_at(buf, vtable + 4 + 2 * id) {
+            0 => None,
+            offset => Some(table + offset),
+        }
+    }
+
+    // Follow the offset at `pos`.
+    fn deref(buf: &[u8], pos: usize) -> usize {
+        pos + u32_at(buf, pos)
+    }
+
+    // Split a message off the stream, returns its metadata and body.
+    fn next_message<'a>(stream: &mut &'a [u8]) -> (&'a [u8], &'a [u8]) {
+        assert_eq!(u32_at(stream, 0), CONTINUATION as usize);
+        let len = u32_at(stream, 4);
+        assert_eq!(len % 8, 0);
+        let metadata = &stream[8..8 + len];
+        let message = u32_at(metadata, 0);
+        assert_eq!(LittleEndian::read_i16(&metadata[field(metadata, message, 0).unwrap()..]), 4);
+        let body_len = i64_at(metadata, field(metadata, message, 3).unwrap()) as usize;
+        assert_eq!(body_len % 8, 0);
+        let body = &stream[8 + len..8 + len + body_len];
+        *stream = &stream[8 + len + body_len..];
+        (metadata, body)
+    }
+
+    #[test]
+    fn test_write_chunk_ipc() {
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let mut blob = field_type(types::BLOB);
+        blob.set_charset(charset::CHARSET_BIN.to_owned());
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            unsigned,
+            field_type(types::FLOAT),
+            field_type(types::VARCHAR),
+            blob,
+            field_type(types::NEW_DECIMAL),
+            field_type(types::JSON),
+        ];
+        let rows = vec![
+            vec![
+                Datum::I64(-1),
+                Datum::U64(u64::max_value()),
+                Datum::F64(1.5),
+                Datum::Bytes("中".as_bytes().to_vec()),
+                Datum::Bytes(b"\xff".to_vec()),
+                Datum::Dec("1.25".parse().unwrap()),
+                Datum::Json(r#"{"a": 1}"#.parse().unwrap()),
+            ],
+            vec![Datum::Null; 7],
+            vec![
+                Datum::I64(3),
+                Datum::U64(0),
+                Datum::F64(-2.0),
+                Datum::Bytes(b"ab".to_vec()),
+                Datum::Bytes(vec![]),
+                Datum::Dec("-3".parse().unwrap()),
+                Datum::Json("[]".parse().unwrap()),
+            ],
+        ];
+        let chunk = Chunk::from_datum_rows(&rows, &fields).unwrap();
+        let mut out = vec![];
+        write_chunk_ipc(&chunk, &fields, &mut out).unwrap();
+        let mut stream = out.as_slice();
+
+        // the schema.
+        let (metadata, body) = next_message(&mut stream);
+        assert!(body.is_empty());
+        let message = u32_at(metadata, 0);
+        assert_eq!(metadata[field(metadata, message, 1).unwrap()], HEADER_SCHEMA);
+        let schema = deref(metadata, field(metadata, message, 2).unwrap());
+        let fields_vec = deref(metadata, field(metadata, schema, 1).unwrap());
+        assert_eq!(u32_at(metadata, fields_vec), fields.len());
+        let expect_types = [
+            (TYPE_INT, Some(true)),
+            (TYPE_INT, Some(false)),
+            (TYPE_FLOATING_POINT, None),
+            (TYPE_UTF8, None),
+            (TYPE_BINARY, None),
+            (TYPE_UTF8, None),
+            (TYPE_UTF8, None),
+        ];
+        for (i, &(tp, signed)) in expect_types.iter().enumerate() {
+            let f = deref(metadata, fields_vec + 4 + 4 * i);
+            let name = deref(metadata, field(metadata, f, 0).unwrap());
+            let name_len = u32_at(metadata, name);
+            assert_eq!(&metadata[name + 4..name + 4 + name_len], format!("c{}", i).as_bytes());
+            assert_eq!(metadata[field(metadata, f, 2).unwrap()], tp, "column {}", i);
+            let children = deref(metadata, field(metadata, f, 5).unwrap());
+            assert_eq!(u32_at(metadata, children), 0);
+            if let Some(signed) = signed {
+                let int = deref(metadata, field(metadata, f, 3).unwrap());
+                assert_eq!(u32_at(metadata, field(metadata, int, 0).unwrap()), 64);
+                assert_eq!(metadata[field(metadata, int, 1).unwrap()] == 1, signed);
+            }
+        }
+
+        // the record batch.
+        let (metadata, body) = next_message(&mut stream);
+        let message = u32_at(metadata, 0);
+        assert_eq!(metadata[field(metadata, message, 1).unwrap()], HEADER_RECORD_BATCH);
+        let batch = deref(metadata, field(metadata, message, 2).unwrap());
+        assert_eq!(i64_at(metadata, field(metadata, batch, 0).unwrap()), 3);
+        let nodes = deref(metadata, field(metadata, batch, 1).unwrap());
+        assert_eq!(u32_at(metadata, nodes), fields.len());
+        assert_eq!((nodes + 4) % 8, 0);
+        for i in 0..fields.len() {
+            assert_eq!(i64_at(metadata, nodes + 4 + 16 * i), 3);
+            assert_eq!(i64_at(metadata, nodes + 4 + 16 * i + 8), 1);
+        }
+        let buffers = deref(metadata, field(metadata, batch, 2).unwrap());
+        // the validity and the data of 3 fixed columns, and the validity,
+        // offsets and data of 4 var-length columns.
+        assert_eq!(u32_at(metadata, buffers), 3 * 2 + 4 * 3);
+        let buffer = |i: usize| {
+            let offset = i64_at(metadata, buffers + 4 + 16 * i) as usize;
+            assert_eq!(offset % 8, 0);
+            let len = i64_at(metadata, buffers + 4 + 16 * i + 8) as usize;
+            &body[offset..offset + len]
+        };
+        assert_eq!(buffer(0), &[0b101]);
+        assert_eq!(i64_at(buffer(1), 0), -1);
+        assert_eq!(i64_at(buffer(1), 16), 3);
+        assert_eq!(LittleEndian::read_f64(&buffer(5)[16..]), -2.0);
+        // the offsets and the data of the strings.
+        assert_eq!(buffer(7), &[0, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(buffer(8), "中ab".as_bytes());
+        assert_eq!(buffer(14), b"1.25-3");
+
+        // the end of the stream.
+        assert_eq!(stream, &[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+
+        let mut sel = chunk.clone();
+        sel.set_sel(vec![0]);
+        assert!(write_chunk_ipc(&sel, &fields, io::sink()).is_err());
+        assert!(write_chunk_ipc(&chunk, &fields[1..], io::sink()).is_err());
+    }
+}
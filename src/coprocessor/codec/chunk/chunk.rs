@@ -0,0 +1,3755 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::{cmp, f32, fmt, i64, mem, usize};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use chrono::FixedOffset;
+use crc::crc64::{self, Digest, Hasher64};
+use tipb::expression::FieldType;
+
+use coprocessor::codec::{Datum, Result};
+use coprocessor::codec::datum::DatumEncoder;
+use coprocessor::codec::mysql::{types, Decimal, Duration, Json, Time};
+use coprocessor::select::xeval::EvalContext;
+use super::{write_fingerprint_u64, DecodeLimits, DEBUG_ROWS};
+use super::column::{Column, ColumnKind};
+
+const CHUNK_INITIAL_CAPACITY: usize = 32;
+
+/// `Chunk` stores multiple rows of data in Apache Arrow format.
+/// See https://arrow.apache.org/docs/memory_layout.html
+/// Values are appended in compact format and can be directly accessed without decoding.
+/// When the chunk is done processing, we can reuse the allocated memory by resetting it.
+///
+/// A chunk may have a capacity, the max number of rows it holds, so the
+/// producers can stop filling it once it's full. Appending rows to a full
+/// chunk is a bug, which is only checked in debug builds.
+///
+/// A chunk may have a selection vector, the indexes of the rows passing a
+/// filter, so the rows can be filtered without moving the data. The rows of
+/// such a chunk are the selected ones, e.g. `num_rows` and the rows got from
+/// an `ArcChunk`. The chunk shouldn't be appended to until it's compacted by
+/// `reconstruct`, which is only checked in debug builds.
+#[derive(Clone)]
+pub struct Chunk {
+    pub(super) columns: Vec<Column>,
+    // the max number of rows, `usize::MAX` if the chunk is unbounded.
+    capacity: usize,
+    // the number of rows if there is no column, e.g. all the columns are
+    // pruned but the rows are still counted by `COUNT(*)`.
+    num_virtual_rows: usize,
+    // the indexes of the selected rows in ascending order, all the rows are
+    // selected if it's `None`.
+    sel: Option<Vec<usize>>,
+}
+
+impl Chunk {
+    /// Create a new unbounded chunk with field types.
+    pub fn new_chunk(tps: &[i32]) -> Chunk {
+        Chunk::with_columns(tps, CHUNK_INITIAL_CAPACITY, usize::MAX)
+    }
+
+    /// Create a new chunk with field types, which holds at most `capacity`
+    /// rows.
+    pub fn new_chunk_with_capacity(tps: &[i32], capacity: usize) -> Chunk {
+        assert!(capacity > 0, "the capacity of a chunk must be positive");
+        let init_cap = cmp::min(capacity, CHUNK_INITIAL_CAPACITY);
+        Chunk::with_columns(tps, init_cap, capacity)
+    }
+
+    /// Create a new unbounded chunk with the field types, which are kept in
+    /// the columns, so the flags like UNSIGNED and the fsp are known when
+    /// the values are read by `Row::get_typed_datum`.
+    pub fn from_field_types(field_types: &[FieldType]) -> Chunk {
+        Chunk::with_field_types(field_types, CHUNK_INITIAL_CAPACITY, usize::MAX)
+    }
+
+    fn with_field_types(field_types: &[FieldType], init_cap: usize, capacity: usize) -> Chunk {
+        let tps: Vec<i32> = field_types.iter().map(|fp| fp.get_tp()).collect();
+        let mut chunk = Chunk::with_columns(&tps, init_cap, capacity);
+        for (col, fp) in chunk.columns.iter_mut().zip(field_types) {
+            col.set_field_type(fp);
+        }
+        chunk
+    }
+
+    fn with_columns(tps: &[i32], init_cap: usize, capacity: usize) -> Chunk {
+        let mut columns = Vec::with_capacity(tps.len());
+        for &tp in tps {
+            columns.push(Column::new(tp, init_cap));
+        }
+        Chunk {
+            columns: columns,
+            capacity: capacity,
+            num_virtual_rows: 0,
+            sel: None,
+        }
+    }
+
+    /// Reset the chunk, so the memory it allocated can be reused.
+    /// Make sure all the data in the chunk is not used anymore before you reuse this chunk.
+    /// The capacity of the chunk is kept.
+    pub fn reset(&mut self) {
+        for c in &mut self.columns {
+            c.reset();
+        }
+        self.num_virtual_rows = 0;
+        self.sel = None;
+    }
+
+    /// Reset the chunk like `reset`, but the memory is released if more than
+    /// `max_retained_bytes` bytes would be kept, so a request with huge values
+    /// doesn't pin the memory in the reused chunk. The columns holding the
+    /// most memory are released first until the chunk is within the limit.
+    /// Returns the number of bytes released.
+    pub fn reset_with_limit(&mut self, max_retained_bytes: usize) -> usize {
+        self.reset();
+        let mut retained = self.mem_usage();
+        if retained <= max_retained_bytes {
+            return 0;
+        }
+        let mut col_idxs: Vec<usize> = (0..self.columns.len()).collect();
+        col_idxs.sort_by(|&a, &b| self.columns[b].mem_usage().cmp(&self.columns[a].mem_usage()));
+        let mut released = 0;
+        for idx in col_idxs {
+            if retained <= max_retained_bytes {
+                break;
+            }
+            let n = self.columns[idx].reset_with_limit(0);
+            retained -= n;
+            released += n;
+        }
+        released
+    }
+
+    /// Get the field type of the column, `None` if the chunk is created from
+    /// the type codes only.
+    #[inline]
+    pub fn field_type(&self, col_idx: usize) -> Option<&FieldType> {
+        self.columns[col_idx].field_type.as_ref()
+    }
+
+    /// Get the max number of rows in the chunk, `usize::MAX` if it's
+    /// unbounded.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Return whether the chunk has reached its capacity.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.num_rows() >= self.capacity
+    }
+
+    /// Reserve the memory for at least `additional_rows` more rows, so a batch
+    /// of a known size can be built without reallocating. The var-length
+    /// values are assumed to be as long as the ones in the column on average.
+    pub fn reserve(&mut self, additional_rows: usize) {
+        for col in &mut self.columns {
+            let avg_var_len = col.avg_var_len();
+            col.reserve(additional_rows, avg_var_len);
+        }
+    }
+
+    /// Get the number of bytes allocated for the values of the chunk, the
+    /// memory held by the datums of the interface columns isn't counted.
+    pub fn mem_usage(&self) -> usize {
+        let sel = self.sel.as_ref().map_or(0, |sel| sel.capacity() * mem::size_of::<usize>());
+        self.columns.iter().map(|col| col.mem_usage()).sum::<usize>() + sel
+    }
+
+    /// Get a crc64 fingerprint of the values in the chunk, which is used to
+    /// find where the results go wrong in a pipeline. The chunks equal to
+    /// each other have the same fingerprint however they are built, but a
+    /// chunk with a selection vector is fingerprinted with the vector, so it
+    /// differs from the chunk reconstructed from it.
+    pub fn fingerprint(&self) -> u64 {
+        let mut digest = Digest::new(crc64::ECMA);
+        write_fingerprint_u64(&mut digest, self.num_physical_rows() as u64);
+        for col in &self.columns {
+            col.write_fingerprint(&mut digest);
+        }
+        if let Some(ref sel) = self.sel {
+            for &idx in sel {
+                write_fingerprint_u64(&mut digest, idx as u64);
+            }
+        }
+        digest.sum64()
+    }
+
+    /// Get the number of rows which can still be appended to the chunk.
+    #[inline]
+    pub fn required_rows(&self) -> usize {
+        self.capacity.saturating_sub(self.num_rows())
+    }
+
+    // Check that `rows` rows can be appended to the column, the whole rows
+    // are checked with column 0.
+    #[inline]
+    fn debug_check_capacity(&self, col_idx: usize, rows: usize) {
+        if cfg!(debug_assertions) {
+            assert!(
+                self.sel.is_none(),
+                "can't append to a chunk with a selection vector"
+            );
+            let len = self.columns
+                .get(col_idx)
+                .map_or(self.num_virtual_rows, |c| c.len());
+            assert!(
+                len + rows <= self.capacity,
+                "can't append {} rows to column {} with {} rows, the capacity is {}",
+                rows,
+                col_idx,
+                len,
+                self.capacity
+            );
+        }
+    }
+
+    /// Get the number of columns in the chunk.
+    #[inline]
+    pub fn num_cols(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Get the number of rows in the chunk, only the selected rows are
+    /// counted if there is a selection vector.
+    #[inline]
+    pub fn num_rows(&self) -> usize {
+        match self.sel {
+            Some(ref sel) => sel.len(),
+            None => self.num_physical_rows(),
+        }
+    }
+
+    // The number of rows stored in the columns.
+    #[inline]
+    fn num_physical_rows(&self) -> usize {
+        if self.columns.is_empty() {
+            self.num_virtual_rows
+        } else {
+            self.columns[0].len()
+        }
+    }
+
+    /// Set the selection vector, the indexes of the rows in the columns
+    /// which are selected, in ascending order.
+    pub fn set_sel(&mut self, sel: Vec<usize>) {
+        if cfg!(debug_assertions) {
+            let rows = self.num_physical_rows();
+            for (i, &idx) in sel.iter().enumerate() {
+                assert!(idx < rows, "row {} is out of range of {} rows", idx, rows);
+                assert!(i == 0 || sel[i - 1] < idx, "the selection vector isn't ascending");
+            }
+        }
+        self.sel = Some(sel);
+    }
+
+    /// Get the selection vector, `None` if all the rows are selected.
+    #[inline]
+    pub fn sel(&self) -> Option<&[usize]> {
+        self.sel.as_ref().map(|sel| sel.as_slice())
+    }
+
+    // Get the index in the columns of the `pos`-th row, the index one past
+    // the last row is returned for `num_rows()`.
+    #[inline]
+    fn physical_idx(&self, pos: usize) -> usize {
+        match self.sel {
+            Some(ref sel) if pos < sel.len() => sel[pos],
+            Some(_) => self.num_physical_rows(),
+            None => pos,
+        }
+    }
+
+    /// Compact the chunk so that it only holds the selected rows, and clear
+    /// the selection vector. It should be called before the chunk is handed
+    /// to the executors unaware of the selection vector.
+    pub fn reconstruct(&mut self) {
+        let sel = match self.sel.take() {
+            Some(sel) => sel,
+            None => return,
+        };
+        let columns = self.columns.iter().map(|c| c.new_like(sel.len())).collect();
+        let src = Chunk {
+            columns: ::std::mem::replace(&mut self.columns, columns),
+            capacity: self.capacity,
+            num_virtual_rows: self.num_virtual_rows,
+            sel: Some(sel),
+        };
+        self.num_virtual_rows = 0;
+        // the columns are of the same kinds, so it never fails.
+        self.append(&src, 0, src.num_rows()).unwrap();
+    }
+
+    /// Append a row to the chunk, an error is returned if the row doesn't
+    /// have the same number of columns as the chunk.
+    pub fn append_row(&mut self, row: &Row) -> Result<()> {
+        if row.len() != self.num_cols() {
+            return Err(box_err!(
+                "can't append a row with {} columns to a chunk with {} columns",
+                row.len(),
+                self.num_cols()
+            ));
+        }
+        self.append_partial_row(0, row)
+    }
+
+    /// Append a row to the chunk, the row's columns are appended to the
+    /// columns of the chunk starting from `dst_offset`, and NULL is appended
+    /// to the other columns, so all the columns still have the same length.
+    /// It's used to concatenate the rows of a join.
+    ///
+    /// An error is returned and the chunk is untouched if the row doesn't fit
+    /// in the columns after `dst_offset`, or the columns are of different
+    /// kinds.
+    pub fn append_partial_row(&mut self, dst_offset: usize, row: &Row) -> Result<()> {
+        if dst_offset + row.len() > self.num_cols() {
+            return Err(box_err!(
+                "can't append a row with {} columns at column {} of a chunk with {} columns",
+                row.len(),
+                dst_offset,
+                self.num_cols()
+            ));
+        }
+        self.debug_check_capacity(0, 1);
+        let end = dst_offset + row.len();
+        for (i, src) in row.c.columns.iter().enumerate() {
+            let idx = dst_offset + i;
+            self.columns[idx]
+                .check_same_kind(src)
+                .map_err(|e| invalid_type!("column {}: {}", idx, e))?;
+        }
+        for (i, dst) in self.columns.iter_mut().enumerate() {
+            if i >= dst_offset && i < end {
+                let src = &row.c.columns[i - dst_offset];
+                dst.append(src, row.idx, row.idx + 1)?;
+            } else {
+                dst.append_null();
+            }
+        }
+        if self.columns.is_empty() {
+            self.num_virtual_rows += 1;
+        }
+        self.debug_check_lengths();
+        Ok(())
+    }
+
+    /// Append rows in [begin,end) in another chunk to a Chunk, the columns
+    /// of the two chunks must be of the same kinds. If `other` has a
+    /// selection vector, the range is of the selected rows, and only they
+    /// are appended.
+    pub fn append(&mut self, other: &Chunk, begin: usize, end: usize) -> Result<()> {
+        self.check_same_kinds(other)?;
+        self.append_selected(other, begin, end, None)
+    }
+
+    /// Append rows in [begin,end) in another chunk whose columns are in a
+    /// different order, `mapping[i]` is the column of `other` appended to
+    /// column `i`, and a column of `other` may be appended to several
+    /// columns. It's the same as `append` otherwise.
+    ///
+    /// An error is returned and the chunk is untouched if there isn't a
+    /// column of `other` for each column, or they are of different kinds.
+    pub fn append_with_mapping(
+        &mut self,
+        other: &Chunk,
+        begin: usize,
+        end: usize,
+        mapping: &[usize],
+    ) -> Result<()> {
+        self.check_mapping(other, mapping)?;
+        self.append_selected(other, begin, end, Some(mapping))
+    }
+
+    fn append_selected(
+        &mut self,
+        other: &Chunk,
+        begin: usize,
+        end: usize,
+        mapping: Option<&[usize]>,
+    ) -> Result<()> {
+        self.debug_check_capacity(0, end - begin);
+        let sel = match other.sel {
+            Some(ref sel) => &sel[begin..end],
+            None => return self.append_physical(other, begin, end, mapping),
+        };
+        // the consecutive rows are appended at once.
+        let mut i = 0;
+        while i < sel.len() {
+            let mut j = i + 1;
+            while j < sel.len() && sel[j] == sel[j - 1] + 1 {
+                j += 1;
+            }
+            self.append_physical(other, sel[i], sel[j - 1] + 1, mapping)?;
+            i = j;
+        }
+        Ok(())
+    }
+
+    // Append the rows in [begin,end) of the columns of another chunk, the
+    // columns are matched by `mapping` if any, or by their indexes.
+    fn append_physical(
+        &mut self,
+        other: &Chunk,
+        begin: usize,
+        end: usize,
+        mapping: Option<&[usize]>,
+    ) -> Result<()> {
+        for (i, dst) in self.columns.iter_mut().enumerate() {
+            let src = &other.columns[mapping.map_or(i, |m| m[i])];
+            dst.append(src, begin, end)?;
+        }
+        if self.columns.is_empty() {
+            self.num_virtual_rows += end - begin;
+        }
+        self.debug_check_lengths();
+        Ok(())
+    }
+
+    /// Append all rows of `other` to the chunk column by column, the columns
+    /// of the two chunks must be of the same kinds. Only the selected rows
+    /// are appended if `other` has a selection vector.
+    pub fn append_all(&mut self, other: &Chunk) -> Result<()> {
+        if other.sel.is_some() {
+            return self.append(other, 0, other.num_rows());
+        }
+        self.check_same_kinds(other)?;
+        self.debug_check_capacity(0, other.num_rows());
+        for (col, other_col) in self.columns.iter_mut().zip(&other.columns) {
+            col.append_all(other_col)?;
+        }
+        if self.columns.is_empty() {
+            self.num_virtual_rows += other.num_rows();
+        }
+        Ok(())
+    }
+
+    // Check all the columns before appending, so nothing is appended if any
+    // of them doesn't match.
+    fn check_same_kinds(&self, other: &Chunk) -> Result<()> {
+        if self.num_cols() != other.num_cols() {
+            return Err(box_err!(
+                "expect {} columns, but got {}",
+                self.num_cols(),
+                other.num_cols()
+            ));
+        }
+        for (i, (col, other_col)) in self.columns.iter().zip(&other.columns).enumerate() {
+            col.check_same_kind(other_col)
+                .map_err(|e| invalid_type!("column {}: {}", i, e))?;
+        }
+        Ok(())
+    }
+
+    fn check_mapping(&self, other: &Chunk, mapping: &[usize]) -> Result<()> {
+        if mapping.len() != self.num_cols() {
+            return Err(box_err!(
+                "expect a mapping of {} columns, but got {}",
+                self.num_cols(),
+                mapping.len()
+            ));
+        }
+        for (i, (col, &src_idx)) in self.columns.iter().zip(mapping).enumerate() {
+            let src = match other.columns.get(src_idx) {
+                Some(src) => src,
+                None => {
+                    return Err(box_err!(
+                        "column {} is mapped to column {}, but there are {} columns",
+                        i,
+                        src_idx,
+                        other.num_cols()
+                    ));
+                }
+            };
+            col.check_same_kind(src)
+                .map_err(|e| invalid_type!("column {}: {}", i, e))?;
+        }
+        Ok(())
+    }
+
+    /// Truncate the chunk to `num_rows` rows. If there is a selection vector,
+    /// only the vector is truncated.
+    pub fn truncate_to(&mut self, num_rows: usize) {
+        if let Some(ref mut sel) = self.sel {
+            sel.truncate(num_rows);
+            return;
+        }
+        for col in &mut self.columns {
+            col.truncate_to(num_rows);
+        }
+        self.num_virtual_rows = cmp::min(self.num_virtual_rows, num_rows);
+        self.debug_check_lengths();
+    }
+
+    /// Check that all the columns have the same length. The values of a row
+    /// are appended to the columns one by one, so it's only checked after
+    /// the mutations of whole rows.
+    #[inline]
+    fn debug_check_lengths(&self) {
+        if cfg!(debug_assertions) {
+            let rows = self.num_physical_rows();
+            for (i, col) in self.columns.iter().enumerate() {
+                assert_eq!(
+                    col.len(),
+                    rows,
+                    "column {} has {} rows, but column 0 has {} rows",
+                    i,
+                    col.len(),
+                    rows
+                );
+            }
+        }
+    }
+
+    /// Append a null value to the chunk.
+    #[inline]
+    pub fn append_null(&mut self, col_idx: usize) {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_null()
+    }
+
+    /// Append an int64 value to the chunk.
+    #[inline]
+    pub fn append_i64(&mut self, col_idx: usize, v: i64) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_i64(v)
+    }
+
+    /// Append an uint64 value to the chunk.
+    #[inline]
+    pub fn append_u64(&mut self, col_idx: usize, v: u64) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_u64(v)
+    }
+
+    /// Append a float32 value to the chunk.
+    #[inline]
+    pub fn append_f32(&mut self, col_idx: usize, v: f32) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_f32(v)
+    }
+
+    /// Append a float64 value to the chunk.
+    #[inline]
+    pub fn append_f64(&mut self, col_idx: usize, v: f64) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_f64(v)
+    }
+
+    /// Append a string value to the chunk.
+    #[inline]
+    pub fn append_str<S: AsRef<str>>(&mut self, col_idx: usize, v: S) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_str(v.as_ref())
+    }
+
+    /// Append a bytes value to the chunk.
+    #[inline]
+    pub fn append_bytes<B: AsRef<[u8]>>(&mut self, col_idx: usize, v: B) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_bytes(v.as_ref())
+    }
+
+    /// Append the string values to the chunk, the memory of the offsets is
+    /// reserved once by the size hint of `values`. If a value can't be
+    /// appended, the values before it are kept in the chunk.
+    pub fn append_strs<I>(&mut self, col_idx: usize, values: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let values = values.into_iter();
+        self.debug_check_capacity(col_idx, values.size_hint().0);
+        self.columns[col_idx].append_strs(values)
+    }
+
+    /// Append the bytes values to the chunk, see `append_strs`.
+    pub fn append_bytes_iter<I>(&mut self, col_idx: usize, values: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let values = values.into_iter();
+        self.debug_check_capacity(col_idx, values.size_hint().0);
+        self.columns[col_idx].append_bytes_iter(values)
+    }
+
+    /// Append `n` null values to the chunk, the null bitmap is updated a byte
+    /// at a time instead of a bit at a time.
+    pub fn append_nulls(&mut self, col_idx: usize, n: usize) {
+        self.debug_check_capacity(col_idx, n);
+        self.columns[col_idx].append_nulls(n)
+    }
+
+    /// Append the int64 values to the chunk at once, it's the same as
+    /// appending them one by one by `append_i64`. Nothing is appended if any
+    /// of them can't be.
+    pub fn append_i64_slice(&mut self, col_idx: usize, vs: &[i64]) -> Result<()> {
+        self.debug_check_capacity(col_idx, vs.len());
+        self.columns[col_idx].append_i64_slice(vs)
+    }
+
+    /// Append the uint64 values to the chunk at once, see `append_i64_slice`.
+    pub fn append_u64_slice(&mut self, col_idx: usize, vs: &[u64]) -> Result<()> {
+        self.debug_check_capacity(col_idx, vs.len());
+        self.columns[col_idx].append_u64_slice(vs)
+    }
+
+    /// Append the float64 values to the chunk at once, see `append_i64_slice`.
+    pub fn append_f64_slice(&mut self, col_idx: usize, vs: &[f64]) -> Result<()> {
+        self.debug_check_capacity(col_idx, vs.len());
+        self.columns[col_idx].append_f64_slice(vs)
+    }
+
+    /// Append an enum value with its name to the chunk, `value` is the index
+    /// of the name in the elements, which starts from 1, and 0 is the value
+    /// of the invalid enum with an empty name.
+    #[inline]
+    pub fn append_enum(&mut self, col_idx: usize, name: &[u8], value: u64) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_name_value(name, value)
+    }
+
+    /// Append a set value with its name to the chunk, `value` is the bitmap
+    /// of the elements in the set, and `name` is the names of the elements
+    /// joined by commas.
+    #[inline]
+    pub fn append_set(&mut self, col_idx: usize, name: &[u8], value: u64) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_name_value(name, value)
+    }
+
+    /// Append a decimal value to the chunk.
+    #[inline]
+    pub fn append_decimal(&mut self, col_idx: usize, v: &Decimal) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_decimal(v)
+    }
+
+    /// Append a time value to the chunk.
+    #[inline]
+    pub fn append_time(&mut self, col_idx: usize, v: &Time) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_time(v)
+    }
+
+    /// Append a duration value to the chunk.
+    #[inline]
+    pub fn append_duration(&mut self, col_idx: usize, v: &Duration) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_duration(v)
+    }
+
+    /// Append an interface value to the chunk.
+    #[inline]
+    pub fn append_interface(&mut self, col_idx: usize, v: Datum) {
+        self.debug_check_capacity(col_idx, 1);
+        self.columns[col_idx].append_interface(v)
+    }
+
+    /// Create a chunk of the field types `fts` holding `rows`, every row must
+    /// have a datum for each column, which is appended by `append_datum`.
+    pub fn from_datum_rows(rows: &[Vec<Datum>], fts: &[FieldType]) -> Result<Chunk> {
+        let mut chunk = Chunk::from_field_types(fts);
+        for (row_idx, row) in rows.iter().enumerate() {
+            if row.len() != fts.len() {
+                return Err(box_err!(
+                    "row {} has {} datums, but there are {} columns",
+                    row_idx,
+                    row.len(),
+                    fts.len()
+                ));
+            }
+            for (col_idx, (datum, fp)) in row.iter().zip(fts).enumerate() {
+                chunk
+                    .append_datum(col_idx, datum, fp)
+                    .map_err(|e| invalid_type!("row {}: {}", row_idx, e))?;
+            }
+        }
+        Ok(chunk)
+    }
+
+    /// Append a datum of the field type `fp` to the chunk, it's the reverse
+    /// of `Row::get_datum`. An error is returned if the column isn't of the
+    /// field type, or the datum can't be stored in the column.
+    pub fn append_datum(&mut self, col_idx: usize, datum: &Datum, fp: &FieldType) -> Result<()> {
+        self.debug_check_capacity(col_idx, 1);
+        let col = &mut self.columns[col_idx];
+        col.check_field_type(col_idx, fp)?;
+        col.check_kind(col_idx, fp)?;
+        let tp = fp.get_tp();
+        let kind = col.kind;
+        let unsigned = types::has_unsigned_flag(fp.get_flag() as u64);
+        match (tp as u8, datum) {
+            (_, &Datum::Null) => {
+                col.append_null();
+                Ok(())
+            }
+            (_, d) if kind == ColumnKind::Interface => {
+                col.append_interface(d.clone());
+                Ok(())
+            }
+            (types::TINY, &Datum::I64(v)) |
+            (types::SHORT, &Datum::I64(v)) |
+            (types::INT24, &Datum::I64(v)) |
+            (types::LONG, &Datum::I64(v)) |
+            (types::LONG_LONG, &Datum::I64(v)) |
+            (types::YEAR, &Datum::I64(v)) if !unsigned || v >= 0 => col.append_i64(v),
+            (types::TINY, &Datum::U64(v)) |
+            (types::SHORT, &Datum::U64(v)) |
+            (types::INT24, &Datum::U64(v)) |
+            (types::LONG, &Datum::U64(v)) |
+            (types::LONG_LONG, &Datum::U64(v)) |
+            (types::YEAR, &Datum::U64(v)) if unsigned || v <= i64::MAX as u64 => {
+                col.append_u64(v)
+            }
+            (types::FLOAT, &Datum::F64(v)) if v.abs() <= f64::from(f32::MAX) => {
+                col.append_f32(v as f32)
+            }
+            (types::DOUBLE, &Datum::F64(v)) => col.append_f64(v),
+            // the datums only hold the values of the enums and the sets, so
+            // their names are left empty.
+            (types::ENUM, &Datum::U64(v)) | (types::SET, &Datum::U64(v)) => {
+                col.append_name_value(b"", v)
+            }
+            (t, &Datum::Bytes(ref v))
+                if kind == ColumnKind::VarLen && t != types::ENUM && t != types::SET =>
+            {
+                col.append_bytes(v)
+            }
+            (types::NEW_DECIMAL, &Datum::Dec(ref v)) => col.append_decimal(v),
+            (types::DATE, &Datum::Time(ref v)) |
+            (types::DATETIME, &Datum::Time(ref v)) |
+            (types::TIMESTAMP, &Datum::Time(ref v)) => col.append_time(v),
+            (types::DURATION, &Datum::Dur(ref v)) => col.append_duration(v),
+            (_, d) => Err(invalid_type!(
+                "{:?} can't be stored in column {} of type {}",
+                d,
+                col_idx,
+                tp
+            )),
+        }
+    }
+
+    /// Create a new chunk with the columns at `offsets` of this chunk. An
+    /// offset may be repeated, every output column is an independent copy.
+    pub fn project(&self, offsets: &[usize]) -> Chunk {
+        let columns = offsets.iter().map(|&i| self.columns[i].clone()).collect();
+        Chunk {
+            columns: columns,
+            capacity: self.capacity,
+            num_virtual_rows: self.num_physical_rows(),
+            sel: self.sel.clone(),
+        }
+    }
+
+    /// Keep only the columns at `used` in the chunk, in the order of `used`,
+    /// so the unused columns can be dropped early. Unlike `project`, the
+    /// columns are moved instead of copied, only a repeated offset is copied.
+    /// The number of rows is kept even if no column is used.
+    pub fn prune_columns(&mut self, used: &[usize]) {
+        let num_rows = self.num_physical_rows();
+        let mut old: Vec<_> = self.columns.drain(..).map(Some).collect();
+        for (i, &offset) in used.iter().enumerate() {
+            let col = match old[offset].take() {
+                Some(col) => col,
+                None => {
+                    let pos = used[..i].iter().position(|&o| o == offset).unwrap();
+                    self.column(pos).clone()
+                }
+            };
+            self.columns.push(col);
+        }
+        self.num_virtual_rows = num_rows;
+        self.debug_check_lengths();
+    }
+
+    /// Get the column at `col_idx`.
+    #[inline]
+    fn column(&self, col_idx: usize) -> &Column {
+        &self.columns[col_idx]
+    }
+
+    /// Encode the whole chunk into `buf` with the format of TiDB's
+    /// `chunk/codec.go`, use `ChunkEncoder` to split it into multiple chunks.
+    /// The interface columns can't be encoded, and the chunk must be
+    /// reconstructed if there is a selection vector.
+    pub fn encode_to(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.check_encodable()?;
+        for col in &self.columns {
+            col.encode_rows(0, col.len(), 0, buf)?;
+        }
+        Ok(())
+    }
+
+    /// Encode the chunk like `encode_to`, but the rows are split into
+    /// multiple encoded chunks of at most `max_bytes` bytes each, so they can
+    /// be sent in messages of limited size. A row is never split, so a row
+    /// larger than `max_bytes` is encoded alone in an oversized chunk. The
+    /// sizes are computed from the columns before encoding, see
+    /// `ChunkEncoder::encode_next_within`.
+    pub fn encode_chunked(&self, max_bytes: usize) -> Result<Vec<Vec<u8>>> {
+        self.check_encodable()?;
+        let mut encoder = ChunkEncoder::unchecked(self);
+        let mut pieces = vec![];
+        while encoder.remaining_rows() > 0 {
+            let mut buf = vec![];
+            encoder.encode_next_within(max_bytes, &mut buf)?;
+            pieces.push(buf);
+        }
+        Ok(pieces)
+    }
+
+    fn check_encodable(&self) -> Result<()> {
+        self.check_no_sel()?;
+        for (i, col) in self.columns.iter().enumerate() {
+            if !col.is_fixed() && !col.is_var_len() {
+                return Err(invalid_type!("column {} holds datums which can't be encoded", i));
+            }
+        }
+        Ok(())
+    }
+
+    // Get the number of bytes the rows in [begin,end) are encoded into, the
+    // columns must be encodable.
+    fn encoded_size_of_rows(&self, begin: usize, end: usize) -> usize {
+        self.columns
+            .iter()
+            .map(|col| col.encoded_size_of_rows(begin, end))
+            .sum()
+    }
+
+    /// Get the number of bytes `encode_to` produces for the chunk, so the
+    /// buffer can be reserved and the output can be split by size before
+    /// encoding. It's exact unless there are interface columns, which can't
+    /// be encoded, or a selection vector, in which case it's an upper bound of
+    /// the size of the chunk after being reconstructed.
+    pub fn encoded_size_hint(&self) -> usize {
+        self.columns.iter().map(|col| col.encoded_size_hint()).sum()
+    }
+
+    fn check_no_sel(&self) -> Result<()> {
+        if self.sel.is_some() {
+            return Err(box_err!("the chunk with a selection vector must be reconstructed"));
+        }
+        Ok(())
+    }
+
+    /// Decode a chunk encoded by `encode_to` or `ChunkEncoder`, `field_types`
+    /// are the types of its columns. The whole `buf` must be consumed.
+    pub fn decode(buf: &[u8], field_types: &[FieldType]) -> Result<Chunk> {
+        Chunk::decode_with_limits(buf, field_types, &DecodeLimits::default())
+    }
+
+    /// Decode a chunk like `decode`, but the sizes in the chunk are checked
+    /// against `limits`. Every size read from `buf` is validated before
+    /// anything is allocated for it, so the memory used is bounded by the
+    /// length of `buf` even if the chunk is corrupted.
+    pub fn decode_with_limits(
+        mut buf: &[u8],
+        field_types: &[FieldType],
+        limits: &DecodeLimits,
+    ) -> Result<Chunk> {
+        let mut columns: Vec<Column> = Vec::with_capacity(field_types.len());
+        for (i, fp) in field_types.iter().enumerate() {
+            let mut col = Column::decode(fp.get_tp(), i, &mut buf, limits)?;
+            col.set_field_type(fp);
+            if let Some(first) = columns.first() {
+                if first.len() != col.len() {
+                    return Err(box_err!(
+                        "column {} has {} rows, but column 0 has {} rows",
+                        columns.len(),
+                        col.len(),
+                        first.len()
+                    ));
+                }
+            }
+            columns.push(col);
+        }
+        if !buf.is_empty() {
+            return Err(box_err!("{} bytes left after decoding the chunk", buf.len()));
+        }
+        Ok(Chunk {
+            columns: columns,
+            capacity: usize::MAX,
+            num_virtual_rows: 0,
+            sel: None,
+        })
+    }
+
+    /// Swap the column with another chunk's column.
+    pub fn swap_columns(&mut self, other: &mut Chunk) {
+        ::std::mem::swap(&mut self.columns, &mut other.columns);
+        ::std::mem::swap(&mut self.num_virtual_rows, &mut other.num_virtual_rows);
+        ::std::mem::swap(&mut self.sel, &mut other.sel);
+    }
+
+    /// Swap the column at `col_idx` with the column at `other_idx` of another
+    /// chunk. The columns must have the same number of rows, which are the
+    /// rows in the columns if the chunks have selection vectors.
+    pub fn swap_column(
+        &mut self,
+        col_idx: usize,
+        other: &mut Chunk,
+        other_idx: usize,
+    ) -> Result<()> {
+        self.check_col_idx(col_idx)?;
+        other.check_col_idx(other_idx)?;
+        let (rows, other_rows) = (self.num_physical_rows(), other.num_physical_rows());
+        if rows != other_rows {
+            return Err(box_err!(
+                "can't swap column {} of {} rows with column {} of {} rows",
+                col_idx,
+                rows,
+                other_idx,
+                other_rows
+            ));
+        }
+        ::std::mem::swap(&mut self.columns[col_idx], &mut other.columns[other_idx]);
+        Ok(())
+    }
+
+    /// Take the column at `col_idx` out of the chunk, it's replaced with a
+    /// column of NULLs of the same kind until `set_column` is called, so the
+    /// rows can still be read.
+    pub fn take_column(&mut self, col_idx: usize) -> Result<Column> {
+        self.check_col_idx(col_idx)?;
+        let rows = self.num_physical_rows();
+        let mut nulls = self.columns[col_idx].new_like(rows);
+        for _ in 0..rows {
+            nulls.append_null();
+        }
+        Ok(::std::mem::replace(&mut self.columns[col_idx], nulls))
+    }
+
+    /// Replace the column at `col_idx` with `col`, which must have the same
+    /// number of rows as the chunk. `col` is dropped if an error is returned.
+    pub fn set_column(&mut self, col_idx: usize, col: Column) -> Result<()> {
+        self.check_col_idx(col_idx)?;
+        let rows = self.num_physical_rows();
+        if col.len() != rows {
+            return Err(box_err!(
+                "can't set a column of {} rows to column {} of a chunk with {} rows",
+                col.len(),
+                col_idx,
+                rows
+            ));
+        }
+        self.columns[col_idx] = col;
+        Ok(())
+    }
+
+    fn check_col_idx(&self, col_idx: usize) -> Result<()> {
+        if col_idx >= self.num_cols() {
+            return Err(box_err!(
+                "column {} is out of range, the chunk has {} columns",
+                col_idx,
+                self.num_cols()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The chunks are equal if they hold the same rows, only the selected rows
+/// are compared if there is a selection vector, see `Column::eq`.
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Chunk) -> bool {
+        let rows = self.num_rows();
+        if self.num_cols() != other.num_cols() || rows != other.num_rows() {
+            return false;
+        }
+        self.columns.iter().zip(&other.columns).all(|(a, b)| {
+            a.is_same_layout(b) &&
+                (0..rows).all(|pos| a.row_eq(self.physical_idx(pos), b, other.physical_idx(pos)))
+        })
+    }
+}
+
+/// The rows are written one per line, at most `DEBUG_ROWS` rows are written
+/// unless the precision is specified, e.g. `{:.100?}` writes 100 rows.
+impl fmt::Debug for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limit = f.precision().unwrap_or(DEBUG_ROWS);
+        let rows = self.num_rows();
+        writeln!(f, "Chunk {{ {} columns, {} rows", self.num_cols(), rows)?;
+        for pos in 0..cmp::min(limit, rows) {
+            let idx = self.physical_idx(pos);
+            write!(f, "    {}:", pos)?;
+            for (i, col) in self.columns.iter().enumerate() {
+                f.write_str(if i == 0 { " " } else { " | " })?;
+                col.fmt_value(idx, f)?;
+            }
+            f.write_str("\n")?;
+        }
+        if rows > limit {
+            writeln!(f, "    ... {} more rows", rows - limit)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// `ArcChunk` shares a chunk between rows, a row keeps the chunk alive
+/// after the `ArcChunk` is dropped.
+pub struct ArcChunk {
+    pub(super) c: Arc<Chunk>,
+}
+
+impl ArcChunk {
+    pub fn new(c: Chunk) -> ArcChunk {
+        ArcChunk { c: Arc::new(c) }
+    }
+
+    /// Share a chunk which is already shared by `Arc`.
+    pub fn from_arc(c: Arc<Chunk>) -> ArcChunk {
+        ArcChunk { c: c }
+    }
+
+    /// Take the chunk back if it isn't shared by any rows or clones of the
+    /// `Arc`, so it can be reset and reused. Otherwise `self` is returned.
+    pub fn try_unwrap(self) -> ::std::result::Result<Chunk, ArcChunk> {
+        Arc::try_unwrap(self.c).map_err(ArcChunk::from_arc)
+    }
+
+    /// Get the Row by index, which is the index in the selected rows if the
+    /// chunk has a selection vector.
+    pub fn get_row(&self, idx: usize) -> Row {
+        Row::new(self.c.clone(), idx)
+    }
+
+    /// Get the first Row in the chunk.
+    pub fn begin(&self) -> Row {
+        self.get_row(0)
+    }
+
+    /// Get the Row one past the last Row in the chunk.
+    pub fn end(&self) -> Row {
+        self.get_row(self.c.num_rows())
+    }
+
+    /// Get the number of rows in the chunk.
+    #[inline]
+    pub fn num_rows(&self) -> usize {
+        self.c.num_rows()
+    }
+
+    /// Get the number of columns in the chunk.
+    #[inline]
+    pub fn num_cols(&self) -> usize {
+        self.c.num_cols()
+    }
+
+    /// Get an iterator over the rows of the chunk.
+    pub fn iter(&self) -> RowIter {
+        RowIter {
+            c: self.c.clone(),
+            idx: 0,
+        }
+    }
+
+    /// Get the datums of all the rows, it's the reverse of
+    /// `Chunk::from_datum_rows`, see `Row::get_datums`.
+    pub fn to_datum_rows(&self, fts: &[FieldType], tz: &FixedOffset) -> Result<Vec<Vec<Datum>>> {
+        self.iter().map(|row| row.get_datums(fts, tz)).collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a ArcChunk {
+    type Item = Row;
+    type IntoIter = RowIter;
+
+    fn into_iter(self) -> RowIter {
+        self.iter()
+    }
+}
+
+/// `RowIter` iterates the rows of an `ArcChunk` in order.
+pub struct RowIter {
+    c: Arc<Chunk>,
+    idx: usize,
+}
+
+impl Iterator for RowIter {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        if self.idx >= self.c.num_rows() {
+            return None;
+        }
+        self.idx += 1;
+        Some(Row::new(self.c.clone(), self.idx - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remain = self.c.num_rows().saturating_sub(self.idx);
+        (remain, Some(remain))
+    }
+}
+
+impl ExactSizeIterator for RowIter {}
+
+/// `MutRow` is a single-row chunk whose values can be set in place, so a row
+/// can be built again and again without allocating a chunk for every row.
+pub struct MutRow {
+    c: Arc<Chunk>,
+    field_types: Vec<FieldType>,
+}
+
+impl MutRow {
+    /// Create a row with `datums` of the types `field_types`.
+    pub fn from_datums(datums: &[Datum], field_types: &[FieldType]) -> Result<MutRow> {
+        if datums.len() != field_types.len() {
+            return Err(box_err!(
+                "{} datums can't be stored in {} columns",
+                datums.len(),
+                field_types.len()
+            ));
+        }
+        let mut c = Chunk::with_field_types(field_types, 1, usize::MAX);
+        for (i, (datum, fp)) in datums.iter().zip(field_types).enumerate() {
+            c.append_datum(i, datum, fp)?;
+        }
+        Ok(MutRow {
+            c: Arc::new(c),
+            field_types: field_types.to_vec(),
+        })
+    }
+
+    /// Set the value of the column, the row is left unchanged if an error
+    /// is returned. The rows got by `as_row` before keep the old values.
+    pub fn set_datum(&mut self, col_idx: usize, datum: &Datum) -> Result<()> {
+        // the chunk is copied only if it's still shared by a row.
+        let c = Arc::make_mut(&mut self.c);
+        c.append_datum(col_idx, datum, &self.field_types[col_idx])?;
+        c.columns[col_idx].keep_last_row();
+        Ok(())
+    }
+
+    /// Get the row, which shares the values with `MutRow`.
+    #[inline]
+    pub fn as_row(&self) -> Row {
+        Row::new(self.c.clone(), 0)
+    }
+}
+
+/// `RowContainer` buffers the rows copied from other chunks in a list of
+/// chunks of the same capacity, so the operators like aggregation and topN
+/// can keep more rows than a chunk holds without pinning the chunks they
+/// come from. The rows are indexed across the chunks.
+pub struct RowContainer {
+    tps: Vec<i32>,
+    chunk_capacity: usize,
+    // all the chunks but the last one are full.
+    chunks: Vec<Arc<Chunk>>,
+    len: usize,
+}
+
+impl RowContainer {
+    /// Create an empty container of the field types, every chunk of which
+    /// holds at most `chunk_capacity` rows.
+    pub fn new(tps: &[i32], chunk_capacity: usize) -> RowContainer {
+        assert!(chunk_capacity > 0, "the capacity of a chunk must be positive");
+        RowContainer {
+            tps: tps.to_vec(),
+            chunk_capacity: chunk_capacity,
+            chunks: vec![],
+            len: 0,
+        }
+    }
+
+    /// Copy the row into the container, an error is returned if the columns
+    /// of the row are different from the container's.
+    pub fn append_row(&mut self, row: &Row) -> Result<()> {
+        if row.len() != self.tps.len() {
+            return Err(box_err!(
+                "can't append a row with {} columns to a container with {} columns",
+                row.len(),
+                self.tps.len()
+            ));
+        }
+        if self.chunks.last().map_or(true, |c| c.is_full()) {
+            let c = Chunk::new_chunk_with_capacity(&self.tps, self.chunk_capacity);
+            self.chunks.push(Arc::new(c));
+        }
+        // the chunk is copied only if it's still shared by a row.
+        let c = Arc::make_mut(self.chunks.last_mut().unwrap());
+        c.append_row(row)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Get the number of rows in the container.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the row at `idx` of all the rows in the container.
+    pub fn get_row(&self, idx: usize) -> Row {
+        assert!(
+            idx < self.len,
+            "row {} is out of range, the container has {} rows",
+            idx,
+            self.len
+        );
+        let c = &self.chunks[idx / self.chunk_capacity];
+        Row::new(c.clone(), idx % self.chunk_capacity)
+    }
+
+    /// Get an iterator over the rows of the container.
+    pub fn iter(&self) -> RowContainerIter {
+        RowContainerIter {
+            rows: self,
+            idx: 0,
+        }
+    }
+
+    /// Get the number of bytes allocated for the rows, see `Chunk::mem_usage`.
+    pub fn mem_usage(&self) -> usize {
+        self.chunks.iter().map(|c| c.mem_usage()).sum()
+    }
+}
+
+impl<'a> IntoIterator for &'a RowContainer {
+    type Item = Row;
+    type IntoIter = RowContainerIter<'a>;
+
+    fn into_iter(self) -> RowContainerIter<'a> {
+        self.iter()
+    }
+}
+
+/// `RowContainerIter` iterates the rows of a `RowContainer` in order.
+pub struct RowContainerIter<'a> {
+    rows: &'a RowContainer,
+    idx: usize,
+}
+
+impl<'a> Iterator for RowContainerIter<'a> {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        if self.idx >= self.rows.len() {
+            return None;
+        }
+        self.idx += 1;
+        Some(self.rows.get_row(self.idx - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remain = self.rows.len() - self.idx;
+        (remain, Some(remain))
+    }
+}
+
+impl<'a> ExactSizeIterator for RowContainerIter<'a> {}
+
+/// `Row` represents a row of data, can be used to assess values.
+pub struct Row {
+    c: Arc<Chunk>,
+    // the position of the row in the rows of the chunk, and the index of the
+    // row in the columns, they differ if the chunk has a selection vector.
+    pos: usize,
+    idx: usize,
+}
+
+impl Row {
+    fn new(c: Arc<Chunk>, pos: usize) -> Row {
+        let idx = c.physical_idx(pos);
+        Row {
+            c: c,
+            pos: pos,
+            idx: idx,
+        }
+    }
+
+    /// Get the row index of Chunk, which is the index in the columns if the
+    /// chunk has a selection vector.
+    #[inline]
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    /// Get the next row in the chunk, or `None` if this is the last row.
+    #[inline]
+    pub fn next(&self) -> Option<Row> {
+        if self.pos + 1 >= self.c.num_rows() {
+            return None;
+        }
+        Some(Row::new(self.c.clone(), self.pos + 1))
+    }
+
+    /// Get the number of values in the row.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.c.num_cols()
+    }
+
+    /// Get the int64 value with the col_idx. Like the other getters of plain
+    /// values below, a null value is read as the zero value of its type, so
+    /// check `is_null` first if NULL should be told apart from it.
+    #[inline]
+    pub fn get_i64(&self, col_idx: usize) -> i64 {
+        self.c.columns[col_idx].get_i64(self.idx)
+    }
+
+    /// Get the uint64 value with the col_idx, 0 if it's null.
+    #[inline]
+    pub fn get_u64(&self, col_idx: usize) -> u64 {
+        self.c.columns[col_idx].get_u64(self.idx)
+    }
+
+    /// Get the float32 value with the col_idx, 0.0 if it's null.
+    #[inline]
+    pub fn get_f32(&self, col_idx: usize) -> f32 {
+        self.c.columns[col_idx].get_f32(self.idx)
+    }
+
+    /// Get the float64 value with the col_idx, 0.0 if it's null.
+    #[inline]
+    pub fn get_f64(&self, col_idx: usize) -> f64 {
+        self.c.columns[col_idx].get_f64(self.idx)
+    }
+
+    /// Get the string value with the col_idx, it's copied, so prefer
+    /// `get_str_ref` unless an owned string is needed.
+    #[inline]
+    pub fn get_str(&self, col_idx: usize) -> Result<String> {
+        self.get_str_ref(col_idx).map(|s| s.to_owned())
+    }
+
+    /// Get the string value with the col_idx without copying it, an error is
+    /// returned if it isn't valid UTF-8, see `get_bytes` for the raw bytes.
+    /// It's empty if the value is null.
+    #[inline]
+    pub fn get_str_ref(&self, col_idx: usize) -> Result<&str> {
+        self.c.columns[col_idx].get_str_ref(self.idx)
+    }
+
+    /// Get the bytes value with the col_idx, it's empty if the value is null.
+    #[inline]
+    pub fn get_bytes(&self, col_idx: usize) -> &[u8] {
+        self.c.columns[col_idx].get_bytes(self.idx)
+    }
+
+    /// Get the decimal value with the col_idx, `None` if it's null. Like the
+    /// other typed getters below, an error is returned if the column doesn't
+    /// hold decimals.
+    #[inline]
+    pub fn get_decimal(&self, col_idx: usize) -> Result<Option<Decimal>> {
+        self.c.columns[col_idx]
+            .get_decimal(self.idx)
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+
+    /// Get the time value with the col_idx, `tz` is the time zone of the result.
+    #[inline]
+    pub fn get_time(&self, col_idx: usize, tz: &FixedOffset) -> Result<Option<Time>> {
+        self.c.columns[col_idx]
+            .get_time(self.idx, tz)
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+
+    /// Get the duration value with the col_idx, the fsp isn't stored in the
+    /// chunk, so it should be provided by the caller.
+    #[inline]
+    pub fn get_duration(&self, col_idx: usize, fsp: i8) -> Result<Option<Duration>> {
+        self.c.columns[col_idx]
+            .get_duration(self.idx, fsp)
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+
+    /// Get the json value with the col_idx without cloning it.
+    #[inline]
+    pub fn get_json(&self, col_idx: usize) -> Result<Option<&Json>> {
+        self.c.columns[col_idx]
+            .get_json(self.idx)
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+
+    /// Get the name and the value of the enum with the col_idx without
+    /// copying the name, see `Chunk::append_enum`.
+    #[inline]
+    pub fn get_enum(&self, col_idx: usize) -> Result<Option<(&[u8], u64)>> {
+        self.c.columns[col_idx]
+            .get_name_value(self.idx, "enum")
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+
+    /// Get the name and the value of the set with the col_idx without
+    /// copying the name, see `Chunk::append_set`.
+    #[inline]
+    pub fn get_set(&self, col_idx: usize) -> Result<Option<(&[u8], u64)>> {
+        self.c.columns[col_idx]
+            .get_name_value(self.idx, "set")
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+
+    /// Get the interface value with the col_idx, `Datum::Null` if it's null.
+    #[inline]
+    pub fn get_interface(&self, col_idx: usize) -> Datum {
+        self.c.columns[col_idx].get_interface(self.idx)
+    }
+
+    /// Return whether the value with the col_idx is null. The row must be in
+    /// the chunk, see `Column::is_null` for the rows out of range.
+    #[inline]
+    pub fn is_null(&self, col_idx: usize) -> bool {
+        self.c.columns[col_idx].is_null(self.idx)
+    }
+
+    /// Get the datum of the column with the specified type in the row, `tz`
+    /// is the time zone of the time values. If the chunk keeps the field
+    /// types, `fp` must be of the same type as the column. The integers are
+    /// read with the signedness they are stored with if it's known, see
+    /// `Column::unsigned`.
+    pub fn get_datum(&self, col_idx: usize, fp: &FieldType, tz: &FixedOffset) -> Result<Datum> {
+        self.c.columns[col_idx].check_field_type(col_idx, fp)?;
+        self.get_datum_as(col_idx, fp, tz)
+    }
+
+    /// Get the datum of the column with the field type kept in the chunk,
+    /// see `Chunk::from_field_types`.
+    pub fn get_typed_datum(&self, col_idx: usize, tz: &FixedOffset) -> Result<Datum> {
+        match self.c.columns[col_idx].field_type {
+            Some(ref fp) => self.get_datum_as(col_idx, fp, tz),
+            None => Err(invalid_type!("column {} has no field type", col_idx)),
+        }
+    }
+
+    /// Compare the row with `other` by the columns in `order_cols`, which are
+    /// the column indexes with whether they are sorted in descending order.
+    /// `fts` are the field types of the columns, the rows can be of different
+    /// chunks as long as the chunks are of the same field types.
+    ///
+    /// NULL is smaller than any other value. The numbers are compared from the
+    /// column buffers without building datums, the strings are compared
+    /// bytewise since all the collations supported are binary, and the enums
+    /// and sets are compared by their values.
+    pub fn cmp(
+        &self,
+        other: &Row,
+        order_cols: &[(usize, bool)],
+        fts: &[FieldType],
+    ) -> Result<Ordering> {
+        for &(col_idx, desc) in order_cols {
+            let ord = self.cmp_col(other, col_idx, &fts[col_idx])?;
+            if ord != Ordering::Equal {
+                return Ok(if desc { ord.reverse() } else { ord });
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+
+    fn cmp_col(&self, other: &Row, col_idx: usize, fp: &FieldType) -> Result<Ordering> {
+        let (lhs, rhs) = (&self.c.columns[col_idx], &other.c.columns[col_idx]);
+        for col in &[lhs, rhs] {
+            col.check_field_type(col_idx, fp)?;
+            col.check_kind(col_idx, fp)?;
+        }
+        match (self.is_null(col_idx), other.is_null(col_idx)) {
+            (true, true) => return Ok(Ordering::Equal),
+            (true, false) => return Ok(Ordering::Less),
+            (false, true) => return Ok(Ordering::Greater),
+            (false, false) => {}
+        }
+        let ord = match fp.get_tp() as u8 {
+            types::TINY |
+            types::SHORT |
+            types::INT24 |
+            types::LONG |
+            types::LONG_LONG |
+            types::YEAR => cmp_int(
+                lhs.get_int(self.idx, col_idx, fp),
+                rhs.get_int(other.idx, col_idx, fp),
+            ),
+            types::FLOAT => cmp_f64(
+                f64::from(self.get_f32(col_idx)),
+                f64::from(other.get_f32(col_idx)),
+            ),
+            types::DOUBLE => cmp_f64(self.get_f64(col_idx), other.get_f64(col_idx)),
+            // durations are stored as their nanoseconds.
+            types::DURATION => lhs.get_i64(self.idx).cmp(&rhs.get_i64(other.idx)),
+            types::NEW_DECIMAL => self.get_decimal(col_idx)?.cmp(&other.get_decimal(col_idx)?),
+            types::DATE | types::DATETIME | types::TIMESTAMP => {
+                // the order doesn't depend on the time zone.
+                let tz = FixedOffset::east(0);
+                self.get_time(col_idx, &tz)?.cmp(&other.get_time(col_idx, &tz)?)
+            }
+            types::ENUM => {
+                let value = |r: &Row| r.get_enum(col_idx).map(|e| e.map(|(_, v)| v));
+                value(self)?.cmp(&value(other)?)
+            }
+            types::SET => {
+                let value = |r: &Row| r.get_set(col_idx).map(|s| s.map(|(_, v)| v));
+                value(self)?.cmp(&value(other)?)
+            }
+            _ if lhs.is_var_len() => self.get_bytes(col_idx).cmp(other.get_bytes(col_idx)),
+            _ => {
+                let tz = FixedOffset::east(0);
+                let l = self.get_datum_as(col_idx, fp, &tz)?;
+                let r = other.get_datum_as(col_idx, fp, &tz)?;
+                l.cmp(&EvalContext::default(), &r)?
+            }
+        };
+        Ok(ord)
+    }
+
+    /// Get the datums of all the columns in the row, `fts` are the field
+    /// types of the columns, see `get_datum`.
+    pub fn get_datums(&self, fts: &[FieldType], tz: &FixedOffset) -> Result<Vec<Datum>> {
+        if fts.len() != self.len() {
+            return Err(box_err!(
+                "expect {} field types, but got {}",
+                self.len(),
+                fts.len()
+            ));
+        }
+        fts.iter()
+            .enumerate()
+            .map(|(col_idx, fp)| self.get_datum(col_idx, fp, tz))
+            .collect()
+    }
+
+    /// Encode the row into `buf` as the value datums of the columns, which
+    /// is how the rows are sent in `SelectResponse`.
+    pub fn encode(&self, fts: &[FieldType], tz: &FixedOffset, buf: &mut Vec<u8>) -> Result<()> {
+        let datums = self.get_datums(fts, tz)?;
+        buf.encode(&datums, false)
+    }
+
+    fn get_datum_as(&self, col_idx: usize, fp: &FieldType, tz: &FixedOffset) -> Result<Datum> {
+        self.c.columns[col_idx]
+            .get_datum(self.idx, col_idx, fp, tz)
+            .map_err(|e| invalid_type!("column {}: {}", col_idx, e))
+    }
+}
+
+// Compare two integers with the signedness they are read with.
+fn cmp_int((l, l_unsigned): (i64, bool), (r, r_unsigned): (i64, bool)) -> Ordering {
+    match (l_unsigned, r_unsigned) {
+        (false, false) => l.cmp(&r),
+        (false, true) if l < 0 => Ordering::Less,
+        (true, false) if r < 0 => Ordering::Greater,
+        _ => (l as u64).cmp(&(r as u64)),
+    }
+}
+
+// NaN can't be stored in MySQL, it's regarded as equal to any value.
+fn cmp_f64(l: f64, r: f64) -> Ordering {
+    l.partial_cmp(&r).unwrap_or(Ordering::Equal)
+}
+
+/// `ChunkEncoder` encodes a chunk into TiDB's chunk format incrementally,
+/// every call of `encode_next` produces a complete encoded chunk of the next
+/// rows, so one logical result can be split into multiple wire chunks at
+/// arbitrary row boundaries without re-walking the columns.
+pub struct ChunkEncoder<'a> {
+    chunk: &'a Chunk,
+    cursor: usize,
+    // the position in the data of every column where the next row begins.
+    data_cursors: Vec<usize>,
+}
+
+impl<'a> ChunkEncoder<'a> {
+    pub fn new(chunk: &'a Chunk, tps: &[i32]) -> Result<ChunkEncoder<'a>> {
+        chunk.check_no_sel()?;
+        if tps.len() != chunk.num_cols() {
+            return Err(box_err!(
+                "expect {} field types, but got {}",
+                chunk.num_cols(),
+                tps.len()
+            ));
+        }
+        for (i, (col, &tp)) in chunk.columns.iter().zip(tps).enumerate() {
+            if !col.is_fixed() && !col.is_var_len() {
+                return Err(invalid_type!("column {} with type {} can't be encoded", i, tp));
+            }
+            let expect = Column::new(tp, 0);
+            if !expect.is_same_kind(col) {
+                return Err(invalid_type!("column {} doesn't match type {}", i, tp));
+            }
+        }
+        Ok(ChunkEncoder::unchecked(chunk))
+    }
+
+    // Create an encoder of a chunk which is known to be encodable.
+    fn unchecked(chunk: &'a Chunk) -> ChunkEncoder<'a> {
+        ChunkEncoder {
+            chunk: chunk,
+            cursor: 0,
+            data_cursors: vec![0; chunk.num_cols()],
+        }
+    }
+
+    /// Get the number of rows which have not been encoded yet.
+    #[inline]
+    pub fn remaining_rows(&self) -> usize {
+        self.chunk.num_rows() - self.cursor
+    }
+
+    /// Encode at most `n` following rows into `buf` as a complete chunk,
+    /// returns the number of rows encoded. Nothing is written if all rows
+    /// have been encoded.
+    pub fn encode_next(&mut self, n: usize, buf: &mut Vec<u8>) -> Result<usize> {
+        let begin = self.cursor;
+        let end = begin + cmp::min(n, self.remaining_rows());
+        if begin == end {
+            return Ok(0);
+        }
+        for (col, data_cursor) in self.chunk.columns.iter().zip(&mut self.data_cursors) {
+            *data_cursor = col.encode_rows(begin, end, *data_cursor, buf)?;
+        }
+        self.cursor = end;
+        Ok(end - begin)
+    }
+
+    /// Encode as many following rows as fit in `max_bytes` bytes into `buf`
+    /// as a complete chunk, returns the number of rows encoded. At least one
+    /// row is encoded unless all rows have been encoded, even if it doesn't
+    /// fit. The size of the rows is computed from the offsets and the null
+    /// bitmaps, so nothing is encoded twice.
+    pub fn encode_next_within(&mut self, max_bytes: usize, buf: &mut Vec<u8>) -> Result<usize> {
+        let begin = self.cursor;
+        // the size grows with the rows, so the most rows that fit can be
+        // searched by bisection.
+        let (mut lo, mut hi) = (1, self.remaining_rows());
+        while lo < hi {
+            let mid = lo + (hi - lo + 1) / 2;
+            if self.chunk.encoded_size_of_rows(begin, begin + mid) <= max_bytes {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        self.encode_next(lo, buf)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{i64, u32, u64, usize};
+
+    use byteorder::{ByteOrder, LittleEndian};
+    use test::{black_box, Bencher};
+
+    use coprocessor::codec::datum;
+    use coprocessor::codec::datum::DatumDecoder;
+    use coprocessor::codec::mysql::NANOS_PER_SEC;
+
+    use super::*;
+
+    pub fn field_type(tp: u8) -> FieldType {
+        let mut fp = FieldType::new();
+        fp.set_tp(tp as i32);
+        fp
+    }
+
+    #[test]
+    fn test_chunk() {
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            field_type(types::VARCHAR),
+            field_type(types::FLOAT),
+            field_type(types::NEW_DECIMAL),
+        ];
+        let tps: Vec<i32> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let tz = FixedOffset::east(0);
+        let dec: Decimal = "123.456".parse().unwrap();
+        let rows = vec![
+            vec![
+                Datum::I64(1),
+                Datum::Bytes(b"abc".to_vec()),
+                Datum::F64(1.5),
+                Datum::Dec(dec.clone()),
+            ],
+            vec![Datum::Null; 4],
+        ];
+        let chunk = Chunk::from_datum_rows(&rows, &fields).unwrap();
+        assert_eq!(chunk.num_cols(), 4);
+        assert_eq!(chunk.num_rows(), 2);
+
+        let c = ArcChunk::new(chunk);
+        assert_eq!(c.to_datum_rows(&fields, &tz).unwrap(), rows);
+        let row = c.begin();
+        assert_eq!(row.idx(), 0);
+        assert_eq!(row.len(), 4);
+        assert_eq!(row.get_i64(0), 1);
+        assert_eq!(row.get_str(1).unwrap(), "abc");
+        assert_eq!(row.get_bytes(1), b"abc");
+        assert_eq!(row.get_f32(2), 1.5);
+        assert_eq!(row.get_datum(2, &fields[2], &tz).unwrap(), Datum::F64(1.5));
+        assert_eq!(row.get_decimal(3).unwrap(), Some(dec.clone()));
+        assert_eq!(row.get_datum(0, &fields[0], &tz).unwrap(), Datum::I64(1));
+        assert_eq!(row.get_datum(3, &fields[3], &tz).unwrap(), Datum::Dec(dec.clone()));
+
+        let row = row.next().unwrap();
+        assert_eq!(row.idx(), 1);
+        for (i, fp) in fields.iter().enumerate() {
+            assert!(row.is_null(i));
+            assert_eq!(row.get_datum(i, fp, &tz).unwrap(), Datum::Null);
+        }
+        assert_eq!(row.get_i64(0), 0);
+        assert_eq!(row.get_bytes(1), b"");
+        assert!(row.next().is_none());
+        assert_eq!(row.idx() + 1, c.end().idx());
+
+        let mut chunk2 = Chunk::new_chunk(&tps);
+        chunk2.append_row(&c.get_row(0)).unwrap();
+        chunk2.append_row(&c.get_row(1)).unwrap();
+        assert_eq!(chunk2, *c.c);
+
+        let mut chunk3 = Chunk::new_chunk(&tps);
+        chunk3.append(&c.c, 0, 2).unwrap();
+        assert_eq!(chunk3, *c.c);
+        chunk3.truncate_to(1);
+        assert_eq!(chunk3.num_rows(), 1);
+        for col in &chunk3.columns {
+            assert_eq!(col.null_cnt, 0);
+        }
+
+        chunk3.reset();
+        assert_eq!(chunk3.num_rows(), 0);
+    }
+
+    pub const ENCODE_TPS: &'static [i32] = &[types::LONG_LONG as i32, types::VARCHAR as i32];
+
+    #[test]
+    fn test_chunk_capacity() {
+        let chunk = Chunk::new_chunk(ENCODE_TPS);
+        assert_eq!(chunk.capacity(), usize::MAX);
+        assert!(!chunk.is_full());
+
+        let mut chunk = Chunk::new_chunk_with_capacity(ENCODE_TPS, 3);
+        for round in 0..2 {
+            assert_eq!(chunk.required_rows(), 3, "{}", round);
+            while !chunk.is_full() {
+                let rows = chunk.num_rows() as i64;
+                chunk.append_i64(0, rows).unwrap();
+                chunk.append_null(1);
+            }
+            assert_eq!(chunk.num_rows(), 3);
+            assert_eq!(chunk.required_rows(), 0);
+
+            // the capacity is kept after resetting.
+            chunk.reset();
+            assert_eq!(chunk.capacity(), 3);
+            assert!(!chunk.is_full());
+        }
+
+        // fill the chunk with the rows of another one.
+        let src = new_encode_chunk(5);
+        let required = chunk.required_rows();
+        chunk.append(&src, 0, required).unwrap();
+        assert!(chunk.is_full());
+        let mut row_chunk = Chunk::new_chunk_with_capacity(ENCODE_TPS, 2);
+        let src = ArcChunk::new(src);
+        for row in &src {
+            if row_chunk.is_full() {
+                break;
+            }
+            row_chunk.append_row(&row).unwrap();
+        }
+        assert_eq!(row_chunk.num_rows(), 2);
+        assert_eq!(row_chunk.project(&[1]).capacity(), 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the capacity is 1")]
+    fn test_append_to_full_chunk() {
+        let mut chunk = Chunk::new_chunk_with_capacity(ENCODE_TPS, 1);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.append_i64(0, 2).unwrap();
+    }
+
+    #[test]
+    fn test_project() {
+        let tps = &[
+            types::LONG_LONG as i32,
+            types::VARCHAR as i32,
+            types::DOUBLE as i32,
+        ];
+        let mut chunk = Chunk::new_chunk(tps);
+        for i in 0..10 {
+            chunk.append_i64(0, i).unwrap();
+            chunk.append_bytes(1, format!("{}", i).as_bytes()).unwrap();
+            if i % 2 == 0 {
+                chunk.append_null(2);
+            } else {
+                chunk.append_f64(2, i as f64).unwrap();
+            }
+        }
+        let offsets = [2, 0, 2, 2];
+        let mut projected = chunk.project(&offsets);
+        assert_eq!(projected.num_cols(), offsets.len());
+        assert_eq!(projected.num_rows(), chunk.num_rows());
+        for (col, &offset) in projected.columns.iter().zip(&offsets) {
+            assert_eq!(col, &chunk.columns[offset]);
+        }
+
+        // the repeated columns don't share their buffers.
+        projected.append_f64(0, 1.5).unwrap();
+        projected.append_null(3);
+        assert_eq!(projected.columns[0].len(), 11);
+        assert!(!projected.columns[0].is_null(10));
+        assert_eq!(projected.columns[2].len(), 10);
+        assert_eq!(projected.columns[2], chunk.columns[2]);
+        assert_eq!(projected.columns[3].len(), 11);
+        assert!(projected.columns[3].is_null(10));
+        assert_eq!(chunk.columns[2].len(), 10);
+
+        // the rows are kept without any column.
+        let projected = chunk.project(&[]);
+        assert_eq!(projected.num_cols(), 0);
+        assert_eq!(projected.num_rows(), 10);
+    }
+
+    #[test]
+    fn test_prune_columns() {
+        let tps = &[
+            types::LONG_LONG as i32,
+            types::VARCHAR as i32,
+            types::DOUBLE as i32,
+        ];
+        let mut chunk = Chunk::new_chunk(tps);
+        for i in 0..10 {
+            chunk.append_i64(0, i).unwrap();
+            chunk.append_bytes(1, format!("{}", i).as_bytes()).unwrap();
+            if i % 2 == 0 {
+                chunk.append_null(2);
+            } else {
+                chunk.append_f64(2, i as f64).unwrap();
+            }
+        }
+        let origin = chunk.clone();
+
+        let mut pruned = chunk.clone();
+        pruned.prune_columns(&[2]);
+        assert_eq!(pruned.num_cols(), 1);
+        assert_eq!(pruned.num_rows(), 10);
+        assert_eq!(pruned.column(0), origin.column(2));
+        let mut src = Chunk::new_chunk(&[types::DOUBLE as i32]);
+        src.append_f64(0, 0.5).unwrap();
+        let src = ArcChunk::new(src);
+        pruned.append_row(&src.get_row(0)).unwrap();
+        assert_eq!(pruned.num_rows(), 11);
+        assert_eq!(pruned.column(0).get_f64(10), 0.5);
+
+        // the repeated columns are copied.
+        let mut pruned = chunk.clone();
+        pruned.prune_columns(&[1, 0, 1]);
+        assert_eq!(pruned.num_rows(), 10);
+        assert_eq!(pruned.column(0), origin.column(1));
+        assert_eq!(pruned.column(1), origin.column(0));
+        assert_eq!(pruned.column(2), origin.column(1));
+        pruned.append_null(0);
+        assert_eq!(pruned.column(0).len(), 11);
+        assert_eq!(pruned.column(2).len(), 10);
+
+        chunk.prune_columns(&[]);
+        assert_eq!(chunk.num_cols(), 0);
+        assert_eq!(chunk.num_rows(), 10);
+        let empty = ArcChunk::new(origin.project(&[]));
+        chunk.append_row(&empty.get_row(0)).unwrap();
+        assert_eq!(chunk.num_rows(), 11);
+        chunk.append(&empty.c, 0, 5).unwrap();
+        assert_eq!(chunk.num_rows(), 16);
+        chunk.truncate_to(3);
+        assert_eq!(chunk.num_rows(), 3);
+        chunk.reset();
+        assert_eq!(chunk.num_rows(), 0);
+    }
+
+    pub fn new_encode_chunk(rows: usize) -> Chunk {
+        let mut chunk = Chunk::new_chunk(ENCODE_TPS);
+        for i in 0..rows {
+            if i % 3 == 0 {
+                chunk.append_null(0);
+            } else {
+                chunk.append_i64(0, i as i64).unwrap();
+            }
+            if i % 5 == 0 {
+                chunk.append_null(1);
+            } else {
+                chunk.append_bytes(1, format!("{}", i).repeat(i % 4).as_bytes()).unwrap();
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn test_chunk_encoder() {
+        let rows = 100;
+        let chunk = new_encode_chunk(rows);
+        let mut single = vec![];
+        ChunkEncoder::new(&chunk, ENCODE_TPS)
+            .unwrap()
+            .encode_next(rows, &mut single)
+            .unwrap();
+        for &n in &[1, 7, 64, rows] {
+            let mut encoder = ChunkEncoder::new(&chunk, ENCODE_TPS).unwrap();
+            let mut begin = 0;
+            while encoder.remaining_rows() > 0 {
+                let mut buf = vec![];
+                let cnt = encoder.encode_next(n, &mut buf).unwrap();
+                assert_eq!(cnt, ::std::cmp::min(n, rows - begin));
+                // the piece should be the same as encoding a chunk which
+                // only contains the rows of the piece.
+                let mut expect = Chunk::new_chunk(ENCODE_TPS);
+                expect.append(&chunk, begin, begin + cnt).unwrap();
+                let mut expect_buf = vec![];
+                ChunkEncoder::new(&expect, ENCODE_TPS)
+                    .unwrap()
+                    .encode_next(cnt, &mut expect_buf)
+                    .unwrap();
+                assert_eq!(buf, expect_buf, "piece size {} at {}", n, begin);
+                if n == rows {
+                    assert_eq!(buf, single);
+                }
+                begin += cnt;
+            }
+            assert_eq!(begin, rows);
+            let mut buf = vec![];
+            assert_eq!(encoder.encode_next(n, &mut buf).unwrap(), 0);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_chunk_encoder_huge_count() {
+        let rows = 20;
+        let chunk = new_encode_chunk(rows);
+        let mut encoder = ChunkEncoder::new(&chunk, ENCODE_TPS).unwrap();
+        let mut buf = vec![];
+        assert_eq!(encoder.encode_next(7, &mut buf).unwrap(), 7);
+        // the count must not overflow when added to the cursor.
+        buf.clear();
+        assert_eq!(encoder.encode_next(usize::MAX, &mut buf).unwrap(), rows - 7);
+        let mut expect = Chunk::new_chunk(ENCODE_TPS);
+        expect.append(&chunk, 7, rows).unwrap();
+        let mut expect_buf = vec![];
+        ChunkEncoder::new(&expect, ENCODE_TPS)
+            .unwrap()
+            .encode_next(usize::MAX, &mut expect_buf)
+            .unwrap();
+        assert_eq!(buf, expect_buf);
+        assert_eq!(encoder.encode_next(usize::MAX, &mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_chunk_encoder_bitmap() {
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        // 0b1010_1010, 0b0101_0101, 0b1
+        for i in 0..17 {
+            if (i < 8 && i % 2 == 1) || (i >= 8 && i % 2 == 0) {
+                chunk.append_i64(0, i).unwrap();
+            } else {
+                chunk.append_null(0);
+            }
+        }
+        let mut encoder = ChunkEncoder::new(&chunk, &[types::LONG_LONG as i32]).unwrap();
+        let mut buf = vec![];
+        // rows [0,3): null, not null, null
+        encoder.encode_next(3, &mut buf).unwrap();
+        assert_eq!(&buf[..9], &[3, 0, 0, 0, 2, 0, 0, 0, 0b010]);
+        buf.clear();
+        // rows [3,13) crosses the byte boundary.
+        encoder.encode_next(10, &mut buf).unwrap();
+        assert_eq!(&buf[..10], &[10, 0, 0, 0, 4, 0, 0, 0, 0b1011_0101, 0b10]);
+        buf.clear();
+        // rows [13,17)
+        encoder.encode_next(10, &mut buf).unwrap();
+        assert_eq!(&buf[..9], &[4, 0, 0, 0, 2, 0, 0, 0, 0b1010]);
+    }
+
+    #[test]
+    fn test_chunk_encoder_invalid_types() {
+        let tps = &[types::JSON as i32];
+        let chunk = Chunk::new_chunk(tps);
+        assert!(ChunkEncoder::new(&chunk, tps).is_err());
+
+        let chunk = new_encode_chunk(3);
+        assert!(ChunkEncoder::new(&chunk, &ENCODE_TPS[..1]).is_err());
+        let tps = &[types::LONG_LONG as i32, types::DOUBLE as i32];
+        assert!(ChunkEncoder::new(&chunk, tps).is_err());
+    }
+
+    #[test]
+    fn test_encode_to() {
+        let mut chunk = Chunk::new_chunk(ENCODE_TPS);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.append_str(1, "ab").unwrap();
+        chunk.append_null(0);
+        chunk.append_null(1);
+        chunk.append_i64(0, -1).unwrap();
+        chunk.append_str(1, "中").unwrap();
+        let mut buf = vec![];
+        chunk.encode_to(&mut buf).unwrap();
+        let expect: &[u8] = &[
+            // length, null count and null bitmap.
+            3, 0, 0, 0, 1, 0, 0, 0, 0b101,
+            // data
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            // length, null count and null bitmap.
+            3, 0, 0, 0, 1, 0, 0, 0, 0b101,
+            // offsets, which are i64 like TiDB's.
+            0, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            5, 0, 0, 0, 0, 0, 0, 0,
+            // data
+            b'a', b'b', 0xe4, 0xb8, 0xad,
+        ];
+        assert_eq!(buf.as_slice(), expect);
+
+        let mut encoded = vec![];
+        let mut encoder = ChunkEncoder::new(&chunk, ENCODE_TPS).unwrap();
+        encoder.encode_next(3, &mut encoded).unwrap();
+        assert_eq!(buf, encoded);
+
+        // an empty chunk.
+        let mut buf = vec![];
+        Chunk::new_chunk(ENCODE_TPS).encode_to(&mut buf).unwrap();
+        assert_eq!(buf, vec![0; 4 + 4 + 4 + 4 + 8]);
+
+        let chunk = Chunk::new_chunk(&[types::LONG_LONG as i32, types::JSON as i32]);
+        let mut buf = vec![];
+        assert!(chunk.encode_to(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_encode_chunked() {
+        let fields: Vec<_> = ENCODE_TPS.iter().map(|&tp| field_type(tp as u8)).collect();
+        let mut chunk = Chunk::new_chunk(ENCODE_TPS);
+        for i in 0..30 {
+            if i % 5 == 0 {
+                chunk.append_null(0);
+            } else {
+                chunk.append_i64(0, i).unwrap();
+            }
+            if i == 7 {
+                chunk.append_str(1, "x".repeat(200)).unwrap();
+            } else if i % 4 == 0 {
+                chunk.append_null(1);
+            } else {
+                chunk.append_str(1, "中".repeat(i as usize % 3)).unwrap();
+            }
+        }
+
+        let budget = 100;
+        let pieces = chunk.encode_chunked(budget).unwrap();
+        let mut decoded = Chunk::new_chunk(ENCODE_TPS);
+        let mut oversized = 0;
+        for piece in &pieces {
+            let c = Chunk::decode(piece, &fields).unwrap();
+            assert_eq!(c.encoded_size_hint(), piece.len());
+            if piece.len() > budget {
+                // the large row is encoded alone.
+                assert_eq!(c.num_rows(), 1);
+                assert_eq!(c.columns[1].get_bytes(0).len(), 200);
+                oversized += 1;
+            }
+            // the next row doesn't fit in the piece.
+            let (begin, end) = (decoded.num_rows(), decoded.num_rows() + c.num_rows());
+            if end < chunk.num_rows() {
+                assert!(chunk.encoded_size_of_rows(begin, end + 1) > budget);
+            }
+            decoded.append_all(&c).unwrap();
+        }
+        assert_eq!(oversized, 1);
+        assert_eq!(decoded, chunk);
+
+        // every row is encoded alone if none fits.
+        assert_eq!(chunk.encode_chunked(0).unwrap().len(), chunk.num_rows());
+        {
+            let mut encoder = ChunkEncoder::new(&chunk, ENCODE_TPS).unwrap();
+            let mut buf = vec![];
+            assert_eq!(encoder.encode_next_within(usize::MAX, &mut buf).unwrap(), 30);
+            assert_eq!(encoder.encode_next_within(usize::MAX, &mut buf).unwrap(), 0);
+            let mut expect = vec![];
+            chunk.encode_to(&mut expect).unwrap();
+            assert_eq!(buf, expect);
+        }
+
+        assert!(Chunk::new_chunk(ENCODE_TPS).encode_chunked(budget).unwrap().is_empty());
+        chunk.set_sel(vec![0, 1]);
+        assert!(chunk.encode_chunked(budget).is_err());
+    }
+
+    #[test]
+    fn test_encoded_size_hint() {
+        let encoded_len = |chunk: &Chunk| {
+            let mut buf = vec![];
+            chunk.encode_to(&mut buf).unwrap();
+            buf.len()
+        };
+        let mut chunk = Chunk::new_chunk(ENCODE_TPS);
+        assert_eq!(chunk.encoded_size_hint(), encoded_len(&chunk));
+        for i in 0..20 {
+            if i % 3 == 0 {
+                chunk.append_null(0);
+            } else {
+                chunk.append_i64(0, i).unwrap();
+            }
+            if i % 4 == 0 {
+                chunk.append_null(1);
+            } else {
+                chunk.append_str(1, "中".repeat(i as usize)).unwrap();
+            }
+            assert_eq!(chunk.encoded_size_hint(), encoded_len(&chunk));
+        }
+
+        // the columns without nulls, and the fixed lengths other than 8.
+        let fields = vec![
+            field_type(types::FLOAT),
+            field_type(types::NEW_DECIMAL),
+            field_type(types::DATETIME),
+            field_type(types::BLOB),
+        ];
+        let tps: Vec<_> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let mut chunk = Chunk::new_chunk(&tps);
+        let t = Time::parse_utc_datetime("2017-10-16 12:34:56", 0).unwrap();
+        for i in 0..10 {
+            chunk.append_f32(0, i as f32).unwrap();
+            chunk.append_decimal(1, &Decimal::from(i)).unwrap();
+            chunk.append_time(2, &t).unwrap();
+            chunk.append_bytes(3, &vec![0; i as usize]).unwrap();
+        }
+        assert_eq!(chunk.encoded_size_hint(), encoded_len(&chunk));
+        chunk.truncate_to(3);
+        assert_eq!(chunk.encoded_size_hint(), encoded_len(&chunk));
+
+        // with a selection vector, it's the size before reconstructing.
+        let mut chunk = Chunk::new_chunk(ENCODE_TPS);
+        for i in 0..10 {
+            chunk.append_i64(0, i).unwrap();
+            chunk.append_null(1);
+        }
+        chunk.set_sel(vec![1, 5, 6]);
+        let hint = chunk.encoded_size_hint();
+        chunk.reconstruct();
+        assert!(hint >= encoded_len(&chunk));
+        assert_eq!(chunk.encoded_size_hint(), encoded_len(&chunk));
+
+        // the interface columns can hold the encoded datums.
+        let mut chunk = Chunk::new_chunk(&[types::JSON as i32]);
+        let datums = vec![
+            Datum::Json(r#"{"a": [1, "b", null]}"#.parse().unwrap()),
+            Datum::Null,
+            Datum::Json("1".parse().unwrap()),
+        ];
+        for d in &datums {
+            chunk.append_interface(0, d.clone());
+        }
+        let data_len: usize = datums
+            .iter()
+            .map(|d| datum::encode_value(&[d.clone()]).unwrap().len())
+            .sum();
+        assert!(chunk.encoded_size_hint() >= 8 + 1 + 4 * 8 + data_len);
+    }
+
+    #[test]
+    fn test_mut_row() {
+        let tz = FixedOffset::east(0);
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            field_type(types::VARCHAR),
+            field_type(types::NEW_DECIMAL),
+            field_type(types::JSON),
+        ];
+        let dec: Decimal = "1.5".parse().unwrap();
+        let datums = vec![
+            Datum::I64(1),
+            Datum::Bytes(b"abc".to_vec()),
+            Datum::Dec(dec.clone()),
+            Datum::Null,
+        ];
+        let mut row = MutRow::from_datums(&datums, &fields).unwrap();
+        let old = row.as_row();
+        for (i, fp) in fields.iter().enumerate() {
+            assert_eq!(old.get_datum(i, fp, &tz).unwrap(), datums[i]);
+        }
+
+        row.set_datum(0, &Datum::I64(-7)).unwrap();
+        row.set_datum(2, &Datum::Null).unwrap();
+        row.set_datum(3, &Datum::I64(3)).unwrap();
+        let new = row.as_row();
+        assert_eq!(new.get_datum(0, &fields[0], &tz).unwrap(), Datum::I64(-7));
+        assert!(new.is_null(2));
+        assert_eq!(new.get_interface(3), Datum::I64(3));
+        // the rows got before keep the old values.
+        assert_eq!(old.get_i64(0), 1);
+        assert_eq!(old.get_decimal(2).unwrap(), Some(dec.clone()));
+        drop(old);
+        drop(new);
+
+        for col in &row.c.columns {
+            assert_eq!(col.len(), 1);
+        }
+        assert!(MutRow::from_datums(&datums[..1], &fields).is_err());
+        // a datum which can't be stored leaves the row unchanged.
+        assert!(row.set_datum(0, &Datum::Bytes(b"1".to_vec())).is_err());
+        assert_eq!(row.as_row().get_i64(0), -7);
+        assert_eq!(row.c.columns[0].len(), 1);
+    }
+
+    #[test]
+    fn test_mut_row_var_len() {
+        let fields = vec![field_type(types::VARCHAR), field_type(types::LONG_LONG)];
+        let mut row =
+            MutRow::from_datums(&[Datum::Bytes(b"a".to_vec()), Datum::I64(1)], &fields).unwrap();
+        let values = vec![
+            // growing
+            Some("hello world"),
+            Some("hello world, hello world"),
+            // shrinking
+            Some("hi"),
+            Some(""),
+            None,
+            Some("xyz"),
+            None,
+            None,
+            Some("hello world"),
+        ];
+        for v in values {
+            let d = v.map_or(Datum::Null, |v| Datum::Bytes(v.as_bytes().to_vec()));
+            row.set_datum(0, &d).unwrap();
+            {
+                let col = &row.c.columns[0];
+                let len = v.map_or(0, |v| v.len());
+                assert_eq!(col.len(), 1);
+                assert_eq!(col.var_offsets, vec![0, len as u32]);
+                assert_eq!(col.data.len(), len);
+                assert_eq!(col.null_cnt, if v.is_some() { 0 } else { 1 });
+                assert_eq!(col.null_bitmap.len(), 1);
+            }
+            let r = row.as_row();
+            match v {
+                Some(v) => assert_eq!(r.get_bytes(0), v.as_bytes()),
+                None => assert!(r.is_null(0)),
+            }
+            // the other column isn't affected.
+            assert_eq!(r.get_i64(1), 1);
+        }
+
+        // the row can be appended to a chunk.
+        let mut chunk = Chunk::new_chunk(&[types::VARCHAR as i32, types::LONG_LONG as i32]);
+        chunk.append_row(&row.as_row()).unwrap();
+        row.set_datum(0, &Datum::Bytes(b"b".to_vec())).unwrap();
+        chunk.append_row(&row.as_row()).unwrap();
+        let chunk = ArcChunk::new(chunk);
+        assert_eq!(chunk.get_row(0).get_bytes(0), b"hello world");
+        assert_eq!(chunk.get_row(1).get_bytes(0), b"b");
+    }
+
+    #[test]
+    fn test_append_partial_row() {
+        let mut src = Chunk::new_chunk(&[types::LONG_LONG as i32, types::VARCHAR as i32]);
+        src.append_i64(0, 1).unwrap();
+        src.append_bytes(1, b"a").unwrap();
+        src.append_null(0);
+        src.append_bytes(1, b"b").unwrap();
+        let src = ArcChunk::new(src);
+
+        let tps = &[
+            types::DOUBLE as i32,
+            types::LONG_LONG as i32,
+            types::VARCHAR as i32,
+            types::LONG_LONG as i32,
+        ];
+        let mut chunk = Chunk::new_chunk(tps);
+        chunk.append_partial_row(1, &src.get_row(0)).unwrap();
+        chunk.append_partial_row(1, &src.get_row(1)).unwrap();
+        assert_eq!(chunk.num_rows(), 2);
+        for col in &chunk.columns {
+            assert_eq!(col.len(), 2);
+        }
+        let c = ArcChunk::new(chunk);
+        let row = c.get_row(0);
+        assert!(row.is_null(0));
+        assert_eq!(row.get_i64(1), 1);
+        assert_eq!(row.get_bytes(2), b"a");
+        assert!(row.is_null(3));
+        let row = c.get_row(1);
+        assert!(row.is_null(0));
+        assert!(row.is_null(1));
+        assert_eq!(row.get_bytes(2), b"b");
+        assert!(row.is_null(3));
+
+        // the row fits exactly at the end.
+        let mut chunk = Chunk::new_chunk(&tps[1..]);
+        chunk.append_partial_row(1, &src.get_row(0)).unwrap();
+        assert!(ArcChunk::new(chunk).get_row(0).is_null(0));
+    }
+
+    #[test]
+    fn test_append_partial_row_offsets() {
+        let mut src = Chunk::new_chunk(ENCODE_TPS);
+        src.append_i64(0, 7).unwrap();
+        src.append_str(1, "ab").unwrap();
+        let src = ArcChunk::new(src);
+        let tps = &[
+            types::LONG_LONG as i32,
+            types::VARCHAR as i32,
+            types::LONG_LONG as i32,
+            types::VARCHAR as i32,
+            types::LONG_LONG as i32,
+        ];
+        let mut chunk = Chunk::new_chunk(tps);
+        // the row fits at 0 and 2, the kinds don't match at 1 and 3.
+        chunk.append_partial_row(0, &src.get_row(0)).unwrap();
+        chunk.append_partial_row(2, &src.get_row(0)).unwrap();
+        assert!(chunk.append_partial_row(1, &src.get_row(0)).is_err());
+        assert!(chunk.append_partial_row(3, &src.get_row(0)).is_err());
+        let c = ArcChunk::new(chunk);
+        assert_eq!(c.num_rows(), 2);
+        for (row, offset) in c.iter().zip(vec![0, 2]) {
+            assert_eq!(row.get_i64(offset), 7);
+            assert_eq!(row.get_str_ref(offset + 1).unwrap(), "ab");
+            for col_idx in (0..tps.len()).filter(|&i| i < offset || i >= offset + 2) {
+                assert!(row.is_null(col_idx));
+            }
+        }
+
+        // the row doesn't fit, the chunk is untouched.
+        let mut chunk = Chunk::new_chunk(tps);
+        for offset in 4..7 {
+            assert!(chunk.append_partial_row(offset, &src.get_row(0)).is_err());
+        }
+        assert_eq!(chunk.num_rows(), 0);
+        let mut chunk = Chunk::new_chunk(ENCODE_TPS);
+        assert!(chunk.append_partial_row(1, &src.get_row(0)).is_err());
+        assert_eq!(chunk.num_rows(), 0);
+
+        // a full row must have the same number of columns.
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        assert!(chunk.append_row(&src.get_row(0)).is_err());
+        let mut chunk = Chunk::new_chunk(tps);
+        assert!(chunk.append_row(&src.get_row(0)).is_err());
+        assert_eq!(chunk.num_rows(), 0);
+    }
+
+    fn new_decode_chunk(fields: &[FieldType], rows: usize) -> Chunk {
+        let tz = FixedOffset::east(0);
+        let tps: Vec<i32> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let mut chunk = Chunk::new_chunk(&tps);
+        for i in 0..rows {
+            for (col, &tp) in tps.iter().enumerate() {
+                if (i + col) % 4 == 1 {
+                    chunk.append_null(col);
+                    continue;
+                }
+                match tp as u8 {
+                    types::LONG_LONG => chunk.append_i64(col, i as i64 - 3).unwrap(),
+                    types::FLOAT => chunk.append_f32(col, i as f32 / 4.0).unwrap(),
+                    types::DOUBLE => chunk.append_f64(col, -(i as f64) / 8.0).unwrap(),
+                    types::VARCHAR => chunk.append_str(col, "中".repeat(i % 3)).unwrap(),
+                    types::NEW_DECIMAL => {
+                        let d: Decimal = format!("{}.{}", i, i * 7).parse().unwrap();
+                        chunk.append_decimal(col, &d).unwrap()
+                    }
+                    types::DATETIME => {
+                        let s = format!("2017-11-{:02} 12:34:56.{:06}", i % 28 + 1, i);
+                        chunk.append_time(col, &Time::parse_datetime(&s, 6, &tz).unwrap()).unwrap()
+                    }
+                    types::DURATION => {
+                        let d = Duration::from_nanos(i as i64 * 1_000_000_007, 6).unwrap();
+                        chunk.append_duration(col, &d).unwrap()
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn test_decode() {
+        let fields = decode_fields();
+        let tps: Vec<i32> = fields.iter().map(|fp| fp.get_tp()).collect();
+        for &rows in &[0, 1, 3, 8, 17] {
+            let chunk = new_decode_chunk(&fields, rows);
+            let mut buf = vec![];
+            chunk.encode_to(&mut buf).unwrap();
+            let decoded = Chunk::decode(&buf, &fields).unwrap();
+            assert_eq!(decoded.num_rows(), rows);
+            assert_eq!(decoded, chunk);
+            let mut encoded = vec![];
+            decoded.encode_to(&mut encoded).unwrap();
+            assert_eq!(encoded, buf);
+
+            // the chunks split by the encoder.
+            let mut encoder = ChunkEncoder::new(&chunk, &tps).unwrap();
+            let mut merged = Chunk::new_chunk(&tps);
+            loop {
+                let mut buf = vec![];
+                if encoder.encode_next(3, &mut buf).unwrap() == 0 {
+                    break;
+                }
+                let decoded = Chunk::decode(&buf, &fields).unwrap();
+                merged.append(&decoded, 0, decoded.num_rows()).unwrap();
+            }
+            assert_eq!(merged, chunk);
+        }
+    }
+
+    #[test]
+    fn test_decode_corrupted() {
+        let fields: Vec<_> = ENCODE_TPS.iter().map(|&tp| field_type(tp as u8)).collect();
+        let mut buf = vec![];
+        new_encode_chunk(10).encode_to(&mut buf).unwrap();
+        Chunk::decode(&buf, &fields).unwrap();
+
+        // truncated at any position.
+        for i in 0..buf.len() {
+            assert!(Chunk::decode(&buf[..i], &fields).is_err(), "{}", i);
+        }
+        // trailing bytes.
+        let mut longer = buf.clone();
+        longer.push(0);
+        assert!(Chunk::decode(&longer, &fields).is_err());
+        // field types which don't match.
+        assert!(Chunk::decode(&buf, &fields[..1]).is_err());
+        let json = vec![fields[0].clone(), field_type(types::JSON)];
+        assert!(Chunk::decode(&buf, &json).is_err());
+
+        let row = [
+            // the fixed column has 2 rows but only 1 value.
+            2, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert!(Chunk::decode(&row, &fields[..1]).is_err());
+        let null_cnt = [
+            // the null count doesn't match the null bitmap.
+            2, 0, 0, 0, 1, 0, 0, 0, 0b11,
+            1, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert!(Chunk::decode(&null_cnt, &fields[..1]).is_err());
+        let null_cnt = [
+            // the null count is larger than the length.
+            1, 0, 0, 0, 2, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert!(Chunk::decode(&null_cnt, &fields[..1]).is_err());
+        let var_fields = &fields[1..];
+        let offsets = [
+            // decreasing offsets.
+            2, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0,
+            b'a', b'b',
+        ];
+        assert!(Chunk::decode(&offsets, var_fields).is_err());
+        let offsets = [
+            // the first offset isn't 0.
+            1, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            b'a', b'b',
+        ];
+        assert!(Chunk::decode(&offsets, var_fields).is_err());
+        let offsets = [
+            // the data is shorter than the last offset.
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            3, 0, 0, 0, 0, 0, 0, 0,
+            b'a', b'b',
+        ];
+        assert!(Chunk::decode(&offsets, var_fields).is_err());
+        let offsets = [
+            // a negative offset.
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            b'a', b'b',
+        ];
+        let err = Chunk::decode(&offsets, var_fields).unwrap_err();
+        assert!(format!("{:?}", err).contains("out of range"), "{:?}", err);
+        let offsets = [
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            b'a', b'b',
+        ];
+        let chunk = ArcChunk::new(Chunk::decode(&offsets, var_fields).unwrap());
+        assert_eq!(chunk.get_row(0).get_bytes(0), b"ab");
+    }
+
+    #[test]
+    fn test_decode_flipped_bytes() {
+        let fields: Vec<_> = ENCODE_TPS.iter().map(|&tp| field_type(tp as u8)).collect();
+        let tz = FixedOffset::east(0);
+        let mut buf = vec![];
+        new_encode_chunk(10).encode_to(&mut buf).unwrap();
+        // a corrupted chunk is either rejected or decoded into readable rows.
+        for i in 0..buf.len() {
+            for &mask in &[0x01, 0x80, 0xff] {
+                let mut corrupted = buf.clone();
+                corrupted[i] ^= mask;
+                let chunk = match Chunk::decode(&corrupted, &fields) {
+                    Ok(chunk) => ArcChunk::new(chunk),
+                    Err(_) => continue,
+                };
+                for row in 0..chunk.num_rows() {
+                    let row = chunk.get_row(row);
+                    row.get_datum(0, &fields[0], &tz).unwrap();
+                    row.get_datum(1, &fields[1], &tz).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_inflated_sizes() {
+        let fields: Vec<_> = ENCODE_TPS.iter().map(|&tp| field_type(tp as u8)).collect();
+        let mut buf = vec![];
+        new_encode_chunk(10).encode_to(&mut buf).unwrap();
+        // the length and the null count of the first column.
+        for &pos in &[0, 4] {
+            let mut inflated = buf.clone();
+            LittleEndian::write_u32(&mut inflated[pos..pos + 4], u32::MAX);
+            assert!(Chunk::decode(&inflated, &fields).is_err(), "{}", pos);
+        }
+        let huge = [
+            // the last offset claims 4GB of data.
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0,
+            b'a',
+        ];
+        let err = Chunk::decode(&huge, &fields[1..]).unwrap_err();
+        assert!(format!("{:?}", err).contains("value 0 has"), "{:?}", err);
+        let huge = [
+            // the last offset can't be held by the u32 offsets in memory.
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 1, 0, 0, 0,
+            b'a',
+        ];
+        let err = Chunk::decode(&huge, &fields[1..]).unwrap_err();
+        assert!(format!("{:?}", err).contains("out of range"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_decode_with_limits() {
+        let fields: Vec<_> = ENCODE_TPS.iter().map(|&tp| field_type(tp as u8)).collect();
+        let mut buf = vec![];
+        new_encode_chunk(10).encode_to(&mut buf).unwrap();
+        let limits = DecodeLimits::default();
+        Chunk::decode_with_limits(&buf, &fields, &limits).unwrap();
+
+        // row 3 holds the first of the longest values, "333".
+        let limits = DecodeLimits {
+            max_value_size: 2,
+            ..DecodeLimits::default()
+        };
+        let err = Chunk::decode_with_limits(&buf, &fields, &limits).unwrap_err();
+        let msg = format!("{:?}", err);
+        assert!(msg.contains("column 1: value 3 has 3 bytes"), "{}", msg);
+
+        let limits = DecodeLimits {
+            max_column_size: 79,
+            ..DecodeLimits::default()
+        };
+        let err = Chunk::decode_with_limits(&buf, &fields, &limits).unwrap_err();
+        let msg = format!("{:?}", err);
+        assert!(msg.contains("column 0: data has 80 bytes"), "{}", msg);
+    }
+
+    #[test]
+    fn test_row_iter() {
+        let c = ArcChunk::new(Chunk::new_chunk(ENCODE_TPS));
+        assert_eq!(c.num_rows(), 0);
+        assert_eq!(c.iter().len(), 0);
+        assert!(c.iter().next().is_none());
+
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        chunk.append_i64(0, 7).unwrap();
+        let c = ArcChunk::new(chunk);
+        let idxs: Vec<_> = c.iter().map(|row| row.idx()).collect();
+        assert_eq!(idxs, vec![0]);
+        assert!(c.begin().next().is_none());
+
+        let c = ArcChunk::new(new_encode_chunk(10));
+        let mut iter = c.iter();
+        assert_eq!(iter.len(), 10);
+        iter.next();
+        assert_eq!(iter.len(), 9);
+        let mut idxs = vec![];
+        for row in &c {
+            assert_eq!(row.len(), 2);
+            assert_eq!(row.is_null(0), row.idx() % 3 == 0);
+            if !row.is_null(0) {
+                assert_eq!(row.get_i64(0), row.idx() as i64);
+            }
+            idxs.push(row.idx());
+        }
+        assert_eq!(idxs, (0..10).collect::<Vec<_>>());
+
+        // `Row::next` stops at the last row as well.
+        let mut idxs = vec![c.begin().idx()];
+        let mut row = c.begin();
+        while let Some(next) = row.next() {
+            idxs.push(next.idx());
+            row = next;
+        }
+        assert_eq!(idxs, (0..10).collect::<Vec<_>>());
+    }
+
+    fn decode_fields() -> Vec<FieldType> {
+        [
+            types::LONG_LONG,
+            types::FLOAT,
+            types::DOUBLE,
+            types::VARCHAR,
+            types::NEW_DECIMAL,
+            types::DATETIME,
+            types::DURATION,
+        ].iter()
+            .map(|&tp| field_type(tp))
+            .collect()
+    }
+
+    fn encode_decode(chunk: &Chunk, fields: &[FieldType]) -> Chunk {
+        let mut buf = vec![];
+        chunk.encode_to(&mut buf).unwrap();
+        Chunk::decode(&buf, fields).unwrap()
+    }
+
+    #[test]
+    fn test_append_all() {
+        let fields = decode_fields();
+        let tps: Vec<i32> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let cases = vec![(13, 29), (0, 5), (5, 0), (8, 8), (16, 3), (7, 9)];
+        for (a, b) in cases {
+            let mut chunk = encode_decode(&new_decode_chunk(&fields, a), &fields);
+            let other = encode_decode(&new_decode_chunk(&fields, b), &fields);
+            let mut expect = Chunk::new_chunk(&tps);
+            expect.append(&chunk, 0, a).unwrap();
+            expect.append(&other, 0, b).unwrap();
+            chunk.append_all(&other).unwrap();
+            assert_eq!(chunk.num_rows(), a + b);
+            assert_eq!(chunk, expect);
+        }
+
+        // the stale null bits left by truncating are cleared.
+        let mut chunk = encode_decode(&new_decode_chunk(&fields, 16), &fields);
+        chunk.truncate_to(13);
+        let other = encode_decode(&new_decode_chunk(&fields, 29), &fields);
+        let mut expect = Chunk::new_chunk(&tps);
+        expect.append(&chunk, 0, 13).unwrap();
+        expect.append(&other, 0, 29).unwrap();
+        chunk.append_all(&other).unwrap();
+        for (x, y) in chunk.columns.iter().zip(&expect.columns) {
+            assert_eq!(x.null_cnt, y.null_cnt);
+            assert_eq!(&x.null_bitmap[..], &y.null_bitmap[..]);
+            assert_eq!(x.data, y.data);
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            field_type(types::VARCHAR),
+            field_type(types::JSON),
+        ];
+        let tps: Vec<_> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let rows: Vec<_> = (0..11)
+            .map(|i| {
+                vec![
+                    if i % 3 == 0 { Datum::Null } else { Datum::I64(i) },
+                    if i % 4 == 0 {
+                        Datum::Null
+                    } else {
+                        Datum::Bytes("中".repeat(i as usize % 3).into_bytes())
+                    },
+                    if i % 5 == 0 {
+                        Datum::Null
+                    } else {
+                        Datum::Json(format!("{}", -i).parse().unwrap())
+                    },
+                ]
+            })
+            .collect();
+        let src = Chunk::from_datum_rows(&rows, &fields).unwrap();
+        let fingerprint = src.fingerprint();
+
+        let mut by_rows = Chunk::new_chunk(&tps);
+        let c = ArcChunk::new(src.clone());
+        for row in &c {
+            by_rows.append_row(&row).unwrap();
+        }
+        let mut by_ranges = Chunk::new_chunk(&tps);
+        by_ranges.append(&src, 0, 5).unwrap();
+        by_ranges.append(&src, 5, 11).unwrap();
+        // the stale null bits left by truncating.
+        let mut truncated = src.clone();
+        truncated.truncate_to(2);
+        truncated.append(&src, 2, 11).unwrap();
+        // the bytes in the null slots.
+        let mut dirty = src.clone();
+        dirty.columns[0].data[..8].copy_from_slice(&[0xff; 8]);
+        dirty.columns[2].ifaces[0] = Datum::Json("1".parse().unwrap());
+        for chunk in &[by_rows, by_ranges, truncated, dirty] {
+            assert_eq!(*chunk, src);
+            assert_eq!(chunk.fingerprint(), fingerprint);
+            for (col, src_col) in chunk.columns.iter().zip(&src.columns) {
+                assert_eq!(col.fingerprint(), src_col.fingerprint());
+            }
+        }
+
+        // the chunks differing in a value, a null or the rows.
+        let mut other = rows.clone();
+        other[1][0] = Datum::I64(2);
+        let mut null = rows.clone();
+        null[1][0] = Datum::Null;
+        for rows in &[other, null, rows[..10].to_vec()] {
+            let chunk = Chunk::from_datum_rows(rows, &fields).unwrap();
+            assert!(chunk.fingerprint() != fingerprint);
+        }
+        let mut sel = src.clone();
+        sel.set_sel((0..10).collect());
+        assert!(sel.fingerprint() != fingerprint);
+    }
+
+    #[test]
+    fn test_arc_chunk() {
+        let tz = FixedOffset::east(0);
+        let fields = vec![field_type(types::LONG_LONG), field_type(types::VARCHAR)];
+        let rows = vec![
+            vec![Datum::I64(1), Datum::Bytes(b"a".to_vec())],
+            vec![Datum::Null, Datum::Bytes(b"b".to_vec())],
+        ];
+        let shared = Arc::new(Chunk::from_datum_rows(&rows, &fields).unwrap());
+        let c = ArcChunk::from_arc(shared.clone());
+        assert_eq!(c.num_rows(), 2);
+        assert_eq!(c.num_cols(), 2);
+        let row = c.get_row(1);
+        let mut iter = c.iter();
+        // the rows keep the chunk alive.
+        drop(shared);
+        drop(c);
+        assert_eq!(row.get_datums(&fields, &tz).unwrap(), rows[1]);
+        assert_eq!(iter.next().unwrap().get_datums(&fields, &tz).unwrap(), rows[0]);
+
+        // the chunk can be taken back once no rows are left.
+        let c = ArcChunk::new(Chunk::from_datum_rows(&rows, &fields).unwrap());
+        let row = c.get_row(0);
+        let c = match c.try_unwrap() {
+            Ok(_) => panic!("the chunk is still shared by a row"),
+            Err(c) => c,
+        };
+        drop(row);
+        let mut chunk = match c.try_unwrap() {
+            Ok(chunk) => chunk,
+            Err(_) => panic!("the chunk isn't shared"),
+        };
+        assert_eq!(chunk.num_rows(), 2);
+        chunk.reset();
+        chunk.append_i64(0, 3).unwrap();
+        chunk.append_bytes(1, b"c").unwrap();
+        let c = ArcChunk::new(chunk);
+        assert_eq!(
+            c.to_datum_rows(&fields, &tz).unwrap(),
+            vec![vec![Datum::I64(3), Datum::Bytes(b"c".to_vec())]]
+        );
+    }
+
+    #[test]
+    fn test_get_null() {
+        let tz = FixedOffset::east(0);
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            field_type(types::FLOAT),
+            field_type(types::DOUBLE),
+            field_type(types::VARCHAR),
+            field_type(types::JSON),
+        ];
+        let rows = vec![
+            vec![
+                Datum::I64(-1),
+                Datum::F64(1.5),
+                Datum::F64(-2.25),
+                Datum::Bytes(b"ab".to_vec()),
+                Datum::Json(r#"{"a": 1}"#.parse().unwrap()),
+            ],
+            vec![Datum::Null; 5],
+        ];
+        let mut chunk = Chunk::from_datum_rows(&rows, &fields).unwrap();
+        // the null slots hold the bytes of other values, like the chunks
+        // encoded by TiDB.
+        for col in &mut chunk.columns[..3] {
+            let len = col.fixed_len;
+            for b in &mut col.data[len..] {
+                *b = 0xff;
+            }
+        }
+        chunk.columns[3].data.extend_from_slice(b"cd");
+        chunk.columns[3].var_offsets[2] = 4;
+        chunk.columns[4].ifaces[1] = Datum::I64(1);
+
+        let c = ArcChunk::new(chunk);
+        let row = c.get_row(1);
+        assert_eq!(row.get_i64(0), 0);
+        assert_eq!(row.get_u64(0), 0);
+        assert_eq!(row.get_f32(1), 0.0);
+        assert_eq!(row.get_f64(2), 0.0);
+        assert_eq!(row.get_bytes(3), b"");
+        assert_eq!(row.get_str_ref(3).unwrap(), "");
+        assert_eq!(row.get_interface(4), Datum::Null);
+        for (i, fp) in fields.iter().enumerate() {
+            assert_eq!(row.get_datum(i, fp, &tz).unwrap(), Datum::Null);
+        }
+        assert_eq!(c.get_row(0).get_datums(&fields, &tz).unwrap(), rows[0]);
+    }
+
+    #[test]
+    fn test_append_with_mapping() {
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            field_type(types::VARCHAR),
+            field_type(types::DOUBLE),
+        ];
+        let rows = vec![
+            vec![Datum::I64(1), Datum::Bytes(b"a".to_vec()), Datum::F64(1.5)],
+            vec![Datum::Null, Datum::Bytes(vec![]), Datum::F64(-2.0)],
+            vec![Datum::I64(3), Datum::Null, Datum::Null],
+        ];
+        let src = Chunk::from_datum_rows(&rows, &fields).unwrap();
+        // the rows of `rows` in `row_idxs` with the columns in `mapping`.
+        let expect = |row_idxs: &[usize], mapping: &[usize]| {
+            let fts: Vec<_> = mapping.iter().map(|&i| fields[i].clone()).collect();
+            let picked: Vec<Vec<_>> = row_idxs
+                .iter()
+                .map(|&r| mapping.iter().map(|&i| rows[r][i].clone()).collect())
+                .collect();
+            Chunk::from_datum_rows(&picked, &fts).unwrap()
+        };
+        let cases = vec![
+            // identity.
+            (&[0, 1, 2][..], 0, 3),
+            // permutation.
+            (&[2, 0, 1][..], 1, 3),
+            // duplicated source columns.
+            (&[1, 1, 0][..], 0, 2),
+        ];
+        for (mapping, begin, end) in cases {
+            let mut dst = expect(&[], mapping);
+            dst.append_with_mapping(&src, begin, end, mapping).unwrap();
+            let row_idxs: Vec<_> = (begin..end).collect();
+            assert_eq!(dst, expect(&row_idxs, mapping), "{:?}", mapping);
+        }
+
+        // only the selected rows are appended.
+        let mut sel = src.clone();
+        sel.set_sel(vec![0, 2]);
+        let mut dst = expect(&[], &[2, 1]);
+        dst.append_with_mapping(&sel, 0, 2, &[2, 1]).unwrap();
+        assert_eq!(dst, expect(&[0, 2], &[2, 1]));
+
+        let mut dst = expect(&[], &[0, 1, 2]);
+        let check_err = |dst: &mut Chunk, mapping: &[usize], msg: &str| {
+            let err = format!("{}", dst.append_with_mapping(&src, 0, 3, mapping).unwrap_err());
+            assert!(err.contains(msg), "{}", err);
+            assert_eq!(dst.num_rows(), 0);
+        };
+        check_err(&mut dst, &[0, 1], "expect a mapping of 3 columns, but got 2");
+        check_err(
+            &mut dst,
+            &[0, 3, 2],
+            "column 1 is mapped to column 3, but there are 3 columns",
+        );
+        check_err(&mut dst, &[0, 2, 1], "column 1: can't append");
+    }
+
+    #[test]
+    fn test_chunk_eq() {
+        let tps = &[types::LONG_LONG as i32, types::VARCHAR as i32, types::JSON as i32];
+        let mut a = Chunk::new_chunk(tps);
+        let mut b = Chunk::new_chunk(tps);
+        for c in &mut [&mut a, &mut b] {
+            c.append_i64(0, 1).unwrap();
+            c.append_bytes(1, b"a").unwrap();
+            c.append_interface(2, Datum::I64(1));
+            c.append_null(0);
+            c.append_null(1);
+            c.append_null(2);
+        }
+        assert_eq!(a, b);
+
+        // the bytes left in the null slots aren't compared.
+        a.columns[0].data[8..].copy_from_slice(&[0xff; 8]);
+        assert_eq!(a.columns[0], b.columns[0]);
+        assert_eq!(a, b);
+        a.columns[0].data[..8].copy_from_slice(&[0xff; 8]);
+        assert_ne!(a.columns[0], b.columns[0]);
+        assert_ne!(a, b);
+
+        // neither is the spare capacity.
+        let mut c = b.clone();
+        c.reserve(100);
+        assert_eq!(b, c);
+        c.append_i64(0, 2).unwrap();
+        c.append_null(1);
+        c.append_null(2);
+        assert_ne!(b, c);
+        c.truncate_to(2);
+        assert_eq!(b, c);
+        assert_ne!(b, Chunk::new_chunk(&tps[..2]));
+        let mut nulls = Column::new(types::LONG_LONG as i32, 0);
+        nulls.append_null();
+        nulls.append_null();
+        assert_ne!(nulls, c.columns[0]);
+
+        // only the selected rows are compared.
+        let mut sel = b.clone();
+        sel.append_i64(0, 2).unwrap();
+        sel.append_bytes(1, b"a").unwrap();
+        sel.append_interface(2, Datum::I64(1));
+        sel.set_sel(vec![0, 2]);
+        let mut expect = Chunk::new_chunk(tps);
+        expect.append(&sel, 0, 2).unwrap();
+        assert_eq!(sel, expect);
+        sel.set_sel(vec![0, 1]);
+        assert_eq!(sel, b);
+    }
+
+    #[test]
+    fn test_chunk_debug() {
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let fields = vec![
+            unsigned,
+            field_type(types::DOUBLE),
+            field_type(types::VARCHAR),
+            field_type(types::DURATION),
+            field_type(types::BIT),
+        ];
+        let mut rows = vec![];
+        for i in 0..12 {
+            rows.push(vec![
+                Datum::U64(u64::MAX - i),
+                Datum::F64(i as f64 + 0.5),
+                Datum::Bytes(vec![b'a', i as u8]),
+                Datum::Null,
+                Datum::I64(i as i64),
+            ]);
+        }
+        rows[1] = vec![Datum::Null; 5];
+        let chunk = Chunk::from_datum_rows(&rows, &fields).unwrap();
+        let debug = format!("{:.2?}", chunk);
+        let expect = "Chunk { 5 columns, 12 rows
+    0: 18446744073709551615 | 0.5 | \"a\\000\" | NULL | I64(0)
+    1: NULL | NULL | NULL | NULL | NULL
+    ... 10 more rows
+}";
+        assert_eq!(debug, expect);
+        assert_eq!(format!("{:?}", chunk).lines().count(), 10 + 3);
+        assert_eq!(format!("{:.20?}", chunk).lines().count(), 12 + 2);
+        assert_eq!(
+            format!("{:.3?}", chunk.columns[2]),
+            "Column { a var-length, 12 rows: [\"a\\000\", NULL, \"a\\002\", ... 9 more] }"
+        );
+
+        // the fixed-length values of unknown types are written as hex.
+        let mut chunk = Chunk::new_chunk(&[types::DATETIME as i32, types::DURATION as i32]);
+        chunk.append_null(0);
+        chunk.append_i64(1, -1).unwrap();
+        assert_eq!(format!("{:?}", chunk), "Chunk { 2 columns, 1 rows\n    0: NULL | -1\n}");
+        let mut chunk = Chunk::new_chunk(&[types::FLOAT as i32]);
+        chunk.append_f32(0, 1.5).unwrap();
+        assert_eq!(
+            format!("{:?}", chunk.columns[0]),
+            "Column { a fixed length(4), 1 rows: [0x0000c03f] }"
+        );
+    }
+
+    #[test]
+    fn test_from_datum_rows() {
+        let tz = FixedOffset::east(0);
+        let fields = vec![field_type(types::LONG_LONG), field_type(types::VARCHAR)];
+        let rows = vec![
+            vec![Datum::I64(1), Datum::Bytes(b"a".to_vec())],
+            vec![Datum::Null, Datum::Bytes(vec![])],
+        ];
+        let c = ArcChunk::new(Chunk::from_datum_rows(&rows, &fields).unwrap());
+        assert_eq!(c.to_datum_rows(&fields, &tz).unwrap(), rows);
+        let c = ArcChunk::new(Chunk::from_datum_rows(&[], &fields).unwrap());
+        assert_eq!(c.num_rows(), 0);
+        assert!(c.to_datum_rows(&fields, &tz).unwrap().is_empty());
+
+        let check_err = |rows: &[Vec<Datum>], msg: &str| {
+            match Chunk::from_datum_rows(rows, &fields) {
+                Ok(_) => panic!("{:?} should be rejected", rows),
+                Err(e) => assert!(format!("{}", e).contains(msg), "{}", e),
+            }
+        };
+        // ragged rows.
+        check_err(
+            &[vec![Datum::I64(1), Datum::Null], vec![Datum::I64(2)]],
+            "row 1 has 1 datums, but there are 2 columns",
+        );
+        // the datums can't be stored in the columns.
+        check_err(
+            &[vec![Datum::I64(1), Datum::Null], vec![Datum::F64(1.5), Datum::Null]],
+            "row 1: F64(1.5) can't be stored in column 0",
+        );
+        check_err(
+            &[vec![Datum::I64(1), Datum::I64(2)]],
+            "row 0: I64(2) can't be stored in column 1",
+        );
+    }
+
+    #[test]
+    fn test_row_encode() {
+        let tz = FixedOffset::east(0);
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            unsigned,
+            field_type(types::VARCHAR),
+            field_type(types::DOUBLE),
+            field_type(types::NEW_DECIMAL),
+            field_type(types::JSON),
+            // an interface column.
+            field_type(types::BIT),
+        ];
+        let rows = vec![
+            vec![
+                Datum::I64(-1),
+                Datum::U64(u64::MAX),
+                Datum::Bytes(b"abc".to_vec()),
+                Datum::F64(1.5),
+                Datum::Dec("-1.25".parse().unwrap()),
+                Datum::Json(r#"{"a": 1}"#.parse().unwrap()),
+                Datum::Bytes(vec![1]),
+            ],
+            vec![Datum::Null; 7],
+            vec![
+                Datum::I64(i64::MAX),
+                Datum::U64(0),
+                Datum::Bytes(vec![]),
+                Datum::F64(-0.5),
+                Datum::Dec("0".parse().unwrap()),
+                Datum::Json("[]".parse().unwrap()),
+                Datum::I64(3),
+            ],
+        ];
+        let mut chunk = Chunk::from_field_types(&fields);
+        for row in &rows {
+            for (col_idx, (d, fp)) in row.iter().zip(&fields).enumerate() {
+                chunk.append_datum(col_idx, d, fp).unwrap();
+            }
+        }
+        let c = ArcChunk::new(chunk);
+        for (row, expect) in c.iter().zip(&rows) {
+            assert_eq!(&row.get_datums(&fields, &tz).unwrap(), expect);
+            let mut buf = vec![];
+            row.encode(&fields, &tz, &mut buf).unwrap();
+            assert_eq!(&buf.as_slice().decode().unwrap(), expect);
+        }
+        assert!(c.begin().get_datums(&fields[1..], &tz).is_err());
+        assert!(c.begin().encode(&fields[1..], &tz, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_swap_column() {
+        let mut a = Chunk::new_chunk(ENCODE_TPS);
+        let mut b = Chunk::new_chunk(&[types::DOUBLE as i32, types::LONG_LONG as i32]);
+        for i in 0..2 {
+            a.append_i64(0, i).unwrap();
+            a.append_str(1, i.to_string()).unwrap();
+            b.append_f64(0, i as f64 + 0.5).unwrap();
+            b.append_i64(1, i + 10).unwrap();
+        }
+        let mut c = Chunk::new_chunk(ENCODE_TPS);
+        for i in 0..3 {
+            c.append_i64(0, i).unwrap();
+            c.append_null(1);
+        }
+
+        // the columns of different row counts can't be swapped.
+        assert!(a.swap_column(0, &mut c, 0).is_err());
+        assert!(a.swap_column(2, &mut b, 0).is_err());
+        assert!(a.swap_column(0, &mut b, 2).is_err());
+        assert_eq!(c.num_rows(), 3);
+
+        // the columns can be of different kinds.
+        a.swap_column(1, &mut b, 0).unwrap();
+        let (a, b) = (ArcChunk::new(a), ArcChunk::new(b));
+        let row = a.get_row(1);
+        assert_eq!(row.get_i64(0), 1);
+        assert_eq!(row.get_f64(1), 1.5);
+        assert_eq!(b.get_row(1).get_str_ref(0).unwrap(), "1");
+        assert_eq!(b.get_row(1).get_i64(1), 11);
+
+        // a taken column reads as NULLs until it's set back.
+        let mut c = Chunk::new_chunk(ENCODE_TPS);
+        c.append_i64(0, 1).unwrap();
+        c.append_str(1, "a").unwrap();
+        let col = c.take_column(1).unwrap();
+        assert_eq!(col.len(), 1);
+        assert!(ArcChunk::new(c.clone()).get_row(0).is_null(1));
+        c.append_i64(0, 2).unwrap();
+        c.append_null(1);
+        assert!(c.set_column(1, col.clone()).is_err());
+        assert!(c.set_column(2, col.clone()).is_err());
+        c.truncate_to(1);
+        c.set_column(1, col).unwrap();
+        assert_eq!(ArcChunk::new(c).get_row(0).get_str_ref(1).unwrap(), "a");
+        assert!(Chunk::new_chunk(&[]).take_column(0).is_err());
+    }
+
+    #[test]
+    fn test_reserve() {
+        let tps = &[types::LONG_LONG as i32, types::VARCHAR as i32, types::JSON as i32];
+        let mut chunk = Chunk::new_chunk(tps);
+        let append = |chunk: &mut Chunk, i: i64| {
+            chunk.append_i64(0, i).unwrap();
+            if i % 5 == 0 {
+                chunk.append_null(1);
+            } else {
+                chunk.append_str(1, "abcd").unwrap();
+            }
+            chunk.append_interface(2, Datum::I64(i));
+        };
+        // the strings are 4 bytes long on average.
+        for i in 1..4 {
+            append(&mut chunk, i);
+        }
+        let n = 1000;
+        chunk.reserve(n);
+        let capacities = |chunk: &Chunk| {
+            let cols = &chunk.columns;
+            vec![
+                cols[0].data.capacity(),
+                cols[0].null_bitmap.capacity(),
+                cols[1].var_offsets.capacity(),
+                cols[1].data.capacity(),
+                cols[2].ifaces.capacity(),
+            ]
+        };
+        let reserved = capacities(&chunk);
+        for i in 0..n as i64 {
+            append(&mut chunk, i + 4);
+        }
+        assert_eq!(chunk.num_rows(), n + 3);
+        assert_eq!(capacities(&chunk), reserved);
+
+        // the fixed-width rows of an empty chunk.
+        let mut chunk = Chunk::new_chunk(&[types::DOUBLE as i32]);
+        chunk.reserve(n);
+        let cap = chunk.columns[0].data.capacity();
+        for i in 0..n {
+            chunk.append_f64(0, i as f64).unwrap();
+        }
+        assert_eq!(chunk.columns[0].data.capacity(), cap);
+    }
+
+    #[test]
+    fn test_append_all_interface() {
+        let tps = &[types::JSON as i32];
+        let mut chunk = Chunk::new_chunk(tps);
+        chunk.append_interface(0, Datum::I64(1));
+        chunk.append_null(0);
+        let mut other = Chunk::new_chunk(tps);
+        other.append_interface(0, Datum::Bytes(b"a".to_vec()));
+        chunk.append_all(&other).unwrap();
+        let expect = vec![Datum::I64(1), Datum::Null, Datum::Bytes(b"a".to_vec())];
+        assert_eq!(chunk.columns[0].ifaces, expect);
+        assert_eq!(chunk.columns[0].null_cnt, 1);
+        assert_eq!(chunk.columns[0].null_bitmap, vec![0b101]);
+    }
+
+    #[test]
+    fn test_append_all_mismatch() {
+        let mut chunk = new_encode_chunk(3);
+        let other = Chunk::new_chunk(&[types::LONG_LONG as i32, types::DOUBLE as i32]);
+        assert!(chunk.append_all(&other).is_err());
+        let other = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        assert!(chunk.append_all(&other).is_err());
+        // nothing is appended if any column mismatches.
+        let mut other = Chunk::new_chunk(&[types::LONG_LONG as i32, types::DOUBLE as i32]);
+        other.append_i64(0, 1).unwrap();
+        other.append_f64(1, 1.0).unwrap();
+        assert!(chunk.append_all(&other).is_err());
+        assert_eq!(chunk.columns[0].len(), 3);
+    }
+
+    #[test]
+    fn test_append_mismatch() {
+        let src = ArcChunk::new(new_encode_chunk(3));
+        let check_err = |res: Result<()>, msg: &str| {
+            let err = format!("{}", res.unwrap_err());
+            assert!(err.contains(msg), "{}", err);
+        };
+        // a var-length column into a fixed length one.
+        let tps = &[types::LONG_LONG as i32, types::LONG_LONG as i32];
+        let mut chunk = Chunk::new_chunk(tps);
+        check_err(
+            chunk.append(&src.c, 0, 3),
+            "column 1: can't append a var-length column to a fixed length(8) column",
+        );
+        check_err(
+            chunk.append_row(&src.get_row(0)),
+            "column 1: can't append a var-length column",
+        );
+        // fixed length columns of different lengths.
+        let tps = &[types::FLOAT as i32, types::VARCHAR as i32];
+        let mut chunk = Chunk::new_chunk(tps);
+        check_err(
+            chunk.append(&src.c, 0, 3),
+            "column 0: can't append a fixed length(8) column to a fixed length(4) column",
+        );
+        // an interface column into a var-length one.
+        let mut chunk = Chunk::new_chunk(&[types::DOUBLE as i32, types::VARCHAR as i32]);
+        let iface = ArcChunk::new(Chunk::new_chunk(&[types::JSON as i32]));
+        check_err(
+            chunk.append_partial_row(1, &iface.begin()),
+            "column 1: can't append an interface column to a var-length column",
+        );
+        check_err(
+            chunk.columns[1].append(&iface.c.columns[0], 0, 0),
+            "can't append an interface column",
+        );
+        // the columns are untouched.
+        for col in &chunk.columns {
+            assert_eq!(col.len(), 0);
+            assert_eq!(col.null_bitmap.len(), 0);
+            assert_eq!(col.data.len(), 0);
+        }
+        assert_eq!(chunk.columns[1].var_offsets, vec![0]);
+        // the number of columns must match.
+        check_err(
+            chunk.append(&Chunk::new_chunk(&tps[..1]), 0, 0),
+            "expect 2 columns",
+        );
+    }
+
+    #[test]
+    fn test_sel() {
+        let fields = vec![field_type(types::LONG_LONG), field_type(types::VARCHAR)];
+        let mut chunk = Chunk::from_field_types(&fields);
+        for i in 0..8 {
+            chunk.append_i64(0, i).unwrap();
+            chunk.append_str(1, format!("{}", i)).unwrap();
+        }
+        let check = |c: &Chunk, exp: &[i64]| {
+            assert_eq!(c.num_rows(), exp.len());
+            let c = ArcChunk::new(c.clone());
+            let values: Vec<_> = c.iter().map(|row| row.get_i64(0)).collect();
+            assert_eq!(values, exp);
+            let strs: Vec<_> = c.iter()
+                .map(|row| row.get_str_ref(1).unwrap().to_owned())
+                .collect();
+            let exp_strs: Vec<_> = exp.iter().map(|i| format!("{}", i)).collect();
+            assert_eq!(strs, exp_strs);
+            // the rows can be walked from the first one as well.
+            let mut values = vec![];
+            let mut row = Some(c.begin());
+            while let Some(r) = row {
+                if r.idx() == c.end().idx() {
+                    break;
+                }
+                values.push(r.get_i64(0));
+                row = r.next();
+            }
+            assert_eq!(values, exp);
+        };
+
+        assert_eq!(chunk.sel(), None);
+        chunk.set_sel(vec![1, 2, 3, 6]);
+        assert_eq!(chunk.sel(), Some(&[1, 2, 3, 6][..]));
+        check(&chunk, &[1, 2, 3, 6]);
+        let c = ArcChunk::new(chunk.clone());
+        assert_eq!(c.get_row(3).idx(), 6);
+        assert_eq!(c.end().idx(), 8);
+        // the projected chunk keeps the selection vector.
+        let projected = chunk.project(&[1, 0]);
+        assert_eq!(projected.sel(), chunk.sel());
+        assert_eq!(ArcChunk::new(projected).get_row(3).get_i64(1), 6);
+
+        // only the selected rows are appended, and the range is of them.
+        let mut dst = Chunk::from_field_types(&fields);
+        dst.append(&chunk, 1, 4).unwrap();
+        check(&dst, &[2, 3, 6]);
+        dst.append_all(&chunk).unwrap();
+        check(&dst, &[2, 3, 6, 1, 2, 3, 6]);
+        let mut dst = Chunk::from_field_types(&fields);
+        dst.append_row(&c.get_row(3)).unwrap();
+        check(&dst, &[6]);
+
+        // a chunk with a selection vector can't be encoded.
+        let mut buf = vec![];
+        assert!(chunk.encode_to(&mut buf).is_err());
+        let tps: Vec<_> = fields.iter().map(|fp| fp.get_tp()).collect();
+        assert!(ChunkEncoder::new(&chunk, &tps).is_err());
+
+        // the truncated chunk keeps the first selected rows.
+        let mut truncated = chunk.clone();
+        truncated.truncate_to(2);
+        check(&truncated, &[1, 2]);
+
+        chunk.reconstruct();
+        assert_eq!(chunk.sel(), None);
+        check(&chunk, &[1, 2, 3, 6]);
+        for col in &chunk.columns {
+            assert_eq!(col.len(), 4);
+        }
+        chunk.encode_to(&mut buf).unwrap();
+        check(&Chunk::decode(&buf, &fields).unwrap(), &[1, 2, 3, 6]);
+        // the chunk can be appended to after it's reconstructed.
+        chunk.append_i64(0, 9).unwrap();
+        chunk.append_str(1, "9").unwrap();
+        check(&chunk, &[1, 2, 3, 6, 9]);
+
+        // nothing is selected.
+        chunk.set_sel(vec![]);
+        check(&chunk, &[]);
+        assert!(ArcChunk::new(chunk.clone()).iter().next().is_none());
+        chunk.reconstruct();
+        check(&chunk, &[]);
+
+        // the rows of a chunk without columns are selected as well.
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        for i in 0..5 {
+            chunk.append_i64(0, i).unwrap();
+        }
+        chunk.prune_columns(&[]);
+        chunk.set_sel(vec![0, 4]);
+        assert_eq!(chunk.num_rows(), 2);
+        chunk.reconstruct();
+        assert_eq!(chunk.num_rows(), 2);
+        assert_eq!(chunk.sel(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't append to a chunk with a selection vector")]
+    #[cfg(debug_assertions)]
+    fn test_append_to_chunk_with_sel() {
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.set_sel(vec![0]);
+        chunk.append_i64(0, 2).unwrap();
+    }
+
+    #[test]
+    fn test_reset_with_limit() {
+        let big = "a".repeat(4 << 20);
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32, types::VARCHAR as i32]);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.append_str(1, &big).unwrap();
+        let usage = chunk.mem_usage();
+        assert!(chunk.columns[1].data.capacity() >= 4 << 20);
+
+        // the memory within the limit is kept.
+        assert_eq!(chunk.reset_with_limit(usage), 0);
+        assert_eq!(chunk.mem_usage(), usage);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.append_str(1, &big).unwrap();
+
+        let released = chunk.reset_with_limit(64 << 10);
+        assert_eq!(released, usage - chunk.mem_usage());
+        assert!(chunk.mem_usage() <= 64 << 10);
+        assert!(chunk.columns[1].data.capacity() < 64 << 10);
+        assert_eq!(chunk.num_rows(), 0);
+        // the small column isn't released.
+        assert!(chunk.columns[0].data.capacity() > 0);
+
+        chunk.append_i64(0, 2).unwrap();
+        chunk.append_str(1, "b").unwrap();
+        assert_eq!(chunk.columns[0].get_i64(0), 2);
+        assert_eq!(chunk.columns[1].get_bytes(0), b"b");
+    }
+
+    #[test]
+    fn test_row_cmp() {
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let fields = vec![
+            field_type(types::LONG_LONG),
+            unsigned,
+            field_type(types::VARCHAR),
+            field_type(types::DOUBLE),
+            field_type(types::NEW_DECIMAL),
+        ];
+        let tps: Vec<_> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let dec = |s: &str| s.parse::<Decimal>().unwrap();
+        let mut a = Chunk::new_chunk(&tps);
+        a.append_i64(0, 1).unwrap();
+        a.append_u64(1, 1).unwrap();
+        a.append_bytes(2, b"a").unwrap();
+        a.append_f64(3, 1.5).unwrap();
+        a.append_decimal(4, &dec("1.5")).unwrap();
+        a.append_null(0);
+        a.append_u64(1, u64::MAX).unwrap();
+        a.append_bytes(2, b"b").unwrap();
+        a.append_null(3);
+        a.append_null(4);
+        let mut b = Chunk::new_chunk(&tps);
+        b.append_i64(0, 1).unwrap();
+        b.append_u64(1, 2).unwrap();
+        b.append_bytes(2, b"a").unwrap();
+        b.append_f64(3, 1.5).unwrap();
+        b.append_decimal(4, &dec("1.50")).unwrap();
+        b.append_null(0);
+        b.append_u64(1, 0).unwrap();
+        b.append_bytes(2, b"ab").unwrap();
+        b.append_f64(3, -1.0).unwrap();
+        b.append_null(4);
+        let (a, b) = (ArcChunk::new(a), ArcChunk::new(b));
+        let cmp = |l: &Row, r: &Row, order_cols: &[(usize, bool)]| {
+            l.cmp(r, order_cols, &fields).unwrap()
+        };
+
+        // ties of the rows in different chunks.
+        let (a0, a1, b0, b1) = (a.get_row(0), a.get_row(1), b.get_row(0), b.get_row(1));
+        let all = [(0, false), (2, false), (3, false), (4, false)];
+        assert_eq!(cmp(&a0, &b0, &all), Ordering::Equal);
+        assert_eq!(cmp(&a0, &a0, &all), Ordering::Equal);
+        assert_eq!(cmp(&a0, &b0, &[]), Ordering::Equal);
+
+        // NULL is the smallest, and NULL is equal to NULL.
+        assert_eq!(cmp(&a1, &b1, &[(0, false)]), Ordering::Equal);
+        assert_eq!(cmp(&a1, &a0, &[(0, false)]), Ordering::Less);
+        assert_eq!(cmp(&a1, &a0, &[(0, true)]), Ordering::Greater);
+        assert_eq!(cmp(&a1, &b1, &[(4, false), (3, false)]), Ordering::Less);
+        assert_eq!(cmp(&b1, &b0, &[(3, false)]), Ordering::Less);
+
+        // the unsigned integers aren't compared as signed.
+        assert_eq!(cmp(&a1, &a0, &[(1, false)]), Ordering::Greater);
+        assert_eq!(cmp(&b1, &a0, &[(1, false)]), Ordering::Less);
+
+        // mixed ascending and descending keys.
+        let mut rows = vec![("a0", a0), ("a1", a1), ("b0", b0), ("b1", b1)];
+        rows.sort_by(|l, r| cmp(&l.1, &r.1, &[(0, false), (2, true), (1, true)]));
+        let order: Vec<_> = rows.iter().map(|r| r.0).collect();
+        assert_eq!(order, vec!["a1", "b1", "b0", "a0"]);
+
+        // the integers are compared with the signedness they are stored with.
+        let mut c = Chunk::new_chunk(&tps);
+        c.append_u64(0, u64::MAX).unwrap();
+        let mut d = Chunk::new_chunk(&tps);
+        d.append_i64(0, -1).unwrap();
+        let (c, d) = (ArcChunk::new(c), ArcChunk::new(d));
+        let (c0, d0) = (c.get_row(0), d.get_row(0));
+        assert_eq!(cmp(&c0, &a.get_row(0), &[(0, false)]), Ordering::Greater);
+        assert_eq!(cmp(&d0, &c0, &[(0, false)]), Ordering::Less);
+        assert_eq!(cmp(&c0, &d0, &[(0, false)]), Ordering::Greater);
+
+        // the field types must match the columns.
+        let strs = vec![field_type(types::VARCHAR); 2];
+        assert!(a.get_row(0).cmp(&b.get_row(0), &[(0, false)], &strs).is_err());
+    }
+
+    #[test]
+    fn test_row_container() {
+        let mut src = Chunk::new_chunk(ENCODE_TPS);
+        for i in 0..7 {
+            src.append_i64(0, i).unwrap();
+            if i == 3 {
+                src.append_null(1);
+            } else {
+                src.append_str(1, i.to_string()).unwrap();
+            }
+        }
+        let src = ArcChunk::new(src);
+        let mut rows = RowContainer::new(ENCODE_TPS, 3);
+        assert!(rows.is_empty());
+        assert_eq!(rows.iter().count(), 0);
+        let mut mem_usage = rows.mem_usage();
+        for row in &src {
+            rows.append_row(&row).unwrap();
+            assert!(rows.mem_usage() >= mem_usage);
+            mem_usage = rows.mem_usage();
+        }
+        assert_eq!(rows.len(), 7);
+        assert!(mem_usage > 0);
+        // the first and the last rows of the chunks.
+        for &i in &[0, 2, 3, 5, 6] {
+            let row = rows.get_row(i);
+            assert_eq!(row.get_i64(0), i as i64);
+            assert_eq!(row.is_null(1), i == 3);
+        }
+        let values: Vec<_> = rows.iter().map(|row| row.get_i64(0)).collect();
+        assert_eq!(values, (0..7).collect::<Vec<_>>());
+        assert_eq!(rows.iter().len(), 7);
+
+        // the rows got before keep their values, and so do the source chunks.
+        let last = rows.get_row(6);
+        rows.append_row(&src.get_row(1)).unwrap();
+        rows.append_row(&src.get_row(2)).unwrap();
+        assert_eq!(last.get_i64(0), 6);
+        assert_eq!(rows.get_row(8).get_str_ref(1).unwrap(), "2");
+        assert_eq!(src.num_rows(), 7);
+
+        // the rows of other columns are rejected.
+        let mut c = Chunk::new_chunk(&[types::VARCHAR as i32; 2]);
+        c.append_str(0, "a").unwrap();
+        c.append_str(1, "b").unwrap();
+        let other = ArcChunk::new(c);
+        assert!(rows.append_row(&other.get_row(0)).is_err());
+        let mut c = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        c.append_i64(0, 1).unwrap();
+        assert!(rows.append_row(&ArcChunk::new(c).get_row(0)).is_err());
+        assert_eq!(rows.len(), 9);
+        assert_eq!(rows.iter().count(), 9);
+    }
+
+    #[test]
+    fn test_get_str_invalid_utf8() {
+        let fp = field_type(types::BLOB);
+        let mut chunk = Chunk::new_chunk(&[fp.get_tp()]);
+        chunk.append_bytes(0, b"\xff\xfe").unwrap();
+        chunk.append_str(0, "中").unwrap();
+        let c = ArcChunk::new(chunk);
+        let tz = FixedOffset::east(0);
+        let row = c.get_row(0);
+        assert!(row.get_str(0).is_err());
+        assert!(row.get_str_ref(0).is_err());
+        assert_eq!(row.get_bytes(0), b"\xff\xfe");
+        assert_eq!(
+            row.get_datum(0, &fp, &tz).unwrap(),
+            Datum::Bytes(b"\xff\xfe".to_vec())
+        );
+        assert_eq!(c.get_row(1).get_str(0).unwrap(), "中");
+        assert_eq!(c.get_row(1).get_str_ref(0).unwrap(), "中");
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let fields = vec![
+            field_type(types::NEW_DECIMAL),
+            field_type(types::DATETIME),
+            field_type(types::DURATION),
+            field_type(types::JSON),
+            field_type(types::ENUM),
+            field_type(types::VARCHAR),
+        ];
+        let tps: Vec<_> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let tz = FixedOffset::east(0);
+        let dec: Decimal = "-1.25".parse().unwrap();
+        let t = Time::parse_utc_datetime("2017-11-24 12:34:56", 0).unwrap();
+        let dur = Duration::from_nanos(NANOS_PER_SEC * 3, 0).unwrap();
+        let json: Json = r#"{"a": [1, true]}"#.parse().unwrap();
+        let datums = vec![
+            Datum::Dec(dec.clone()),
+            Datum::Time(t.clone()),
+            Datum::Dur(dur.clone()),
+            Datum::Json(json.clone()),
+            Datum::U64(2),
+            Datum::Bytes(b"abc".to_vec()),
+        ];
+        let mut chunk = Chunk::new_chunk(&tps);
+        for (col_idx, (d, fp)) in datums.iter().zip(&fields).enumerate() {
+            chunk.append_datum(col_idx, d, fp).unwrap();
+            chunk.append_null(col_idx);
+        }
+        let c = ArcChunk::new(chunk);
+
+        let row = c.get_row(0);
+        assert_eq!(row.get_decimal(0).unwrap(), Some(dec));
+        assert_eq!(row.get_time(1, &tz).unwrap(), Some(t));
+        assert_eq!(row.get_duration(2, 0).unwrap(), Some(dur));
+        assert_eq!(row.get_json(3).unwrap(), Some(&json));
+        assert_eq!(row.get_enum(4).unwrap(), Some((&b""[..], 2)));
+        for (col_idx, fp) in fields.iter().enumerate() {
+            assert_eq!(row.get_datum(col_idx, fp, &tz).unwrap(), datums[col_idx]);
+        }
+
+        let row = c.get_row(1);
+        assert_eq!(row.get_decimal(0).unwrap(), None);
+        assert_eq!(row.get_time(1, &tz).unwrap(), None);
+        assert_eq!(row.get_duration(2, 0).unwrap(), None);
+        assert_eq!(row.get_json(3).unwrap(), None);
+        assert_eq!(row.get_enum(4).unwrap(), None);
+
+        let check_err = |res: Result<()>, msg: &str| {
+            let err = format!("{}", res.unwrap_err());
+            assert!(err.contains(msg), "{}", err);
+        };
+        let row = c.get_row(0);
+        check_err(
+            row.get_decimal(1).map(|_| ()),
+            "column 1: a fixed length(16) column can't hold decimal values",
+        );
+        check_err(
+            row.get_time(5, &tz).map(|_| ()),
+            "column 5: a var-length column can't hold time values",
+        );
+        check_err(
+            row.get_duration(0, 0).map(|_| ()),
+            "column 0: a fixed length(40) column can't hold duration values",
+        );
+        check_err(
+            row.get_json(2).map(|_| ()),
+            "column 2: a fixed length(8) column doesn't hold datums",
+        );
+        check_err(
+            row.get_json(4).map(|_| ()),
+            "column 4: a var-length column doesn't hold datums",
+        );
+        check_err(
+            row.get_enum(3).map(|_| ()),
+            "column 3: an interface column can't hold enum values",
+        );
+        // the strings are var-length as well, but they are too short.
+        check_err(
+            row.get_enum(5).map(|_| ()),
+            "column 5: row 0 holds 3 bytes, which are too short for the enum value",
+        );
+    }
+
+    #[test]
+    fn test_from_field_types() {
+        let tz = FixedOffset::east(0);
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let mut decimal = field_type(types::NEW_DECIMAL);
+        decimal.set_flen(10);
+        decimal.set_decimal(3);
+        let fields = vec![unsigned.clone(), decimal, field_type(types::BLOB)];
+        let dec: Decimal = "-12.345".parse().unwrap();
+        let datums = vec![
+            Datum::U64(u64::MAX),
+            Datum::Dec(dec),
+            Datum::Bytes(b"\x00\xff".to_vec()),
+        ];
+        let mut chunk = Chunk::from_field_types(&fields);
+        for (col_idx, (d, fp)) in datums.iter().zip(&fields).enumerate() {
+            assert_eq!(chunk.field_type(col_idx), Some(fp));
+            chunk.append_datum(col_idx, d, fp).unwrap();
+            chunk.append_null(col_idx);
+        }
+        // the field types are kept by the copies of the columns.
+        let projected = chunk.project(&[2, 0]);
+        assert_eq!(projected.field_type(0), Some(&fields[2]));
+        assert_eq!(projected.field_type(1), Some(&fields[0]));
+        let mut buf = vec![];
+        chunk.encode_to(&mut buf).unwrap();
+
+        let c = ArcChunk::new(chunk);
+        for (col_idx, fp) in fields.iter().enumerate() {
+            let row = c.get_row(0);
+            assert_eq!(row.get_typed_datum(col_idx, &tz).unwrap(), datums[col_idx]);
+            assert_eq!(row.get_datum(col_idx, fp, &tz).unwrap(), datums[col_idx]);
+            assert_eq!(c.get_row(1).get_typed_datum(col_idx, &tz).unwrap(), Datum::Null);
+        }
+
+        let check_err = |res: Result<()>, msg: &str| {
+            let err = format!("{}", res.unwrap_err());
+            assert!(err.contains(msg), "{}", err);
+        };
+        // the type must match the kept field type, and the integers are read
+        // with the kept signedness.
+        let signed = field_type(types::LONG_LONG);
+        assert_eq!(
+            c.get_row(0).get_datum(0, &signed, &tz).unwrap(),
+            Datum::U64(u64::MAX)
+        );
+        let mut other = Chunk::from_field_types(&fields);
+        check_err(
+            other.append_datum(0, &Datum::I64(-1), &signed),
+            "an unsigned integer column can't hold -1",
+        );
+        check_err(
+            other.append_datum(2, &Datum::Bytes(vec![]), &field_type(types::VARCHAR)),
+            "column 2 is of type 252, which can't be accessed as type 15",
+        );
+        assert_eq!(other.num_rows(), 0);
+
+        // the chunks created from the type codes don't know the field types.
+        let mut legacy = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        assert_eq!(legacy.field_type(0), None);
+        legacy.append_datum(0, &Datum::U64(1), &unsigned).unwrap();
+        let c = ArcChunk::new(legacy);
+        assert_eq!(c.get_row(0).get_datum(0, &signed, &tz).unwrap(), Datum::I64(1));
+        check_err(
+            c.get_row(0).get_typed_datum(0, &tz).map(|_| ()),
+            "column 0 has no field type",
+        );
+
+        // the decoded chunks keep the field types they are decoded with.
+        let decoded = ArcChunk::new(Chunk::decode(&buf, &fields).unwrap());
+        for (col_idx, d) in datums.iter().enumerate() {
+            assert_eq!(decoded.get_row(0).get_typed_datum(col_idx, &tz).unwrap(), *d);
+            assert!(decoded.get_row(1).is_null(col_idx));
+        }
+    }
+
+    fn new_str_chunk(rows: usize) -> ArcChunk {
+        let mut chunk = Chunk::new_chunk(&[types::VARCHAR as i32]);
+        for i in 0..rows {
+            chunk.append_str(0, format!("value of the row {}", i)).unwrap();
+        }
+        ArcChunk::new(chunk)
+    }
+
+    #[bench]
+    fn bench_get_str(b: &mut Bencher) {
+        let c = new_str_chunk(10000);
+        b.iter(|| {
+            let mut len = 0;
+            for row in &c {
+                len += row.get_str(0).unwrap().len();
+            }
+            black_box(len)
+        });
+    }
+
+    #[bench]
+    fn bench_get_str_ref(b: &mut Bencher) {
+        let c = new_str_chunk(10000);
+        b.iter(|| {
+            let mut len = 0;
+            for row in &c {
+                len += row.get_str_ref(0).unwrap().len();
+            }
+            black_box(len)
+        });
+    }
+
+    #[bench]
+    fn bench_get_i64(b: &mut Bencher) {
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        for i in 0..1_000_000 {
+            chunk.append_i64(0, i).unwrap();
+        }
+        let c = ArcChunk::new(chunk);
+        b.iter(|| {
+            let mut sum = 0i64;
+            for row in &c {
+                sum = sum.wrapping_add(row.get_i64(0));
+            }
+            black_box(sum)
+        });
+    }
+}
@@ -0,0 +1,2152 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::{cmp, fmt, i64, mem, str, u32};
+use std::io::Write;
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::FixedOffset;
+use crc::crc64::{self, Digest, Hasher64};
+use tipb::expression::FieldType;
+
+use coprocessor::codec::{Datum, Result};
+use coprocessor::codec::datum::approximate_size;
+use coprocessor::codec::mysql::{self, types, Decimal, Duration, Json, Time, DECIMAL_STRUCT_SIZE,
+                                TIME_STRUCT_SIZE};
+use coprocessor::util::checked_mul_add;
+use util::escape;
+use super::{count_ones_in_range, max_var_data_len, write_fingerprint_u64, DecodeLimits,
+            DEBUG_ROWS};
+
+/// The layout of the values in a `Column`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum ColumnKind {
+    /// The values are of `fixed_len` bytes and stored in `data`.
+    Fixed,
+    /// The values are stored in `data` and delimited by `var_offsets`.
+    VarLen,
+    /// The values are stored in `ifaces` as datums.
+    Interface,
+}
+
+// The kind and the fixed length of the columns of type `tp`.
+fn column_kind(tp: i32) -> (ColumnKind, usize) {
+    match tp as u8 {
+        types::TINY |
+        types::SHORT |
+        types::INT24 |
+        types::LONG |
+        types::LONG_LONG |
+        types::YEAR |
+        types::DOUBLE => (ColumnKind::Fixed, 8),
+        types::FLOAT => (ColumnKind::Fixed, 4),
+        types::NEW_DECIMAL => (ColumnKind::Fixed, DECIMAL_STRUCT_SIZE),
+        types::DATE | types::DATETIME | types::TIMESTAMP => (ColumnKind::Fixed, TIME_STRUCT_SIZE),
+        // only the nanoseconds are stored, like TiDB does.
+        types::DURATION => (ColumnKind::Fixed, 8),
+        types::VARCHAR |
+        types::VAR_STRING |
+        types::STRING |
+        types::BLOB |
+        types::TINY_BLOB |
+        types::MEDIUM_BLOB |
+        types::LONG_BLOB |
+        types::ENUM |
+        types::SET => (ColumnKind::VarLen, 0),
+        _ => (ColumnKind::Interface, 0),
+    }
+}
+
+// Whether the integers of the field type are unsigned, `None` if it isn't an
+// integer type.
+fn int_signedness(fp: &FieldType) -> Option<bool> {
+    match fp.get_tp() as u8 {
+        types::TINY |
+        types::SHORT |
+        types::INT24 |
+        types::LONG |
+        types::LONG_LONG |
+        types::YEAR => Some(types::has_unsigned_flag(fp.get_flag() as u64)),
+        _ => None,
+    }
+}
+
+fn signedness_name(unsigned: bool) -> &'static str {
+    if unsigned {
+        "unsigned"
+    } else {
+        "signed"
+    }
+}
+
+/// `Column` stores one column of data in Apache Arrow format. It's only
+/// moved between chunks by `Chunk::take_column` and `Chunk::set_column`.
+#[derive(Clone)]
+pub struct Column {
+    length: usize,
+    pub(super) null_cnt: usize,
+    pub(super) null_bitmap: Vec<u8>,
+    pub(super) var_offsets: Vec<u32>,
+    pub(super) data: Vec<u8>,
+    pub(super) ifaces: Vec<Datum>,
+    // if the data's length is fixed, fixed_len should be bigger than 0
+    pub(super) fixed_len: usize,
+    pub(super) kind: ColumnKind,
+    // the field type the column is created from, if it's known.
+    pub(super) field_type: Option<FieldType>,
+    // whether the integers are stored as unsigned. It's known if the column
+    // is created from an integer field type, or once a value out of the range
+    // of the other signedness is stored, then the values are never read with
+    // the other signedness, so a negative value can't wrap around.
+    unsigned: Option<bool>,
+}
+
+impl Column {
+    pub(super) fn new(tp: i32, init_cap: usize) -> Column {
+        match column_kind(tp) {
+            (ColumnKind::Fixed, fixed_len) => Column::new_fixed_len_column(fixed_len, init_cap),
+            (ColumnKind::VarLen, _) => Column::new_var_len_column(init_cap),
+            (ColumnKind::Interface, _) => Column::new_interface_column(init_cap),
+        }
+    }
+
+    fn new_fixed_len_column(fixed_len: usize, init_cap: usize) -> Column {
+        Column {
+            length: 0,
+            null_cnt: 0,
+            null_bitmap: Vec::with_capacity(init_cap),
+            var_offsets: vec![],
+            data: Vec::with_capacity(fixed_len * init_cap),
+            ifaces: vec![],
+            fixed_len: fixed_len,
+            kind: ColumnKind::Fixed,
+            field_type: None,
+            unsigned: None,
+        }
+    }
+
+    fn new_var_len_column(init_cap: usize) -> Column {
+        let mut offsets = Vec::with_capacity(init_cap + 1);
+        offsets.push(0);
+        Column {
+            length: 0,
+            null_cnt: 0,
+            null_bitmap: Vec::with_capacity(init_cap),
+            var_offsets: offsets,
+            data: Vec::with_capacity(4 * init_cap),
+            ifaces: vec![],
+            fixed_len: 0,
+            kind: ColumnKind::VarLen,
+            field_type: None,
+            unsigned: None,
+        }
+    }
+
+    fn new_interface_column(init_cap: usize) -> Column {
+        Column {
+            length: 0,
+            null_cnt: 0,
+            null_bitmap: Vec::with_capacity(init_cap),
+            var_offsets: vec![],
+            data: vec![],
+            ifaces: Vec::with_capacity(init_cap),
+            fixed_len: 0,
+            kind: ColumnKind::Interface,
+            field_type: None,
+            unsigned: None,
+        }
+    }
+
+    // Create an empty column of the same kind and the same field type.
+    pub(super) fn new_like(&self, init_cap: usize) -> Column {
+        let mut col = match self.kind {
+            ColumnKind::Fixed => Column::new_fixed_len_column(self.fixed_len, init_cap),
+            ColumnKind::VarLen => Column::new_var_len_column(init_cap),
+            ColumnKind::Interface => Column::new_interface_column(init_cap),
+        };
+        col.field_type = self.field_type.clone();
+        col.unsigned = self.unsigned;
+        col
+    }
+
+    #[inline]
+    pub(super) fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Reserve the memory for at least `additional_rows` more rows, the
+    /// var-length values are assumed to be `avg_var_len_hint` bytes long.
+    pub(super) fn reserve(&mut self, additional_rows: usize, avg_var_len_hint: usize) {
+        let bitmap_len = (self.length + additional_rows + 7) >> 3;
+        let additional_bytes = bitmap_len.saturating_sub(self.null_bitmap.len());
+        self.null_bitmap.reserve(additional_bytes);
+        match self.kind {
+            ColumnKind::Fixed => self.data.reserve(additional_rows * self.fixed_len),
+            ColumnKind::VarLen => {
+                self.var_offsets.reserve(additional_rows);
+                self.data.reserve(additional_rows * avg_var_len_hint);
+            }
+            ColumnKind::Interface => self.ifaces.reserve(additional_rows),
+        }
+    }
+
+    // The average length of the var-length values, the guess of an empty
+    // column is the same as `new_var_len_column`'s.
+    pub(super) fn avg_var_len(&self) -> usize {
+        if !self.is_var_len() {
+            return 0;
+        }
+        if self.length == 0 {
+            return 4;
+        }
+        (self.data.len() + self.length - 1) / self.length
+    }
+
+    /// Get a crc64 fingerprint of the values in the column, see
+    /// `Chunk::fingerprint`.
+    pub fn fingerprint(&self) -> u64 {
+        let mut digest = Digest::new(crc64::ECMA);
+        self.write_fingerprint(&mut digest);
+        digest.sum64()
+    }
+
+    // Only the bytes which make up the values are written, the bits beyond
+    // the length left by `truncate_to` and the bytes in the slots of the null
+    // values may differ between the columns equal to each other.
+    pub(super) fn write_fingerprint(&self, digest: &mut Digest) {
+        write_fingerprint_u64(digest, self.length as u64);
+        let bytes = (self.length + 7) >> 3;
+        let tail_bits = self.length & 7;
+        for (i, &b) in self.null_bitmap[..bytes].iter().enumerate() {
+            let b = if i + 1 == bytes && tail_bits != 0 {
+                b & ((1 << tail_bits) - 1)
+            } else {
+                b
+            };
+            digest.write(&[b]);
+        }
+        for idx in (0..self.length).filter(|&idx| !self.is_null(idx)) {
+            match self.kind {
+                ColumnKind::Fixed => digest.write(self.fixed_bytes(idx)),
+                ColumnKind::VarLen => {
+                    let bytes = self.get_bytes(idx);
+                    write_fingerprint_u64(digest, bytes.len() as u64);
+                    digest.write(bytes);
+                }
+                ColumnKind::Interface => {
+                    digest.write(format!("{:?}", self.ifaces[idx]).as_bytes())
+                }
+            }
+        }
+    }
+
+    pub(super) fn mem_usage(&self) -> usize {
+        self.null_bitmap.capacity() + self.var_offsets.capacity() * mem::size_of::<u32>() +
+            self.data.capacity() + self.ifaces.capacity() * mem::size_of::<Datum>()
+    }
+
+    #[inline]
+    pub(super) fn is_fixed(&self) -> bool {
+        self.kind == ColumnKind::Fixed
+    }
+
+    #[inline]
+    pub(super) fn is_var_len(&self) -> bool {
+        self.kind == ColumnKind::VarLen
+    }
+
+    pub(super) fn set_field_type(&mut self, fp: &FieldType) {
+        self.unsigned = int_signedness(fp);
+        self.field_type = Some(fp.clone());
+    }
+
+    /// Check that `fp` is of the same type as the field type of the column,
+    /// if it's known. The signedness is checked when the values are stored
+    /// or read instead.
+    pub(super) fn check_field_type(&self, col_idx: usize, fp: &FieldType) -> Result<()> {
+        let ft = match self.field_type {
+            Some(ref ft) => ft,
+            None => return Ok(()),
+        };
+        if ft.get_tp() != fp.get_tp() {
+            return Err(invalid_type!(
+                "column {} is of type {}, which can't be accessed as type {}",
+                col_idx,
+                ft.get_tp(),
+                fp.get_tp()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the values of type `fp` are stored in the layout of the
+    /// column.
+    pub(super) fn check_kind(&self, col_idx: usize, fp: &FieldType) -> Result<()> {
+        let (kind, fixed_len) = column_kind(fp.get_tp());
+        if self.kind != kind || self.fixed_len != fixed_len {
+            return Err(invalid_type!(
+                "column {} is {} column, which can't store type {}",
+                col_idx,
+                self.kind_name(),
+                fp.get_tp()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the integers should be read as unsigned, the signedness they
+    /// are stored with wins if it conflicts with `fp`.
+    pub(super) fn read_unsigned(&self, col_idx: usize, fp: &FieldType) -> bool {
+        let unsigned = types::has_unsigned_flag(fp.get_flag() as u64);
+        match self.unsigned {
+            Some(stored) if stored != unsigned => {
+                debug!(
+                    "column {} stores {} integers, but is read as {}",
+                    col_idx,
+                    signedness_name(stored),
+                    signedness_name(unsigned)
+                );
+                stored
+            }
+            _ => unsigned,
+        }
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.length = 0;
+        self.null_cnt = 0;
+        self.null_bitmap.clear();
+        if self.is_var_len() {
+            self.var_offsets.truncate(1);
+        }
+        self.data.clear();
+        self.ifaces.clear();
+        self.unsigned = self.field_type.as_ref().and_then(int_signedness);
+    }
+
+    /// Reset the column, and release the memory if it holds more than
+    /// `max_retained_bytes` bytes. Returns the number of bytes released.
+    pub(super) fn reset_with_limit(&mut self, max_retained_bytes: usize) -> usize {
+        self.reset();
+        let retained = self.mem_usage();
+        if retained <= max_retained_bytes {
+            return 0;
+        }
+        self.null_bitmap.shrink_to_fit();
+        self.var_offsets.shrink_to_fit();
+        self.data.shrink_to_fit();
+        self.ifaces.shrink_to_fit();
+        retained - self.mem_usage()
+    }
+
+    /// Reading a row out of range is a bug of the caller, it panics in debug
+    /// builds like TiDB does, and the row is regarded as NULL in release builds
+    /// since there is no value in it.
+    pub(super) fn is_null(&self, row_idx: usize) -> bool {
+        debug_assert!(
+            row_idx < self.length,
+            "row {} is out of range, the column has {} rows",
+            row_idx,
+            self.length
+        );
+        if row_idx >= self.length {
+            return true;
+        }
+        self.null_bitmap[row_idx >> 3] & (1 << (row_idx & 7)) == 0
+    }
+
+    fn append_null_bitmap(&mut self, on: bool) {
+        let idx = self.length >> 3;
+        if idx >= self.null_bitmap.len() {
+            self.null_bitmap.push(0);
+        }
+        if on {
+            let pos = self.length & 7;
+            self.null_bitmap[idx] |= 1 << pos;
+        } else {
+            self.null_cnt += 1;
+        }
+    }
+
+    pub(super) fn append_nulls(&mut self, n: usize) {
+        self.append_null_bits(n, false);
+        match self.kind {
+            ColumnKind::Fixed => {
+                let len = n * self.fixed_len + self.data.len();
+                self.data.resize(len, 0);
+            }
+            ColumnKind::VarLen => {
+                let offset = self.var_offsets[self.length];
+                let len = self.var_offsets.len() + n;
+                self.var_offsets.resize(len, offset);
+            }
+            ColumnKind::Interface => {
+                let len = self.ifaces.len() + n;
+                self.ifaces.resize(len, Datum::Null);
+            }
+        }
+        self.length += n;
+    }
+
+    // Set the null bits of the next `n` rows like calling `append_null_bitmap`
+    // `n` times, but a byte at a time.
+    fn append_null_bits(&mut self, n: usize, on: bool) {
+        if n == 0 {
+            return;
+        }
+        // the bits beyond the length are cleared first, so are the bytes.
+        let shift = self.length & 7;
+        self.null_bitmap.truncate((self.length + 7) >> 3);
+        if shift != 0 {
+            let last = self.null_bitmap.last_mut().unwrap();
+            *last &= (1 << shift) - 1;
+            if on {
+                *last |= !((1 << shift) - 1);
+            }
+        }
+        let end = self.length + n;
+        self.null_bitmap.resize((end + 7) >> 3, if on { 0xff } else { 0 });
+        if end & 7 != 0 {
+            *self.null_bitmap.last_mut().unwrap() &= (1 << (end & 7)) - 1;
+        }
+        if !on {
+            self.null_cnt += n;
+        }
+    }
+
+    pub(super) fn append_null(&mut self) {
+        self.append_null_bitmap(false);
+        if self.is_fixed() {
+            let len = self.fixed_len + self.data.len();
+            self.data.resize(len, 0);
+        } else if self.is_var_len() {
+            let offset = self.var_offsets[self.length];
+            self.var_offsets.push(offset);
+        } else {
+            self.ifaces.push(Datum::Null);
+        }
+        self.length += 1;
+    }
+
+    fn finish_append_fixed(&mut self) {
+        self.append_null_bitmap(true);
+        self.length += 1;
+    }
+
+    pub(super) fn append_i64(&mut self, v: i64) -> Result<()> {
+        if v < 0 {
+            if self.unsigned == Some(true) {
+                return Err(invalid_type!("an unsigned integer column can't hold {}", v));
+            }
+            self.unsigned = Some(false);
+        }
+        self.data.write_i64::<LittleEndian>(v)?;
+        self.finish_append_fixed();
+        Ok(())
+    }
+
+    // Get the bytes of the fixed-length value at `idx` without copying them.
+    #[inline]
+    fn fixed_bytes(&self, idx: usize) -> &[u8] {
+        debug_assert!(
+            idx < self.length,
+            "row {} is out of range, the column has {} rows",
+            idx,
+            self.length
+        );
+        let start = idx * self.fixed_len;
+        &self.data[start..start + self.fixed_len]
+    }
+
+    // Get the integer at `idx` with the signedness it should be read with,
+    // the bits are returned as is.
+    pub(super) fn get_int(&self, idx: usize, col_idx: usize, fp: &FieldType) -> (i64, bool) {
+        let v = LittleEndian::read_i64(self.fixed_bytes(idx));
+        (v, self.read_unsigned(col_idx, fp))
+    }
+
+    // The getters of the plain values return the zero values for the null
+    // rows, the bytes in their slots aren't always zeros, e.g. they are left
+    // by the previous values in the chunks encoded by TiDB.
+    pub(super) fn get_i64(&self, idx: usize) -> i64 {
+        if self.is_null(idx) {
+            return 0;
+        }
+        let v = LittleEndian::read_i64(self.fixed_bytes(idx));
+        debug_assert!(
+            v >= 0 || self.unsigned != Some(true),
+            "read the unsigned {} as {}",
+            v as u64,
+            v
+        );
+        v
+    }
+
+    pub(super) fn append_i64_slice(&mut self, vs: &[i64]) -> Result<()> {
+        if let Some(&v) = vs.iter().find(|&&v| v < 0) {
+            if self.unsigned == Some(true) {
+                return Err(invalid_type!("an unsigned integer column can't hold {}", v));
+            }
+            self.unsigned = Some(false);
+        }
+        self.append_fixed_slice(vs, LittleEndian::write_i64);
+        Ok(())
+    }
+
+    pub(super) fn append_u64(&mut self, v: u64) -> Result<()> {
+        if v > i64::MAX as u64 {
+            if self.unsigned == Some(false) {
+                return Err(invalid_type!("a signed integer column can't hold {}", v));
+            }
+            self.unsigned = Some(true);
+        }
+        self.data.write_u64::<LittleEndian>(v)?;
+        self.finish_append_fixed();
+        Ok(())
+    }
+
+    pub(super) fn get_u64(&self, idx: usize) -> u64 {
+        if self.is_null(idx) {
+            return 0;
+        }
+        let v = LittleEndian::read_u64(self.fixed_bytes(idx));
+        debug_assert!(
+            v <= i64::MAX as u64 || self.unsigned != Some(false),
+            "read the signed {} as {}",
+            v as i64,
+            v
+        );
+        v
+    }
+
+    pub(super) fn append_u64_slice(&mut self, vs: &[u64]) -> Result<()> {
+        if let Some(&v) = vs.iter().find(|&&v| v > i64::MAX as u64) {
+            if self.unsigned == Some(false) {
+                return Err(invalid_type!("a signed integer column can't hold {}", v));
+            }
+            self.unsigned = Some(true);
+        }
+        self.append_fixed_slice(vs, LittleEndian::write_u64);
+        Ok(())
+    }
+
+    pub(super) fn append_f32(&mut self, v: f32) -> Result<()> {
+        debug_assert_eq!(self.fixed_len, 4, "append f32 to a column of {} bytes", self.fixed_len);
+        self.data.write_f32::<LittleEndian>(v)?;
+        self.finish_append_fixed();
+        Ok(())
+    }
+
+    pub(super) fn get_f32(&self, idx: usize) -> f32 {
+        if self.is_null(idx) {
+            return 0.0;
+        }
+        LittleEndian::read_f32(self.fixed_bytes(idx))
+    }
+
+    pub(super) fn append_f64(&mut self, v: f64) -> Result<()> {
+        debug_assert_eq!(self.fixed_len, 8, "append f64 to a column of {} bytes", self.fixed_len);
+        self.data.write_f64::<LittleEndian>(v)?;
+        self.finish_append_fixed();
+        Ok(())
+    }
+
+    pub(super) fn append_f64_slice(&mut self, vs: &[f64]) -> Result<()> {
+        self.append_fixed_slice(vs, LittleEndian::write_f64);
+        Ok(())
+    }
+
+    // Append the 8-byte values, the data is grown once, and the null bits
+    // are set a byte at a time.
+    fn append_fixed_slice<T: Copy>(&mut self, vs: &[T], write: fn(&mut [u8], T)) {
+        debug_assert_eq!(self.fixed_len, 8, "append {} bytes values", self.fixed_len);
+        let start = self.data.len();
+        self.data.resize(start + vs.len() * 8, 0);
+        for (buf, &v) in self.data[start..].chunks_mut(8).zip(vs) {
+            write(buf, v);
+        }
+        self.append_null_bits(vs.len(), true);
+        self.length += vs.len();
+    }
+
+    pub(super) fn get_f64(&self, idx: usize) -> f64 {
+        if self.is_null(idx) {
+            return 0.0;
+        }
+        LittleEndian::read_f64(self.fixed_bytes(idx))
+    }
+
+    // Check that `additional` more bytes of var-length values can be
+    // appended without overflowing the offsets.
+    fn check_var_data_len(&self, additional: usize) -> Result<()> {
+        match self.data.len().checked_add(additional) {
+            Some(len) if len <= max_var_data_len() => Ok(()),
+            _ => Err(box_err!(
+                "can't append {} bytes to a var-length column of {} bytes, the limit is {}",
+                additional,
+                self.data.len(),
+                max_var_data_len()
+            )),
+        }
+    }
+
+    fn finish_append_var(&mut self) {
+        self.append_null_bitmap(true);
+        let offset = self.data.len() as u32;
+        self.var_offsets.push(offset);
+        self.length += 1;
+    }
+
+    pub(super) fn append_str(&mut self, v: &str) -> Result<()> {
+        self.append_bytes(v.as_bytes())
+    }
+
+    pub(super) fn append_bytes(&mut self, byte: &[u8]) -> Result<()> {
+        self.check_var_data_len(byte.len())?;
+        self.data.extend_from_slice(byte);
+        self.finish_append_var();
+        Ok(())
+    }
+
+    pub(super) fn append_strs<I>(&mut self, values: I) -> Result<()>
+    where
+        I: Iterator,
+        I::Item: AsRef<str>,
+    {
+        self.reserve(values.size_hint().0, 0);
+        for v in values {
+            self.append_bytes(v.as_ref().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn append_bytes_iter<I>(&mut self, values: I) -> Result<()>
+    where
+        I: Iterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.reserve(values.size_hint().0, 0);
+        for v in values {
+            self.append_bytes(v.as_ref())?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn get_bytes(&self, idx: usize) -> &[u8] {
+        if self.is_null(idx) {
+            return &[];
+        }
+        let start = self.var_offsets[idx] as usize;
+        let end = self.var_offsets[idx + 1] as usize;
+        &self.data[start..end]
+    }
+
+    pub(super) fn get_str_ref(&self, idx: usize) -> Result<&str> {
+        let s = str::from_utf8(self.get_bytes(idx))?;
+        Ok(s)
+    }
+
+    pub(super) fn append_decimal(&mut self, v: &Decimal) -> Result<()> {
+        v.write_fixed(&mut self.data);
+        self.finish_append_fixed();
+        Ok(())
+    }
+
+    pub(super) fn get_decimal(&self, idx: usize) -> Result<Option<Decimal>> {
+        self.check_fixed_len(DECIMAL_STRUCT_SIZE, "decimal")?;
+        if self.is_null(idx) {
+            return Ok(None);
+        }
+        let start = idx * self.fixed_len;
+        let end = start + self.fixed_len;
+        Ok(Some(Decimal::read_fixed(&self.data[start..end])))
+    }
+
+    pub(super) fn append_time(&mut self, v: &Time) -> Result<()> {
+        v.write_fixed(&mut self.data);
+        self.finish_append_fixed();
+        Ok(())
+    }
+
+    pub(super) fn get_time(&self, idx: usize, tz: &FixedOffset) -> Result<Option<Time>> {
+        self.check_fixed_len(TIME_STRUCT_SIZE, "time")?;
+        if self.is_null(idx) {
+            return Ok(None);
+        }
+        let start = idx * self.fixed_len;
+        let end = start + self.fixed_len;
+        Time::read_fixed(&self.data[start..end], tz).map(Some)
+    }
+
+    pub(super) fn append_duration(&mut self, v: &Duration) -> Result<()> {
+        self.append_i64(v.to_nanos())
+    }
+
+    // The nanoseconds are stored like the int64 values, so the durations can
+    // only be told apart from them by the field type.
+    pub(super) fn get_duration(&self, idx: usize, fsp: i8) -> Result<Option<Duration>> {
+        self.check_fixed_len(8, "duration")?;
+        if self.is_null(idx) {
+            return Ok(None);
+        }
+        Duration::from_nanos(self.get_i64(idx), fsp).map(Some)
+    }
+
+    pub(super) fn append_interface(&mut self, v: Datum) {
+        self.ifaces.push(v);
+        self.append_null_bitmap(true);
+        self.length += 1;
+    }
+
+    // Get the datum at `idx` as a value of type `fp`, see `Row::get_datum`,
+    // `col_idx` is only used in the messages.
+    pub(super) fn get_datum(
+        &self,
+        idx: usize,
+        col_idx: usize,
+        fp: &FieldType,
+        tz: &FixedOffset,
+    ) -> Result<Datum> {
+        if self.is_null(idx) {
+            return Ok(Datum::Null);
+        }
+        let d = match fp.get_tp() as u8 {
+            types::TINY |
+            types::SHORT |
+            types::INT24 |
+            types::LONG |
+            types::LONG_LONG |
+            types::YEAR => if self.read_unsigned(col_idx, fp) {
+                Datum::U64(self.get_u64(idx))
+            } else {
+                Datum::I64(self.get_i64(idx))
+            },
+            // there is no Datum::F32, so FLOAT is widened to float64.
+            types::FLOAT => Datum::F64(f64::from(self.get_f32(idx))),
+            types::DOUBLE => Datum::F64(self.get_f64(idx)),
+            types::VARCHAR |
+            types::VAR_STRING |
+            types::STRING |
+            types::BLOB |
+            types::TINY_BLOB |
+            types::MEDIUM_BLOB |
+            types::LONG_BLOB => Datum::Bytes(self.get_bytes(idx).to_vec()),
+            types::NEW_DECIMAL => self.get_decimal(idx)?.map_or(Datum::Null, Datum::Dec),
+            types::DATE | types::DATETIME | types::TIMESTAMP => {
+                match self.get_time(idx, tz)? {
+                    Some(mut t) => {
+                        let fsp = fp.get_decimal();
+                        if fsp >= mysql::MIN_FSP as i32 && fsp <= mysql::MAX_FSP as i32 {
+                            t.set_fsp(fsp as u8);
+                        }
+                        Datum::Time(t)
+                    }
+                    None => Datum::Null,
+                }
+            }
+            types::DURATION => self.get_duration(idx, fp.get_decimal() as i8)?
+                .map_or(Datum::Null, Datum::Dur),
+            types::JSON => self.get_json(idx)?
+                .map_or(Datum::Null, |j| Datum::Json(j.clone())),
+            types::ENUM => self.get_name_value(idx, "enum")?
+                .map_or(Datum::Null, |(_, v)| Datum::U64(v)),
+            types::SET => self.get_name_value(idx, "set")?
+                .map_or(Datum::Null, |(_, v)| Datum::U64(v)),
+            _ => self.get_interface(idx),
+        };
+        Ok(d)
+    }
+
+    pub(super) fn get_interface(&self, idx: usize) -> Datum {
+        if self.is_null(idx) {
+            return Datum::Null;
+        }
+        self.ifaces[idx].clone()
+    }
+
+    pub(super) fn get_json(&self, idx: usize) -> Result<Option<&Json>> {
+        self.check_interface()?;
+        if self.is_null(idx) {
+            return Ok(None);
+        }
+        match self.ifaces[idx] {
+            Datum::Json(ref j) => Ok(Some(j)),
+            ref d => Err(invalid_type!("row {} holds {:?} instead of a json value", idx, d)),
+        }
+    }
+
+    // The enum and the set values are stored as their uint64 values followed
+    // by their names, like TiDB does.
+    pub(super) fn append_name_value(&mut self, name: &[u8], value: u64) -> Result<()> {
+        self.check_var_data_len(8 + name.len())?;
+        self.data.write_u64::<LittleEndian>(value)?;
+        self.data.write_all(name)?;
+        self.finish_append_var();
+        Ok(())
+    }
+
+    pub(super) fn get_name_value(&self, idx: usize, tp: &str) -> Result<Option<(&[u8], u64)>> {
+        if !self.is_var_len() {
+            return Err(invalid_type!("{} column can't hold {} values", self.kind_name(), tp));
+        }
+        if self.is_null(idx) {
+            return Ok(None);
+        }
+        let bytes = self.get_bytes(idx);
+        if bytes.len() < 8 {
+            return Err(invalid_type!(
+                "row {} holds {} bytes, which are too short for the {} value",
+                idx,
+                bytes.len(),
+                tp
+            ));
+        }
+        let (value, name) = bytes.split_at(8);
+        Ok(Some((name, LittleEndian::read_u64(value))))
+    }
+
+    fn check_fixed_len(&self, fixed_len: usize, tp: &str) -> Result<()> {
+        if self.is_fixed() && self.fixed_len == fixed_len {
+            return Ok(());
+        }
+        Err(invalid_type!("{} column can't hold {} values", self.kind_name(), tp))
+    }
+
+    fn check_interface(&self) -> Result<()> {
+        if self.kind == ColumnKind::Interface {
+            return Ok(());
+        }
+        Err(invalid_type!("{} column doesn't hold datums", self.kind_name()))
+    }
+
+    /// Append rows in [begin,end) of another column of the same kind.
+    pub(super) fn append(&mut self, other: &Column, begin: usize, end: usize) -> Result<()> {
+        self.check_same_kind(other)?;
+        self.unsigned = self.unsigned.or(other.unsigned);
+        if self.is_fixed() {
+            let start_idx = begin * self.fixed_len;
+            let end_idx = end * self.fixed_len;
+            self.data.extend_from_slice(&other.data[start_idx..end_idx]);
+        } else if self.is_var_len() {
+            let start = other.var_offsets[begin] as usize;
+            let end_offset = other.var_offsets[end] as usize;
+            self.check_var_data_len(end_offset - start)?;
+            self.data.extend_from_slice(&other.data[start..end_offset]);
+            let mut last_offset = *self.var_offsets.last().unwrap();
+            for i in begin..end {
+                last_offset += other.var_offsets[i + 1] - other.var_offsets[i];
+                self.var_offsets.push(last_offset);
+            }
+        } else {
+            self.ifaces.extend_from_slice(&other.ifaces[begin..end]);
+        }
+        for i in begin..end {
+            self.append_null_bitmap(!other.is_null(i));
+            self.length += 1;
+        }
+        Ok(())
+    }
+
+    // The rows of a column can be appended to another only if their values
+    // are laid out in the same way, and the integers aren't stored with
+    // different signedness.
+    #[inline]
+    pub(super) fn is_same_kind(&self, other: &Column) -> bool {
+        self.is_same_layout(other) && !self.is_sign_conflicted(other)
+    }
+
+    #[inline]
+    fn is_sign_conflicted(&self, other: &Column) -> bool {
+        match (self.unsigned, other.unsigned) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub(super) fn check_same_kind(&self, other: &Column) -> Result<()> {
+        if self.is_same_kind(other) {
+            return Ok(());
+        }
+        if self.is_sign_conflicted(other) {
+            return Err(invalid_type!(
+                "can't append {} integers to a column of {} integers",
+                signedness_name(other.unsigned.unwrap()),
+                signedness_name(self.unsigned.unwrap())
+            ));
+        }
+        Err(invalid_type!(
+            "can't append {} column to {} column",
+            other.kind_name(),
+            self.kind_name()
+        ))
+    }
+
+    #[inline]
+    pub(super) fn is_same_layout(&self, other: &Column) -> bool {
+        self.kind == other.kind && self.fixed_len == other.fixed_len
+    }
+
+    // Whether the value at `idx` equals the value at `other_idx` of `other`,
+    // which must be of the same layout. The bytes left in the slots of the
+    // null values aren't compared.
+    pub(super) fn row_eq(&self, idx: usize, other: &Column, other_idx: usize) -> bool {
+        match (self.is_null(idx), other.is_null(other_idx)) {
+            (true, true) => return true,
+            (false, false) => {}
+            _ => return false,
+        }
+        match self.kind {
+            ColumnKind::Fixed => self.fixed_bytes(idx) == other.fixed_bytes(other_idx),
+            ColumnKind::VarLen => self.get_bytes(idx) == other.get_bytes(other_idx),
+            ColumnKind::Interface => self.ifaces[idx] == other.ifaces[other_idx],
+        }
+    }
+
+    // Write the value at `idx` for debugging. The fixed-length values are
+    // written as numbers if the column is known to hold integers or floats,
+    // or as hex bytes otherwise.
+    pub(super) fn fmt_value(&self, idx: usize, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_null(idx) {
+            return f.write_str("NULL");
+        }
+        match self.kind {
+            ColumnKind::Fixed => {
+                let bytes = self.fixed_bytes(idx);
+                let tp = self.field_type.as_ref().map(|ft| ft.get_tp() as u8);
+                match (self.unsigned, tp) {
+                    (Some(true), _) => write!(f, "{}", LittleEndian::read_u64(bytes)),
+                    (Some(false), _) => write!(f, "{}", LittleEndian::read_i64(bytes)),
+                    (_, Some(types::FLOAT)) => write!(f, "{}", LittleEndian::read_f32(bytes)),
+                    (_, Some(types::DOUBLE)) => write!(f, "{}", LittleEndian::read_f64(bytes)),
+                    _ => {
+                        f.write_str("0x")?;
+                        for b in bytes {
+                            write!(f, "{:02x}", b)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            ColumnKind::VarLen => write!(f, "\"{}\"", escape(self.get_bytes(idx))),
+            ColumnKind::Interface => write!(f, "{}", self.ifaces[idx]),
+        }
+    }
+
+    fn kind_name(&self) -> String {
+        match self.kind {
+            ColumnKind::Fixed => format!("a fixed length({})", self.fixed_len),
+            ColumnKind::VarLen => "a var-length".to_owned(),
+            ColumnKind::Interface => "an interface".to_owned(),
+        }
+    }
+
+    /// Append all rows of `other`, it's faster than `append` as the data is
+    /// copied at once and the null bitmap is copied byte by byte.
+    pub(super) fn append_all(&mut self, other: &Column) -> Result<()> {
+        self.check_same_kind(other)?;
+        self.unsigned = self.unsigned.or(other.unsigned);
+        if self.is_fixed() {
+            let len = checked_mul_add(other.length, other.fixed_len, 0, "data length")?;
+            self.data.extend_from_slice(&other.data[..len]);
+        } else if self.is_var_len() {
+            let len = other.var_offsets[other.length] as usize;
+            self.check_var_data_len(len)?;
+            let base = *self.var_offsets.last().unwrap();
+            let offsets = &other.var_offsets[1..other.length + 1];
+            self.var_offsets.reserve(offsets.len());
+            self.var_offsets.extend(offsets.iter().map(|&o| o + base));
+            self.data.extend_from_slice(&other.data[..len]);
+        } else {
+            self.ifaces.extend_from_slice(&other.ifaces[..other.length]);
+        }
+        self.append_null_bitmap_of(other);
+        self.length += other.length;
+        self.null_cnt += other.null_cnt;
+        Ok(())
+    }
+
+    // Append the null bits of all rows of `other`, the bits beyond the length
+    // are cleared on both sides as they may be left by `truncate_to`.
+    fn append_null_bitmap_of(&mut self, other: &Column) {
+        let shift = self.length & 7;
+        self.null_bitmap.truncate((self.length + 7) >> 3);
+        if shift != 0 {
+            *self.null_bitmap.last_mut().unwrap() &= (1 << shift) - 1;
+        }
+        let bytes = (other.length + 7) >> 3;
+        let tail_bits = other.length & 7;
+        self.null_bitmap.reserve(bytes);
+        for (i, &b) in other.null_bitmap[..bytes].iter().enumerate() {
+            let b = if i + 1 == bytes && tail_bits != 0 {
+                b & ((1 << tail_bits) - 1)
+            } else {
+                b
+            };
+            if shift == 0 {
+                self.null_bitmap.push(b);
+            } else {
+                *self.null_bitmap.last_mut().unwrap() |= b << shift;
+                self.null_bitmap.push(b >> (8 - shift));
+            }
+        }
+        self.null_bitmap
+            .truncate((self.length + other.length + 7) >> 3);
+    }
+
+    /// Truncate the column to `num_rows` rows.
+    pub(super) fn truncate_to(&mut self, num_rows: usize) {
+        if num_rows >= self.length {
+            return;
+        }
+        if self.is_fixed() {
+            self.data.truncate(num_rows * self.fixed_len);
+        } else if self.is_var_len() {
+            let end = self.var_offsets[num_rows] as usize;
+            self.data.truncate(end);
+            self.var_offsets.truncate(num_rows + 1);
+        } else {
+            self.ifaces.truncate(num_rows);
+        }
+        let removed_nulls = (num_rows..self.length).filter(|&i| self.is_null(i)).count();
+        self.null_cnt -= removed_nulls;
+        self.length = num_rows;
+        // clear the bits of the removed rows, so appending a NULL later won't
+        // see a stale bit.
+        self.null_bitmap.truncate((num_rows + 7) >> 3);
+        if num_rows & 7 != 0 {
+            *self.null_bitmap.last_mut().unwrap() &= (1 << (num_rows & 7)) - 1;
+        }
+    }
+
+    /// Remove all the rows but the last one, so a value appended to a
+    /// single-row column replaces the old value, and the memory is reused.
+    pub(super) fn keep_last_row(&mut self) {
+        if self.length <= 1 {
+            return;
+        }
+        let last = self.length - 1;
+        let is_null = self.is_null(last);
+        if self.is_fixed() {
+            self.data.drain(..last * self.fixed_len);
+        } else if self.is_var_len() {
+            let (start, end) = (self.var_offsets[last], self.var_offsets[last + 1]);
+            self.data.drain(..start as usize);
+            self.var_offsets.clear();
+            self.var_offsets.push(0);
+            self.var_offsets.push(end - start);
+        } else {
+            self.ifaces.drain(..last);
+        }
+        self.null_bitmap.clear();
+        if is_null {
+            self.null_bitmap.push(0);
+            self.null_cnt = 1;
+        } else {
+            self.null_bitmap.push(1);
+            self.null_cnt = 0;
+        }
+        self.length = 1;
+    }
+
+    /// Count the null values in rows [begin,end), the bits are counted a
+    /// word at a time, so it's cheap enough to be checked for every batch.
+    pub fn null_count_in_range(&self, begin: usize, end: usize) -> usize {
+        if self.null_cnt == 0 || begin >= end {
+            return 0;
+        }
+        end - begin - count_ones_in_range(&self.null_bitmap, begin, end)
+    }
+
+    /// Return whether none of the values in rows [begin,end) is null, so the
+    /// values can be read without checking the rows one by one.
+    pub fn all_not_null(&self, begin: usize, end: usize) -> bool {
+        self.null_count_in_range(begin, end) == 0
+    }
+
+    /// Encode rows [begin,end) of the column into `buf` with the format of
+    /// TiDB's `chunk/codec.go`: length, null count, null bitmap (only if
+    /// there are nulls), offsets (only for var-len columns) and raw data.
+    /// The offsets are i64 like TiDB's, though they are u32 in memory.
+    ///
+    /// `data_begin` is the position of row `begin` in `data`, the position
+    /// of row `end` is returned so the caller can continue from there.
+    pub(super) fn encode_rows(
+        &self,
+        begin: usize,
+        end: usize,
+        data_begin: usize,
+        buf: &mut Vec<u8>,
+    ) -> Result<usize> {
+        let length = end - begin;
+        if length > u32::MAX as usize {
+            return Err(box_err!("too many rows to encode: {}", length));
+        }
+        let null_cnt = self.null_count_in_range(begin, end);
+        buf.write_u32::<LittleEndian>(length as u32)?;
+        buf.write_u32::<LittleEndian>(null_cnt as u32)?;
+        if null_cnt > 0 {
+            self.encode_null_bitmap(begin, end, buf);
+        }
+        if self.is_fixed() {
+            let data_end = checked_mul_add(length, self.fixed_len, data_begin, "data end")?;
+            buf.extend_from_slice(&self.data[data_begin..data_end]);
+            return Ok(data_end);
+        }
+        for &offset in &self.var_offsets[begin..end + 1] {
+            buf.write_i64::<LittleEndian>((offset as usize - data_begin) as i64)?;
+        }
+        let data_end = self.var_offsets[end] as usize;
+        buf.extend_from_slice(&self.data[data_begin..data_end]);
+        Ok(data_end)
+    }
+
+    /// Get the number of bytes `encode_rows` produces for the rows in
+    /// [begin,end) of a fixed-length or var-length column.
+    pub(super) fn encoded_size_of_rows(&self, begin: usize, end: usize) -> usize {
+        let length = end - begin;
+        // the length and the null count.
+        let mut size = 8;
+        if !self.all_not_null(begin, end) {
+            size += (length + 7) >> 3;
+        }
+        if self.is_fixed() {
+            return size + length * self.fixed_len;
+        }
+        let data_len = self.var_offsets[end] - self.var_offsets[begin];
+        size + (length + 1) * 8 + data_len as usize
+    }
+
+    /// Get the number of bytes `encode_rows` produces for all the rows of the
+    /// column. The datums of an interface column are regarded as the values
+    /// of a var-len column, whose sizes are estimated by `approximate_size`.
+    pub(super) fn encoded_size_hint(&self) -> usize {
+        // the length and the null count.
+        let mut size = 8;
+        if self.null_cnt > 0 {
+            size += (self.length + 7) >> 3;
+        }
+        size += match self.kind {
+            ColumnKind::Fixed => self.length * self.fixed_len,
+            ColumnKind::VarLen => {
+                let data_len = self.var_offsets[self.length] - self.var_offsets[0];
+                (self.length + 1) * 8 + data_len as usize
+            }
+            ColumnKind::Interface => {
+                (self.length + 1) * 8 + approximate_size(&self.ifaces, false)
+            }
+        };
+        size
+    }
+
+    /// Encode the null bits of rows [begin,end), the bits of `begin` is
+    /// shifted to the lowest bit of the first byte.
+    fn encode_null_bitmap(&self, begin: usize, end: usize, buf: &mut Vec<u8>) {
+        let length = end - begin;
+        let bytes = (length + 7) >> 3;
+        let shift = begin & 7;
+        let first = begin >> 3;
+        for i in 0..bytes {
+            let lo = self.null_bitmap[first + i];
+            let mut b = if shift == 0 {
+                lo
+            } else {
+                let hi = self.null_bitmap.get(first + i + 1).cloned().unwrap_or(0);
+                (lo >> shift) | (hi << (8 - shift))
+            };
+            let remain = length - i * 8;
+            if remain < 8 {
+                b &= (1 << remain) - 1;
+            }
+            buf.push(b);
+        }
+    }
+
+    /// Decode a column encoded by `encode_rows` from the front of `buf`,
+    /// `buf` is advanced to the end of the column. `col_idx` is only used
+    /// in the errors.
+    pub(super) fn decode(
+        tp: i32,
+        col_idx: usize,
+        buf: &mut &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<Column> {
+        let mut col = Column::new(tp, 0);
+        if !col.is_fixed() && !col.is_var_len() {
+            return Err(invalid_type!(
+                "column {} with type {} can't be decoded",
+                col_idx,
+                tp
+            ));
+        }
+        let mut header = read_bytes(buf, 8, col_idx, "header")?;
+        let length = header.read_u32::<LittleEndian>()? as usize;
+        let null_cnt = header.read_u32::<LittleEndian>()? as usize;
+        if null_cnt > length {
+            return Err(box_err!(
+                "column {}: null count {} is larger than the length {}",
+                col_idx,
+                null_cnt,
+                length
+            ));
+        }
+        let bitmap_len = (length + 7) >> 3;
+        let null_bitmap = if null_cnt > 0 {
+            Some(read_bytes(buf, bitmap_len, col_idx, "null bitmap")?)
+        } else {
+            None
+        };
+        if col.is_fixed() {
+            let data_len = checked_mul_add(length, col.fixed_len, 0, "data length")?;
+            check_column_size(data_len, col_idx, limits)?;
+            col.data = read_bytes(buf, data_len, col_idx, "data")?.to_vec();
+        } else {
+            let offsets_len = checked_mul_add(length, 8, 8, "offsets length")?;
+            let offsets = read_bytes(buf, offsets_len, col_idx, "offsets")?;
+            let data_len = check_offsets(offsets, col_idx, limits)?;
+            check_column_size(data_len, col_idx, limits)?;
+            col.data = read_bytes(buf, data_len, col_idx, "data")?.to_vec();
+            col.var_offsets = offsets
+                .chunks(8)
+                .map(|b| LittleEndian::read_i64(b) as u32)
+                .collect();
+        }
+
+        // the bits beyond the length are always unset, like appending does.
+        let tail_mask = if length & 7 == 0 {
+            0xff
+        } else {
+            (1u8 << (length & 7)) - 1
+        };
+        col.null_bitmap = match null_bitmap {
+            Some(bitmap) => bitmap.to_vec(),
+            None => vec![0xff; bitmap_len],
+        };
+        if let Some(last) = col.null_bitmap.last_mut() {
+            *last &= tail_mask;
+        }
+        col.length = length;
+        col.null_cnt = null_cnt;
+        if col.null_count_in_range(0, length) != null_cnt {
+            return Err(box_err!(
+                "column {}: null count {} doesn't match the null bitmap",
+                col_idx,
+                null_cnt
+            ));
+        }
+        Ok(col)
+    }
+}
+
+// Check the offsets of a var-length column, returns the length of the data.
+// They must fit in the u32 offsets kept in memory.
+fn check_offsets(offsets: &[u8], col_idx: usize, limits: &DecodeLimits) -> Result<usize> {
+    let mut last = 0;
+    for (i, b) in offsets.chunks(8).enumerate() {
+        let offset = LittleEndian::read_i64(b);
+        if offset < 0 || offset > i64::from(u32::MAX) {
+            return Err(box_err!(
+                "column {}: offset {} at index {} is out of range",
+                col_idx,
+                offset,
+                i
+            ));
+        }
+        let offset = offset as usize;
+        if i == 0 && offset != 0 {
+            return Err(box_err!(
+                "column {}: the first offset is {}, but it should be 0",
+                col_idx,
+                offset
+            ));
+        }
+        if offset < last {
+            return Err(box_err!(
+                "column {}: offset {} at index {} is less than the previous offset {}",
+                col_idx,
+                offset,
+                i,
+                last
+            ));
+        }
+        if offset - last > limits.max_value_size {
+            return Err(box_err!(
+                "column {}: value {} has {} bytes, which exceeds the limit {}",
+                col_idx,
+                i - 1,
+                offset - last,
+                limits.max_value_size
+            ));
+        }
+        last = offset;
+    }
+    Ok(last)
+}
+
+#[inline]
+fn check_column_size(data_len: usize, col_idx: usize, limits: &DecodeLimits) -> Result<()> {
+    if data_len > limits.max_column_size {
+        return Err(box_err!(
+            "column {}: data has {} bytes, which exceeds the limit {}",
+            col_idx,
+            data_len,
+            limits.max_column_size
+        ));
+    }
+    Ok(())
+}
+
+// Take `n` bytes of the column from the front of `buf`.
+fn read_bytes<'a>(buf: &mut &'a [u8], n: usize, col_idx: usize, what: &str) -> Result<&'a [u8]> {
+    if buf.len() < n {
+        return Err(box_err!(
+            "column {}: {} needs {} bytes, but only {} bytes left",
+            col_idx,
+            what,
+            n,
+            buf.len()
+        ));
+    }
+    let (bytes, rest) = buf.split_at(n);
+    *buf = rest;
+    Ok(bytes)
+}
+
+/// The columns are equal if they are of the same layout and hold the same
+/// values. The spare capacity, the field types and the bytes left in the
+/// slots of the null values aren't compared.
+impl PartialEq for Column {
+    fn eq(&self, other: &Column) -> bool {
+        self.is_same_layout(other) && self.length == other.length &&
+            (0..self.length).all(|i| self.row_eq(i, other, i))
+    }
+}
+
+/// See `Chunk`'s `Debug`, the values are written in a line.
+impl fmt::Debug for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limit = f.precision().unwrap_or(DEBUG_ROWS);
+        write!(f, "Column {{ {}, {} rows: [", self.kind_name(), self.length)?;
+        for idx in 0..cmp::min(limit, self.length) {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            self.fmt_value(idx, f)?;
+        }
+        if self.length > limit {
+            write!(f, ", ... {} more", self.length - limit)?;
+        }
+        write!(f, "] }}")
+    }
+}
+
+// Count the set bits of [begin,end) in `bitmap`, where bit `i` is the bit
+// `i & 7` of byte `i >> 3`. The bytes in the middle are counted by u64 words,
+// and the bits out of the range in the first and the last bytes are masked.
+
+#[cfg(test)]
+mod tests {
+    use std::{f32, f64, u64};
+
+    use rand::{thread_rng, Rng};
+
+    use coprocessor::codec::mysql::MAX_DURATION_NANOS;
+
+    use super::*;
+    use super::super::{ArcChunk, Chunk, ChunkEncoder, MutRow, MAX_VAR_DATA_LEN};
+    use super::super::chunk::tests::{field_type, new_encode_chunk, ENCODE_TPS};
+
+    #[test]
+    fn test_decimal_column() {
+        let fp = field_type(types::NEW_DECIMAL);
+        let tps = &[fp.get_tp()];
+        let max: Decimal = "9".repeat(65).parse().unwrap();
+        let max_frac: Decimal = format!("0.{}", "9".repeat(30)).parse().unwrap();
+        let cases = vec![
+            Some("-123.456".parse::<Decimal>().unwrap()),
+            Some(Decimal::from(0i64)),
+            None,
+            Some(max.clone()),
+            Some(-max),
+            Some(max_frac),
+            Some("0.000001".parse().unwrap()),
+            None,
+            Some("-0.5".parse().unwrap()),
+        ];
+        let mut chunk = Chunk::new_chunk(tps);
+        let tz = FixedOffset::east(0);
+        for d in &cases {
+            match *d {
+                Some(ref d) => chunk.append_decimal(0, d).unwrap(),
+                None => chunk.append_null(0),
+            }
+        }
+        assert_eq!(chunk.columns[0].data.len(), cases.len() * DECIMAL_STRUCT_SIZE);
+        assert_eq!(chunk.columns[0].null_cnt, 2);
+
+        let c = ArcChunk::new(chunk);
+        for (i, d) in cases.iter().enumerate() {
+            let row = c.get_row(i);
+            match *d {
+                Some(ref d) => {
+                    let got = row.get_decimal(0).unwrap().unwrap();
+                    assert_eq!(format!("{}", got), format!("{}", d));
+                    assert_eq!(row.get_datum(0, &fp, &tz).unwrap(), Datum::Dec(d.clone()));
+                }
+                None => {
+                    assert!(row.is_null(0));
+                    assert_eq!(row.get_datum(0, &fp, &tz).unwrap(), Datum::Null);
+                }
+            }
+        }
+
+        // the fixed data is copied byte for byte.
+        let mut chunk2 = Chunk::new_chunk(tps);
+        for i in 0..cases.len() {
+            chunk2.append_row(&c.get_row(i)).unwrap();
+        }
+        assert_eq!(chunk2.columns[0], c.c.columns[0]);
+        let mut chunk3 = Chunk::new_chunk(tps);
+        chunk3.append(&c.c, 0, cases.len()).unwrap();
+        assert_eq!(chunk3.columns[0], c.c.columns[0]);
+        chunk3.truncate_to(3);
+        assert_eq!(chunk3.columns[0].data.len(), 3 * DECIMAL_STRUCT_SIZE);
+        assert_eq!(chunk3.columns[0].null_cnt, 1);
+        assert_eq!(&chunk3.columns[0].data[..], &c.c.columns[0].data[..3 * DECIMAL_STRUCT_SIZE]);
+
+        // decimal columns can be encoded now.
+        let mut buf = vec![];
+        let mut encoder = ChunkEncoder::new(&c.c, tps).unwrap();
+        assert_eq!(encoder.encode_next(cases.len(), &mut buf).unwrap(), cases.len());
+    }
+
+    #[test]
+    fn test_append_datum() {
+        let tz = FixedOffset::east(0);
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let mut datetime = field_type(types::DATETIME);
+        datetime.set_decimal(6);
+        let mut duration = field_type(types::DURATION);
+        duration.set_decimal(6);
+        let cases = vec![
+            (field_type(types::LONG_LONG), Datum::I64(i64::MIN)),
+            (field_type(types::TINY), Datum::I64(-1)),
+            (unsigned, Datum::U64(u64::MAX)),
+            (field_type(types::FLOAT), Datum::F64(1.5)),
+            (field_type(types::DOUBLE), Datum::F64(-2.25)),
+            (field_type(types::VARCHAR), Datum::Bytes(b"abc".to_vec())),
+            (field_type(types::BLOB), Datum::Bytes(vec![])),
+            (field_type(types::NEW_DECIMAL), Datum::Dec("-1.5".parse().unwrap())),
+            (
+                datetime,
+                Datum::Time(Time::parse_utc_datetime("2017-11-24 12:34:56.123456", 6).unwrap()),
+            ),
+            (duration, Datum::Dur(Duration::parse(b"-12:34:56.789", 6).unwrap())),
+            (field_type(types::JSON), Datum::Json(r#"{"a": 1}"#.parse().unwrap())),
+        ];
+        let tps: Vec<i32> = cases.iter().map(|&(ref fp, _)| fp.get_tp()).collect();
+        let mut chunk = Chunk::new_chunk(&tps);
+        for (col_idx, &(ref fp, ref d)) in cases.iter().enumerate() {
+            chunk.append_datum(col_idx, d, fp).unwrap();
+            chunk.append_datum(col_idx, &Datum::Null, fp).unwrap();
+        }
+        let c = ArcChunk::new(chunk);
+        for (col_idx, &(ref fp, ref d)) in cases.iter().enumerate() {
+            assert_eq!(c.get_row(0).get_datum(col_idx, fp, &tz).unwrap(), *d);
+            assert_eq!(c.get_row(1).get_datum(col_idx, fp, &tz).unwrap(), Datum::Null);
+        }
+
+        // the integers are stored if they are in the range of the column.
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        let fp = field_type(types::LONG_LONG);
+        chunk.append_datum(0, &Datum::U64(1), &fp).unwrap();
+        let mut unsigned = fp.clone();
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        chunk.append_datum(0, &Datum::I64(2), &unsigned).unwrap();
+        let c = ArcChunk::new(chunk);
+        assert_eq!(c.get_row(0).get_datum(0, &fp, &tz).unwrap(), Datum::I64(1));
+        assert_eq!(c.get_row(1).get_datum(0, &unsigned, &tz).unwrap(), Datum::U64(2));
+    }
+
+    #[test]
+    fn test_append_datum_mismatch() {
+        let mut unsigned = field_type(types::LONG_LONG);
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let cases = vec![
+            (field_type(types::LONG_LONG), Datum::U64(u64::MAX)),
+            (unsigned, Datum::I64(-1)),
+            (field_type(types::LONG_LONG), Datum::Bytes(b"1".to_vec())),
+            (field_type(types::FLOAT), Datum::F64(f64::MAX)),
+            (field_type(types::DOUBLE), Datum::I64(1)),
+            (field_type(types::VARCHAR), Datum::I64(1)),
+            (field_type(types::DATETIME), Datum::Dur(Duration::zero())),
+            (field_type(types::DURATION), Datum::I64(1)),
+        ];
+        for (fp, d) in cases {
+            let mut chunk = Chunk::new_chunk(&[fp.get_tp()]);
+            let err = chunk.append_datum(0, &d, &fp).unwrap_err();
+            assert!(
+                format!("{}", err).contains("can't be stored in column 0"),
+                "{:?}: {}",
+                d,
+                err
+            );
+            assert_eq!(chunk.num_rows(), 0);
+        }
+
+        // the column doesn't store the field type.
+        let mut chunk = Chunk::new_chunk(&[types::VARCHAR as i32]);
+        let err = chunk
+            .append_datum(0, &Datum::I64(1), &field_type(types::LONG_LONG))
+            .unwrap_err();
+        assert!(format!("{}", err).contains("a var-length column"), "{}", err);
+        assert_eq!(chunk.num_rows(), 0);
+    }
+
+    #[test]
+    fn test_time_column() {
+        let tz = FixedOffset::east(8 * 3600);
+        let fields: Vec<_> = [types::DATETIME, types::TIMESTAMP, types::DATE]
+            .iter()
+            .map(|&tp| {
+                let mut fp = field_type(tp);
+                fp.set_decimal(if tp == types::DATE { 0 } else { 6 });
+                fp
+            })
+            .collect();
+        let tps: Vec<i32> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let new_time = |s: &str, tp: u8| {
+            let fsp = if tp == types::DATE { 0 } else { 6 };
+            let mut t = Time::parse_datetime(s, fsp, &tz).unwrap();
+            if tp == types::TIMESTAMP {
+                return Time::from_packed_u64(t.to_packed_u64(), tp, fsp, &tz).unwrap();
+            }
+            t.set_tp(tp).unwrap();
+            t
+        };
+        let cases = vec![
+            Some("2017-11-24 12:34:56.123456"),
+            None,
+            Some("0000-00-00 00:00:00"),
+            Some("1000-01-01 00:00:00.000001"),
+            Some("9999-12-31 23:59:59.999999"),
+            None,
+        ];
+        let mut chunk = Chunk::new_chunk(&tps);
+        for c in &cases {
+            for (col_idx, fp) in fields.iter().enumerate() {
+                match *c {
+                    Some(s) => chunk.append_time(col_idx, &new_time(s, fp.get_tp() as u8)).unwrap(),
+                    None => chunk.append_null(col_idx),
+                }
+            }
+        }
+        for col in &chunk.columns {
+            assert_eq!(col.data.len(), cases.len() * TIME_STRUCT_SIZE);
+            assert_eq!(col.null_cnt, 2);
+        }
+
+        let c = ArcChunk::new(chunk);
+        for (i, case) in cases.iter().enumerate() {
+            let row = c.get_row(i);
+            for (col_idx, fp) in fields.iter().enumerate() {
+                let d = row.get_datum(col_idx, fp, &tz).unwrap();
+                let s = match *case {
+                    Some(s) => s,
+                    None => {
+                        assert!(row.is_null(col_idx));
+                        assert_eq!(d, Datum::Null);
+                        continue;
+                    }
+                };
+                let expect = new_time(s, fp.get_tp() as u8);
+                match d {
+                    Datum::Time(t) => {
+                        assert_eq!(t, expect);
+                        assert_eq!(t.get_tp(), expect.get_tp());
+                        assert_eq!(t.get_fsp(), expect.get_fsp());
+                        assert_eq!(format!("{}", t), format!("{}", expect));
+                    }
+                    d => panic!("expect time, got {:?}", d),
+                }
+            }
+        }
+
+        // the values are kept in the fixed data buffer.
+        let mut chunk2 = Chunk::new_chunk(&tps);
+        for i in 0..cases.len() {
+            chunk2.append_row(&c.get_row(i)).unwrap();
+        }
+        let mut chunk3 = Chunk::new_chunk(&tps);
+        chunk3.append(&c.c, 0, cases.len()).unwrap();
+        for ((a, b), col) in chunk2.columns.iter().zip(&chunk3.columns).zip(&c.c.columns) {
+            assert_eq!(a, col);
+            assert_eq!(b, col);
+        }
+    }
+
+    #[test]
+    fn test_duration_column() {
+        let mut fp = field_type(types::DURATION);
+        fp.set_decimal(6);
+        let tps = &[fp.get_tp()];
+        let cases = vec![
+            Some(Duration::parse(b"-12:34:56.789", 6).unwrap()),
+            None,
+            Some(Duration::zero()),
+            None,
+            Some(Duration::from_nanos(MAX_DURATION_NANOS, 6).unwrap()),
+            Some(Duration::from_nanos(-MAX_DURATION_NANOS, 6).unwrap()),
+            Some(Duration::parse(b"1 01:02:03", 6).unwrap()),
+        ];
+        let mut chunk = Chunk::new_chunk(tps);
+        for d in &cases {
+            match *d {
+                Some(ref d) => chunk.append_duration(0, d).unwrap(),
+                None => chunk.append_null(0),
+            }
+        }
+        assert_eq!(chunk.columns[0].data.len(), cases.len() * 8);
+        assert_eq!(chunk.columns[0].null_cnt, 2);
+
+        let c = ArcChunk::new(chunk);
+        let tz = FixedOffset::east(0);
+        for (i, d) in cases.iter().enumerate() {
+            let row = c.get_row(i);
+            match *d {
+                Some(ref d) => {
+                    let got = row.get_duration(0, 6).unwrap().unwrap();
+                    assert_eq!(got, *d);
+                    assert_eq!(got.to_nanos(), d.to_nanos());
+                    assert_eq!(row.get_datum(0, &fp, &tz).unwrap(), Datum::Dur(d.clone()));
+                }
+                None => {
+                    assert!(row.is_null(0));
+                    assert_eq!(row.get_datum(0, &fp, &tz).unwrap(), Datum::Null);
+                }
+            }
+        }
+
+        let mut chunk2 = Chunk::new_chunk(tps);
+        for i in 0..cases.len() {
+            chunk2.append_row(&c.get_row(i)).unwrap();
+        }
+        assert_eq!(chunk2.columns[0], c.c.columns[0]);
+        let mut chunk3 = Chunk::new_chunk(tps);
+        chunk3.append(&c.c, 2, cases.len()).unwrap();
+        assert_eq!(chunk3.num_rows(), cases.len() - 2);
+        assert_eq!(&chunk3.columns[0].data[..], &c.c.columns[0].data[2 * 8..]);
+    }
+
+    #[test]
+    fn test_f32_column() {
+        let fp = field_type(types::FLOAT);
+        let tps = &[fp.get_tp()];
+        let cases = vec![Some(1.5f32), None, Some(-0.1), Some(f32::MAX), Some(f32::MIN_POSITIVE)];
+        let mut chunk = Chunk::new_chunk(tps);
+        for v in &cases {
+            match *v {
+                Some(v) => chunk.append_f32(0, v).unwrap(),
+                None => chunk.append_null(0),
+            }
+        }
+        assert_eq!(chunk.columns[0].fixed_len, 4);
+        assert_eq!(chunk.columns[0].data.len(), cases.len() * 4);
+
+        let c = ArcChunk::new(chunk);
+        let tz = FixedOffset::east(0);
+        for (i, v) in cases.iter().enumerate() {
+            let row = c.get_row(i);
+            match *v {
+                Some(v) => {
+                    assert_eq!(row.get_f32(0), v);
+                    assert_eq!(row.get_datum(0, &fp, &tz).unwrap(), Datum::F64(f64::from(v)));
+                }
+                None => assert_eq!(row.get_datum(0, &fp, &tz).unwrap(), Datum::Null),
+            }
+        }
+
+        let mut buf = vec![];
+        let mut encoder = ChunkEncoder::new(&c.c, tps).unwrap();
+        encoder.encode_next(cases.len(), &mut buf).unwrap();
+        // length, null count, null bitmap and data.
+        assert_eq!(buf.len(), 4 + 4 + 1 + cases.len() * 4);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn test_append_f64_to_f32_column() {
+        let mut chunk = Chunk::new_chunk(&[types::FLOAT as i32]);
+        chunk.append_f64(0, 1.5).unwrap();
+    }
+
+    #[test]
+    fn test_append_strs() {
+        let tps = &[types::VARCHAR as i32, types::BLOB as i32];
+        let mut chunk = Chunk::new_chunk(tps);
+        chunk.append_strs(0, Vec::<String>::new()).unwrap();
+        chunk.append_bytes_iter(1, Vec::<Vec<u8>>::new()).unwrap();
+        assert_eq!(chunk.num_rows(), 0);
+        assert_eq!(chunk.columns[0].var_offsets, vec![0]);
+
+        let owned = vec!["a".to_owned(), String::new(), "中".to_owned()];
+        chunk.append_strs(0, &owned).unwrap();
+        chunk.append_bytes_iter(1, vec![&b""[..], b"\xff", b""]).unwrap();
+        chunk.append_str(0, "").unwrap();
+        chunk.append_bytes(1, vec![1u8, 2]).unwrap();
+        assert_eq!(chunk.num_rows(), 4);
+        assert_eq!(chunk.columns[0].var_offsets, vec![0, 1, 1, 4, 4]);
+        assert_eq!(chunk.columns[1].var_offsets, vec![0, 0, 1, 1, 3]);
+        let chunk = ArcChunk::new(chunk);
+        let strs: Vec<_> = chunk.iter().map(|r| r.get_str(0).unwrap()).collect();
+        assert_eq!(strs, vec!["a", "", "中", ""]);
+        assert_eq!(chunk.get_row(1).get_bytes(1), b"\xff");
+
+        // the values before the one over the limit are kept.
+        MAX_VAR_DATA_LEN.with(|l| l.set(3));
+        let mut chunk = Chunk::new_chunk(tps);
+        assert!(chunk.append_strs(0, vec!["ab", "", "cd", "e"]).is_err());
+        assert_eq!(chunk.columns[0].var_offsets, vec![0, 2, 2]);
+        MAX_VAR_DATA_LEN.with(|l| l.set(u32::MAX as usize));
+    }
+
+    #[test]
+    fn test_var_data_limit() {
+        MAX_VAR_DATA_LEN.with(|l| l.set(10));
+        let tps = &[types::VARCHAR as i32];
+        let mut chunk = Chunk::new_chunk(tps);
+        chunk.append_bytes(0, b"12345").unwrap();
+        chunk.append_bytes(0, b"6789").unwrap();
+        assert!(chunk.append_bytes(0, b"ab").is_err());
+        assert!(chunk.append_str(0, "ab").is_err());
+        chunk.append_bytes(0, b"a").unwrap();
+        chunk.append_null(0);
+        assert!(chunk.append_bytes(0, b"b").is_err());
+        chunk.append_bytes(0, b"").unwrap();
+        assert_eq!(chunk.num_rows(), 5);
+        assert_eq!(chunk.columns[0].var_offsets, vec![0, 5, 9, 10, 10, 10]);
+
+        // the rows appended from other chunks.
+        let mut dst = Chunk::new_chunk(tps);
+        dst.append_bytes(0, b"ab").unwrap();
+        assert!(dst.append(&chunk, 0, 2).is_err());
+        assert!(dst.append_all(&chunk).is_err());
+        dst.append(&chunk, 1, 2).unwrap();
+        assert!(dst.append_row(&ArcChunk::new(chunk.clone()).get_row(0)).is_err());
+        dst.append(&chunk, 2, 5).unwrap();
+        assert_eq!(dst.num_rows(), 5);
+        assert_eq!(dst.columns[0].var_offsets, vec![0, 2, 6, 7, 7, 7]);
+
+        // the enums hold the values besides the names.
+        let mut chunk = Chunk::new_chunk(&[types::ENUM as i32]);
+        chunk.append_enum(0, b"a", 1).unwrap();
+        assert!(chunk.append_enum(0, b"", 2).is_err());
+        assert_eq!(chunk.num_rows(), 1);
+        MAX_VAR_DATA_LEN.with(|l| l.set(u32::MAX as usize));
+    }
+
+    #[test]
+    fn test_append_slices() {
+        let tps = &[
+            types::LONG_LONG as i32,
+            types::LONG_LONG as i32,
+            types::DOUBLE as i32,
+            types::VARCHAR as i32,
+            types::JSON as i32,
+        ];
+        let mut rng = thread_rng();
+        for _ in 0..20 {
+            let mut bulk = Chunk::new_chunk(tps);
+            let mut single = Chunk::new_chunk(tps);
+            for _ in 0..rng.gen_range(0, 30) {
+                let n = rng.gen_range(0, 20);
+                if rng.gen() {
+                    for col in 0..tps.len() {
+                        bulk.append_nulls(col, n);
+                        for _ in 0..n {
+                            single.append_null(col);
+                        }
+                    }
+                    continue;
+                }
+                let ints: Vec<i64> = (0..n).map(|_| rng.gen()).collect();
+                let uints: Vec<u64> = ints.iter().map(|&v| v as u64 >> 1).collect();
+                let floats: Vec<f64> = ints.iter().map(|&v| v as f64).collect();
+                bulk.append_i64_slice(0, &ints).unwrap();
+                bulk.append_u64_slice(1, &uints).unwrap();
+                bulk.append_f64_slice(2, &floats).unwrap();
+                bulk.append_nulls(3, n);
+                bulk.append_nulls(4, n);
+                for i in 0..n {
+                    single.append_i64(0, ints[i]).unwrap();
+                    single.append_u64(1, uints[i]).unwrap();
+                    single.append_f64(2, floats[i]).unwrap();
+                    single.append_null(3);
+                    single.append_null(4);
+                }
+                // the stale bits left by truncating are cleared.
+                if rng.gen_weighted_bool(4) {
+                    let rows = rng.gen_range(0, bulk.num_rows() + 1);
+                    bulk.truncate_to(rows);
+                    single.truncate_to(rows);
+                }
+            }
+            assert_eq!(bulk, single);
+            for (x, y) in bulk.columns.iter().zip(&single.columns) {
+                assert_eq!(x.null_cnt, y.null_cnt);
+                assert_eq!(x.null_bitmap, y.null_bitmap);
+                assert_eq!(x.data, y.data);
+                assert_eq!(x.var_offsets, y.var_offsets);
+                assert_eq!(x.ifaces.len(), y.ifaces.len());
+            }
+        }
+
+        // nothing is appended if any value is out of the range.
+        let mut chunk = Chunk::from_field_types(&[field_type(types::LONG_LONG)]);
+        assert!(chunk.append_u64_slice(0, &[1, u64::MAX]).is_err());
+        chunk.append_i64_slice(0, &[1, -1]).unwrap();
+        assert!(chunk.append_u64_slice(0, &[u64::MAX]).is_err());
+        assert_eq!(chunk.num_rows(), 2);
+    }
+
+    #[test]
+    fn test_null_count_in_range() {
+        let check = |col: &Column| {
+            for begin in 0..col.len() + 1 {
+                for end in begin..col.len() + 1 {
+                    let expect = (begin..end).filter(|&i| col.is_null(i)).count();
+                    assert_eq!(col.null_count_in_range(begin, end), expect, "[{},{})", begin, end);
+                    assert_eq!(col.all_not_null(begin, end), expect == 0);
+                }
+            }
+        };
+        let mut rng = thread_rng();
+        for &(rows, nulls) in &[(0, 0), (1, 1), (7, 3), (8, 8), (64, 1), (130, 40), (200, 199)] {
+            let mut col = Column::new(types::LONG_LONG as i32, 0);
+            let mut null_rows: Vec<usize> = (0..rows).collect();
+            rng.shuffle(&mut null_rows);
+            null_rows.truncate(nulls);
+            for i in 0..rows {
+                if null_rows.contains(&i) {
+                    col.append_null();
+                } else {
+                    col.append_i64(i as i64).unwrap();
+                }
+            }
+            check(&col);
+
+            // the bitmaps produced by truncating and appending.
+            let mut other = col.clone();
+            other.truncate_to(rows / 3);
+            check(&other);
+            other.append_all(&col).unwrap();
+            check(&other);
+            other.append(&col, rows / 2, rows).unwrap();
+            check(&other);
+        }
+    }
+
+    #[test]
+    fn test_truncate_to_null_bitmap() {
+        // NULL if the value is a multiple of 3.
+        let append = |col: &mut Column, v: i64| if v % 3 == 0 {
+            col.append_null();
+        } else {
+            col.append_i64(v).unwrap();
+        };
+        for &n in &[0, 7, 8, 9] {
+            let mut col = Column::new(types::LONG_LONG as i32, 0);
+            for v in 0..16 {
+                append(&mut col, v);
+            }
+            col.truncate_to(n);
+            let mut expect = Column::new(types::LONG_LONG as i32, 0);
+            for v in 0..n as i64 {
+                append(&mut expect, v);
+            }
+            assert_eq!(col, expect);
+            assert_eq!(col.null_bitmap, expect.null_bitmap);
+            for v in 100..110 {
+                append(&mut col, v);
+                append(&mut expect, v);
+            }
+            assert_eq!(col, expect);
+            assert_eq!(col.null_cnt, expect.null_cnt);
+            assert_eq!(col.null_bitmap, expect.null_bitmap);
+            for i in 0..col.len() {
+                assert_eq!(col.is_null(i), expect.is_null(i), "{} rows, row {}", n, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_int_signedness() {
+        let tz = FixedOffset::east(0);
+        let signed = field_type(types::LONG_LONG);
+        let mut unsigned = signed.clone();
+        unsigned.set_flag(types::UNSIGNED_FLAG as u32);
+        let check_err = |res: Result<()>, msg: &str| {
+            let err = format!("{}", res.unwrap_err());
+            assert!(err.contains(msg), "{}", err);
+        };
+
+        // the signedness is unknown until a value out of the range of the
+        // other signedness is stored.
+        let mut chunk = Chunk::new_chunk(&[signed.get_tp()]);
+        chunk.append_i64(0, 1).unwrap();
+        assert_eq!(chunk.columns[0].unsigned, None);
+        chunk.append_i64(0, -1).unwrap();
+        assert_eq!(chunk.columns[0].unsigned, Some(false));
+        chunk.append_u64(0, 2).unwrap();
+        check_err(
+            chunk.append_u64(0, u64::MAX),
+            "a signed integer column can't hold 18446744073709551615",
+        );
+        assert_eq!(chunk.num_rows(), 3);
+        let c = ArcChunk::new(chunk.clone());
+        // the values are never read as unsigned.
+        let exp = [Datum::I64(1), Datum::I64(-1), Datum::I64(2)];
+        for (row, d) in c.iter().zip(&exp) {
+            assert_eq!(row.get_datum(0, &unsigned, &tz).unwrap(), *d);
+            assert_eq!(row.get_datum(0, &signed, &tz).unwrap(), *d);
+        }
+
+        // the signedness of a column created from an integer field type is
+        // known at once.
+        let mut typed = Chunk::from_field_types(&[unsigned.clone()]);
+        assert_eq!(typed.columns[0].unsigned, Some(true));
+        typed.append_u64(0, u64::MAX).unwrap();
+        check_err(typed.append_i64(0, -1), "an unsigned integer column can't hold -1");
+        check_err(
+            typed.append_all(&chunk),
+            "column 0: can't append signed integers to a column of unsigned integers",
+        );
+        check_err(
+            typed.append_row(&c.get_row(0)),
+            "column 0: can't append signed integers to a column of unsigned integers",
+        );
+        assert_eq!(typed.num_rows(), 1);
+        // the rows of unknown signedness can be appended to either.
+        let mut untagged = Chunk::new_chunk(&[signed.get_tp()]);
+        untagged.append_i64(0, 3).unwrap();
+        typed.append_all(&untagged).unwrap();
+        chunk.append_all(&untagged).unwrap();
+        untagged.append_all(&typed).unwrap();
+        assert_eq!(untagged.columns[0].unsigned, Some(true));
+        let typed = ArcChunk::new(typed);
+        let exp = [Datum::U64(u64::MAX), Datum::U64(3)];
+        for (row, d) in typed.iter().zip(&exp) {
+            assert_eq!(row.get_datum(0, &signed, &tz).unwrap(), *d);
+        }
+
+        // the signedness of a reset column is unknown again unless it's
+        // created from a field type.
+        chunk.reset();
+        assert_eq!(chunk.columns[0].unsigned, None);
+        let mut typed = Chunk::from_field_types(&[signed.clone(), field_type(types::DOUBLE)]);
+        typed.append_i64(0, -1).unwrap();
+        typed.reset();
+        assert_eq!(typed.columns[0].unsigned, Some(false));
+        assert_eq!(typed.columns[1].unsigned, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "row 0 is out of range, the column has 0 rows")]
+    #[cfg(debug_assertions)]
+    fn test_get_from_reset_column() {
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.reset();
+        chunk.columns[0].get_i64(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "row 1 is out of range, the column has 1 rows")]
+    #[cfg(debug_assertions)]
+    fn test_get_from_truncated_column() {
+        let mut chunk = Chunk::new_chunk(&[types::VARCHAR as i32]);
+        chunk.append_bytes(0, b"a").unwrap();
+        chunk.append_bytes(0, b"b").unwrap();
+        chunk.truncate_to(1);
+        chunk.columns[0].get_bytes(1);
+    }
+
+    #[test]
+    fn test_var_len_bytes_kept() {
+        // the bytes are never trimmed or padded, whatever the collation is.
+        let values = vec!["a", "a ", " a", "a\0", "a\0 ", "", "  "];
+        let fields = vec![field_type(types::VARCHAR), field_type(types::LONG_LONG)];
+        let mut chunk = Chunk::from_field_types(&fields);
+        for (i, v) in values.iter().enumerate() {
+            chunk.append_bytes(0, v.as_bytes()).unwrap();
+            chunk.append_i64(1, i as i64).unwrap();
+        }
+        let check = |c: &Chunk, rows: &[usize]| {
+            let c = ArcChunk::new(c.clone());
+            assert_eq!(c.num_rows(), rows.len());
+            for (row, &i) in c.iter().zip(rows) {
+                assert_eq!(row.get_bytes(0), values[i].as_bytes());
+                assert_eq!(row.get_i64(1), i as i64);
+            }
+        };
+
+        // the rows are gathered into another chunk.
+        let rows = vec![4, 1, 1, 6, 0];
+        let src = ArcChunk::new(chunk.clone());
+        let mut gathered = Chunk::from_field_types(&fields);
+        for &i in &rows {
+            gathered.append_row(&src.get_row(i)).unwrap();
+        }
+        check(&gathered, &rows);
+        let mut appended = Chunk::from_field_types(&fields);
+        appended.append(&chunk, 2, 5).unwrap();
+        appended.append_all(&gathered).unwrap();
+        check(&appended, &[2, 3, 4, 4, 1, 1, 6, 0]);
+        appended.truncate_to(3);
+        check(&appended, &[2, 3, 4]);
+
+        // the chunk is encoded as a whole or split by the encoder.
+        let mut buf = vec![];
+        chunk.encode_to(&mut buf).unwrap();
+        let all: Vec<usize> = (0..values.len()).collect();
+        check(&Chunk::decode(&buf, &fields).unwrap(), &all);
+        let tps: Vec<i32> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let mut encoder = ChunkEncoder::new(&chunk, &tps).unwrap();
+        let mut merged = Chunk::from_field_types(&fields);
+        loop {
+            let mut buf = vec![];
+            if encoder.encode_next(2, &mut buf).unwrap() == 0 {
+                break;
+            }
+            merged.append_all(&Chunk::decode(&buf, &fields).unwrap()).unwrap();
+        }
+        check(&merged, &all);
+
+        // the datums are stored and read as they are.
+        let d = Datum::Bytes(b"b\0  ".to_vec());
+        let row = MutRow::from_datums(&[d.clone(), Datum::I64(0)], &fields).unwrap();
+        let tz = FixedOffset::east(0);
+        assert_eq!(row.as_row().get_datum(0, &fields[0], &tz).unwrap(), d);
+    }
+
+    #[test]
+    fn test_enum_set() {
+        let tz = FixedOffset::east(0);
+        let fields = vec![field_type(types::ENUM), field_type(types::SET)];
+        let tps: Vec<_> = fields.iter().map(|fp| fp.get_tp()).collect();
+        let mut chunk = Chunk::new_chunk(&tps);
+        let values: Vec<(&[u8], u64)> = vec![
+            (&b"a"[..], 1),
+            // the invalid enum, and the empty set.
+            (&b""[..], 0),
+            // an empty name.
+            (&b""[..], 2),
+            (&b"a,c"[..], 5),
+            (&b"\xff\x00"[..], u64::MAX),
+        ];
+        for &(name, value) in &values {
+            chunk.append_enum(0, name, value).unwrap();
+            chunk.append_set(1, name, value).unwrap();
+        }
+        chunk.append_null(0);
+        chunk.append_null(1);
+        let check = |c: &Chunk| {
+            let c = ArcChunk::new(c.clone());
+            for (row, &(name, value)) in c.iter().zip(&values) {
+                assert_eq!(row.get_enum(0).unwrap(), Some((name, value)));
+                assert_eq!(row.get_set(1).unwrap(), Some((name, value)));
+                for (col_idx, fp) in fields.iter().enumerate() {
+                    assert_eq!(row.get_datum(col_idx, fp, &tz).unwrap(), Datum::U64(value));
+                }
+            }
+            let row = c.get_row(values.len());
+            assert_eq!(row.get_enum(0).unwrap(), None);
+            assert_eq!(row.get_set(1).unwrap(), None);
+            assert_eq!(row.get_datum(0, &fields[0], &tz).unwrap(), Datum::Null);
+        };
+        check(&chunk);
+
+        // the values are encoded like the strings, without boxing datums.
+        let mut buf = vec![];
+        chunk.encode_to(&mut buf).unwrap();
+        check(&Chunk::decode(&buf, &fields).unwrap());
+        let mut copied = Chunk::new_chunk(&tps);
+        copied.append_all(&chunk).unwrap();
+        check(&copied);
+
+        // a datum only holds the value, so the name is empty.
+        let mut chunk = Chunk::new_chunk(&tps);
+        chunk.append_datum(0, &Datum::U64(3), &fields[0]).unwrap();
+        chunk.append_datum(1, &Datum::U64(6), &fields[1]).unwrap();
+        assert!(chunk.append_datum(0, &Datum::Bytes(b"a".to_vec()), &fields[0]).is_err());
+        let c = ArcChunk::new(chunk);
+        assert_eq!(c.get_row(0).get_enum(0).unwrap(), Some((&b""[..], 3)));
+        assert_eq!(c.get_row(0).get_set(1).unwrap(), Some((&b""[..], 6)));
+    }
+
+    #[test]
+    fn test_is_null_in_range() {
+        for rows in 0..18 {
+            let chunk = new_encode_chunk(rows);
+            for i in 0..rows {
+                assert_eq!(chunk.columns[0].is_null(i), i % 3 == 0);
+                assert_eq!(chunk.columns[1].is_null(i), i % 5 == 0);
+            }
+            // appending relies on the rows in range only.
+            let mut other = Chunk::new_chunk(ENCODE_TPS);
+            other.append(&chunk, 0, rows).unwrap();
+            assert_eq!(other, chunk);
+        }
+
+        // a NULL appended after truncating isn't hidden by the removed rows.
+        let mut chunk = new_encode_chunk(8);
+        chunk.truncate_to(2);
+        chunk.append_null(0);
+        chunk.append_null(1);
+        assert!(chunk.columns[0].is_null(2));
+        assert!(chunk.columns[1].is_null(2));
+        assert_eq!(chunk.columns[0].null_cnt, 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "out of range")]
+    fn test_is_null_out_of_range() {
+        // the bitmap has a byte for row 3, but the row isn't appended.
+        let chunk = new_encode_chunk(3);
+        chunk.columns[1].is_null(3);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn test_is_null_out_of_range() {
+        let chunk = new_encode_chunk(3);
+        assert!(!chunk.columns[1].is_null(2));
+        assert!(chunk.columns[1].is_null(3));
+        assert!(chunk.columns[1].is_null(8));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "row 1 is out of range, the column has 1 rows")]
+    fn test_get_fixed_out_of_range() {
+        // the data is long enough for row 1, but the row isn't appended.
+        let mut chunk = Chunk::new_chunk(&[types::LONG_LONG as i32]);
+        chunk.append_i64(0, 1).unwrap();
+        chunk.columns[0].data.extend_from_slice(&[0; 8]);
+        chunk.columns[0].get_i64(1);
+    }
+}
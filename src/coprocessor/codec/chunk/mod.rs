@@ -0,0 +1,100 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::u32;
+
+use byteorder::{ByteOrder, LittleEndian};
+use crc::crc64::{Digest, Hasher64};
+
+pub mod arrow_ipc;
+mod chunk;
+mod column;
+
+pub use self::chunk::{ArcChunk, Chunk, ChunkEncoder, MutRow, Row, RowContainer, RowContainerIter,
+                      RowIter};
+pub use self::column::Column;
+
+// The number of rows written by the `Debug` of chunks and columns by default.
+const DEBUG_ROWS: usize = 10;
+
+// The offsets of the var-length values are u32 in memory, half the size of
+// TiDB's int64 ones, so the data of a var-length column can't exceed
+// `u32::MAX` bytes. They are still encoded as i64 like TiDB does.
+#[cfg(not(test))]
+#[inline]
+fn max_var_data_len() -> usize {
+    u32::MAX as usize
+}
+
+// The limit can be lowered by the tests, so the overflow can be tested
+// without allocating 4GB.
+#[cfg(test)]
+thread_local!(static MAX_VAR_DATA_LEN: ::std::cell::Cell<usize> =
+    ::std::cell::Cell::new(u32::MAX as usize));
+
+#[cfg(test)]
+fn max_var_data_len() -> usize {
+    MAX_VAR_DATA_LEN.with(|l| l.get())
+}
+
+/// The limits of the sizes in a chunk being decoded, a chunk exceeding them
+/// is regarded as corrupted.
+#[derive(Clone, Copy, Debug)]
+pub struct DecodeLimits {
+    /// The max size of a var-length value.
+    pub max_value_size: usize,
+    /// The max size of the data of a column.
+    pub max_column_size: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> DecodeLimits {
+        DecodeLimits {
+            max_value_size: 64 * 1024 * 1024,
+            max_column_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+// Count the set bits of [begin,end) in `bitmap`, where bit `i` is the bit
+// `i & 7` of byte `i >> 3`. The bytes in the middle are counted by u64 words,
+// and the bits out of the range in the first and the last bytes are masked.
+fn count_ones_in_range(bitmap: &[u8], begin: usize, end: usize) -> usize {
+    debug_assert!(begin < end && end <= bitmap.len() * 8);
+    let (first, last) = (begin >> 3, (end - 1) >> 3);
+    let head_mask = !0u8 << (begin & 7);
+    let tail_mask = if end & 7 == 0 {
+        !0u8
+    } else {
+        (1u8 << (end & 7)) - 1
+    };
+    if first == last {
+        return (bitmap[first] & head_mask & tail_mask).count_ones() as usize;
+    }
+    let mut count = (bitmap[first] & head_mask).count_ones() +
+        (bitmap[last] & tail_mask).count_ones();
+    let mut middle = &bitmap[first + 1..last];
+    while middle.len() >= 8 {
+        count += LittleEndian::read_u64(middle).count_ones();
+        middle = &middle[8..];
+    }
+    count += middle.iter().map(|b| b.count_ones()).sum::<u32>();
+    count as usize
+}
+
+fn write_fingerprint_u64(digest: &mut Digest, v: u64) {
+    let mut buf = [0; 8];
+    LittleEndian::write_u64(&mut buf, v);
+    digest.write(&buf);
+}
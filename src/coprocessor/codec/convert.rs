@@ -15,6 +15,7 @@ use std::{self, str, i64, u64};
 use std::borrow::Cow;
 
 use coprocessor::select::xeval::EvalContext;
+use coprocessor::select::xeval::evaluator::{ERR_DATA_OUT_OF_RANGE, ERR_WARN_DATA_OUT_OF_RANGE};
 use super::mysql::Res;
 use super::Result;
 // `UNSPECIFIED_LENGTH` is unspecified length from FieldType
@@ -94,6 +95,22 @@ pub fn convert_float_to_uint(fval: f64, upper_bound: u64, tp: u8) -> Result<u64>
     Ok(val as u64)
 }
 
+/// `cast_int_as_uint` converts a signed value to an unsigned one, which follows
+/// MySQL's behavior: the two's-complement value of a negative value is used in
+/// SELECT statements, otherwise the value is out of range, and it's clamped to
+/// 0 if overflow should be returned as warning.
+pub fn cast_int_as_uint(ctx: &EvalContext, val: i64, in_select: bool) -> Result<u64> {
+    if val >= 0 || in_select {
+        return Ok(val as u64);
+    }
+    let msg = format!("Out of range value {} for column", val);
+    if !handle_overflow_as_error(ctx) {
+        ctx.on_overflow_as_warning(ERR_WARN_DATA_OUT_OF_RANGE, msg);
+        return Ok(0);
+    }
+    Err(box_err!("[{}] {}", ERR_WARN_DATA_OUT_OF_RANGE, msg))
+}
+
 /// `bytes_to_int_without_context` converts a byte arrays to an i64
 /// in best effort, but without context.
 /// Note that it does NOT handle overflow.
@@ -175,6 +192,52 @@ fn bytes_to_f64_without_context(bytes: &[u8]) -> Result<f64> {
     Ok(f)
 }
 
+/// `bytes_to_int_checked` converts a byte array to an i64 like `bytes_to_int`,
+/// but `None` is returned if it overflows.
+pub fn bytes_to_int_checked(ctx: &EvalContext, bytes: &[u8]) -> Result<Option<i64>> {
+    let s = str::from_utf8(bytes)?.trim();
+    let vs = get_valid_int_prefix(ctx, s)?;
+    // the valid prefix can only fail to parse if it overflows.
+    Ok(vs.parse().ok())
+}
+
+/// `bytes_to_uint_checked` converts a byte array to an u64 like
+/// `bytes_to_uint`, but `None` is returned if it overflows.
+pub fn bytes_to_uint_checked(ctx: &EvalContext, bytes: &[u8]) -> Result<Option<u64>> {
+    let s = str::from_utf8(bytes)?.trim();
+    let vs = get_valid_int_prefix(ctx, s)?;
+    Ok(vs.parse().ok())
+}
+
+/// `overflow_from_cast_str_as_int` handles the overflow of casting the string
+/// `bytes` to an integer, which is unsigned if `is_unsigned`. The value is
+/// clipped to the bound of the target type only in SELECT statements where
+/// overflow is returned as warning, and it's an error otherwise. A negative
+/// value is clipped to `i64::MIN`, which is wrapped for the unsigned target
+/// as the other negative values are in SELECT statements.
+pub fn overflow_from_cast_str_as_int(
+    ctx: &EvalContext,
+    bytes: &[u8],
+    is_negative: bool,
+    is_unsigned: bool,
+) -> Result<i64> {
+    let msg = format!(
+        "BIGINT value is out of range in '{}'",
+        String::from_utf8_lossy(bytes)
+    );
+    if !ctx.in_select_stmt || handle_overflow_as_error(ctx) {
+        return Err(box_err!("[{}] {}", ERR_DATA_OUT_OF_RANGE, msg));
+    }
+    ctx.on_overflow_as_warning(ERR_DATA_OUT_OF_RANGE, msg);
+    if is_negative {
+        Ok(i64::MIN)
+    } else if is_unsigned {
+        Ok(u64::MAX as i64)
+    } else {
+        Ok(i64::MAX)
+    }
+}
+
 /// `bytes_to_f64` converts a byte array to a float64 in best effort.
 pub fn bytes_to_f64(ctx: &EvalContext, bytes: &[u8]) -> Result<f64> {
     let s = str::from_utf8(bytes)?.trim();
@@ -183,18 +246,30 @@ pub fn bytes_to_f64(ctx: &EvalContext, bytes: &[u8]) -> Result<f64> {
     bytes_to_f64_without_context(vs.as_bytes())
 }
 
+/// Truncation is an error unless it's ignored or returned as warning, in
+/// any statement like TiDB. It's a warning in the non-strict sql mode too.
 #[inline]
 pub fn handle_truncate_as_error(ctx: &EvalContext) -> bool {
-    !(ctx.ignore_truncate || ctx.truncate_as_warning)
+    !(ctx.ignore_truncate || ctx.truncate_as_warning || !ctx.strict_sql_mode())
+}
+
+/// Overflow is an error unless it's returned as warning, or the sql mode
+/// isn't strict.
+#[inline]
+pub fn handle_overflow_as_error(ctx: &EvalContext) -> bool {
+    !(ctx.overflow_as_warning || !ctx.strict_sql_mode())
 }
 
 #[inline]
 pub fn handle_truncate(ctx: &EvalContext, is_truncated: bool) -> Result<()> {
-    if is_truncated && handle_truncate_as_error(ctx) {
-        Err(box_err!("[1265] Data Truncated"))
-    } else {
-        Ok(())
+    if !is_truncated {
+        return Ok(());
+    }
+    if handle_truncate_as_error(ctx) {
+        return Err(box_err!("[1265] Data Truncated"));
     }
+    ctx.on_truncate_as_warning();
+    Ok(())
 }
 
 fn get_valid_int_prefix<'a>(ctx: &EvalContext, s: &'a str) -> Result<Cow<'a, str>> {
@@ -328,6 +403,16 @@ mod test {
     use chrono::FixedOffset;
 
     use coprocessor::select::xeval::EvalContext;
+    use coprocessor::select::xeval::evaluator::{ERR_DIVISION_BY_ZERO, ERR_TRUNCATED,
+                                                FLAG_DIVIDED_BY_ZERO_AS_WARNING,
+                                                FLAG_IGNORE_TRUNCATE, FLAG_IN_INSERT_STMT,
+                                                FLAG_IN_SELECT_STMT,
+                                                FLAG_IN_UPDATE_OR_DELETE_STMT,
+                                                FLAG_OVERFLOW_AS_WARNING,
+                                                FLAG_TRUNCATE_AS_WARNING,
+                                                MODE_ERROR_FOR_DIVISION_BY_ZERO,
+                                                MODE_NO_ZERO_DATE, MODE_STRICT_ALL_TABLES,
+                                                MODE_STRICT_TRANS_TABLES};
     use coprocessor::codec::mysql::types;
 
     use super::*;
@@ -411,21 +496,25 @@ mod test {
                 tz: FixedOffset::east(0),
                 ignore_truncate: true,
                 truncate_as_warning: true,
+                ..EvalContext::default()
             },
             EvalContext {
                 tz: FixedOffset::east(0),
                 ignore_truncate: true,
                 truncate_as_warning: false,
+                ..EvalContext::default()
             },
             EvalContext {
                 tz: FixedOffset::east(0),
                 ignore_truncate: false,
                 truncate_as_warning: true,
+                ..EvalContext::default()
             },
             EvalContext {
                 tz: FixedOffset::east(0),
                 ignore_truncate: false,
                 truncate_as_warning: false,
+                ..EvalContext::default()
             },
         ];
 
@@ -439,6 +528,235 @@ mod test {
         assert!(super::handle_truncate(&ctxs[3], true).is_err());
     }
 
+    #[test]
+    fn test_cast_int_as_uint() {
+        let strict = EvalContext::default();
+        let warning = EvalContext {
+            overflow_as_warning: true,
+            ..EvalContext::default()
+        };
+        for ctx in &[&strict, &warning] {
+            for &in_select in &[true, false] {
+                for &v in &[0, 1, i64::MAX] {
+                    assert_eq!(cast_int_as_uint(ctx, v, in_select).unwrap(), v as u64);
+                }
+            }
+            // negative values are wrapped in SELECT statements.
+            assert_eq!(cast_int_as_uint(ctx, -1, true).unwrap(), u64::MAX);
+            assert_eq!(
+                cast_int_as_uint(ctx, i64::MIN, true).unwrap(),
+                i64::MAX as u64 + 1
+            );
+        }
+
+        for &v in &[-1, i64::MIN] {
+            assert_eq!(cast_int_as_uint(&warning, v, false).unwrap(), 0);
+            let err = cast_int_as_uint(&strict, v, false).unwrap_err();
+            assert!(format!("{}", err).contains("[1264] Out of range value"));
+        }
+    }
+
+    #[test]
+    fn test_handle_truncate_in_stmts() {
+        let cases = vec![
+            (FLAG_IN_SELECT_STMT, false),
+            (FLAG_IN_SELECT_STMT | FLAG_IGNORE_TRUNCATE, true),
+            (FLAG_IN_SELECT_STMT | FLAG_TRUNCATE_AS_WARNING, true),
+            (FLAG_IN_INSERT_STMT, false),
+            (FLAG_IN_INSERT_STMT | FLAG_IGNORE_TRUNCATE, true),
+            (FLAG_IN_INSERT_STMT | FLAG_TRUNCATE_AS_WARNING, true),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT, false),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT | FLAG_IGNORE_TRUNCATE, true),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT | FLAG_TRUNCATE_AS_WARNING, true),
+        ];
+        for (flags, is_ok) in cases {
+            let ctx = EvalContext::new(0, flags).unwrap();
+            assert!(super::handle_truncate(&ctx, false).is_ok());
+            assert_eq!(
+                super::handle_truncate(&ctx, true).is_ok(),
+                is_ok,
+                "flags {}",
+                flags
+            );
+            // an ignored truncation isn't a warning, even in the write
+            // statements.
+            let warning_cnt = if is_ok && flags & FLAG_IGNORE_TRUNCATE == 0 {
+                1
+            } else {
+                0
+            };
+            assert_eq!(ctx.take_warnings().warning_cnt, warning_cnt, "flags {}", flags);
+        }
+    }
+
+    #[test]
+    fn test_legacy_flags_compatibility() {
+        // the requests without the sql mode are handled as in TiDB's default
+        // sql mode, which is strict and errors for division by zero.
+        for flags in 0..(1 << 9) {
+            let ctx = match EvalContext::new(0, flags) {
+                Ok(ctx) => ctx,
+                // a statement can't be of more than one kind.
+                Err(_) => continue,
+            };
+            let ignore_truncate = flags & FLAG_IGNORE_TRUNCATE > 0;
+            let truncate_as_warning = flags & FLAG_TRUNCATE_AS_WARNING > 0;
+            assert_eq!(
+                handle_truncate_as_error(&ctx),
+                !(ignore_truncate || truncate_as_warning),
+                "flags {}",
+                flags
+            );
+            assert_eq!(
+                handle_overflow_as_error(&ctx),
+                flags & FLAG_OVERFLOW_AS_WARNING == 0,
+                "flags {}",
+                flags
+            );
+            // only the write statements may fail for dividing by zero.
+            let div_by_zero_as_warning = flags &
+                (FLAG_IN_INSERT_STMT | FLAG_IN_UPDATE_OR_DELETE_STMT) == 0 ||
+                flags & FLAG_DIVIDED_BY_ZERO_AS_WARNING > 0;
+            assert_eq!(
+                ctx.handle_division_by_zero().is_ok(),
+                div_by_zero_as_warning,
+                "flags {}",
+                flags
+            );
+            assert!(ctx.strict_sql_mode());
+            assert!(ctx.error_for_division_by_zero());
+            assert!(!ctx.no_zero_in_date());
+            assert!(!ctx.no_zero_date());
+        }
+    }
+
+    #[test]
+    fn test_sql_mode() {
+        let cases = vec![
+            // sql_mode, truncate as error, overflow as error, division by zero as error
+            (0, false, false, false),
+            (MODE_STRICT_TRANS_TABLES, true, true, false),
+            (MODE_STRICT_ALL_TABLES, true, true, false),
+            (MODE_ERROR_FOR_DIVISION_BY_ZERO, false, false, false),
+            (
+                MODE_STRICT_TRANS_TABLES | MODE_ERROR_FOR_DIVISION_BY_ZERO,
+                true,
+                true,
+                true,
+            ),
+        ];
+        for (sql_mode, truncate, overflow, div_by_zero) in cases {
+            let mut ctx = EvalContext::new(0, FLAG_IN_INSERT_STMT).unwrap();
+            ctx.sql_mode = Some(sql_mode);
+            assert_eq!(handle_truncate_as_error(&ctx), truncate, "{}", sql_mode);
+            assert_eq!(handle_overflow_as_error(&ctx), overflow, "{}", sql_mode);
+            assert_eq!(
+                ctx.handle_division_by_zero().is_err(),
+                div_by_zero,
+                "{}",
+                sql_mode
+            );
+
+            // the flags still turn the errors into warnings.
+            let flags = FLAG_IN_INSERT_STMT | FLAG_TRUNCATE_AS_WARNING |
+                FLAG_OVERFLOW_AS_WARNING |
+                FLAG_DIVIDED_BY_ZERO_AS_WARNING;
+            let mut ctx = EvalContext::new(0, flags).unwrap();
+            ctx.sql_mode = Some(sql_mode);
+            assert!(!handle_truncate_as_error(&ctx));
+            assert!(!handle_overflow_as_error(&ctx));
+            assert!(ctx.handle_division_by_zero().is_ok());
+        }
+
+        let mut ctx = EvalContext::default();
+        ctx.sql_mode = Some(MODE_NO_ZERO_DATE);
+        assert!(ctx.no_zero_date());
+        assert!(!ctx.no_zero_in_date());
+        assert!(!ctx.strict_sql_mode());
+    }
+
+    #[test]
+    fn test_eval_stats() {
+        let flags = FLAG_TRUNCATE_AS_WARNING | FLAG_OVERFLOW_AS_WARNING |
+            FLAG_DIVIDED_BY_ZERO_AS_WARNING;
+        let ctx = EvalContext::new(0, flags).unwrap();
+        handle_truncate(&ctx, true).unwrap();
+        handle_truncate(&ctx, false).unwrap();
+        bytes_to_int(&ctx, b"12abc").unwrap();
+        cast_int_as_uint(&ctx, -1, false).unwrap();
+        // wrapping in SELECT statements isn't an overflow.
+        cast_int_as_uint(&ctx, -1, true).unwrap();
+        ctx.handle_division_by_zero().unwrap();
+        ctx.handle_division_by_zero().unwrap();
+        ctx.handle_division_by_zero().unwrap();
+        let stats = ctx.take_eval_stats();
+        assert_eq!(stats.truncate_as_warning_cnt, 2);
+        assert_eq!(stats.overflow_as_warning_cnt, 1);
+        assert_eq!(stats.div_by_zero_cnt, 3);
+        assert_eq!(ctx.take_eval_stats(), Default::default());
+        let warnings = ctx.take_warnings();
+        assert_eq!(warnings.warning_cnt, 6);
+        let codes: Vec<_> = warnings.warnings.iter().map(|w| w.get_code()).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ERR_TRUNCATED,
+                ERR_TRUNCATED,
+                ERR_WARN_DATA_OUT_OF_RANGE,
+                ERR_DIVISION_BY_ZERO,
+                ERR_DIVISION_BY_ZERO,
+                ERR_DIVISION_BY_ZERO,
+            ]
+        );
+        assert_eq!(ctx.take_warnings().warning_cnt, 0);
+
+        // the errors aren't counted.
+        let ctx = EvalContext::new(0, FLAG_IN_INSERT_STMT).unwrap();
+        assert!(handle_truncate(&ctx, true).is_err());
+        assert!(cast_int_as_uint(&ctx, -1, false).is_err());
+        assert!(ctx.handle_division_by_zero().is_err());
+        assert_eq!(ctx.take_eval_stats(), Default::default());
+        assert_eq!(ctx.take_warnings().warning_cnt, 0);
+    }
+
+    #[test]
+    fn test_overflow_from_cast_str_as_int() {
+        let cases = vec![
+            (0, false),
+            (FLAG_IN_SELECT_STMT, false),
+            (FLAG_OVERFLOW_AS_WARNING, false),
+            (FLAG_IN_SELECT_STMT | FLAG_OVERFLOW_AS_WARNING, true),
+            (FLAG_IN_INSERT_STMT, false),
+            (FLAG_IN_INSERT_STMT | FLAG_OVERFLOW_AS_WARNING, false),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT | FLAG_OVERFLOW_AS_WARNING, false),
+        ];
+        let too_small = b"-9223372036854775809";
+        let too_big = b"18446744073709551616";
+        for (flags, is_ok) in cases {
+            let ctx = EvalContext::new(0, flags).unwrap();
+            assert_eq!(bytes_to_int_checked(&ctx, too_small).unwrap(), None);
+            assert_eq!(bytes_to_uint_checked(&ctx, too_big).unwrap(), None);
+            // (bytes, is_negative, is_unsigned, clipped)
+            let values: Vec<(&[u8], _, _, _)> = vec![
+                (too_small, true, false, i64::MIN),
+                (too_small, true, true, i64::MIN),
+                (too_big, false, false, i64::MAX),
+                (too_big, false, true, u64::MAX as i64),
+            ];
+            for (bytes, is_negative, is_unsigned, clipped) in values {
+                let res = overflow_from_cast_str_as_int(&ctx, bytes, is_negative, is_unsigned);
+                if is_ok {
+                    assert_eq!(res.unwrap(), clipped);
+                } else {
+                    assert!(res.is_err(), "flags {}", flags);
+                }
+            }
+        }
+        let ctx = EvalContext::default();
+        assert_eq!(bytes_to_int_checked(&ctx, b" -12").unwrap(), Some(-12));
+        assert_eq!(bytes_to_uint_checked(&ctx, b"18446744073709551615").unwrap(), Some(u64::MAX));
+    }
+
     #[test]
     fn test_get_valid_float_prefix() {
         let cases = vec![
@@ -462,6 +780,7 @@ mod test {
             tz: FixedOffset::east(0),
             ignore_truncate: true,
             truncate_as_warning: false,
+            ..EvalContext::default()
         };
         for (i, o) in cases {
             assert_eq!(super::get_valid_float_prefix(&ctx, i).unwrap(), o);
@@ -196,7 +196,7 @@ impl Datum {
             }
             Datum::Time(ref t) => {
                 let s = str::from_utf8(bs)?;
-                let t2 = Time::parse_datetime(s, DEFAULT_FSP, &ctx.tz)?;
+                let t2 = Time::parse_datetime_in_ctx(ctx, s, DEFAULT_FSP)?;
                 Ok(t.cmp(&t2))
             }
             Datum::Dur(ref d) => {
@@ -240,7 +240,7 @@ impl Datum {
         match *self {
             Datum::Bytes(ref bs) => {
                 let s = str::from_utf8(bs)?;
-                let t = Time::parse_datetime(s, DEFAULT_FSP, &ctx.tz)?;
+                let t = Time::parse_datetime_in_ctx(ctx, s, DEFAULT_FSP)?;
                 Ok(t.cmp(time))
             }
             Datum::Time(ref t) => Ok(t.cmp(time)),
@@ -506,6 +506,7 @@ impl Datum {
             (Datum::F64(f), d) => {
                 let f2 = d.into_f64(ctx)?;
                 if f2 == 0f64 {
+                    ctx.handle_division_by_zero()?;
                     return Ok(Datum::Null);
                 }
                 Ok(Datum::F64(f / f2))
@@ -514,7 +515,10 @@ impl Datum {
                 let a = a.into_dec()?;
                 let b = b.into_dec()?;
                 match a / b {
-                    None => Ok(Datum::Null),
+                    None => {
+                        ctx.handle_division_by_zero()?;
+                        Ok(Datum::Null)
+                    }
                     Some(res) => {
                         let d = res.into_result()?;
                         Ok(Datum::Dec(d))
@@ -603,10 +607,16 @@ impl Datum {
     }
 
     // `checked_rem` computes the result of a mod b.
-    pub fn checked_rem(self, _: &EvalContext, d: Datum) -> Result<Datum> {
+    pub fn checked_rem(self, ctx: &EvalContext, d: Datum) -> Result<Datum> {
         match d {
-            Datum::I64(0) | Datum::U64(0) => return Ok(Datum::Null),
-            Datum::F64(f) if f == 0f64 => return Ok(Datum::Null),
+            Datum::I64(0) | Datum::U64(0) => {
+                ctx.handle_division_by_zero()?;
+                return Ok(Datum::Null);
+            }
+            Datum::F64(f) if f == 0f64 => {
+                ctx.handle_division_by_zero()?;
+                return Ok(Datum::Null);
+            }
             _ => {}
         }
         match (self, d) {
@@ -620,7 +630,10 @@ impl Datum {
             (Datum::U64(l), Datum::U64(r)) => Ok(Datum::U64(l % r)),
             (Datum::F64(l), Datum::F64(r)) => Ok(Datum::F64(l % r)),
             (Datum::Dec(l), Datum::Dec(r)) => match l % r {
-                None => Ok(Datum::Null),
+                None => {
+                    ctx.handle_division_by_zero()?;
+                    Ok(Datum::Null)
+                }
                 Some(res) => {
                     let d = res.into_result()?;
                     Ok(Datum::Dec(d))
@@ -631,9 +644,12 @@ impl Datum {
     }
 
     // `checked_int_div` computes the result of a / b, both a and b are integer.
-    pub fn checked_int_div(self, _: &EvalContext, d: Datum) -> Result<Datum> {
+    pub fn checked_int_div(self, ctx: &EvalContext, d: Datum) -> Result<Datum> {
         match d {
-            Datum::I64(0) | Datum::U64(0) => return Ok(Datum::Null),
+            Datum::I64(0) | Datum::U64(0) => {
+                ctx.handle_division_by_zero()?;
+                return Ok(Datum::Null);
+            }
             _ => {}
         }
         match (self, d) {
@@ -664,7 +680,10 @@ impl Datum {
                 let a = l.into_dec()?;
                 let b = r.into_dec()?;
                 match a / b {
-                    None => Ok(Datum::Null),
+                    None => {
+                        ctx.handle_division_by_zero()?;
+                        Ok(Datum::Null)
+                    }
                     Some(res) => {
                         let i = res.unwrap().as_i64().unwrap();
                         Ok(Datum::I64(i))
@@ -1674,6 +1693,7 @@ mod test {
             tz: FixedOffset::east(0),
             ignore_truncate: true,
             truncate_as_warning: true,
+            ..EvalContext::default()
         };
 
         for (d, b) in tests {
@@ -42,5 +42,7 @@ pub mod datum;
 pub mod table;
 pub mod convert;
 pub mod mysql;
+pub mod chunk;
+pub mod prelude;
 
 pub use self::datum::Datum;
@@ -15,6 +15,8 @@
 pub const CHARSET_BIN: &'static str = "binary";
 // `COLLATION_BIN` is the default collation for `CHARSET_BIN`.
 pub const COLLATION_BIN: &'static str = "binary";
+// `COLLATION_BIN_ID` is the id of `COLLATION_BIN`.
+pub const COLLATION_BIN_ID: i32 = 63;
 // `CHARSET_UTF8` is the default charset for string types.
 pub const CHARSET_UTF8: &'static str = "utf8";
 // `COLLATION_UTF8` is the default collation for `CHARSET_UTF8`.
@@ -19,7 +19,7 @@ use std::ops::{Add, Deref, DerefMut, Div, Mul, Neg, Rem, Sub};
 use std::{cmp, mem, i32, i64, u32, u64};
 use std::cmp::Ordering;
 
-use byteorder::ReadBytesExt;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use coprocessor::select::xeval::EvalContext;
 use util::codec::bytes::BytesDecoder;
@@ -125,6 +125,10 @@ const FRAC_MAX: &'static [u32] = &[
 ];
 const NOT_FIXED_DEC: u8 = 31;
 
+/// The length of a decimal in the fixed-length format of chunk columns, which
+/// is the memory layout of `MyDecimal` in TiDB.
+pub const DECIMAL_STRUCT_SIZE: usize = 40;
+
 macro_rules! word_cnt {
     ($len:expr) => (word_cnt!($len, u8));
     ($len:expr, $t:ty) => ({
@@ -991,6 +995,33 @@ impl Decimal {
         }
     }
 
+    /// `write_fixed` appends the decimal to `buf` in `DECIMAL_STRUCT_SIZE` bytes:
+    /// int_cnt, frac_cnt, result_frac_cnt, negative and then 9 little-endian
+    /// words. The precision isn't kept, see `prec_and_frac`.
+    pub fn write_fixed(&self, buf: &mut Vec<u8>) {
+        buf.push(self.int_cnt);
+        buf.push(self.frac_cnt);
+        buf.push(self.result_frac_cnt);
+        buf.push(self.negative as u8);
+        for i in 0..WORD_BUF_LEN as usize {
+            let word = self.word_buf.get(i).cloned().unwrap_or(0);
+            buf.write_u32::<LittleEndian>(word).unwrap();
+        }
+    }
+
+    /// `read_fixed` reads a decimal written by `write_fixed`, `data` should
+    /// contain exactly `DECIMAL_STRUCT_SIZE` bytes.
+    pub fn read_fixed(mut data: &[u8]) -> Decimal {
+        assert_eq!(data.len(), DECIMAL_STRUCT_SIZE);
+        let mut d = Decimal::new(data[0], data[1], data[3] != 0);
+        d.result_frac_cnt = data[2];
+        data = &data[4..];
+        for word in d.word_buf.iter_mut() {
+            *word = data.read_u32::<LittleEndian>().unwrap();
+        }
+        d
+    }
+
     /// `digit_bounds` returns bounds of decimal digits in the number.
     fn digit_bounds(&self) -> (u8, u8) {
         let mut buf_beg = 0;
@@ -28,6 +28,8 @@ const SECS_PER_MINUTE: u64 = 60;
 
 /// `MAX_TIME_IN_SECS` is the maximum for mysql time type.
 const MAX_TIME_IN_SECS: u64 = 838 * SECS_PER_HOUR + 59 * SECS_PER_MINUTE + 59;
+/// `MAX_DURATION_NANOS` is the maximum for mysql time type in nanoseconds.
+pub const MAX_DURATION_NANOS: i64 = MAX_TIME_IN_SECS as i64 * NANOS_PER_SEC;
 
 fn check_dur(dur: &StdDuration) -> Result<()> {
     let secs = dur.as_secs();
@@ -65,11 +65,12 @@ pub mod types;
 mod time;
 pub mod json;
 
-pub use self::duration::Duration;
-pub use self::decimal::{dec_encoded_len, Decimal, DecimalDecoder, DecimalEncoder, Res};
+pub use self::duration::{Duration, MAX_DURATION_NANOS, NANOS_PER_SEC};
+pub use self::decimal::{dec_encoded_len, Decimal, DecimalDecoder, DecimalEncoder, Res,
+                        DECIMAL_STRUCT_SIZE};
 pub use self::types::{has_is_boolean_flag, has_not_null_flag, has_parse_to_json_flag,
                       has_unsigned_flag};
-pub use self::time::Time;
+pub use self::time::{Time, TIME_STRUCT_SIZE};
 pub use self::json::{parse_json_path_expr, Json, JsonDecoder, JsonEncoder, ModifyType,
                      PathExpression};
 
@@ -16,11 +16,13 @@ use std::cmp::Ordering;
 use std::str;
 use std::fmt::{self, Display, Formatter};
 
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone, Timelike, Utc};
 
 use coprocessor::codec::mysql::{self, check_fsp, parse_frac, types};
 use coprocessor::codec::mysql::Decimal;
 use coprocessor::codec::mysql::duration::{Duration as MyDuration, NANOS_PER_SEC, NANO_WIDTH};
+use coprocessor::select::xeval::EvalContext;
 use super::super::{Result, TEN_POW};
 
 
@@ -30,6 +32,9 @@ const ZERO_DATE_STR: &'static str = "0000-00-00";
 /// `-0001-11-30 00:00:00 +0000 UTC`, whose timestamp is -62169984000.
 const ZERO_TIMESTAMP: i64 = -62169984000;
 
+/// The length of a time in the fixed-length format of chunk columns.
+pub const TIME_STRUCT_SIZE: usize = 16;
+
 #[inline]
 fn zero_time(tz: &FixedOffset) -> DateTime<FixedOffset> {
     tz.timestamp(ZERO_TIMESTAMP, 0)
@@ -202,6 +207,24 @@ impl Time {
     }
 
     pub fn parse_datetime(s: &str, fsp: i8, tz: &FixedOffset) -> Result<Time> {
+        Time::parse_datetime_with(s, fsp, tz, || Err(box_err!("invalid datetime: {}", s)))
+    }
+
+    /// Parse the datetime in the timezone of `ctx`, a date with zero month or
+    /// day is parsed as the zero date or rejected as `ctx` decides.
+    pub fn parse_datetime_in_ctx(ctx: &EvalContext, s: &str, fsp: i8) -> Result<Time> {
+        Time::parse_datetime_with(s, fsp, &ctx.tz, || ctx.handle_zero_in_date(s))
+    }
+
+    fn parse_datetime_with<F>(
+        s: &str,
+        fsp: i8,
+        tz: &FixedOffset,
+        on_zero_in_date: F,
+    ) -> Result<Time>
+    where
+        F: FnOnce() -> Result<()>,
+    {
         let fsp = check_fsp(fsp)?;
         let mut frac_str = "";
         let mut need_adjust = false;
@@ -269,6 +292,10 @@ impl Time {
         if y < 0 || y > 9999 {
             return Err(box_err!("unsupport year: {}", y));
         }
+        if m == 0 || d == 0 {
+            on_zero_in_date()?;
+            return Ok(zero_datetime(tz));
+        }
         let t = ymd_hms_nanos(
             tz,
             y,
@@ -310,10 +337,11 @@ impl Time {
         Time::new(t, tp, fsp as i8)
     }
 
-    pub fn from_duration(tz: &FixedOffset, tp: u8, d: &MyDuration) -> Result<Time> {
+    /// Get the time of the duration since the midnight of the current date in
+    /// the timezone of `ctx`.
+    pub fn from_duration(ctx: &EvalContext, tp: u8, d: &MyDuration) -> Result<Time> {
         let dur = Duration::nanoseconds(d.to_nanos());
-        let t = Utc::now()
-            .with_timezone(tz)
+        let t = ctx.now()
             .date()
             .and_hms(0, 0, 0)
             .checked_add_signed(dur);
@@ -363,6 +391,60 @@ impl Time {
         (((ymd << 17) | hms) << 24) | micro
     }
 
+    /// `write_fixed` appends the time to `buf` in `TIME_STRUCT_SIZE` bytes with
+    /// the layout of TiDB's chunk: hour(u32), microsecond(u32), year(u16),
+    /// month, day, minute, second, type and fsp. Like `to_packed_u64`, the
+    /// fields of a TIMESTAMP are in UTC.
+    pub fn write_fixed(&self, buf: &mut Vec<u8>) {
+        let (year, month, day, hour, minute, second, micro) = if self.is_zero() {
+            (0, 0, 0, 0, 0, 0, 0)
+        } else {
+            let t = if self.tp == types::TIMESTAMP {
+                self.time.naive_utc()
+            } else {
+                self.time.naive_local()
+            };
+            (
+                t.year() as u16,
+                t.month() as u8,
+                t.day() as u8,
+                t.hour(),
+                t.minute() as u8,
+                t.second() as u8,
+                t.nanosecond() / 1000,
+            )
+        };
+        buf.write_u32::<LittleEndian>(hour).unwrap();
+        buf.write_u32::<LittleEndian>(micro).unwrap();
+        buf.write_u16::<LittleEndian>(year).unwrap();
+        buf.extend_from_slice(&[month, day, minute, second, self.tp, self.fsp]);
+    }
+
+    /// `read_fixed` reads a time written by `write_fixed`, `data` should contain
+    /// exactly `TIME_STRUCT_SIZE` bytes.
+    pub fn read_fixed(mut data: &[u8], tz: &FixedOffset) -> Result<Time> {
+        if data.len() != TIME_STRUCT_SIZE {
+            return Err(box_err!(
+                "invalid fixed time length {}, expect {}",
+                data.len(),
+                TIME_STRUCT_SIZE
+            ));
+        }
+        let hour = data.read_u32::<LittleEndian>()? as u64;
+        let micro = data.read_u32::<LittleEndian>()? as u64;
+        let year = data.read_u16::<LittleEndian>()? as u64;
+        let (month, day, minute, second) = (
+            data[0] as u64,
+            data[1] as u64,
+            data[2] as u64,
+            data[3] as u64,
+        );
+        let (tp, fsp) = (data[4], data[5] as i8);
+        let ymd = ((year * 13 + month) << 5) | day;
+        let hms = (hour << 12) | (minute << 6) | second;
+        Time::from_packed_u64((((ymd << 17) | hms) << 24) | micro, tp, fsp, tz)
+    }
+
     pub fn round_frac(&mut self, fsp: i8) -> Result<()> {
         if self.tp == types::DATE || self.is_zero() {
             // date type has no fsp
@@ -450,6 +532,8 @@ mod test {
     use chrono::{Duration, FixedOffset};
 
     use coprocessor::codec::mysql::{types, Duration as MyDuration, MAX_FSP, UN_SPECIFIED_FSP};
+    use coprocessor::select::xeval::evaluator::{FLAG_IGNORE_ZERO_IN_DATE, MODE_NO_ZERO_IN_DATE,
+                                                MODE_STRICT_ALL_TABLES};
 
     const MIN_OFFSET: i32 = -60 * 24 + 1;
     const MAX_OFFSET: i32 = 60 * 24;
@@ -535,6 +619,46 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_parse_datetime_zero_in_date() {
+        let strict = MODE_STRICT_ALL_TABLES | MODE_NO_ZERO_IN_DATE;
+        let contexts = vec![
+            // flags, sql mode, whether the zero parts are allowed
+            (0, None, true),
+            (0, Some(MODE_STRICT_ALL_TABLES), true),
+            (0, Some(MODE_NO_ZERO_IN_DATE), true),
+            (0, Some(strict), false),
+            (FLAG_IGNORE_ZERO_IN_DATE, Some(strict), true),
+        ];
+        for (flags, sql_mode, allowed) in contexts {
+            let mut builder = EvalContext::builder().flags(flags);
+            if let Some(sql_mode) = sql_mode {
+                builder = builder.sql_mode(sql_mode);
+            }
+            let ctx = builder.build().unwrap();
+            // the zero date isn't a date with zero parts.
+            for s in &["0000-00-00", "0000-00-00 00:00:00"] {
+                let t = Time::parse_datetime_in_ctx(&ctx, s, 0).unwrap();
+                assert!(t.is_zero());
+            }
+            for s in &["2020-00-10", "2020-10-00", "2020-00-10 11:30:45"] {
+                let res = Time::parse_datetime_in_ctx(&ctx, s, 0);
+                if allowed {
+                    assert!(res.unwrap().is_zero(), "{}", s);
+                } else {
+                    assert!(res.is_err(), "{} {:?}", s, sql_mode);
+                }
+            }
+            let t = Time::parse_datetime_in_ctx(&ctx, "2020-01-10", 0).unwrap();
+            assert_eq!(format!("{}", t), "2020-01-10 00:00:00");
+            let warnings = ctx.take_eval_stats().zero_in_date_cnt;
+            assert_eq!(warnings, if allowed { 3 } else { 0 });
+        }
+        // the dates with zero parts are rejected without the context.
+        let tz = FixedOffset::east(0);
+        assert!(Time::parse_datetime("2020-00-10", 0, &tz).is_err());
+    }
+
     #[test]
     fn test_codec() {
         let cases = vec![
@@ -809,10 +933,10 @@ mod test {
     #[test]
     fn test_from_duration() {
         let cases = vec![("11:30:45.123456"), ("-35:30:46")];
-        let tz = FixedOffset::east(0);
+        let ctx = EvalContext::default();
         for s in cases {
             let d = MyDuration::parse(s.as_bytes(), MAX_FSP).unwrap();
-            let get = Time::from_duration(&tz, types::DATETIME, &d).unwrap();
+            let get = Time::from_duration(&ctx, types::DATETIME, &d).unwrap();
             let get_today = get.time
                 .checked_sub_signed(Duration::nanoseconds(d.to_nanos()))
                 .unwrap();
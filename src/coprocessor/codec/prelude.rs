@@ -0,0 +1,30 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `prelude` re-exports the codec types which can be used outside the
+//! coprocessor, e.g. by tools and by tests of other crates.
+//!
+//! The paths of the items here are stable: the inner modules of `codec` may be
+//! reorganized freely, but an item re-exported here is only removed or changed
+//! incompatibly after a replacement has been added next to it. Users should not
+//! reach into the inner modules directly.
+//!
+//! ```ignore
+//! use tikv::coprocessor::codec::prelude::*;
+//! ```
+
+pub use super::{Error, Result};
+pub use super::datum::{encode_key, encode_value, Datum, DatumDecoder, DatumEncoder};
+pub use super::chunk::{ArcChunk, Chunk, ChunkEncoder, DecodeLimits, MutRow, Row,
+                       RowContainer};
+pub use super::mysql::{types, Decimal, Duration, Json, Time};
@@ -13,7 +13,7 @@
 
 
 use std::io::Write;
-use std::{cmp, u8};
+use std::{cmp, str, u8};
 use tipb::schema::ColumnInfo;
 
 use coprocessor::select::xeval::EvalContext;
@@ -24,7 +24,7 @@ use util::codec::number::{NumberDecoder, NumberEncoder};
 use util::codec::bytes::BytesDecoder;
 use super::datum::DatumDecoder;
 use super::{datum, Datum, Result};
-use super::mysql::{types, Duration, Time};
+use super::mysql::{charset, types, Duration, Time};
 
 // handle or index id
 pub const ID_LEN: usize = 8;
@@ -169,6 +169,17 @@ fn unflatten(ctx: &EvalContext, datum: Datum, col: &ColumnInfo) -> Result<Datum>
         error!("unknown type {} {:?}", col.get_tp(), datum);
     }
     match col.get_tp() as u8 {
+        types::STRING
+            if ctx.pad_char_to_full_length && col.get_collation() != charset::COLLATION_BIN_ID =>
+        {
+            match datum {
+                Datum::Bytes(bs) => {
+                    let padded = pad_char_to_full_length(bs, col.get_columnLen());
+                    Ok(Datum::Bytes(padded))
+                }
+                d => Ok(d),
+            }
+        }
         types::FLOAT => Ok(Datum::F64(datum.f64() as f32 as f64)),
         types::TINY |
         types::SHORT |
@@ -200,6 +211,20 @@ fn unflatten(ctx: &EvalContext, datum: Datum, col: &ColumnInfo) -> Result<Datum>
     }
 }
 
+// The CHAR values are stored with the trailing spaces removed, pad them back
+// to `flen` characters.
+fn pad_char_to_full_length(mut bs: Vec<u8>, flen: i32) -> Vec<u8> {
+    let char_cnt = match str::from_utf8(&bs) {
+        Ok(s) => s.chars().count(),
+        Err(_) => bs.len(),
+    };
+    if flen > 0 && char_cnt < flen as usize {
+        let len = bs.len() + flen as usize - char_cnt;
+        bs.resize(len, b' ');
+    }
+    bs
+}
+
 pub trait TableDecoder: DatumDecoder {
     // `decode_col_value` decodes data to a Datum according to the column info.
     fn decode_col_value(&mut self, ctx: &EvalContext, col: &ColumnInfo) -> Result<Datum> {
@@ -363,8 +388,9 @@ mod test {
 
     use tipb::schema::ColumnInfo;
 
-    use coprocessor::codec::mysql::types;
+    use coprocessor::codec::mysql::{charset, types};
     use coprocessor::codec::datum::{self, Datum, DatumDecoder};
+    use coprocessor::select::xeval::EvalContext;
     use util::codec::number::NumberEncoder;
     use util::collections::{HashMap, HashSet};
 
@@ -499,6 +525,41 @@ mod test {
         assert!(datums.is_empty());
     }
 
+    #[test]
+    fn test_pad_char_to_full_length() {
+        let mut col = new_col_info(types::STRING);
+        col.set_columnLen(5);
+        let mut binary = col.clone();
+        binary.set_collation(charset::COLLATION_BIN_ID);
+        let ctx = EvalContext::default();
+        let pad_ctx = EvalContext {
+            pad_char_to_full_length: true,
+            ..EvalContext::default()
+        };
+        let cases = vec![
+            (&col, &ctx, "ab", "ab"),
+            (&col, &pad_ctx, "ab", "ab   "),
+            (&col, &pad_ctx, "中文", "中文   "),
+            (&col, &pad_ctx, "abcde", "abcde"),
+            (&col, &pad_ctx, "", "     "),
+            (&binary, &pad_ctx, "ab", "ab"),
+        ];
+        for (col, ctx, v, exp) in cases {
+            let bs = datum::encode_value(&[Datum::Bytes(v.as_bytes().to_vec())]).unwrap();
+            let d = bs.as_slice().decode_col_value(ctx, col).unwrap();
+            assert_eq!(d, Datum::Bytes(exp.as_bytes().to_vec()));
+        }
+
+        // only the CHAR columns are padded.
+        let varchar = new_col_info(types::VARCHAR);
+        let bs = datum::encode_value(&[Datum::Bytes(b"ab".to_vec())]).unwrap();
+        let d = bs.as_slice().decode_col_value(&pad_ctx, &varchar).unwrap();
+        assert_eq!(d, Datum::Bytes(b"ab".to_vec()));
+        let bs = datum::encode_value(&[Datum::Null]).unwrap();
+        let d = bs.as_slice().decode_col_value(&pad_ctx, &col).unwrap();
+        assert_eq!(d, Datum::Null);
+    }
+
     #[test]
     fn test_idx_codec() {
         let mut col_ids = vec![1, 2, 3];
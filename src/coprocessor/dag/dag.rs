@@ -11,97 +11,325 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp;
 use std::rc::Rc;
+use std::time::Duration;
 
-use tipb::executor::{ExecType, Executor};
+use tipb::executor::{ExecType, Executor, ExecutorExecutionSummary};
+use tipb::expression::FieldType;
 use tipb::schema::ColumnInfo;
-use tipb::select::{DAGRequest, SelectResponse};
+use tipb::select::{Chunk, DAGRequest, EncodeType, SelectResponse};
 use kvproto::coprocessor::{KeyRange, Response};
 use protobuf::{Message as PbMsg, RepeatedField};
 
-use coprocessor::codec::mysql;
+use coprocessor::codec::mysql::{self, types};
+use coprocessor::codec::chunk::{Chunk as ColumnarChunk, ChunkEncoder};
 use coprocessor::codec::datum::{Datum, DatumEncoder};
+use coprocessor::codec::table::TableDecoder;
 use coprocessor::select::xeval::EvalContext;
-use coprocessor::{Error, Result};
-use coprocessor::endpoint::{get_chunk, get_pk, to_pb_error, ReqContext};
+use coprocessor::select::xeval::evaluator::MAX_WARNING_CNT_LIMIT;
+use coprocessor::{BuildError, Error, Result};
+use coprocessor::endpoint::{get_chunk, get_pk, to_pb_error, ReqContext, BATCH_ROW_COUNT};
+use coprocessor::key_ranges::KeyRanges;
 use storage::{Snapshot, SnapshotStore, Statistics};
+use util::time::Instant;
 
-use super::executor::{AggregationExecutor, Executor as DAGExecutor, IndexScanExecutor,
-                      LimitExecutor, Row, SelectionExecutor, TableScanExecutor, TopNExecutor};
+use super::executor::{AggregationExecutor, ExecSummaries, ExecSummary, Executor as DAGExecutor,
+                      IndexScanExecutor, LimitExecutor, RangeFeedback, Row, ScannedRange,
+                      SelectionExecutor, SummaryExecutor, TableScanExecutor, TopNExecutor};
 
 pub struct DAGContext<'s> {
     columns: Rc<Vec<ColumnInfo>>,
     has_aggr: bool,
+    // the encoding of the rows in the response, only the chunk encoding is
+    // supported besides the default one.
+    encode_type: EncodeType,
+    // the field types of the output columns, only used by the chunk encoding.
+    output_field_types: Vec<FieldType>,
     req: DAGRequest,
-    ranges: Vec<KeyRange>,
+    ranges: KeyRanges,
     snap: &'s Snapshot,
     eval_ctx: Rc<EvalContext>,
     req_ctx: &'s ReqContext,
+    // only allocated if the request asks for the feedback.
+    feedback: Option<RangeFeedback>,
+    // only allocated if the request asks for the execution summaries.
+    summaries: Option<ExecSummaries>,
+    // only allocated for the streaming requests.
+    scanned_range: Option<ScannedRange>,
 }
 
 impl<'s> DAGContext<'s> {
     pub fn new(
         req: DAGRequest,
-        ranges: Vec<KeyRange>,
+        ranges: KeyRanges,
         snap: &'s Snapshot,
         eval_ctx: Rc<EvalContext>,
         req_ctx: &'s ReqContext,
     ) -> DAGContext<'s> {
+        let feedback = if req_ctx.collect_feedback {
+            Some(RangeFeedback::new(ranges.len()))
+        } else {
+            None
+        };
+        let summaries = if req_ctx.collect_summaries {
+            Some(ExecSummaries::new(req.get_executors().len()))
+        } else {
+            None
+        };
+        // clamped before the cast, so it isn't wrapped on 32-bit platforms.
+        let max_warning_cnt = cmp::min(req.get_max_warning_count(), MAX_WARNING_CNT_LIMIT as u64);
+        eval_ctx.set_max_warning_cnt(max_warning_cnt as usize);
         DAGContext {
+            encode_type: requested_encode_type(&req),
             req: req,
             columns: Rc::new(vec![]),
             ranges: ranges,
             snap: snap,
             has_aggr: false,
+            output_field_types: vec![],
             eval_ctx: eval_ctx,
             req_ctx: req_ctx,
+            feedback: feedback,
+            summaries: summaries,
+            scanned_range: None,
         }
     }
 
-    pub fn handle_request(mut self, statistics: &'s mut Statistics) -> Result<Response> {
+    /// Handle the request, the time spent in encoding the rows and the
+    /// response is added to `encode_time`.
+    pub fn handle_request(
+        mut self,
+        statistics: &'s mut Statistics,
+        encode_time: &mut Duration,
+    ) -> Result<Response> {
+        self.validate_dag()?;
+        let mut exec = self.build_dag(statistics)?;
+        let res = self.handle_rows(&mut *exec, encode_time);
+        // the metrics of the executors are flushed once per request, even if
+        // it fails.
+        exec.take_statistics().flush();
+        res
+    }
+
+    /// Handle the request in a stream of responses, each of them holds at most
+    /// `batch_limit` rows. `on_response` is called with every response and
+    /// whether it's the last one, the stream ends early if it fails.
+    pub fn handle_streaming_request<F>(
+        mut self,
+        statistics: &'s mut Statistics,
+        batch_limit: usize,
+        encode_time: &mut Duration,
+        mut on_response: F,
+    ) -> Result<()>
+    where
+        F: FnMut(Response, bool) -> Result<()>,
+    {
+        assert!(batch_limit > 0);
         self.validate_dag()?;
+        self.scanned_range = Some(ScannedRange::default());
         let mut exec = self.build_dag(statistics)?;
+        let res = self.stream_rows(&mut *exec, batch_limit, encode_time, &mut on_response);
+        exec.take_statistics().flush();
+        res
+    }
+
+    fn stream_rows<F>(
+        &self,
+        exec: &mut DAGExecutor,
+        batch_limit: usize,
+        encode_time: &mut Duration,
+        on_response: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(Response, bool) -> Result<()>,
+    {
+        loop {
+            let (resp, finished) = self.next_stream_response(exec, batch_limit, encode_time)?;
+            on_response(resp, finished)?;
+            if finished {
+                return Ok(());
+            }
+        }
+    }
+
+    // Pull at most `batch_limit` rows from `exec` into a response, and returns
+    // whether all the rows are pulled. The executors keep their states between
+    // the calls, so the next call continues from the row pulled last. If the
+    // rows run out right at the limit, the next response is an empty one.
+    fn next_stream_response(
+        &self,
+        exec: &mut DAGExecutor,
+        batch_limit: usize,
+        encode_time: &mut Duration,
+    ) -> Result<(Response, bool)> {
         let mut chunks = vec![];
+        let mut rows = Vec::with_capacity(cmp::min(batch_limit, BATCH_ROW_COUNT));
+        let mut count = 0;
+        let mut finished = false;
+        while count < batch_limit {
+            match exec.next()? {
+                Some(row) => {
+                    rows.push(row);
+                    count += 1;
+                    if rows.len() >= BATCH_ROW_COUNT {
+                        *encode_time += self.encode_rows(&mut rows, &mut chunks)?;
+                    }
+                }
+                None => {
+                    finished = true;
+                    break;
+                }
+            }
+        }
+        *encode_time += self.encode_rows(&mut rows, &mut chunks)?;
+        let timer = Instant::now();
+        let mut sel_resp = SelectResponse::new();
+        sel_resp.set_encode_type(self.encode_type);
+        self.set_summaries(&mut sel_resp);
+        self.set_warnings(&mut sel_resp);
+        sel_resp.set_chunks(RepeatedField::from_vec(chunks));
+        if finished {
+            if let Some(ref feedback) = self.feedback {
+                set_range_feedback(&mut sel_resp, feedback.counts());
+            }
+        }
+        let mut resp = Response::new();
+        if let Some(range) = self.scanned_range.as_ref().and_then(|r| r.take()) {
+            set_scanned_range(&mut resp, range);
+        }
+        resp.set_data(box_try!(sel_resp.write_to_bytes()));
+        *encode_time += timer.elapsed();
+        Ok((resp, finished))
+    }
+
+    fn handle_rows(&self, exec: &mut DAGExecutor, encode_time: &mut Duration) -> Result<Response> {
+        let mut chunks = vec![];
+        // the rows are encoded in batches, so the precise clock is only read
+        // once per batch.
+        let mut rows = Vec::with_capacity(BATCH_ROW_COUNT);
         loop {
             match exec.next() {
                 Ok(Some(row)) => {
-                    self.req_ctx.check_if_outdated()?;
-                    let chunk = get_chunk(&mut chunks);
-                    if self.has_aggr {
-                        chunk.mut_rows_data().extend_from_slice(&row.data.value);
-                    } else {
-                        let value =
-                            inflate_cols(&row, &self.columns, self.req.get_output_offsets())?;
-                        chunk.mut_rows_data().extend_from_slice(&value);
+                    rows.push(row);
+                    if rows.len() >= BATCH_ROW_COUNT {
+                        *encode_time += self.encode_rows(&mut rows, &mut chunks)?;
                     }
                 }
                 Ok(None) => {
+                    *encode_time += self.encode_rows(&mut rows, &mut chunks)?;
+                    let timer = Instant::now();
                     let mut resp = Response::new();
                     let mut sel_resp = SelectResponse::new();
+                    sel_resp.set_encode_type(self.encode_type);
+                    self.set_summaries(&mut sel_resp);
+                    self.set_warnings(&mut sel_resp);
                     sel_resp.set_chunks(RepeatedField::from_vec(chunks));
+                    if let Some(ref feedback) = self.feedback {
+                        set_range_feedback(&mut sel_resp, feedback.counts());
+                    }
                     let data = box_try!(sel_resp.write_to_bytes());
                     resp.set_data(data);
+                    *encode_time += timer.elapsed();
                     return Ok(resp);
                 }
                 Err(e) => if let Error::Other(_) = e {
                     let mut resp = Response::new();
                     let mut sel_resp = SelectResponse::new();
+                    sel_resp.set_encode_type(self.encode_type);
+                    self.set_summaries(&mut sel_resp);
+                    self.set_warnings(&mut sel_resp);
                     sel_resp.set_error(to_pb_error(&e));
                     resp.set_data(box_try!(sel_resp.write_to_bytes()));
                     resp.set_other_error(format!("{}", e));
                     return Ok(resp);
                 } else {
+                    // the chunks are dropped, so no row is responded.
                     return Err(e);
                 },
             }
         }
     }
 
+    // The summaries are attached to every response, including the ones of the
+    // errors, so the executors run before the failure are still analyzed.
+    fn set_summaries(&self, sel_resp: &mut SelectResponse) {
+        if let Some(ref summaries) = self.summaries {
+            set_execution_summaries(sel_resp, summaries.summaries());
+        }
+    }
+
+    // The warnings are taken from the context, so every response of a stream
+    // only carries the warnings generated since the previous one, including
+    // the ones generated while its rows are encoded. The count of a response
+    // is the count of its slice, tipb has no field for the total, which is the
+    // sum of the counts of all the responses.
+    fn set_warnings(&self, sel_resp: &mut SelectResponse) {
+        let warnings = self.eval_ctx.take_warnings();
+        sel_resp.set_warning_count(warnings.warning_cnt as i64);
+        sel_resp.set_warnings(RepeatedField::from_vec(warnings.warnings));
+    }
+
+    // Encode and drain `rows` into `chunks`, returns the time spent.
+    fn encode_rows(&self, rows: &mut Vec<Row>, chunks: &mut Vec<Chunk>) -> Result<Duration> {
+        if self.encode_type == EncodeType::TypeChunk {
+            return self.encode_rows_in_chunk(rows, chunks);
+        }
+        let timer = Instant::now();
+        for row in rows.drain(..) {
+            // a row is appended to the chunk only after it's encoded entirely,
+            // so the chunk never holds a partial row even if the encoding fails.
+            let value = if self.has_aggr {
+                row.data.value
+            } else {
+                inflate_cols(&row, &self.columns, self.req.get_output_offsets())?
+            };
+            let chunk = get_chunk(chunks);
+            chunk.mut_rows_data().extend_from_slice(&value);
+        }
+        Ok(timer.elapsed())
+    }
+
+    // Encode and drain `rows` into chunks in the columnar format of TiDB's
+    // chunks, returns the time spent. The rows are encoded by a `ChunkEncoder`
+    // in batches of at most `BATCH_ROW_COUNT` rows, so every chunk holds a batch.
+    fn encode_rows_in_chunk(
+        &self,
+        rows: &mut Vec<Row>,
+        chunks: &mut Vec<Chunk>,
+    ) -> Result<Duration> {
+        let timer = Instant::now();
+        if rows.is_empty() {
+            return Ok(timer.elapsed());
+        }
+        let offsets = self.req.get_output_offsets();
+        let mut columnar = ColumnarChunk::from_field_types(&self.output_field_types);
+        for row in rows.drain(..) {
+            let value = inflate_cols(&row, &self.columns, offsets)?;
+            let mut buf = value.as_slice();
+            for (i, (&offset, ft)) in offsets.iter().zip(&self.output_field_types).enumerate() {
+                let col = &self.columns[offset as usize];
+                let datum = box_try!(buf.decode_col_value(&self.eval_ctx, col));
+                box_try!(columnar.append_datum(i, &datum, ft));
+            }
+        }
+        let tps: Vec<_> = self.output_field_types.iter().map(|ft| ft.get_tp()).collect();
+        let mut encoder = box_try!(ChunkEncoder::new(&columnar, &tps));
+        while encoder.remaining_rows() > 0 {
+            let mut data = vec![];
+            box_try!(encoder.encode_next(BATCH_ROW_COUNT, &mut data));
+            let mut chunk = Chunk::new();
+            chunk.set_rows_data(data);
+            chunks.push(chunk);
+        }
+        Ok(timer.elapsed())
+    }
+
     fn validate_dag(&mut self) -> Result<()> {
         let execs = self.req.get_executors();
         let first = execs
             .first()
-            .ok_or_else(|| Error::Other(box_err!("has no executor")))?;
+            .ok_or_else(|| BuildError::invalid_request("has no executor"))?;
         // check whether first exec is *scan and get the column info
         match first.get_tp() {
             ExecType::TypeTableScan => {
@@ -111,10 +339,10 @@ impl<'s> DAGContext<'s> {
                 self.columns = Rc::new(first.get_idx_scan().get_columns().to_vec());
             }
             _ => {
-                return Err(box_err!(
+                return Err(BuildError::invalid_request(format!(
                     "first exec type should be *Scan, but get {:?}",
                     first.get_tp()
-                ))
+                )).into())
             }
         }
         // check whether dag has a aggregation action and take a flag
@@ -124,6 +352,27 @@ impl<'s> DAGContext<'s> {
             .any(|exec| exec.get_tp() == ExecType::TypeAggregation)
         {
             self.has_aggr = true;
+            // the aggregation results are always datum-encoded.
+            self.encode_type = EncodeType::TypeDefault;
+        } else {
+            // the output offsets refer to the scanned columns only if there is no aggregation.
+            for &offset in self.req.get_output_offsets() {
+                if offset as usize >= self.columns.len() {
+                    return Err(BuildError::invalid_request(format!(
+                        "output offset {} overflow, should be less than {}",
+                        offset,
+                        self.columns.len()
+                    )).into());
+                }
+            }
+            self.output_field_types = self.req
+                .get_output_offsets()
+                .iter()
+                .map(|&offset| column_field_type(&self.columns[offset as usize]))
+                .collect();
+            if !self.output_field_types.iter().all(is_chunk_encodable) {
+                self.encode_type = EncodeType::TypeDefault;
+            }
         }
         Ok(())
     }
@@ -143,29 +392,53 @@ impl<'s> DAGContext<'s> {
         );
 
         match first.get_tp() {
-            ExecType::TypeTableScan => Box::new(TableScanExecutor::new(
-                first.get_tbl_scan(),
-                self.ranges.clone(),
-                store,
-                statistics,
-            )),
-            ExecType::TypeIndexScan => Box::new(IndexScanExecutor::new(
-                first.take_idx_scan(),
-                self.ranges.clone(),
-                store,
-                statistics,
-            )),
+            ExecType::TypeTableScan => {
+                let mut scan = TableScanExecutor::new(
+                    first.get_tbl_scan(),
+                    self.ranges.clone(),
+                    store,
+                    statistics,
+                );
+                scan.set_interrupt(self.req_ctx.interrupt.clone());
+                if let Some(ref feedback) = self.feedback {
+                    scan.set_feedback(feedback.clone());
+                }
+                if let Some(ref scanned_range) = self.scanned_range {
+                    scan.set_scanned_range(scanned_range.clone());
+                }
+                Box::new(scan)
+            }
+            ExecType::TypeIndexScan => {
+                let mut scan = IndexScanExecutor::new(
+                    first.take_idx_scan(),
+                    self.ranges.clone(),
+                    store,
+                    statistics,
+                );
+                scan.set_interrupt(self.req_ctx.interrupt.clone());
+                if let Some(ref feedback) = self.feedback {
+                    scan.set_feedback(feedback.clone());
+                }
+                if let Some(ref scanned_range) = self.scanned_range {
+                    scan.set_scanned_range(scanned_range.clone());
+                }
+                Box::new(scan)
+            }
             _ => unreachable!(),
         }
     }
 
     fn build_dag(&'s self, statistics: &'s mut Statistics) -> Result<Box<DAGExecutor + 's>> {
-        let mut execs = self.req.get_executors().to_vec().into_iter();
-        let mut src = self.build_first(execs.next().unwrap(), statistics);
-        for mut exec in execs {
+        let mut execs = self.req.get_executors().to_vec().into_iter().enumerate();
+        let first = self.build_first(execs.next().unwrap().1, statistics);
+        let mut src = self.with_summary(first, 0);
+        for (i, mut exec) in execs {
             let curr: Box<DAGExecutor> = match exec.get_tp() {
                 ExecType::TypeTableScan | ExecType::TypeIndexScan => {
-                    return Err(box_err!("got too much *scan exec, should be only one"))
+                    return Err(
+                        BuildError::invalid_request("got too much *scan exec, should be only one")
+                            .into(),
+                    )
                 }
                 ExecType::TypeSelection => Box::new(SelectionExecutor::new(
                     exec.take_selection(),
@@ -187,10 +460,73 @@ impl<'s> DAGContext<'s> {
                 )?),
                 ExecType::TypeLimit => Box::new(LimitExecutor::new(exec.take_limit(), src)),
             };
-            src = curr;
+            src = self.with_summary(curr, i);
         }
         Ok(src)
     }
+
+    // Wrap the `idx`-th executor of the request to collect its execution
+    // summary if the request asks for it.
+    fn with_summary(&self, exec: Box<DAGExecutor + 's>, idx: usize) -> Box<DAGExecutor + 's> {
+        match self.summaries {
+            Some(ref summaries) => Box::new(SummaryExecutor::new(exec, idx, summaries.clone())),
+            None => exec,
+        }
+    }
+}
+
+// TODO: kvproto doesn't carry the range scanned in the response yet, set it
+// once it does, so TiDB can resume the scan after region errors.
+fn set_scanned_range(_: &mut Response, _: KeyRange) {}
+
+// The counts of the rows scanned in every range are responded as the output
+// counts, in the order of the request ranges.
+fn set_range_feedback(sel_resp: &mut SelectResponse, counts: Vec<u64>) {
+    sel_resp.set_output_counts(counts.into_iter().map(|c| c as i64).collect());
+}
+
+// The summaries are responded in the order of the executors in the request.
+fn set_execution_summaries(sel_resp: &mut SelectResponse, summaries: Vec<ExecSummary>) {
+    let summaries = summaries
+        .into_iter()
+        .map(|s| {
+            let mut summary = ExecutorExecutionSummary::new();
+            summary.set_time_processed_ns(s.time_processed_ns);
+            summary.set_num_produced_rows(s.num_produced_rows);
+            summary.set_num_iterations(s.num_iterations);
+            summary
+        })
+        .collect();
+    sel_resp.set_execution_summaries(RepeatedField::from_vec(summaries));
+}
+
+// The other encodings requested fall back to the default one, the response
+// tells the client the encoding actually used.
+fn requested_encode_type(req: &DAGRequest) -> EncodeType {
+    if req.get_encode_type() == EncodeType::TypeChunk {
+        EncodeType::TypeChunk
+    } else {
+        EncodeType::TypeDefault
+    }
+}
+
+fn column_field_type(col: &ColumnInfo) -> FieldType {
+    let mut ft = FieldType::new();
+    ft.set_tp(col.get_tp());
+    ft.set_flag(col.get_flag() as u32);
+    ft.set_flen(col.get_columnLen());
+    ft.set_decimal(col.get_decimal());
+    ft.set_collate(col.get_collation());
+    ft
+}
+
+// The enums, the sets and the bits can't be decoded from the rows yet, so the
+// rows holding them are always datum-encoded.
+fn is_chunk_encodable(ft: &FieldType) -> bool {
+    match ft.get_tp() as u8 {
+        types::ENUM | types::SET | types::BIT => false,
+        _ => true,
+    }
 }
 
 #[inline]
@@ -220,3 +556,433 @@ fn inflate_cols(row: &Row, cols: &[ColumnInfo], output_offsets: &[u32]) -> Resul
     }
     Ok(values)
 }
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use kvproto::kvrpcpb::{Context, IsolationLevel};
+    use protobuf::{self, RepeatedField};
+    use tipb::executor::{ExecType, Executor, TableScan};
+    use tipb::schema::ColumnInfo;
+    use tipb::select::{DAGRequest, EncodeType, SelectResponse};
+
+    use chrono::FixedOffset;
+
+    use coprocessor::codec::chunk::{ArcChunk, Chunk as ColumnarChunk};
+    use coprocessor::codec::datum::{Datum, DatumDecoder};
+    use coprocessor::codec::mysql::types;
+    use coprocessor::codec::table;
+    use coprocessor::endpoint::{ReqContext, BATCH_ROW_COUNT, STR_REQ_TYPE_SELECT};
+    use coprocessor::interrupt::Interrupt;
+    use coprocessor::key_ranges::KeyRanges;
+    use coprocessor::select::xeval::EvalContext;
+    use coprocessor::{Error, Result};
+    use storage::Statistics;
+    use storage::engine::{self, Engine, TEMP_DIR};
+    use util::collections::HashSet;
+    use util::time::Instant;
+
+    use super::DAGContext;
+    use super::super::executor::{Executor as DAGExecutor, ExecutorMetrics, Row};
+
+    // `InterruptedExecutor` outputs the rows and then fails with `Canceled`.
+    struct InterruptedExecutor {
+        rows: Vec<Row>,
+    }
+
+    impl DAGExecutor for InterruptedExecutor {
+        fn next(&mut self) -> Result<Option<Row>> {
+            if self.rows.is_empty() {
+                return Err(Error::Canceled);
+            }
+            Ok(Some(self.rows.remove(0)))
+        }
+
+        fn take_statistics(&mut self) -> ExecutorMetrics {
+            ExecutorMetrics::default()
+        }
+    }
+
+    // `RowsExecutor` outputs the rows and then finishes.
+    struct RowsExecutor {
+        rows: Vec<Row>,
+    }
+
+    impl DAGExecutor for RowsExecutor {
+        fn next(&mut self) -> Result<Option<Row>> {
+            if self.rows.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(self.rows.remove(0)))
+        }
+
+        fn take_statistics(&mut self) -> ExecutorMetrics {
+            ExecutorMetrics::default()
+        }
+    }
+
+    // `WarningsExecutor` outputs the rows, and appends a warning with the handle
+    // as its message for every row in `warn_on`, and another one once the rows
+    // run out.
+    struct WarningsExecutor {
+        rows: Vec<Row>,
+        warn_on: Vec<i64>,
+        eval_ctx: Rc<EvalContext>,
+    }
+
+    impl DAGExecutor for WarningsExecutor {
+        fn next(&mut self) -> Result<Option<Row>> {
+            if self.rows.is_empty() {
+                self.eval_ctx.append_warning(1, "end".to_owned());
+                return Ok(None);
+            }
+            let row = self.rows.remove(0);
+            if self.warn_on.contains(&row.handle) {
+                self.eval_ctx.append_warning(1, format!("{}", row.handle));
+            }
+            Ok(Some(row))
+        }
+
+        fn take_statistics(&mut self) -> ExecutorMetrics {
+            ExecutorMetrics::default()
+        }
+    }
+
+    fn new_col_info(cid: i64, tp: u8) -> ColumnInfo {
+        let mut col_info = ColumnInfo::new();
+        col_info.set_tp(tp as i32);
+        col_info.set_column_id(cid);
+        col_info
+    }
+
+    fn row_datums(handle: i64) -> Vec<Datum> {
+        vec![
+            Datum::I64(handle),
+            Datum::Bytes(format!("name:{}", handle).into_bytes()),
+        ]
+    }
+
+    fn new_rows(count: usize) -> Vec<Row> {
+        let col_ids: HashSet<i64> = vec![1, 2].into_iter().collect();
+        (0..count as i64)
+            .map(|handle| {
+                let value = table::encode_row(row_datums(handle), &[1, 2]).unwrap();
+                Row::new(handle, table::cut_row(value, &col_ids).unwrap())
+            })
+            .collect()
+    }
+
+    fn new_dag() -> DAGRequest {
+        let mut tbl_scan = TableScan::new();
+        tbl_scan.set_columns(RepeatedField::from_vec(vec![
+            new_col_info(1, types::LONG_LONG),
+            new_col_info(2, types::VARCHAR),
+        ]));
+        let mut scan = Executor::new();
+        scan.set_tp(ExecType::TypeTableScan);
+        scan.set_tbl_scan(tbl_scan);
+        let mut dag = DAGRequest::new();
+        dag.set_executors(RepeatedField::from_vec(vec![scan]));
+        dag.set_output_offsets(vec![0, 1]);
+        dag
+    }
+
+    fn new_req_ctx() -> ReqContext {
+        ReqContext {
+            interrupt: Interrupt::new(
+                Instant::now_coarse() + Duration::from_secs(60),
+                STR_REQ_TYPE_SELECT,
+            ),
+            isolation_level: IsolationLevel::SI,
+            fill_cache: true,
+            table_scan: true,
+            collect_feedback: false,
+            collect_summaries: false,
+        }
+    }
+
+    // Decode the rows of the chunks in the response, and returns the handles.
+    fn decode_handles(sel_resp: &SelectResponse) -> Vec<i64> {
+        let mut data = vec![];
+        for chunk in sel_resp.get_chunks() {
+            data.extend_from_slice(chunk.get_rows_data());
+        }
+        let mut buf = data.as_slice();
+        let mut handles = vec![];
+        while !buf.is_empty() {
+            let handle = buf.decode_datum().unwrap().i64();
+            assert_eq!(buf.decode_datum().unwrap(), row_datums(handle)[1]);
+            handles.push(handle);
+        }
+        handles
+    }
+
+    fn handle_interrupted(rows: usize) -> Result<SelectResponse> {
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let snap = engine.snapshot(&Context::new()).unwrap();
+        let req_ctx = new_req_ctx();
+        let mut ctx = DAGContext::new(
+            new_dag(),
+            KeyRanges::from_slice(&[]),
+            &*snap,
+            Rc::new(EvalContext::default()),
+            &req_ctx,
+        );
+        ctx.validate_dag().unwrap();
+        let mut exec = InterruptedExecutor {
+            rows: new_rows(rows),
+        };
+        let mut encode_time = Duration::default();
+        let resp = ctx.handle_rows(&mut exec, &mut encode_time)?;
+        Ok(protobuf::parse_from_bytes(resp.get_data()).unwrap())
+    }
+
+    // Handle the rows with the encode type, returns the rows decoded and the
+    // number of chunks they are encoded in.
+    fn handle_encoded(rows: usize, encode_type: EncodeType) -> (Vec<Vec<Datum>>, usize) {
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let snap = engine.snapshot(&Context::new()).unwrap();
+        let req_ctx = new_req_ctx();
+        let mut dag = new_dag();
+        dag.set_encode_type(encode_type);
+        let mut ctx = DAGContext::new(
+            dag,
+            KeyRanges::from_slice(&[]),
+            &*snap,
+            Rc::new(EvalContext::default()),
+            &req_ctx,
+        );
+        ctx.validate_dag().unwrap();
+        let mut exec = RowsExecutor {
+            rows: new_rows(rows),
+        };
+        let mut encode_time = Duration::default();
+        let resp = ctx.handle_rows(&mut exec, &mut encode_time).unwrap();
+        let sel_resp: SelectResponse = protobuf::parse_from_bytes(resp.get_data()).unwrap();
+        assert_eq!(sel_resp.get_encode_type(), encode_type);
+        let chunks = sel_resp.get_chunks();
+        let mut decoded = vec![];
+        for chunk in chunks {
+            if encode_type == EncodeType::TypeChunk {
+                let fts = &ctx.output_field_types;
+                let columnar = ColumnarChunk::decode(chunk.get_rows_data(), fts).unwrap();
+                let rows = ArcChunk::new(columnar)
+                    .to_datum_rows(fts, &FixedOffset::east(0))
+                    .unwrap();
+                decoded.extend(rows);
+            } else {
+                let mut buf = chunk.get_rows_data();
+                while !buf.is_empty() {
+                    let row = (0..2).map(|_| buf.decode_datum().unwrap()).collect();
+                    decoded.push(row);
+                }
+            }
+        }
+        (decoded, chunks.len())
+    }
+
+    #[test]
+    fn test_chunk_encoding() {
+        for &rows in &[0, 1, BATCH_ROW_COUNT, BATCH_ROW_COUNT * 2 + 3] {
+            let (datum_rows, _) = handle_encoded(rows, EncodeType::TypeDefault);
+            let (chunk_rows, chunks) = handle_encoded(rows, EncodeType::TypeChunk);
+            assert_eq!(chunk_rows, datum_rows);
+            let expect: Vec<_> = (0..rows as i64).map(row_datums).collect();
+            assert_eq!(chunk_rows, expect);
+            // every chunk holds a batch of rows.
+            assert_eq!(chunks, (rows + BATCH_ROW_COUNT - 1) / BATCH_ROW_COUNT);
+        }
+
+        // the aggregation results are always datum-encoded.
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let snap = engine.snapshot(&Context::new()).unwrap();
+        let req_ctx = new_req_ctx();
+        let mut dag = new_dag();
+        dag.set_encode_type(EncodeType::TypeChunk);
+        let mut aggr = Executor::new();
+        aggr.set_tp(ExecType::TypeAggregation);
+        dag.mut_executors().push(aggr);
+        let mut ctx = DAGContext::new(
+            dag,
+            KeyRanges::from_slice(&[]),
+            &*snap,
+            Rc::new(EvalContext::default()),
+            &req_ctx,
+        );
+        assert_eq!(ctx.encode_type, EncodeType::TypeChunk);
+        ctx.validate_dag().unwrap();
+        assert_eq!(ctx.encode_type, EncodeType::TypeDefault);
+    }
+
+    #[test]
+    fn test_interrupted() {
+        // the rows produced before the interruption are dropped, so no
+        // partially encoded row can be responded.
+        for &rows in &[0, 1, BATCH_ROW_COUNT, BATCH_ROW_COUNT * 2 + 3] {
+            match handle_interrupted(rows) {
+                Err(Error::Canceled) => {}
+                res => panic!("expect canceled, got {:?}", res.map(|r| r.get_chunks().len())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_rows_atomically() {
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let snap = engine.snapshot(&Context::new()).unwrap();
+        let req_ctx = new_req_ctx();
+        let mut dag = new_dag();
+        // the second column is NOT NULL, so a row without it fails to encode
+        // after its first column is encoded.
+        dag.mut_executors()[0].mut_tbl_scan().mut_columns()[1].set_flag(1);
+        let mut ctx = DAGContext::new(
+            dag,
+            KeyRanges::from_slice(&[]),
+            &*snap,
+            Rc::new(EvalContext::default()),
+            &req_ctx,
+        );
+        ctx.validate_dag().unwrap();
+        let mut rows = new_rows(3);
+        let col_ids: HashSet<i64> = vec![1].into_iter().collect();
+        let value = table::encode_row(vec![Datum::I64(3)], &[1]).unwrap();
+        rows.push(Row::new(3, table::cut_row(value, &col_ids).unwrap()));
+
+        let mut chunks = vec![];
+        assert!(ctx.encode_rows(&mut rows, &mut chunks).is_err());
+        let mut data = vec![];
+        for chunk in &chunks {
+            data.extend_from_slice(chunk.get_rows_data());
+        }
+        // only the complete rows are in the chunks.
+        let mut buf = data.as_slice();
+        for handle in 0..3 {
+            for expect in row_datums(handle) {
+                assert_eq!(buf.decode_datum().unwrap(), expect);
+            }
+        }
+        assert!(buf.is_empty(), "{} bytes left", buf.len());
+    }
+
+    #[test]
+    fn test_streaming_response() {
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let snap = engine.snapshot(&Context::new()).unwrap();
+        let req_ctx = new_req_ctx();
+        let cases = vec![
+            // rows, batch limit, responses
+            (0, 10, 1),
+            (BATCH_ROW_COUNT * 2 + 3, 50, 3),
+            (BATCH_ROW_COUNT * 2 + 3, BATCH_ROW_COUNT, 3),
+            // the rows run out right at the limit, so an empty response ends
+            // the stream.
+            (BATCH_ROW_COUNT * 2, BATCH_ROW_COUNT, 3),
+            (10, 1, 11),
+        ];
+        for (rows, batch_limit, expect_resps) in cases {
+            let mut ctx = DAGContext::new(
+                new_dag(),
+                KeyRanges::from_slice(&[]),
+                &*snap,
+                Rc::new(EvalContext::default()),
+                &req_ctx,
+            );
+            ctx.validate_dag().unwrap();
+            let mut exec = RowsExecutor {
+                rows: new_rows(rows),
+            };
+            let mut encode_time = Duration::default();
+            let mut handles = vec![];
+            let mut resps = 0;
+            loop {
+                let (resp, finished) = ctx
+                    .next_stream_response(&mut exec, batch_limit, &mut encode_time)
+                    .unwrap();
+                resps += 1;
+                let sel_resp: SelectResponse = protobuf::parse_from_bytes(resp.get_data()).unwrap();
+                let batch = decode_handles(&sel_resp);
+                assert!(batch.len() <= batch_limit);
+                handles.extend(batch);
+                if finished {
+                    break;
+                }
+            }
+            assert_eq!(resps, expect_resps, "{} rows in batches of {}", rows, batch_limit);
+            // the batches reassemble the whole result.
+            assert_eq!(handles, (0..rows as i64).collect::<Vec<_>>());
+        }
+
+        // nothing is scanned in empty ranges.
+        let ctx = DAGContext::new(
+            new_dag(),
+            KeyRanges::from_slice(&[]),
+            &*snap,
+            Rc::new(EvalContext::default()),
+            &req_ctx,
+        );
+        let mut statistics = Statistics::default();
+        let mut encode_time = Duration::default();
+        let mut resps = vec![];
+        ctx.handle_streaming_request(&mut statistics, 10, &mut encode_time, |resp, finished| {
+            resps.push((resp, finished));
+            Ok(())
+        }).unwrap();
+        assert_eq!(resps.len(), 1);
+        assert!(resps[0].1);
+    }
+
+    #[test]
+    fn test_streaming_warnings() {
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let snap = engine.snapshot(&Context::new()).unwrap();
+        let req_ctx = new_req_ctx();
+        let mut dag = new_dag();
+        dag.set_max_warning_count(10);
+        let eval_ctx = Rc::new(EvalContext::default());
+        let mut ctx = DAGContext::new(
+            dag,
+            KeyRanges::from_slice(&[]),
+            &*snap,
+            eval_ctx.clone(),
+            &req_ctx,
+        );
+        ctx.validate_dag().unwrap();
+        let mut exec = WarningsExecutor {
+            rows: new_rows(20),
+            warn_on: vec![0, 5, 6, 19],
+            eval_ctx: eval_ctx,
+        };
+        let mut encode_time = Duration::default();
+        let mut slices = vec![];
+        loop {
+            let (resp, finished) = ctx
+                .next_stream_response(&mut exec, 5, &mut encode_time)
+                .unwrap();
+            let sel_resp: SelectResponse = protobuf::parse_from_bytes(resp.get_data()).unwrap();
+            let msgs: Vec<_> = sel_resp
+                .get_warnings()
+                .iter()
+                .map(|w| w.get_msg().to_owned())
+                .collect();
+            assert_eq!(sel_resp.get_warning_count(), msgs.len() as i64);
+            slices.push(msgs);
+            if finished {
+                break;
+            }
+        }
+        // every response only carries the warnings of its own slice, the one
+        // generated when the rows run out is in the last response.
+        let expect = vec![
+            vec!["0"],
+            vec!["5", "6"],
+            vec![],
+            vec!["19"],
+            vec!["end"],
+        ];
+        assert_eq!(slices, expect);
+        // nothing is left for the following responses.
+        assert_eq!(ctx.eval_ctx.take_warnings().warning_cnt, 0);
+    }
+}
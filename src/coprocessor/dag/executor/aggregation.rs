@@ -15,19 +15,20 @@ use std::rc::Rc;
 
 use tipb::schema::ColumnInfo;
 use tipb::executor::Aggregation;
-use tipb::expression::{Expr, ExprType};
+use tipb::expression::{Expr, ExprType, ScalarFuncSig};
 use util::collections::{HashMap, HashMapEntry as Entry};
 
+use coprocessor::codec::convert::UNSPECIFIED_LENGTH;
+use coprocessor::codec::mysql::types;
 use coprocessor::codec::table::RowColsDict;
 use coprocessor::codec::datum::{self, approximate_size, Datum, DatumEncoder};
 use coprocessor::endpoint::SINGLE_GROUP;
 use coprocessor::select::aggregate::{self, AggrFunc};
 use coprocessor::select::xeval::EvalContext;
 use coprocessor::dag::expr::Expression;
-use coprocessor::metrics::*;
-use coprocessor::Result;
+use coprocessor::{BuildError, Result};
 
-use super::{inflate_with_col_for_dag, Executor, ExprColumnRefVisitor, Row};
+use super::{inflate_with_col_for_dag, Executor, ExecutorMetrics, ExprColumnRefVisitor, Row};
 
 struct AggrFuncExpr {
     args: Vec<Expression>,
@@ -42,11 +43,9 @@ impl AggrFuncExpr {
     }
 
     fn build(ctx: &EvalContext, mut expr: Expr) -> Result<AggrFuncExpr> {
-        let args = box_try!(Expression::batch_build(
-            ctx,
-            expr.take_children().into_vec()
-        ));
         let tp = expr.get_tp();
+        let args = check_aggr_args(tp, expr.take_children().into_vec())?;
+        let args = Expression::batch_build(ctx, args).map_err(BuildError::from)?;
         Ok(AggrFuncExpr { args: args, tp: tp })
     }
 
@@ -56,6 +55,58 @@ impl AggrFuncExpr {
     }
 }
 
+/// `check_aggr_args` checks the number and the types of the arguments of the
+/// aggregate function `tp` when it's built, so a request which can never be
+/// handled fails before reading any row. An argument of a type the function
+/// doesn't support is casted like MySQL does, e.g. strings are summed as
+/// doubles, and the values which can't be converted are handled as truncated.
+fn check_aggr_args(tp: ExprType, mut args: Vec<Expr>) -> Result<Vec<Expr>> {
+    match tp {
+        // COUNT(*) has no argument.
+        ExprType::Count => return Ok(args),
+        ExprType::First | ExprType::Sum | ExprType::Avg | ExprType::Max | ExprType::Min => {}
+        _ => {
+            return Err(BuildError::unsupported(format!("aggregate function {:?}", tp)).into())
+        }
+    }
+    if args.len() != 1 {
+        return Err(BuildError::invalid_request(format!(
+            "{:?} can't take {} arguments",
+            tp,
+            args.len()
+        )).into());
+    }
+    if tp != ExprType::Sum && tp != ExprType::Avg {
+        // MIN and MAX compare the values of any type, so no cast is needed.
+        return Ok(args);
+    }
+    let arg = args.pop().unwrap();
+    let arg = match arg.get_field_type().get_tp() as u8 {
+        types::JSON => {
+            return Err(BuildError::unsupported(format!("{:?} over JSON", tp)).into());
+        }
+        types::VARCHAR | types::VAR_STRING | types::STRING | types::TINY_BLOB |
+        types::MEDIUM_BLOB | types::LONG_BLOB | types::BLOB => cast_string_as_real(arg),
+        _ => arg,
+    };
+    args.push(arg);
+    Ok(args)
+}
+
+fn cast_string_as_real(arg: Expr) -> Expr {
+    let mut cast = Expr::new();
+    cast.set_tp(ExprType::ScalarFunc);
+    cast.set_sig(ScalarFuncSig::CastStringAsReal);
+    {
+        let tp = cast.mut_field_type();
+        tp.set_tp(types::DOUBLE as i32);
+        tp.set_flen(UNSPECIFIED_LENGTH);
+        tp.set_decimal(UNSPECIFIED_LENGTH);
+    }
+    cast.mut_children().push(arg);
+    cast
+}
+
 impl AggrFunc {
     fn update_with_expr(
         &mut self,
@@ -80,6 +131,7 @@ pub struct AggregationExecutor<'a> {
     cols: Rc<Vec<ColumnInfo>>,
     related_cols_offset: Vec<usize>, // offset of related columns
     src: Box<Executor + 'a>,
+    metrics: ExecutorMetrics,
 }
 
 impl<'a> AggregationExecutor<'a> {
@@ -95,11 +147,11 @@ impl<'a> AggregationExecutor<'a> {
         visitor.batch_visit(&group_by)?;
         let aggr_func = meta.take_agg_func().into_vec();
         visitor.batch_visit(&aggr_func)?;
-        COPR_EXECUTOR_COUNT
-            .with_label_values(&["aggregation"])
-            .inc();
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.aggregation = 1;
         Ok(AggregationExecutor {
-            group_by: box_try!(Expression::batch_build(ctx.as_ref(), group_by)),
+            group_by: Expression::batch_build(ctx.as_ref(), group_by)
+                .map_err(BuildError::from)?,
             aggr_func: AggrFuncExpr::batch_build(ctx.as_ref(), aggr_func)?,
             group_keys: vec![],
             group_key_aggrs: map![],
@@ -109,6 +161,7 @@ impl<'a> AggregationExecutor<'a> {
             cols: columns,
             related_cols_offset: visitor.column_offsets(),
             src: src,
+            metrics: metrics,
         })
     }
 
@@ -189,6 +242,11 @@ impl<'a> Executor for AggregationExecutor<'a> {
             data: RowColsDict::new(map![], value),
         }))
     }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        self.metrics.merge(&self.src.take_statistics());
+        self.metrics.take()
+    }
 }
 
 #[cfg(test)]
@@ -201,8 +259,10 @@ mod test {
     use tipb::expression::{Expr, ExprType};
 
     use coprocessor::codec::datum::{Datum, DatumDecoder};
+    use coprocessor::codec::mysql::charset;
     use coprocessor::codec::mysql::decimal::Decimal;
     use coprocessor::codec::mysql::types;
+    use coprocessor::Error;
     use storage::{SnapshotStore, Statistics};
     use util::codec::number::NumberEncoder;
 
@@ -294,7 +354,7 @@ mod test {
         table_scan.set_table_id(tid);
         table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
         // init TableScan Exectutor
-        let key_ranges = vec![get_range(tid, i64::MIN, i64::MAX)];
+        let key_ranges = vec![get_range(tid, i64::MIN, i64::MAX)].into();
         let (snapshot, start_ts) = test_store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
 
@@ -363,4 +423,181 @@ mod test {
             assert_eq!(ds[4], Datum::from(expect_cols.4));
         }
     }
+    fn col_expr(offset: i64, tp: u8, charset: &str) -> Expr {
+        let mut expr = build_expr(ExprType::ColumnRef, Some(offset), None);
+        expr.mut_field_type().set_tp(tp as i32);
+        expr.mut_field_type().set_charset(charset.to_owned());
+        expr
+    }
+
+    // Aggregates `strs` in a single group, returns the results.
+    fn aggr_strings(ctx: EvalContext, aggr_funcs: Vec<Expr>, strs: &[&str]) -> Result<Vec<Datum>> {
+        let tid = 1;
+        let cis = vec![
+            new_col_info(1, types::LONG_LONG),
+            new_col_info(2, types::VARCHAR),
+        ];
+        let raw_data: Vec<_> = strs.iter()
+            .enumerate()
+            .map(|(i, s)| vec![Datum::I64(i as i64), Datum::Bytes(s.as_bytes().to_vec())])
+            .collect();
+        let table_data = gen_table_data(tid, &cis, &raw_data);
+        let mut test_store = TestStore::new(&table_data);
+        let mut table_scan = TableScan::new();
+        table_scan.set_table_id(tid);
+        table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
+        let key_ranges = vec![get_range(tid, i64::MIN, i64::MAX)].into();
+        let (snapshot, start_ts) = test_store.get_snapshot();
+        let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+        let mut statistics = Statistics::default();
+        let ts_ect = TableScanExecutor::new(&table_scan, key_ranges, store, &mut statistics);
+
+        let mut aggregation = Aggregation::default();
+        aggregation.set_agg_func(RepeatedField::from_vec(aggr_funcs));
+        let mut aggr_ect =
+            AggregationExecutor::new(aggregation, Rc::new(ctx), Rc::new(cis), Box::new(ts_ect))?;
+        let row = aggr_ect.next()?.unwrap();
+        assert!(aggr_ect.next()?.is_none());
+        Ok(row.data.value.as_slice().decode().unwrap())
+    }
+
+    #[test]
+    fn test_sum_strings() {
+        let strs = ["1.5", "abc", "2x", " -0.5"];
+        let aggr_funcs = vec![
+            build_expr(
+                ExprType::Sum,
+                None,
+                Some(col_expr(1, types::VARCHAR, charset::CHARSET_UTF8)),
+            ),
+            build_expr(
+                ExprType::Avg,
+                None,
+                Some(col_expr(1, types::VARCHAR, charset::CHARSET_UTF8)),
+            ),
+        ];
+
+        // the strings are summed as doubles, the invalid ones are truncated.
+        let mut ctx = EvalContext::default();
+        ctx.truncate_as_warning = true;
+        let res = aggr_strings(ctx, aggr_funcs.clone(), &strs).unwrap();
+        let sum = Datum::Dec("3".parse().unwrap());
+        assert_eq!(res, vec![sum.clone(), Datum::U64(4), sum]);
+
+        match aggr_strings(EvalContext::default(), aggr_funcs, &strs) {
+            Err(e) => assert!(format!("{:?}", e).contains("Truncated"), "{:?}", e),
+            Ok(res) => panic!("expect truncated error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_min_max_strings() {
+        // the strings are compared as they are, no cast is inserted whatever
+        // the collation is.
+        let strs = ["b", "B", "ä", "a"];
+        let aggr_funcs = vec![
+            build_expr(
+                ExprType::Min,
+                None,
+                Some(col_expr(1, types::VARCHAR, charset::CHARSET_UTF8)),
+            ),
+            build_expr(
+                ExprType::Max,
+                None,
+                Some(col_expr(1, types::VARCHAR, charset::CHARSET_BIN)),
+            ),
+        ];
+        let res = aggr_strings(EvalContext::default(), aggr_funcs, &strs).unwrap();
+        assert_eq!(
+            res,
+            vec![
+                Datum::Bytes(b"B".to_vec()),
+                Datum::Bytes("ä".as_bytes().to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_aggr_args() {
+        let json = || col_expr(1, types::JSON, charset::CHARSET_BIN);
+        let int = || col_expr(0, types::LONG_LONG, charset::CHARSET_BIN);
+        let mut two_args = build_expr(ExprType::Sum, None, Some(int()));
+        two_args.mut_children().push(int());
+        let mut no_arg = Expr::new();
+        no_arg.set_tp(ExprType::Max);
+        let cases = vec![
+            (build_expr(ExprType::Sum, None, Some(json())), true),
+            (build_expr(ExprType::Avg, None, Some(json())), true),
+            (build_expr(ExprType::Int64, None, Some(int())), true),
+            (two_args, false),
+            (no_arg, false),
+        ];
+        for (aggr_func, unsupported) in cases {
+            let desc = format!("{:?}", aggr_func);
+            match aggr_strings(EvalContext::default(), vec![aggr_func], &["1"]) {
+                Err(Error::Build(BuildError::Unsupported { .. })) => {
+                    assert!(unsupported, "{}", desc)
+                }
+                Err(Error::Build(BuildError::InvalidRequest { .. })) => {
+                    assert!(!unsupported, "{}", desc)
+                }
+                r => panic!("{}: expect build error, got {:?}", desc, r),
+            }
+        }
+
+        // MIN and COUNT take any argument.
+        let aggr_funcs = vec![
+            build_expr(ExprType::Min, None, Some(json())),
+            build_expr(ExprType::Count, None, Some(json())),
+        ];
+        let res = aggr_strings(EvalContext::default(), aggr_funcs, &["1"]).unwrap();
+        assert_eq!(res[1], Datum::U64(1));
+    }
+
+    #[test]
+    fn test_aggr_deleted_versions() {
+        let tid = 1;
+        let cis = vec![new_col_info(1, types::LONG_LONG)];
+        let raw_data: Vec<_> = (0..100).map(|i| vec![Datum::I64(i)]).collect();
+        let table_data = gen_table_data(tid, &cis, &raw_data);
+        let mut test_store = TestStore::new(&table_data);
+        let (_, read_ts) = test_store.get_snapshot();
+        // delete all the rows but the multiples of 10.
+        let keys: Vec<_> = table_data
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i % 10 != 0)
+            .map(|(_, &(ref k, _))| k.clone())
+            .collect();
+        test_store.delete_version(&keys, read_ts + 10, read_ts + 20);
+
+        let mut table_scan = TableScan::new();
+        table_scan.set_table_id(tid);
+        table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
+        let key_ranges = vec![get_range(tid, i64::MIN, i64::MAX)].into();
+        let mut statistics = Statistics::default();
+        {
+            let (snapshot, _) = test_store.get_snapshot();
+            let store = SnapshotStore::new(snapshot, read_ts + 30, IsolationLevel::SI, true);
+            let ts_ect = TableScanExecutor::new(&table_scan, key_ranges, store, &mut statistics);
+            let mut aggregation = Aggregation::default();
+            let aggr_funcs = build_aggr_func(&[(ExprType::Count, 0), (ExprType::Sum, 0)]);
+            aggregation.set_agg_func(RepeatedField::from_vec(aggr_funcs));
+            let mut aggr_ect = AggregationExecutor::new(
+                aggregation,
+                Rc::new(EvalContext::default()),
+                Rc::new(cis),
+                Box::new(ts_ect),
+            ).unwrap();
+            let row = aggr_ect.next().unwrap().unwrap();
+            assert!(aggr_ect.next().unwrap().is_none());
+            let ds = row.data.value.as_slice().decode().unwrap();
+            assert_eq!(ds[0], Datum::U64(10));
+            assert_eq!(ds[1], Datum::Dec(450.into()));
+        }
+        assert_eq!(statistics.write.versions, 100);
+        assert_eq!(statistics.write.skipped_versions, 90);
+        assert_eq!(statistics.write.tombstones, 90);
+        assert_eq!(statistics.write.visible_ratio(), Some(0.1));
+    }
 }
@@ -0,0 +1,332 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use coprocessor::codec::Datum;
+use coprocessor::dag::expr::Expression;
+use coprocessor::select::xeval::EvalContext;
+use coprocessor::Result;
+
+// The number of rows evaluated between two reorderings.
+const REORDER_INTERVAL: u64 = 1024;
+
+struct Conjunct {
+    expr: Expression,
+    cost: u64,
+    pure: bool,
+    // the rows evaluated and rejected since the last reordering.
+    evals: u64,
+    rejects: u64,
+    // the rows evaluated in total.
+    total_evals: u64,
+}
+
+impl Conjunct {
+    // The expected cost spent to reject a row, the conjuncts with lower ranks
+    // should be evaluated first. The rejection rate is smoothed so that the
+    // conjuncts without enough samples are ordered by their static costs.
+    fn rank(&self) -> f64 {
+        let reject_rate = (self.rejects as f64 + 1.0) / (self.evals as f64 + 2.0);
+        (self.cost + 1) as f64 / reject_rate
+    }
+}
+
+/// `Conjunction` evaluates the conjuncts of a selection in the order of the
+/// cost spent to reject a row, which is learned from the rows evaluated, so
+/// the cheap and selective conjuncts filter the rows first and the expensive
+/// ones are evaluated less.
+///
+/// The conjuncts are initially ordered by their static costs, and reordered
+/// by their observed selectivities every `REORDER_INTERVAL` rows. A conjunct
+/// which isn't pure is never moved, and no conjunct is moved across it, so
+/// it's evaluated on exactly the same rows as in the request order and the
+/// warnings it produces don't change.
+pub struct Conjunction {
+    conjuncts: Vec<Conjunct>,
+    // the indexes of the conjuncts in the order they are evaluated.
+    order: Vec<usize>,
+    rows: u64,
+    adaptive: bool,
+}
+
+impl Conjunction {
+    pub fn new(exprs: Vec<Expression>) -> Conjunction {
+        let conjuncts = exprs
+            .into_iter()
+            .map(|expr| Conjunct {
+                cost: expr.cost(),
+                pure: expr.is_pure(),
+                expr: expr,
+                evals: 0,
+                rejects: 0,
+                total_evals: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut conjunction = Conjunction {
+            order: (0..conjuncts.len()).collect(),
+            conjuncts: conjuncts,
+            rows: 0,
+            adaptive: true,
+        };
+        conjunction.reorder();
+        conjunction
+    }
+
+    /// Evaluates the conjuncts in the request order only.
+    #[cfg(test)]
+    fn new_fixed(exprs: Vec<Expression>) -> Conjunction {
+        let mut conjunction = Conjunction::new(exprs);
+        conjunction.order = (0..conjunction.conjuncts.len()).collect();
+        conjunction.adaptive = false;
+        conjunction
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.conjuncts.is_empty()
+    }
+
+    /// Returns whether the row satisfies all the conjuncts.
+    pub fn eval(&mut self, ctx: &EvalContext, row: &[Datum]) -> Result<bool> {
+        let mut passed = true;
+        for &i in &self.order {
+            let conjunct = &mut self.conjuncts[i];
+            conjunct.evals += 1;
+            conjunct.total_evals += 1;
+            let val = box_try!(conjunct.expr.eval(ctx, row));
+            if !box_try!(val.into_bool(ctx)).unwrap_or(false) {
+                conjunct.rejects += 1;
+                passed = false;
+                break;
+            }
+        }
+        self.rows += 1;
+        if self.adaptive && self.rows % REORDER_INTERVAL == 0 {
+            self.reorder();
+        }
+        Ok(passed)
+    }
+
+    // Sorts every run of pure conjuncts between the impure ones by their
+    // ranks, the sort is stable so the ties keep their current order.
+    fn reorder(&mut self) {
+        {
+            let conjuncts = &self.conjuncts;
+            for run in self.order.split_mut(|&i| !conjuncts[i].pure) {
+                run.sort_by(|&a, &b| {
+                    conjuncts[a]
+                        .rank()
+                        .partial_cmp(&conjuncts[b].rank())
+                        .unwrap_or(Ordering::Equal)
+                });
+            }
+        }
+        // the older samples are faded out, so the order follows the changes of
+        // the data. The conjuncts rarely evaluated keep their samples, or they
+        // would be moved back without any evidence.
+        for conjunct in &mut self.conjuncts {
+            if conjunct.evals >= 2 * REORDER_INTERVAL {
+                conjunct.evals /= 2;
+                conjunct.rejects /= 2;
+            }
+        }
+    }
+
+    /// Returns the number of rows every conjunct is evaluated on, in the
+    /// request order.
+    #[cfg(test)]
+    fn total_evals(&self) -> Vec<u64> {
+        self.conjuncts.iter().map(|c| c.total_evals).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tipb::expression::{Expr, ExprType, FieldType, ScalarFuncSig};
+
+    use coprocessor::codec::mysql::{charset, types};
+    use util::codec::number::NumberEncoder;
+
+    use super::*;
+
+    fn col_expr(offset: i64, tp: u8) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ColumnRef);
+        let mut buf = Vec::with_capacity(8);
+        buf.encode_i64(offset).unwrap();
+        expr.set_val(buf);
+        let mut ft = FieldType::new();
+        ft.set_tp(tp as i32);
+        ft.set_charset(charset::CHARSET_UTF8.to_owned());
+        expr.set_field_type(ft);
+        expr
+    }
+
+    fn int_expr(v: i64) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::Int64);
+        let mut buf = Vec::with_capacity(8);
+        buf.encode_i64(v).unwrap();
+        expr.set_val(buf);
+        let mut ft = FieldType::new();
+        ft.set_tp(types::LONG_LONG as i32);
+        expr.set_field_type(ft);
+        expr
+    }
+
+    fn str_expr(v: &str) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::String);
+        expr.set_val(v.as_bytes().to_vec());
+        let mut ft = FieldType::new();
+        ft.set_tp(types::VARCHAR as i32);
+        ft.set_charset(charset::CHARSET_UTF8.to_owned());
+        expr.set_field_type(ft);
+        expr
+    }
+
+    fn fn_expr(sig: ScalarFuncSig, children: Vec<Expr>) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ScalarFunc);
+        expr.set_sig(sig);
+        for child in children {
+            expr.mut_children().push(child);
+        }
+        let mut ft = FieldType::new();
+        ft.set_tp(types::LONG_LONG as i32);
+        expr.set_field_type(ft);
+        expr
+    }
+
+    // `col_1 LIKE pattern`, which is expensive.
+    fn like(pattern: &str) -> Expr {
+        let children = vec![col_expr(1, types::VARCHAR), str_expr(pattern), int_expr(92)];
+        fn_expr(ScalarFuncSig::LikeSig, children)
+    }
+
+    // `col_0 < v`, which is cheap.
+    fn lt(v: i64) -> Expr {
+        fn_expr(ScalarFuncSig::LTInt, vec![col_expr(0, types::LONG_LONG), int_expr(v)])
+    }
+
+    // `col_0 + 1 > v`, which may overflow and isn't pure.
+    fn plus_gt(v: i64) -> Expr {
+        let plus = fn_expr(
+            ScalarFuncSig::PlusInt,
+            vec![col_expr(0, types::LONG_LONG), int_expr(1)],
+        );
+        fn_expr(ScalarFuncSig::GTInt, vec![plus, int_expr(v)])
+    }
+
+    fn build(ctx: &EvalContext, exprs: Vec<Expr>, adaptive: bool) -> Conjunction {
+        let exprs = Expression::batch_build(ctx, exprs).unwrap();
+        if adaptive {
+            Conjunction::new(exprs)
+        } else {
+            Conjunction::new_fixed(exprs)
+        }
+    }
+
+    // The column 0 is a permutation of [0, n), and the column 1 starts with
+    // 'a' in 1/3 of the rows.
+    fn rows(n: i64) -> Vec<Vec<Datum>> {
+        (0..n)
+            .map(|i| {
+                let s = if i % 3 == 0 { "abc" } else { "xyz" };
+                vec![Datum::I64(i * 7919 % n), Datum::Bytes(s.as_bytes().to_vec())]
+            })
+            .collect()
+    }
+
+    fn eval_all(ctx: &EvalContext, c: &mut Conjunction, rows: &[Vec<Datum>]) -> Vec<bool> {
+        rows.iter().map(|row| c.eval(ctx, row).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_static_order() {
+        let ctx = EvalContext::default();
+        let c = build(&ctx, vec![like("a%"), plus_gt(0), like("%c"), lt(10)], true);
+        // the impure conjunct isn't moved, and nothing is moved across it.
+        assert_eq!(c.order, vec![0, 1, 3, 2]);
+        assert!(c.conjuncts[0].cost > c.conjuncts[1].cost);
+        assert!(c.conjuncts[1].cost > c.conjuncts[3].cost);
+        assert!(!c.conjuncts[1].pure);
+    }
+
+    #[test]
+    fn test_same_results() {
+        let ctx = EvalContext::default();
+        let rows = rows(5000);
+        let conds = || vec![like("%c"), lt(4000), like("a%"), lt(3000)];
+        let mut fixed = build(&ctx, conds(), false);
+        let mut adaptive = build(&ctx, conds(), true);
+        let expect = eval_all(&ctx, &mut fixed, &rows);
+        assert_eq!(eval_all(&ctx, &mut adaptive, &rows), expect);
+        let passed = expect.iter().filter(|&&p| p).count();
+        assert!(passed > 0 && passed < rows.len(), "{}", passed);
+    }
+
+    #[test]
+    fn test_fewer_expensive_evals() {
+        let ctx = EvalContext::default();
+        let rows = rows(10000);
+        // the LIKE rejects 2/3 of the rows, but the comparison rejects 90% of
+        // them and is much cheaper.
+        let conds = || vec![like("a%"), lt(1000)];
+        let mut fixed = build(&ctx, conds(), false);
+        let mut adaptive = build(&ctx, conds(), true);
+        assert_eq!(
+            eval_all(&ctx, &mut adaptive, &rows),
+            eval_all(&ctx, &mut fixed, &rows)
+        );
+        assert_eq!(fixed.total_evals(), vec![10000, 3334]);
+        // the LIKE is only evaluated on the rows passing the comparison.
+        assert_eq!(adaptive.total_evals(), vec![1000, 10000]);
+
+        // the order follows the selectivities even if it's against the costs.
+        let conds = || vec![lt(9900), like("b%")];
+        let mut fixed = build(&ctx, conds(), false);
+        let mut adaptive = build(&ctx, conds(), true);
+        assert_eq!(adaptive.order, vec![0, 1]);
+        assert_eq!(
+            eval_all(&ctx, &mut adaptive, &rows),
+            eval_all(&ctx, &mut fixed, &rows)
+        );
+        assert_eq!(adaptive.order, vec![1, 0]);
+        // the comparison isn't evaluated once the LIKE rejecting all the rows
+        // is moved before it.
+        assert_eq!(fixed.total_evals()[0], 10000);
+        assert_eq!(adaptive.total_evals()[0], REORDER_INTERVAL);
+    }
+
+    #[test]
+    fn test_impure_evals_unchanged() {
+        let ctx = EvalContext::default();
+        let rows = rows(5000);
+        // the impure conjunct in the middle must be evaluated on the same rows
+        // as in the request order, so the warnings it produces are the same.
+        let conds = || vec![like("%c"), lt(4500), plus_gt(100), like("a%"), lt(500)];
+        let mut fixed = build(&ctx, conds(), false);
+        let mut adaptive = build(&ctx, conds(), true);
+        assert_eq!(
+            eval_all(&ctx, &mut adaptive, &rows),
+            eval_all(&ctx, &mut fixed, &rows)
+        );
+        let (fixed_evals, evals) = (fixed.total_evals(), adaptive.total_evals());
+        assert_eq!(evals[2], fixed_evals[2]);
+        assert_eq!(adaptive.order[2], 2);
+        // the conjuncts after it are still reordered.
+        assert!(evals[3] < fixed_evals[3], "{:?} {:?}", evals, fixed_evals);
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use kvproto::coprocessor::KeyRange;
+
+/// `RangeFeedback` counts the rows scanned in every key range of a request,
+/// which are responded as the output counts for TiDB to correct the
+/// statistics. The counts are kept in the order of the request ranges, and
+/// shared through the clones of the handle.
+#[derive(Clone)]
+pub struct RangeFeedback {
+    counts: Rc<RefCell<Vec<u64>>>,
+}
+
+impl RangeFeedback {
+    pub fn new(ranges: usize) -> RangeFeedback {
+        RangeFeedback {
+            counts: Rc::new(RefCell::new(vec![0; ranges])),
+        }
+    }
+
+    /// Records a row scanned in the `range_idx`-th range of the request.
+    #[inline]
+    pub fn on_scanned(&self, range_idx: usize) {
+        self.counts.borrow_mut()[range_idx] += 1;
+    }
+
+    /// Returns the counts of the ranges in the request.
+    pub fn counts(&self) -> Vec<u64> {
+        self.counts.borrow().clone()
+    }
+}
+
+/// `ScannedRange` records the range of the keys scanned since it's taken last
+/// time, it's taken for every response of a streaming request, so TiDB can
+/// resume the scan from it after a region error once kvproto carries it. It's
+/// shared through the clones of the handle.
+#[derive(Clone, Default)]
+pub struct ScannedRange {
+    // the smallest and the largest keys scanned.
+    range: Rc<RefCell<Option<(Vec<u8>, Vec<u8>)>>>,
+}
+
+impl ScannedRange {
+    /// Records a key scanned, in either direction.
+    pub fn on_scanned(&self, key: &[u8]) {
+        let mut range = self.range.borrow_mut();
+        if let Some((ref mut start, ref mut end)) = *range {
+            if key < start.as_slice() {
+                *start = key.to_vec();
+            } else if key > end.as_slice() {
+                *end = key.to_vec();
+            }
+            return;
+        }
+        *range = Some((key.to_vec(), key.to_vec()));
+    }
+
+    /// Takes the range of the keys scanned so far, the end is exclusive, so
+    /// it's the key right after the largest one. It's `None` if no key is
+    /// scanned.
+    pub fn take(&self) -> Option<KeyRange> {
+        self.range.borrow_mut().take().map(|(start, mut end)| {
+            end.push(0);
+            let mut range = KeyRange::new();
+            range.set_start(start);
+            range.set_end(end);
+            range
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_range_feedback() {
+        let feedback = RangeFeedback::new(3);
+        let cloned = feedback.clone();
+        feedback.on_scanned(0);
+        feedback.on_scanned(2);
+        feedback.on_scanned(2);
+        assert_eq!(cloned.counts(), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_scanned_range() {
+        let scanned = ScannedRange::default();
+        let cloned = scanned.clone();
+        assert!(scanned.take().is_none());
+        for key in &[b"b", b"a", b"d", b"c"] {
+            cloned.on_scanned(*key);
+        }
+        let range = scanned.take().unwrap();
+        assert_eq!(range.get_start(), b"a");
+        assert_eq!(range.get_end(), b"d\0");
+        // the range is reset once it's taken.
+        assert!(scanned.take().is_none());
+        cloned.on_scanned(b"e");
+        let range = scanned.take().unwrap();
+        assert_eq!(range.get_start(), b"e");
+        assert_eq!(range.get_end(), b"e\0");
+    }
+}
@@ -13,17 +13,17 @@
 
 use byteorder::{BigEndian, ReadBytesExt};
 
-use kvproto::coprocessor::KeyRange;
 use tipb::executor::IndexScan;
 use tipb::schema::ColumnInfo;
 
 use coprocessor::codec::{datum, mysql, table};
 use coprocessor::endpoint::prefix_next;
-use coprocessor::metrics::*;
+use coprocessor::interrupt::Interrupt;
+use coprocessor::key_ranges::KeyRanges;
 use coprocessor::Result;
 use storage::{SnapshotStore, Statistics};
 
-use super::{Executor, Row};
+use super::{Executor, ExecutorMetrics, RangeFeedback, Row, ScannedRange};
 use super::scanner::Scanner;
 
 
@@ -31,15 +31,19 @@ pub struct IndexScanExecutor<'a> {
     desc: bool,
     col_ids: Vec<i64>,
     cursor: usize,
-    key_ranges: Vec<KeyRange>,
+    key_ranges: KeyRanges,
     scanner: Scanner<'a>,
     pk_col: Option<ColumnInfo>,
+    interrupt: Option<Interrupt>,
+    feedback: Option<RangeFeedback>,
+    scanned_range: Option<ScannedRange>,
+    metrics: ExecutorMetrics,
 }
 
 impl<'a> IndexScanExecutor<'a> {
     pub fn new(
         mut meta: IndexScan,
-        mut key_ranges: Vec<KeyRange>,
+        mut key_ranges: KeyRanges,
         store: SnapshotStore<'a>,
         statistics: &'a mut Statistics,
     ) -> IndexScanExecutor<'a> {
@@ -55,7 +59,8 @@ impl<'a> IndexScanExecutor<'a> {
         let col_ids = cols.iter().map(|c| c.get_column_id()).collect();
         let scanner = Scanner::new(store, desc, false, statistics);
 
-        COPR_EXECUTOR_COUNT.with_label_values(&["idxscan"]).inc();
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.index_scan = 1;
         IndexScanExecutor {
             desc: desc,
             col_ids: col_ids,
@@ -63,17 +68,22 @@ impl<'a> IndexScanExecutor<'a> {
             key_ranges: key_ranges,
             cursor: Default::default(),
             pk_col: pk_col,
+            interrupt: None,
+            feedback: None,
+            scanned_range: None,
+            metrics: metrics,
         }
     }
 
     pub fn new_with_cols_len(
         cols: i64,
-        key_ranges: Vec<KeyRange>,
+        key_ranges: KeyRanges,
         store: SnapshotStore<'a>,
         statistics: &'a mut Statistics,
     ) -> IndexScanExecutor<'a> {
         let col_ids: Vec<i64> = (0..cols).collect();
-        COPR_EXECUTOR_COUNT.with_label_values(&["idxscan"]).inc();
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.index_scan = 1;
         let scanner = Scanner::new(store, false, false, statistics);
         IndexScanExecutor {
             desc: false,
@@ -82,11 +92,30 @@ impl<'a> IndexScanExecutor<'a> {
             key_ranges: key_ranges,
             cursor: Default::default(),
             pk_col: None,
+            interrupt: None,
+            feedback: None,
+            scanned_range: None,
+            metrics: metrics,
         }
     }
 
+    /// Stops the scan once `interrupt` is triggered.
+    pub fn set_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Counts the rows scanned in every range into `feedback`.
+    pub fn set_feedback(&mut self, feedback: RangeFeedback) {
+        self.feedback = Some(feedback);
+    }
+
+    /// Records the keys of the rows scanned into `scanned_range`.
+    pub fn set_scanned_range(&mut self, scanned_range: ScannedRange) {
+        self.scanned_range = Some(scanned_range);
+    }
+
     pub fn get_row_from_range(&mut self) -> Result<Option<Row>> {
-        let range = &self.key_ranges[self.cursor];
+        let range = self.key_ranges.get(self.cursor);
         if range.get_start() > range.get_end() {
             return Ok(None);
         }
@@ -95,6 +124,9 @@ impl<'a> IndexScanExecutor<'a> {
             Some((key, value)) => (key, value),
             None => return Ok(None),
         };
+        if let Some(ref scanned_range) = self.scanned_range {
+            scanned_range.on_scanned(&key);
+        }
 
         let seek_key = if self.desc {
             key.clone()
@@ -127,24 +159,40 @@ impl<'a> IndexScanExecutor<'a> {
 
 impl<'a> Executor for IndexScanExecutor<'a> {
     fn next(&mut self) -> Result<Option<Row>> {
+        if let Some(ref interrupt) = self.interrupt {
+            interrupt.check()?;
+        }
         while self.cursor < self.key_ranges.len() {
             let data = self.get_row_from_range()?;
             if data.is_none() {
-                CORP_GET_OR_SCAN_COUNT.with_label_values(&["range"]).inc();
+                self.metrics.scan_counter.range += 1;
                 self.scanner.set_seek_key(None);
                 self.cursor += 1;
                 continue;
             }
+            if let Some(ref feedback) = self.feedback {
+                // the ranges are reversed for desc scans.
+                if self.desc {
+                    feedback.on_scanned(self.key_ranges.len() - 1 - self.cursor);
+                } else {
+                    feedback.on_scanned(self.cursor);
+                }
+            }
             return Ok(data);
         }
         Ok(None)
     }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        self.metrics.take()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::i64;
 
+    use kvproto::coprocessor::KeyRange;
     use kvproto::kvrpcpb::IsolationLevel;
     use protobuf::RepeatedField;
     use tipb::schema::ColumnInfo;
@@ -271,8 +319,12 @@ mod test {
         let (snapshot, start_ts) = wrapper.store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
 
-        let mut scanner =
-            IndexScanExecutor::new(wrapper.scan, wrapper.ranges, store, &mut statistics);
+        let mut scanner = IndexScanExecutor::new(
+            wrapper.scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
 
         for handle in 0..KEY_NUMBER / 2 {
             let row = scanner.next().unwrap().unwrap();
@@ -302,8 +354,12 @@ mod test {
         let (snapshot, start_ts) = wrapper.store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
 
-        let mut scanner =
-            IndexScanExecutor::new(wrapper.scan, wrapper.ranges, store, &mut statistics);
+        let mut scanner = IndexScanExecutor::new(
+            wrapper.scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
 
         for tid in 0..KEY_NUMBER {
             let handle = KEY_NUMBER - tid - 1;
@@ -327,8 +383,12 @@ mod test {
         let (snapshot, start_ts) = wrapper.store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
 
-        let mut scanner =
-            IndexScanExecutor::new(wrapper.scan, wrapper.ranges, store, &mut statistics);
+        let mut scanner = IndexScanExecutor::new(
+            wrapper.scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
 
         for handle in 0..KEY_NUMBER {
             let row = scanner.next().unwrap().unwrap();
@@ -17,23 +17,25 @@
 use tipb::executor::Limit;
 
 use coprocessor::Result;
-use coprocessor::metrics::*;
 
-use super::{Executor, Row};
+use super::{Executor, ExecutorMetrics, Row};
 
 pub struct LimitExecutor<'a> {
     limit: u64,
     cursor: u64,
     src: Box<Executor + 'a>,
+    metrics: ExecutorMetrics,
 }
 
 impl<'a> LimitExecutor<'a> {
     pub fn new(limit: Limit, src: Box<Executor + 'a>) -> LimitExecutor {
-        COPR_EXECUTOR_COUNT.with_label_values(&["limit"]).inc();
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.limit = 1;
         LimitExecutor {
             limit: limit.get_limit(),
             cursor: 0,
             src: src,
+            metrics: metrics,
         }
     }
 }
@@ -50,6 +52,11 @@ impl<'a> Executor for LimitExecutor<'a> {
             Ok(None)
         }
     }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        self.metrics.merge(&self.src.take_statistics());
+        self.metrics.take()
+    }
 }
 
 #[cfg(test)]
@@ -63,6 +70,7 @@ mod test {
     use storage::{SnapshotStore, Statistics};
 
     use super::*;
+    use super::super::{ExecCounter, ScanCounter};
     use super::super::table_scan::TableScanExecutor;
     use super::super::scanner::test::{get_range, new_col_info, TestStore};
     use super::super::topn::test::gen_table_data;
@@ -93,7 +101,7 @@ mod test {
         // prepare range
         let range1 = get_range(tid, 0, 4);
         let range2 = get_range(tid, 5, 10);
-        let key_ranges = vec![range1, range2];
+        let key_ranges = vec![range1, range2].into();
         // init TableScan
         let (snapshot, start_ts) = test_store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
@@ -115,5 +123,19 @@ mod test {
         for (row, handle) in limit_rows.iter().zip(expect_row_handles) {
             assert_eq!(row.handle, handle);
         }
+
+        // the second range isn't drained since the limit is reached.
+        let metrics = limit_ect.take_statistics();
+        assert_eq!(metrics.scan_counter, ScanCounter { range: 1, point: 0 });
+        assert_eq!(
+            metrics.executor_count,
+            ExecCounter {
+                limit: 1,
+                table_scan: 1,
+                ..Default::default()
+            }
+        );
+        // the metrics are taken, so they are counted only once.
+        assert_eq!(limit_ect.take_statistics(), ExecutorMetrics::default());
     }
 }
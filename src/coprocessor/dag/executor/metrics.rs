@@ -0,0 +1,170 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+
+use coprocessor::metrics::*;
+
+/// `ScanCounter` counts the key ranges scanned by the scan executors.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ScanCounter {
+    pub range: usize,
+    pub point: usize,
+}
+
+impl ScanCounter {
+    pub fn merge(&mut self, other: &ScanCounter) {
+        self.range += other.range;
+        self.point += other.point;
+    }
+
+    fn flush(&self) {
+        if self.range > 0 {
+            CORP_GET_OR_SCAN_COUNT
+                .with_label_values(&["range"])
+                .inc_by(self.range as f64)
+                .unwrap();
+        }
+        if self.point > 0 {
+            CORP_GET_OR_SCAN_COUNT
+                .with_label_values(&["point"])
+                .inc_by(self.point as f64)
+                .unwrap();
+        }
+    }
+}
+
+/// `ExecCounter` counts the executors built for the requests.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ExecCounter {
+    pub aggregation: usize,
+    pub index_scan: usize,
+    pub limit: usize,
+    pub selection: usize,
+    pub table_scan: usize,
+    pub topn: usize,
+}
+
+impl ExecCounter {
+    pub fn merge(&mut self, other: &ExecCounter) {
+        self.aggregation += other.aggregation;
+        self.index_scan += other.index_scan;
+        self.limit += other.limit;
+        self.selection += other.selection;
+        self.table_scan += other.table_scan;
+        self.topn += other.topn;
+    }
+
+    fn flush(&self) {
+        let counts = [
+            ("aggregation", self.aggregation),
+            ("idxscan", self.index_scan),
+            ("limit", self.limit),
+            ("selection", self.selection),
+            ("tblscan", self.table_scan),
+            ("topn", self.topn),
+        ];
+        for &(tp, count) in &counts {
+            if count > 0 {
+                COPR_EXECUTOR_COUNT
+                    .with_label_values(&[tp])
+                    .inc_by(count as f64)
+                    .unwrap();
+            }
+        }
+    }
+}
+
+/// `ExecutorMetrics` is the metrics of the executors of a request. Every
+/// executor keeps its own, and they are taken and merged once the request is
+/// handled, so the metrics are flushed once per request.
+///
+/// The ops of the storage aren't included, since the scanners share the
+/// `Statistics` of the request, which are flushed by the endpoint.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ExecutorMetrics {
+    pub scan_counter: ScanCounter,
+    pub executor_count: ExecCounter,
+}
+
+impl ExecutorMetrics {
+    pub fn merge(&mut self, other: &ExecutorMetrics) {
+        self.scan_counter.merge(&other.scan_counter);
+        self.executor_count.merge(&other.executor_count);
+    }
+
+    /// Take the metrics, they are reset.
+    pub fn take(&mut self) -> ExecutorMetrics {
+        mem::replace(self, ExecutorMetrics::default())
+    }
+
+    /// Flush the metrics to prometheus.
+    pub fn flush(&self) {
+        self.scan_counter.flush();
+        self.executor_count.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut metrics = ExecutorMetrics {
+            scan_counter: ScanCounter { range: 1, point: 2 },
+            executor_count: ExecCounter {
+                aggregation: 1,
+                index_scan: 2,
+                limit: 3,
+                selection: 4,
+                table_scan: 5,
+                topn: 6,
+            },
+        };
+        let other = ExecutorMetrics {
+            scan_counter: ScanCounter {
+                range: 10,
+                point: 20,
+            },
+            executor_count: ExecCounter {
+                aggregation: 10,
+                index_scan: 20,
+                limit: 30,
+                selection: 40,
+                table_scan: 50,
+                topn: 60,
+            },
+        };
+        metrics.merge(&other);
+        assert_eq!(metrics.scan_counter, ScanCounter { range: 11, point: 22 });
+        assert_eq!(
+            metrics.executor_count,
+            ExecCounter {
+                aggregation: 11,
+                index_scan: 22,
+                limit: 33,
+                selection: 44,
+                table_scan: 55,
+                topn: 66,
+            }
+        );
+        // merging nothing changes nothing.
+        let before = metrics.clone();
+        metrics.merge(&ExecutorMetrics::default());
+        assert_eq!(metrics, before);
+
+        assert_eq!(metrics.take(), before);
+        assert_eq!(metrics.take(), ExecutorMetrics::default());
+    }
+}
@@ -23,22 +23,31 @@ use coprocessor::codec::datum::{self, Datum};
 use coprocessor::codec::table::{RowColsDict, TableDecoder};
 use coprocessor::endpoint::get_pk;
 use coprocessor::select::xeval::EvalContext;
-use coprocessor::{Error, Result};
+use coprocessor::{util, BuildError, Result};
 
 mod scanner;
+mod row_scanner;
 mod table_scan;
 mod index_scan;
 mod selection;
 mod topn;
 mod limit;
 mod aggregation;
+mod feedback;
+mod conjunction;
+mod summary;
+mod metrics;
 
+pub use self::row_scanner::{DecodedRow, RowScanner, RowValue};
 pub use self::table_scan::TableScanExecutor;
 pub use self::index_scan::IndexScanExecutor;
 pub use self::selection::SelectionExecutor;
 pub use self::topn::TopNExecutor;
 pub use self::limit::LimitExecutor;
 pub use self::aggregation::AggregationExecutor;
+pub use self::feedback::{RangeFeedback, ScannedRange};
+pub use self::summary::{ExecSummaries, ExecSummary, SummaryExecutor};
+pub use self::metrics::{ExecCounter, ExecutorMetrics, ScanCounter};
 
 pub struct ExprColumnRefVisitor {
     cols_offset: HashSet<usize>,
@@ -55,13 +64,18 @@ impl ExprColumnRefVisitor {
 
     pub fn visit(&mut self, expr: &Expr) -> Result<()> {
         if expr.get_tp() == ExprType::ColumnRef {
-            let offset = box_try!(expr.get_val().decode_i64()) as usize;
+            let offset = expr.get_val()
+                .decode_i64()
+                .map_err(|e| BuildError::invalid_request(format!("{}", e)))?;
+            let offset = util::i64_to_usize(offset, "column offset")?;
             if offset >= self.cols_len {
-                return Err(Error::Other(box_err!(
-                    "offset {} overflow, should be less than {}",
-                    offset,
-                    self.cols_len
-                )));
+                return Err(
+                    BuildError::invalid_request(format!(
+                        "offset {} overflow, should be less than {}",
+                        offset,
+                        self.cols_len
+                    )).into(),
+                );
             }
             self.cols_offset.insert(offset);
         } else {
@@ -125,6 +139,10 @@ impl Row {
 
 pub trait Executor {
     fn next(&mut self) -> Result<Option<Row>>;
+
+    /// Take the metrics of the executor and its children, they are reset, so
+    /// the metrics taken again are zeroed.
+    fn take_statistics(&mut self) -> ExecutorMetrics;
 }
 
 pub fn inflate_with_col_for_dag(
@@ -0,0 +1,433 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+
+use tipb::schema::ColumnInfo;
+
+use util::collections::{HashMap, HashSet};
+use storage::{SnapshotStore, Statistics, Value};
+use coprocessor::codec::table::{self, RowColsDict};
+use coprocessor::endpoint::prefix_next;
+use coprocessor::key_ranges::KeyRanges;
+use coprocessor::Result;
+
+use super::{RangeFeedback, Row, ScanCounter, ScannedRange};
+use super::scanner::Scanner;
+
+/// The value of a row returned by `RowScanner`.
+pub enum RowValue {
+    /// The values of the requested columns.
+    Cols(RowColsDict),
+    /// The raw value of the row.
+    Raw(Value),
+}
+
+pub struct DecodedRow {
+    pub handle: i64,
+    pub value: RowValue,
+}
+
+impl DecodedRow {
+    pub fn into_row(self) -> Row {
+        let data = match self.value {
+            RowValue::Cols(data) => data,
+            RowValue::Raw(value) => RowColsDict::new(HashMap::default(), value),
+        };
+        Row::new(self.handle, data)
+    }
+}
+
+/// `RowScanner` scans the table rows in the given ranges of a `SnapshotStore`,
+/// the MVCC versions are resolved by the store, the keys are decoded into the
+/// handles and the values are cut into the requested columns.
+///
+/// Point ranges are read with a point get instead of a scan.
+pub struct RowScanner<'a> {
+    desc: bool,
+    // None means the raw values are returned.
+    col_ids: Option<HashSet<i64>>,
+    cursor: usize,
+    key_ranges: KeyRanges,
+    scanner: Scanner<'a>,
+    feedback: Option<RangeFeedback>,
+    scanned_range: Option<ScannedRange>,
+    scan_counter: ScanCounter,
+}
+
+impl<'a> RowScanner<'a> {
+    /// If `columns` is `None`, the raw values of the rows are returned, and if
+    /// `key_only` is true, the values are always empty.
+    pub fn new(
+        store: SnapshotStore<'a>,
+        mut key_ranges: KeyRanges,
+        columns: Option<&[ColumnInfo]>,
+        desc: bool,
+        key_only: bool,
+        statistics: &'a mut Statistics,
+    ) -> RowScanner<'a> {
+        let col_ids = columns.map(|cols| {
+            cols.iter()
+                .filter(|c| !c.get_pk_handle())
+                .map(|c| c.get_column_id())
+                .collect()
+        });
+        if desc {
+            key_ranges.reverse();
+        }
+        RowScanner {
+            desc: desc,
+            col_ids: col_ids,
+            cursor: 0,
+            key_ranges: key_ranges,
+            scanner: Scanner::new(store, desc, key_only, statistics),
+            feedback: None,
+            scanned_range: None,
+            scan_counter: ScanCounter::default(),
+        }
+    }
+
+    /// Counts the rows scanned in every range into `feedback`.
+    pub fn set_feedback(&mut self, feedback: RangeFeedback) {
+        self.feedback = Some(feedback);
+    }
+
+    /// Records the keys of the rows scanned into `scanned_range`.
+    pub fn set_scanned_range(&mut self, scanned_range: ScannedRange) {
+        self.scanned_range = Some(scanned_range);
+    }
+
+    /// Take the counts of the ranges scanned, they are reset.
+    pub fn take_scan_counter(&mut self) -> ScanCounter {
+        mem::replace(&mut self.scan_counter, ScanCounter::default())
+    }
+
+    // The ranges are reversed for desc scans, `feedback` counts the rows with
+    // the index of the range in the request.
+    #[inline]
+    fn record_scanned(&self, cursor: usize) {
+        if let Some(ref feedback) = self.feedback {
+            if self.desc {
+                feedback.on_scanned(self.key_ranges.len() - 1 - cursor);
+            } else {
+                feedback.on_scanned(cursor);
+            }
+        }
+    }
+
+    pub fn next_row(&mut self) -> Result<Option<DecodedRow>> {
+        while self.cursor < self.key_ranges.len() {
+            if self.key_ranges.get(self.cursor).is_point() {
+                self.scan_counter.point += 1;
+                let data = self.get_row_from_point()?;
+                self.scanner.set_seek_key(None);
+                self.cursor += 1;
+                if data.is_some() {
+                    self.record_scanned(self.cursor - 1);
+                    return Ok(data);
+                }
+                continue;
+            }
+
+            let data = self.get_row_from_range()?;
+            if data.is_none() {
+                self.scan_counter.range += 1;
+                self.scanner.set_seek_key(None);
+                self.cursor += 1;
+                continue;
+            }
+            self.record_scanned(self.cursor);
+            return Ok(data);
+        }
+        Ok(None)
+    }
+
+    fn get_row_from_range(&mut self) -> Result<Option<DecodedRow>> {
+        let range = self.key_ranges.get(self.cursor);
+        let kv = self.scanner.next_row(range)?;
+        let (key, value) = match kv {
+            Some((key, value)) => (key, value),
+            None => return Ok(None),
+        };
+        let row = self.decode_row(&key, value)?;
+        if let Some(ref scanned_range) = self.scanned_range {
+            scanned_range.on_scanned(&key);
+        }
+        let seek_key = if self.desc {
+            box_try!(table::truncate_as_row_key(&key)).to_vec()
+        } else {
+            prefix_next(&key)
+        };
+        self.scanner.set_seek_key(Some(seek_key));
+        Ok(Some(row))
+    }
+
+    fn get_row_from_point(&mut self) -> Result<Option<DecodedRow>> {
+        let key = self.key_ranges.get(self.cursor).get_start();
+        if let Some(ref scanned_range) = self.scanned_range {
+            scanned_range.on_scanned(key);
+        }
+        match self.scanner.get_row(key)? {
+            Some(value) => Ok(Some(self.decode_row(key, value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_row(&self, key: &[u8], value: Value) -> Result<DecodedRow> {
+        let handle = box_try!(table::decode_handle(key));
+        let value = match self.col_ids {
+            Some(ref col_ids) => RowValue::Cols(box_try!(table::cut_row(value, col_ids))),
+            None => RowValue::Raw(value),
+        };
+        Ok(DecodedRow {
+            handle: handle,
+            value: value,
+        })
+    }
+}
+
+impl<'a> Iterator for RowScanner<'a> {
+    type Item = Result<DecodedRow>;
+
+    fn next(&mut self) -> Option<Result<DecodedRow>> {
+        match self.next_row() {
+            Ok(Some(row)) => Some(Ok(row)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::i64;
+
+    use kvproto::coprocessor::KeyRange;
+    use kvproto::kvrpcpb::IsolationLevel;
+
+    use coprocessor::codec::datum::{self, Datum};
+    use util::codec::number::NumberEncoder;
+
+    use super::*;
+    use super::super::scanner::test::{get_point_range, get_range, prepare_table_data, Data,
+                                      TestStore};
+
+    const TABLE_ID: i64 = 1;
+    const KEY_NUMBER: usize = 10;
+
+    fn scan_all(
+        store: &mut TestStore,
+        ranges: &[KeyRange],
+        cols: Option<&[ColumnInfo]>,
+        desc: bool,
+        key_only: bool,
+    ) -> Vec<DecodedRow> {
+        let mut statistics = Statistics::default();
+        let (snapshot, start_ts) = store.get_snapshot();
+        let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+        let scanner = RowScanner::new(
+            store,
+            KeyRanges::from_slice(ranges),
+            cols,
+            desc,
+            key_only,
+            &mut statistics,
+        );
+        scanner.map(|r| r.unwrap()).collect()
+    }
+
+    fn check_cols(data: &Data, cols: &[ColumnInfo], row: &DecodedRow) {
+        let expect_row = &data.expect_rows[row.handle as usize];
+        match row.value {
+            RowValue::Cols(ref values) => {
+                assert_eq!(values.len(), cols.len());
+                for col in cols {
+                    let cid = col.get_column_id();
+                    assert_eq!(expect_row[&cid], values.get(cid).unwrap());
+                }
+            }
+            RowValue::Raw(_) => panic!("expect columns"),
+        }
+    }
+
+    fn handles(rows: &[DecodedRow]) -> Vec<i64> {
+        rows.iter().map(|r| r.handle).collect()
+    }
+
+    #[test]
+    fn test_range_transitions() {
+        let data = prepare_table_data(KEY_NUMBER, TABLE_ID);
+        let mut store = TestStore::new(&data.kv_data);
+        let cols = data.get_prev_2_cols();
+        let ranges = vec![
+            // empty range
+            get_range(TABLE_ID, i64::MIN, 0),
+            get_range(TABLE_ID, 0, 3),
+            // missing point
+            get_point_range(TABLE_ID, -1),
+            get_point_range(TABLE_ID, 5),
+            get_range(TABLE_ID, 7, i64::MAX),
+        ];
+        let rows = scan_all(&mut store, &ranges, Some(&cols), false, false);
+        assert_eq!(handles(&rows), vec![0, 1, 2, 5, 7, 8, 9]);
+        for row in &rows {
+            check_cols(&data, &cols, row);
+        }
+    }
+
+    #[test]
+    fn test_desc() {
+        let data = prepare_table_data(KEY_NUMBER, TABLE_ID);
+        let mut store = TestStore::new(&data.kv_data);
+        let cols = data.get_prev_2_cols();
+        let ranges = vec![
+            get_range(TABLE_ID, 0, 3),
+            get_point_range(TABLE_ID, 5),
+            get_range(TABLE_ID, 7, i64::MAX),
+        ];
+        let rows = scan_all(&mut store, &ranges, Some(&cols), true, false);
+        assert_eq!(handles(&rows), vec![9, 8, 7, 5, 2, 1, 0]);
+        for row in &rows {
+            check_cols(&data, &cols, row);
+        }
+    }
+
+    #[test]
+    fn test_raw_and_key_only() {
+        let data = prepare_table_data(KEY_NUMBER, TABLE_ID);
+        let mut store = TestStore::new(&data.kv_data);
+        let ranges = vec![
+            get_range(TABLE_ID, 0, 2),
+            get_point_range(TABLE_ID, 5),
+        ];
+
+        let rows = scan_all(&mut store, &ranges, None, false, false);
+        assert_eq!(handles(&rows), vec![0, 1, 5]);
+        for row in &rows {
+            match row.value {
+                RowValue::Raw(ref value) => {
+                    assert_eq!(*value, data.kv_data[row.handle as usize].1);
+                }
+                RowValue::Cols(_) => panic!("expect raw value"),
+            }
+        }
+
+        let rows = scan_all(&mut store, &ranges[..1], None, false, true);
+        assert_eq!(handles(&rows), vec![0, 1]);
+        for row in rows {
+            match row.value {
+                RowValue::Raw(ref value) => assert!(value.is_empty()),
+                RowValue::Cols(_) => panic!("expect raw value"),
+            }
+            assert!(row.into_row().data.value.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_mvcc_versions() {
+        let data = prepare_table_data(KEY_NUMBER, TABLE_ID);
+        let mut store = TestStore::new(&data.kv_data);
+        let (_, read_ts) = store.get_snapshot();
+
+        // a newer version of row 1 which is invisible to the read.
+        let mut buf = vec![];
+        buf.encode_i64(1).unwrap();
+        let key = table::encode_row_key(TABLE_ID, &buf);
+        let value = table::encode_row(vec![Datum::I64(100)], &[1]).unwrap();
+        store.put_version(&[(key, value)], read_ts + 10, read_ts + 20);
+
+        let cols = data.get_prev_2_cols();
+        let ranges = vec![get_range(TABLE_ID, 0, 3), get_point_range(TABLE_ID, 1)];
+        let rows = scan_all(&mut store, &ranges, Some(&cols), false, false);
+        assert_eq!(handles(&rows), vec![0, 1, 2, 1]);
+        for row in &rows {
+            check_cols(&data, &cols, row);
+        }
+
+        // the newer version is read once it's visible.
+        let mut statistics = Statistics::default();
+        let (snapshot, _) = store.get_snapshot();
+        let snap = SnapshotStore::new(snapshot, read_ts + 30, IsolationLevel::SI, true);
+        let mut scanner = RowScanner::new(
+            snap,
+            KeyRanges::from_slice(&ranges[1..]),
+            Some(&cols),
+            false,
+            false,
+            &mut statistics,
+        );
+        let row = scanner.next_row().unwrap().unwrap();
+        assert!(scanner.next_row().unwrap().is_none());
+        match row.value {
+            RowValue::Cols(values) => {
+                let expect = datum::encode_value(&[Datum::I64(100)]).unwrap();
+                assert_eq!(values.get(1).unwrap(), expect.as_slice());
+                assert!(values.get(2).is_none());
+            }
+            RowValue::Raw(_) => panic!("expect columns"),
+        }
+    }
+
+    #[test]
+    fn test_deleted_versions() {
+        let data = prepare_table_data(KEY_NUMBER, TABLE_ID);
+        let mut store = TestStore::new(&data.kv_data);
+        let (_, read_ts) = store.get_snapshot();
+        let keys: Vec<_> = data.kv_data[..8].iter().map(|&(ref k, _)| k.clone()).collect();
+        store.delete_version(&keys, read_ts + 10, read_ts + 20);
+
+        let mut statistics = Statistics::default();
+        {
+            let (snapshot, _) = store.get_snapshot();
+            let snap = SnapshotStore::new(snapshot, read_ts + 30, IsolationLevel::SI, true);
+            let ranges = vec![get_range(TABLE_ID, 0, i64::MAX), get_point_range(TABLE_ID, 3)];
+            let scanner = RowScanner::new(
+                snap,
+                KeyRanges::from_slice(&ranges),
+                None,
+                false,
+                false,
+                &mut statistics,
+            );
+            let rows: Vec<_> = scanner.map(|r| r.unwrap()).collect();
+            assert_eq!(handles(&rows), vec![8, 9]);
+        }
+        // the older versions of the deleted rows aren't examined.
+        assert_eq!(statistics.write.versions, 11);
+        assert_eq!(statistics.write.skipped_versions, 9);
+        assert_eq!(statistics.write.tombstones, 9);
+        assert_eq!(statistics.write.visible_ratio(), Some(2.0 / 11.0));
+        assert_eq!(statistics.data.visible_ratio(), None);
+
+        // the tombstones newer than the read ts are examined by the scanner,
+        // and the rows deleted by them are still visible.
+        let mut statistics = Statistics::default();
+        {
+            let (snapshot, _) = store.get_snapshot();
+            let snap = SnapshotStore::new(snapshot, read_ts + 15, IsolationLevel::SI, true);
+            let ranges = vec![get_range(TABLE_ID, 0, i64::MAX)];
+            let scanner = RowScanner::new(
+                snap,
+                KeyRanges::from_slice(&ranges),
+                None,
+                false,
+                false,
+                &mut statistics,
+            );
+            assert_eq!(scanner.count(), KEY_NUMBER);
+        }
+        assert_eq!(statistics.write.versions, 18);
+        assert_eq!(statistics.write.skipped_versions, 8);
+        assert_eq!(statistics.write.tombstones, 0);
+    }
+}
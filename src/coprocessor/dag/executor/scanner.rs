@@ -11,8 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use kvproto::coprocessor::KeyRange;
-
+use coprocessor::key_ranges::KeyRangeRef;
 use storage::{Key, ScanMode, SnapshotStore, Statistics, StoreScanner, Value};
 use storage::txn::Result;
 use util::escape;
@@ -49,7 +48,7 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    pub fn next_row(&mut self, range: &KeyRange) -> Result<Option<(Vec<u8>, Value)>> {
+    pub fn next_row(&mut self, range: KeyRangeRef) -> Result<Option<(Vec<u8>, Value)>> {
         if self.seek_key.is_none() {
             self.init_with_range(range)?;
         }
@@ -93,7 +92,7 @@ impl<'a> Scanner<'a> {
         self.seek_key = seek_key;
     }
 
-    pub fn init_with_range(&mut self, range: &KeyRange) -> Result<()> {
+    pub fn init_with_range(&mut self, range: KeyRangeRef) -> Result<()> {
         let upper_bound = if self.scan_mode == ScanMode::Backward {
             self.seek_key = Some(range.get_end().to_vec());
             None
@@ -121,6 +120,7 @@ impl<'a> Scanner<'a> {
 pub mod test {
     use std::i64;
 
+    use kvproto::coprocessor::KeyRange;
     use kvproto::kvrpcpb::{Context, IsolationLevel};
     use tipb::schema::ColumnInfo;
 
@@ -131,7 +131,8 @@ pub mod test {
     use util::collections::HashMap;
     use util::codec::number::NumberEncoder;
     use storage::mvcc::MvccTxn;
-    use storage::{make_key, Mutation, Options, Snapshot, SnapshotStore, Statistics, ALL_CFS};
+    use storage::{make_key, Key, Mutation, Options, Snapshot, SnapshotStore, Statistics,
+                  ALL_CFS};
     use storage::engine::{self, Engine, Modify, TEMP_DIR};
 
     use super::*;
@@ -228,32 +229,46 @@ pub mod test {
                 ctx: ctx,
                 engine: engine,
             };
-            store.init_data(kv_data);
+            store.put_version(kv_data, START_TS, COMMIT_TS);
             store
         }
 
-        fn init_data(&mut self, kv_data: &[(Vec<u8>, Vec<u8>)]) {
+        /// Writes a new version of the rows committed at `commit_ts`.
+        pub fn put_version(
+            &mut self,
+            kv_data: &[(Vec<u8>, Vec<u8>)],
+            start_ts: u64,
+            commit_ts: u64,
+        ) {
+            let mutations = kv_data
+                .iter()
+                .map(|&(ref key, ref value)| Mutation::Put((make_key(key), value.to_vec())))
+                .collect();
+            self.commit_mutations(mutations, start_ts, commit_ts);
+        }
+
+        /// Deletes the rows with a tombstone committed at `commit_ts`.
+        pub fn delete_version(&mut self, keys: &[Vec<u8>], start_ts: u64, commit_ts: u64) {
+            let mutations = keys.iter().map(|key| Mutation::Delete(make_key(key))).collect();
+            self.commit_mutations(mutations, start_ts, commit_ts);
+        }
+
+        fn commit_mutations(&mut self, mutations: Vec<Mutation>, start_ts: u64, commit_ts: u64) {
             let mut statistics = Statistics::default();
+            let keys: Vec<Key> = mutations.iter().map(|m| m.key().clone()).collect();
             // do prewrite.
             let txn_motifies = {
                 let mut txn = MvccTxn::new(
                     self.snapshot.as_ref(),
                     &mut statistics,
-                    START_TS,
+                    start_ts,
                     None,
                     IsolationLevel::SI,
                     true,
                 );
-                let mut pk = vec![];
-                for &(ref key, ref value) in kv_data {
-                    if pk.is_empty() {
-                        pk = key.clone();
-                    }
-                    txn.prewrite(
-                        Mutation::Put((make_key(key), value.to_vec())),
-                        &pk,
-                        &Options::default(),
-                    ).unwrap();
+                let pk = keys[0].raw().unwrap();
+                for m in mutations {
+                    txn.prewrite(m, &pk, &Options::default()).unwrap();
                 }
                 txn.modifies()
             };
@@ -263,13 +278,13 @@ pub mod test {
                 let mut txn = MvccTxn::new(
                     self.snapshot.as_ref(),
                     &mut statistics,
-                    START_TS,
+                    start_ts,
                     None,
                     IsolationLevel::SI,
                     true,
                 );
-                for &(ref key, _) in kv_data {
-                    txn.commit(&make_key(key), COMMIT_TS).unwrap();
+                for key in &keys {
+                    txn.commit(key, commit_ts).unwrap();
                 }
                 txn.modifies()
             };
@@ -344,13 +359,13 @@ pub mod test {
         let mut scanner = Scanner::new(store, false, false, &mut statistics);
         let range = get_range(table_id, i64::MIN, i64::MAX);
         for &(ref k, ref v) in &test_data {
-            let (key, value) = scanner.next_row(&range).unwrap().unwrap();
+            let (key, value) = scanner.next_row((&range).into()).unwrap().unwrap();
             let seek_key = prefix_next(&key);
             scanner.set_seek_key(Some(seek_key));
             assert_eq!(*k, key);
             assert_eq!(*v, value);
         }
-        assert!(scanner.next_row(&range).unwrap().is_none());
+        assert!(scanner.next_row((&range).into()).unwrap().is_none());
     }
 
     #[test]
@@ -366,13 +381,13 @@ pub mod test {
         let range = get_range(table_id, i64::MIN, i64::MAX);
         data.kv_data.reverse();
         for &(ref k, ref v) in &data.kv_data {
-            let (key, value) = scanner.next_row(&range).unwrap().unwrap();
+            let (key, value) = scanner.next_row((&range).into()).unwrap().unwrap();
             let seek_key = table::truncate_as_row_key(&key).unwrap().to_vec();
             scanner.set_seek_key(Some(seek_key));
             assert_eq!(*k, key);
             assert_eq!(*v, value);
         }
-        assert!(scanner.next_row(&range).unwrap().is_none());
+        assert!(scanner.next_row((&range).into()).unwrap().is_none());
     }
 
     #[test]
@@ -391,7 +406,7 @@ pub mod test {
         let mut scanner = Scanner::new(store, false, true, &mut statistics);
 
         let range = get_range(table_id, i64::MIN, i64::MAX);
-        let (_, value) = scanner.next_row(&range).unwrap().unwrap();
+        let (_, value) = scanner.next_row((&range).into()).unwrap().unwrap();
         assert!(value.is_empty());
     }
 
@@ -413,12 +428,12 @@ pub mod test {
 
         // 1. desc scan
         scanner.scan_mode = ScanMode::Backward;
-        scanner.init_with_range(&range).unwrap();
+        scanner.init_with_range((&range).into()).unwrap();
         assert_eq!(scanner.seek_key.take().unwrap(), range.get_end());
 
         // 1.asc scan
         scanner.scan_mode = ScanMode::Forward;
-        scanner.init_with_range(&range).unwrap();
+        scanner.init_with_range((&range).into()).unwrap();
         assert_eq!(scanner.seek_key.take().unwrap(), range.get_start());
     }
 }
@@ -14,21 +14,27 @@
 use std::rc::Rc;
 
 use tipb::executor::Selection;
+use tipb::expression::{Expr, ExprType, ScalarFuncSig};
 use tipb::schema::ColumnInfo;
 
-use coprocessor::metrics::*;
+use coprocessor::codec::mysql::{charset, types};
 use coprocessor::select::xeval::EvalContext;
 use coprocessor::dag::expr::Expression;
-use coprocessor::Result;
+use coprocessor::{BuildError, Result};
+use util::codec::number::NumberDecoder;
 
-use super::{inflate_with_col_for_dag, Executor, ExprColumnRefVisitor, Row};
+use super::{inflate_with_col_for_dag, Executor, ExecutorMetrics, ExprColumnRefVisitor, Row};
+use super::conjunction::Conjunction;
 
 pub struct SelectionExecutor<'a> {
-    conditions: Vec<Expression>,
+    conditions: Conjunction,
     cols: Rc<Vec<ColumnInfo>>,
     related_cols_offset: Vec<usize>, // offset of related columns
     ctx: Rc<EvalContext>,
     src: Box<Executor + 'a>,
+    // the conditions can never be satisfied, so the source is not read.
+    always_false: bool,
+    metrics: ExecutorMetrics,
 }
 
 impl<'a> SelectionExecutor<'a> {
@@ -41,21 +47,176 @@ impl<'a> SelectionExecutor<'a> {
         let conditions = meta.take_conditions().into_vec();
         let mut visitor = ExprColumnRefVisitor::new(columns_info.len());
         visitor.batch_visit(&conditions)?;
-        COPR_EXECUTOR_COUNT.with_label_values(&["selection"]).inc();
+        let (conditions, always_false) =
+            propagate_constants(ctx.as_ref(), columns_info.len(), conditions);
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.selection = 1;
+        let conditions =
+            Expression::batch_build(ctx.as_ref(), conditions).map_err(BuildError::from)?;
         Ok(SelectionExecutor {
-            conditions: box_try!(Expression::batch_build(ctx.as_ref(), conditions)),
+            conditions: Conjunction::new(conditions),
             cols: columns_info,
             related_cols_offset: visitor.column_offsets(),
             ctx: ctx,
             src: src,
+            always_false: always_false,
+            metrics: metrics,
         })
     }
 }
 
+// Returns the offset of the column and the constant if `cond` is `col = constant`
+// and the comparison is exact, which means a row satisfies it only if the column
+// holds exactly the datum the constant is built to. Only the integer comparisons
+// with the same signedness and the binary string comparisons are exact, the
+// others may coerce the operands or compare them with a collation.
+fn get_pinned_column(cond: &Expr) -> Option<(i64, &Expr)> {
+    if cond.get_tp() != ExprType::ScalarFunc || cond.get_children().len() != 2 {
+        return None;
+    }
+    let children = cond.get_children();
+    let (col, constant) = if children[0].get_tp() == ExprType::ColumnRef {
+        (&children[0], &children[1])
+    } else {
+        (&children[1], &children[0])
+    };
+    if col.get_tp() != ExprType::ColumnRef {
+        return None;
+    }
+    let (col_tp, const_tp) = (col.get_field_type(), constant.get_field_type());
+    let exact = match cond.get_sig() {
+        ScalarFuncSig::EQInt => {
+            let unsigned = types::has_unsigned_flag(col_tp.get_flag());
+            let const_unsigned = types::has_unsigned_flag(const_tp.get_flag());
+            let expected_tp = if unsigned {
+                ExprType::Uint64
+            } else {
+                ExprType::Int64
+            };
+            match col_tp.get_tp() as u8 {
+                types::TINY | types::SHORT | types::INT24 | types::LONG | types::LONG_LONG => {
+                    constant.get_tp() == expected_tp && const_unsigned == unsigned
+                }
+                _ => false,
+            }
+        }
+        ScalarFuncSig::EQString => match col_tp.get_tp() as u8 {
+            types::VARCHAR | types::VAR_STRING | types::STRING | types::TINY_BLOB |
+            types::MEDIUM_BLOB | types::LONG_BLOB | types::BLOB => {
+                (constant.get_tp() == ExprType::String || constant.get_tp() == ExprType::Bytes) &&
+                    col_tp.get_charset() == charset::CHARSET_BIN &&
+                    const_tp.get_charset() == charset::CHARSET_BIN
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    if !exact {
+        return None;
+    }
+    match col.get_val().decode_i64() {
+        Ok(offset) => Some((offset, constant)),
+        Err(_) => None,
+    }
+}
+
+// Returns the offset of the column if `cond` references exactly one column.
+fn get_only_column(cond: &Expr, cols_len: usize) -> Option<i64> {
+    let mut visitor = ExprColumnRefVisitor::new(cols_len);
+    if visitor.visit(cond).is_err() {
+        return None;
+    }
+    let offsets = visitor.column_offsets();
+    if offsets.len() == 1 {
+        Some(offsets[0] as i64)
+    } else {
+        None
+    }
+}
+
+fn substitute_column(expr: &mut Expr, offset: i64, constant: &Expr) {
+    if expr.get_tp() == ExprType::ColumnRef {
+        if expr.get_val().decode_i64().ok() == Some(offset) {
+            let tp = expr.take_field_type();
+            *expr = constant.clone();
+            expr.set_field_type(tp);
+        }
+        return;
+    }
+    for child in expr.mut_children().iter_mut() {
+        substitute_column(child, offset, constant);
+    }
+}
+
+// Evaluates `cond` without any row, `None` is returned if it can't be evaluated.
+fn fold_condition(ctx: &EvalContext, cond: Expr) -> Option<bool> {
+    let expr = match Expression::build(ctx, cond) {
+        Ok(expr) => expr,
+        Err(_) => return None,
+    };
+    match expr.eval(ctx, &[]).map(|val| val.into_bool(ctx)) {
+        Ok(Ok(res)) => Some(res.unwrap_or(false)),
+        _ => None,
+    }
+}
+
+/// `propagate_constants` substitutes the constant of every exact `col = constant`
+/// condition into the other conditions referencing only that column, which is
+/// valid because only the rows with the column equal to the constant can pass.
+/// The conditions folded to true are dropped, and if any of them is folded to
+/// false or NULL, no row can pass and `true` is returned with the conditions.
+/// The conditions which fail to be folded are kept as they are.
+fn propagate_constants(
+    ctx: &EvalContext,
+    cols_len: usize,
+    conditions: Vec<Expr>,
+) -> (Vec<Expr>, bool) {
+    let mut pinned: Vec<(i64, usize)> = vec![];
+    for (i, cond) in conditions.iter().enumerate() {
+        if let Some((offset, _)) = get_pinned_column(cond) {
+            if pinned.iter().all(|&(o, _)| o != offset) {
+                pinned.push((offset, i));
+            }
+        }
+    }
+    if pinned.is_empty() {
+        return (conditions, false);
+    }
+
+    let mut folded = vec![None; conditions.len()];
+    for (i, cond) in conditions.iter().enumerate() {
+        let offset = match get_only_column(cond, cols_len) {
+            Some(offset) => offset,
+            None => continue,
+        };
+        let pin = match pinned.iter().find(|&&(o, idx)| o == offset && idx != i) {
+            Some(&(_, idx)) => idx,
+            None => continue,
+        };
+        let (_, constant) = get_pinned_column(&conditions[pin]).unwrap();
+        let mut cond = cond.clone();
+        substitute_column(&mut cond, offset, constant);
+        folded[i] = fold_condition(ctx, cond);
+        if folded[i] == Some(false) {
+            return (conditions, true);
+        }
+    }
+    let conditions = conditions
+        .into_iter()
+        .zip(folded)
+        .filter(|&(_, res)| res != Some(true))
+        .map(|(cond, _)| cond)
+        .collect();
+    (conditions, false)
+}
+
 #[allow(never_loop)]
 impl<'a> Executor for SelectionExecutor<'a> {
     fn next(&mut self) -> Result<Option<Row>> {
-        'next: while let Some(row) = self.src.next()? {
+        if self.always_false {
+            return Ok(None);
+        }
+        while let Some(row) = self.src.next()? {
             let cols = inflate_with_col_for_dag(
                 &self.ctx,
                 &row.data,
@@ -63,16 +224,18 @@ impl<'a> Executor for SelectionExecutor<'a> {
                 &self.related_cols_offset,
                 row.handle,
             )?;
-            for filter in &self.conditions {
-                let val = box_try!(filter.eval(&self.ctx, &cols));
-                if !box_try!(val.into_bool(&self.ctx)).unwrap_or(false) {
-                    continue 'next;
-                }
+            if !self.conditions.eval(&self.ctx, &cols)? {
+                continue;
             }
             return Ok(Some(row));
         }
         Ok(None)
     }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        self.metrics.merge(&self.src.take_statistics());
+        self.metrics.take()
+    }
 }
 
 #[cfg(test)]
@@ -82,14 +245,16 @@ mod tests {
     use kvproto::kvrpcpb::IsolationLevel;
     use protobuf::RepeatedField;
     use tipb::executor::TableScan;
-    use tipb::expression::{Expr, ExprType, ScalarFuncSig};
+    use tipb::expression::FieldType;
 
-    use coprocessor::codec::mysql::types;
     use coprocessor::codec::datum::Datum;
     use storage::{SnapshotStore, Statistics};
     use util::codec::number::NumberEncoder;
 
+    use coprocessor::codec::mysql::types::UNSIGNED_FLAG;
+
     use super::*;
+    use super::super::RangeFeedback;
     use super::super::topn::test::gen_table_data;
     use super::super::scanner::test::{get_range, new_col_info, TestStore};
     use super::super::table_scan::TableScanExecutor;
@@ -183,7 +348,7 @@ mod tests {
         table_scan.set_table_id(tid);
         table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
         // prepare range
-        let key_ranges = vec![get_range(tid, 0, i64::MAX)];
+        let key_ranges = vec![get_range(tid, 0, i64::MAX)].into();
 
         let (snapshot, start_ts) = test_store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
@@ -241,7 +406,7 @@ mod tests {
         table_scan.set_table_id(tid);
         table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
         // prepare range
-        let key_ranges = vec![get_range(tid, 0, i64::MAX)];
+        let key_ranges = vec![get_range(tid, 0, i64::MAX)].into();
 
         let (snapshot, start_ts) = test_store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
@@ -277,4 +442,324 @@ mod tests {
         let result_row = selection_rows.iter().map(|r| r.handle).collect::<Vec<_>>();
         assert_eq!(result_row, expect_row_handles);
     }
+
+    #[test]
+    fn test_selection_feedback() {
+        let tid = 1;
+        let cis = vec![
+            new_col_info(1, types::LONG_LONG),
+            new_col_info(2, types::LONG_LONG),
+        ];
+        let raw_data: Vec<_> = [7, 7, 8, 3, 5, 9, 6]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| vec![Datum::I64(i as i64 + 1), Datum::I64(v)])
+            .collect();
+        let table_data = gen_table_data(tid, &cis, &raw_data);
+        let mut test_store = TestStore::new(&table_data);
+        let ranges = vec![
+            get_range(tid, 2, 5),
+            // no row matches.
+            get_range(tid, 5, 6),
+            // no row is in the range.
+            get_range(tid, 100, 200),
+        ];
+
+        // b > 5
+        let gt = vec![int_cond(ScalarFuncSig::GTInt, 1, int_ft(), 5)];
+        // b = 2 and b > 5, which is folded to false, so nothing is scanned.
+        let never = vec![
+            int_cond(ScalarFuncSig::EQInt, 1, int_ft(), 2),
+            int_cond(ScalarFuncSig::GTInt, 1, int_ft(), 5),
+        ];
+        let cases = vec![
+            (gt.clone(), false, vec![3, 1, 0]),
+            (gt, true, vec![3, 1, 0]),
+            (never, false, vec![0, 0, 0]),
+        ];
+        for (i, (conditions, desc, exp)) in cases.into_iter().enumerate() {
+            let mut table_scan = TableScan::new();
+            table_scan.set_table_id(tid);
+            table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
+            table_scan.set_desc(desc);
+            let (snapshot, start_ts) = test_store.get_snapshot();
+            let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+            let mut statistics = Statistics::default();
+            let feedback = RangeFeedback::new(ranges.len());
+            let mut scan = TableScanExecutor::new(
+                &table_scan,
+                ranges.clone().into(),
+                store,
+                &mut statistics,
+            );
+            scan.set_feedback(feedback.clone());
+
+            let mut selection = Selection::new();
+            selection.set_conditions(RepeatedField::from_vec(conditions));
+            let mut selection_executor = SelectionExecutor::new(
+                selection,
+                Rc::new(EvalContext::default()),
+                Rc::new(cis.clone()),
+                Box::new(scan),
+            ).unwrap();
+            while selection_executor.next().unwrap().is_some() {}
+            assert_eq!(feedback.counts(), exp, "{}", i);
+        }
+    }
+
+    fn new_field_type(tp: u8, flag: u64, charset: &str) -> FieldType {
+        let mut ft = FieldType::new();
+        ft.set_tp(tp as i32);
+        ft.set_flag(flag as u32);
+        ft.set_charset(charset.to_owned());
+        ft
+    }
+
+    fn new_col_expr(offset: i64, ft: FieldType) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ColumnRef);
+        expr.mut_val().encode_i64(offset).unwrap();
+        expr.set_field_type(ft);
+        expr
+    }
+
+    fn new_const_int_expr(val: i64, ft: FieldType) -> Expr {
+        let mut expr = Expr::new();
+        if types::has_unsigned_flag(ft.get_flag()) {
+            expr.set_tp(ExprType::Uint64);
+            expr.mut_val().encode_u64(val as u64).unwrap();
+        } else {
+            expr.set_tp(ExprType::Int64);
+            expr.mut_val().encode_i64(val).unwrap();
+        }
+        expr.set_field_type(ft);
+        expr
+    }
+
+    fn new_const_bytes_expr(val: &[u8], ft: FieldType) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::Bytes);
+        expr.set_val(val.to_vec());
+        expr.set_field_type(ft);
+        expr
+    }
+
+    fn new_func_expr(sig: ScalarFuncSig, lhs: Expr, rhs: Expr) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ScalarFunc);
+        expr.set_sig(sig);
+        expr.mut_children().push(lhs);
+        expr.mut_children().push(rhs);
+        expr.set_field_type(new_field_type(types::LONG_LONG, 0, charset::CHARSET_BIN));
+        expr
+    }
+
+    fn int_ft() -> FieldType {
+        new_field_type(types::LONG_LONG, 0, charset::CHARSET_BIN)
+    }
+
+    fn uint_ft() -> FieldType {
+        new_field_type(types::LONG_LONG, UNSIGNED_FLAG, charset::CHARSET_BIN)
+    }
+
+    fn bin_str_ft() -> FieldType {
+        new_field_type(types::VARCHAR, 0, charset::CHARSET_BIN)
+    }
+
+    fn utf8_str_ft() -> FieldType {
+        new_field_type(types::VARCHAR, 0, charset::CHARSET_UTF8)
+    }
+
+    // `a op int`
+    fn int_cond(sig: ScalarFuncSig, offset: i64, ft: FieldType, val: i64) -> Expr {
+        new_func_expr(
+            sig,
+            new_col_expr(offset, ft.clone()),
+            new_const_int_expr(val, ft),
+        )
+    }
+
+    // `a op 'val'`
+    fn str_cond(sig: ScalarFuncSig, offset: i64, ft: FieldType, val: &[u8]) -> Expr {
+        new_func_expr(
+            sig,
+            new_col_expr(offset, ft.clone()),
+            new_const_bytes_expr(val, ft),
+        )
+    }
+
+    // Runs the selection with and without the constant propagation, checks they
+    // return the same rows and returns the propagated conditions.
+    fn check_propagation(conditions: Vec<Expr>, expect_always_false: bool) -> Vec<Expr> {
+        let tid = 1;
+        let cis = vec![
+            new_col_info(1, types::LONG_LONG),
+            new_col_info(2, types::VARCHAR),
+            new_col_info(3, types::LONG_LONG),
+        ];
+        let raw_data = vec![
+            vec![Datum::I64(1), Datum::Bytes(b"a".to_vec()), Datum::U64(7)],
+            vec![Datum::I64(2), Datum::Bytes(b"b".to_vec()), Datum::U64(7)],
+            vec![Datum::I64(3), Datum::Bytes(b"b".to_vec()), Datum::U64(8)],
+            vec![Datum::I64(4), Datum::Bytes(b"d".to_vec()), Datum::U64(3)],
+        ];
+        let table_data = gen_table_data(tid, &cis, &raw_data);
+        let mut test_store = TestStore::new(&table_data);
+        let mut table_scan = TableScan::new();
+        table_scan.set_table_id(tid);
+        table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
+        let ctx = Rc::new(EvalContext::default());
+        let cis = Rc::new(cis);
+
+        let mut results = vec![];
+        for &propagate in &[false, true] {
+            let key_ranges = vec![get_range(tid, 0, i64::MAX)].into();
+            let (snapshot, start_ts) = test_store.get_snapshot();
+            let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+            let mut statistics = Statistics::default();
+            let handles = {
+                let src = TableScanExecutor::new(&table_scan, key_ranges, store, &mut statistics);
+                let mut selection = Selection::new();
+                selection.set_conditions(RepeatedField::from_vec(conditions.clone()));
+                let mut executor =
+                    SelectionExecutor::new(selection, ctx.clone(), cis.clone(), Box::new(src))
+                        .unwrap();
+                if !propagate {
+                    let exprs = Expression::batch_build(&ctx, conditions.clone()).unwrap();
+                    executor.conditions = Conjunction::new(exprs);
+                    executor.always_false = false;
+                }
+                let mut handles = vec![];
+                while let Some(row) = executor.next().unwrap() {
+                    handles.push(row.handle);
+                }
+                handles
+            };
+            if propagate && expect_always_false {
+                // the scan is skipped.
+                assert_eq!(statistics.total_op_count(), 0);
+            }
+            results.push(handles);
+        }
+        assert_eq!(results[0], results[1]);
+
+        let (propagated, always_false) = propagate_constants(&ctx, cis.len(), conditions);
+        assert_eq!(always_false, expect_always_false);
+        if always_false {
+            assert!(results[1].is_empty());
+        }
+        propagated
+    }
+
+    #[test]
+    fn test_propagate_constants_to_false() {
+        // a = 2 and a > 5
+        let conditions = vec![
+            int_cond(ScalarFuncSig::EQInt, 0, int_ft(), 2),
+            int_cond(ScalarFuncSig::GTInt, 0, int_ft(), 5),
+        ];
+        check_propagation(conditions, true);
+
+        // a > 0 and 2 = a and a + 1 = 2
+        let conditions = vec![
+            int_cond(ScalarFuncSig::GTInt, 0, int_ft(), 0),
+            new_func_expr(
+                ScalarFuncSig::EQInt,
+                new_const_int_expr(2, int_ft()),
+                new_col_expr(0, int_ft()),
+            ),
+            new_func_expr(
+                ScalarFuncSig::EQInt,
+                new_func_expr(
+                    ScalarFuncSig::PlusInt,
+                    new_col_expr(0, int_ft()),
+                    new_const_int_expr(1, int_ft()),
+                ),
+                new_const_int_expr(2, int_ft()),
+            ),
+        ];
+        check_propagation(conditions, true);
+
+        // a = 2 and a = 3
+        let conditions = vec![
+            int_cond(ScalarFuncSig::EQInt, 0, int_ft(), 2),
+            int_cond(ScalarFuncSig::EQInt, 0, int_ft(), 3),
+        ];
+        check_propagation(conditions, true);
+
+        // b = 'b' and b < 'a' on a binary string.
+        let conditions = vec![
+            str_cond(ScalarFuncSig::EQString, 1, bin_str_ft(), b"b"),
+            str_cond(ScalarFuncSig::LTString, 1, bin_str_ft(), b"a"),
+        ];
+        check_propagation(conditions, true);
+    }
+
+    #[test]
+    fn test_propagate_constants_simplify() {
+        // a = 2 and a > 1 and c > 5
+        let conditions = vec![
+            int_cond(ScalarFuncSig::EQInt, 0, int_ft(), 2),
+            int_cond(ScalarFuncSig::GTInt, 0, int_ft(), 1),
+            int_cond(ScalarFuncSig::GTInt, 2, uint_ft(), 5),
+        ];
+        let propagated = check_propagation(conditions.clone(), false);
+        assert_eq!(propagated, vec![conditions[0].clone(), conditions[2].clone()]);
+
+        // b = 'b' and b >= 'b' and c = 7 and c != 8 on the unsigned column.
+        let conditions = vec![
+            str_cond(ScalarFuncSig::EQString, 1, bin_str_ft(), b"b"),
+            str_cond(ScalarFuncSig::GEString, 1, bin_str_ft(), b"b"),
+            int_cond(ScalarFuncSig::EQInt, 2, uint_ft(), 7),
+            int_cond(ScalarFuncSig::NEInt, 2, uint_ft(), 8),
+        ];
+        let propagated = check_propagation(conditions.clone(), false);
+        assert_eq!(propagated, vec![conditions[0].clone(), conditions[2].clone()]);
+    }
+
+    #[test]
+    fn test_propagate_constants_unsafe() {
+        let cases = vec![
+            // the signedness of the column and the constant differ.
+            vec![
+                new_func_expr(
+                    ScalarFuncSig::EQInt,
+                    new_col_expr(2, uint_ft()),
+                    new_const_int_expr(7, int_ft()),
+                ),
+                int_cond(ScalarFuncSig::GTInt, 2, uint_ft(), 100),
+            ],
+            // the string is compared with a non-binary collation.
+            vec![
+                str_cond(ScalarFuncSig::EQString, 1, utf8_str_ft(), b"b"),
+                str_cond(ScalarFuncSig::LTString, 1, utf8_str_ft(), b"a"),
+            ],
+            // `a = c` isn't a constant.
+            vec![
+                new_func_expr(
+                    ScalarFuncSig::EQInt,
+                    new_col_expr(0, int_ft()),
+                    new_col_expr(2, uint_ft()),
+                ),
+                int_cond(ScalarFuncSig::GTInt, 0, int_ft(), 5),
+            ],
+            // `a > 1` references another column as well.
+            vec![
+                int_cond(ScalarFuncSig::EQInt, 0, int_ft(), 2),
+                new_func_expr(
+                    ScalarFuncSig::GTInt,
+                    new_func_expr(
+                        ScalarFuncSig::PlusInt,
+                        new_col_expr(0, int_ft()),
+                        new_col_expr(2, uint_ft()),
+                    ),
+                    new_const_int_expr(100, int_ft()),
+                ),
+            ],
+        ];
+        for conditions in cases {
+            let propagated = check_propagation(conditions.clone(), false);
+            assert_eq!(propagated, conditions);
+        }
+    }
 }
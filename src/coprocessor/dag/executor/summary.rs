@@ -0,0 +1,185 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use coprocessor::Result;
+use util::time::{duration_to_nanos, Instant};
+
+use super::{Executor, ExecutorMetrics, Row};
+
+/// The execution summary of an executor, which is reported to TiDB for
+/// `EXPLAIN ANALYZE`. The time includes the time spent in its children.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ExecSummary {
+    pub num_produced_rows: u64,
+    pub num_iterations: u64,
+    pub time_processed_ns: u64,
+}
+
+/// `ExecSummaries` collects the execution summaries of the executors of a
+/// request, the executors are indexed by their order in the request, and
+/// share the summaries through the clones of the handle.
+#[derive(Clone)]
+pub struct ExecSummaries {
+    inner: Rc<RefCell<Vec<ExecSummary>>>,
+}
+
+impl ExecSummaries {
+    pub fn new(executors: usize) -> ExecSummaries {
+        ExecSummaries {
+            inner: Rc::new(RefCell::new(vec![ExecSummary::default(); executors])),
+        }
+    }
+
+    #[inline]
+    fn record(&self, idx: usize, produced: bool, elapsed_ns: u64) {
+        let mut summaries = self.inner.borrow_mut();
+        let summary = &mut summaries[idx];
+        summary.num_iterations += 1;
+        if produced {
+            summary.num_produced_rows += 1;
+        }
+        summary.time_processed_ns += elapsed_ns;
+    }
+
+    /// Returns the summaries of the executors in the request order.
+    pub fn summaries(&self) -> Vec<ExecSummary> {
+        self.inner.borrow().clone()
+    }
+}
+
+/// `SummaryExecutor` records the execution summary of the executor it wraps,
+/// which is the `idx`-th executor of the request.
+pub struct SummaryExecutor<'a> {
+    src: Box<Executor + 'a>,
+    idx: usize,
+    summaries: ExecSummaries,
+}
+
+impl<'a> SummaryExecutor<'a> {
+    pub fn new(
+        src: Box<Executor + 'a>,
+        idx: usize,
+        summaries: ExecSummaries,
+    ) -> SummaryExecutor<'a> {
+        SummaryExecutor {
+            src: src,
+            idx: idx,
+            summaries: summaries,
+        }
+    }
+}
+
+impl<'a> Executor for SummaryExecutor<'a> {
+    fn next(&mut self) -> Result<Option<Row>> {
+        let timer = Instant::now();
+        let res = self.src.next();
+        let produced = match res {
+            Ok(Some(_)) => true,
+            _ => false,
+        };
+        self.summaries
+            .record(self.idx, produced, duration_to_nanos(timer.elapsed()));
+        res
+    }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        self.src.take_statistics()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::i64;
+    use std::rc::Rc;
+
+    use kvproto::kvrpcpb::IsolationLevel;
+    use protobuf::RepeatedField;
+    use tipb::executor::{Limit, Selection, TableScan};
+    use tipb::expression::ScalarFuncSig;
+
+    use coprocessor::codec::datum::Datum;
+    use coprocessor::codec::mysql::types;
+    use coprocessor::dag::expr::test::fncall_expr;
+    use coprocessor::select::xeval::EvalContext;
+    use coprocessor::select::xeval::evaluator::test::{col_expr, datum_expr};
+    use storage::{SnapshotStore, Statistics};
+
+    use super::*;
+    use super::super::{LimitExecutor, SelectionExecutor, TableScanExecutor};
+    use super::super::scanner::test::{get_range, new_col_info, TestStore};
+    use super::super::topn::test::gen_table_data;
+
+    #[test]
+    fn test_exec_summaries() {
+        let tid = 1;
+        let cis = vec![
+            new_col_info(1, types::LONG_LONG),
+            new_col_info(2, types::LONG_LONG),
+        ];
+        let raw_data: Vec<_> = [7, 3, 8, 3, 5, 9, 6, 1, 7]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| vec![Datum::I64(i as i64 + 1), Datum::I64(v)])
+            .collect();
+        let table_data = gen_table_data(tid, &cis, &raw_data);
+        let mut test_store = TestStore::new(&table_data);
+        let (snapshot, start_ts) = test_store.get_snapshot();
+        let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+        let mut statistics = Statistics::default();
+
+        // scan -> selection(col_1 > 5) -> limit 3
+        let summaries = ExecSummaries::new(3);
+        let mut table_scan = TableScan::new();
+        table_scan.set_table_id(tid);
+        table_scan.set_columns(RepeatedField::from_vec(cis.clone()));
+        let key_ranges = vec![get_range(tid, 0, i64::MAX)].into();
+        let scan = TableScanExecutor::new(&table_scan, key_ranges, store, &mut statistics);
+        let scan = SummaryExecutor::new(Box::new(scan), 0, summaries.clone());
+        let mut selection = Selection::new();
+        let cond = fncall_expr(ScalarFuncSig::GTInt, &[col_expr(1), datum_expr(Datum::I64(5))]);
+        selection.mut_conditions().push(cond);
+        let selection = SelectionExecutor::new(
+            selection,
+            Rc::new(EvalContext::default()),
+            Rc::new(cis),
+            Box::new(scan),
+        ).unwrap();
+        let selection = SummaryExecutor::new(Box::new(selection), 1, summaries.clone());
+        let mut limit = Limit::new();
+        limit.set_limit(3);
+        let limit = LimitExecutor::new(limit, Box::new(selection));
+        let mut limit = SummaryExecutor::new(Box::new(limit), 2, summaries.clone());
+
+        let mut handles = vec![];
+        while let Some(row) = limit.next().unwrap() {
+            handles.push(row.handle);
+        }
+        assert_eq!(handles, vec![1, 3, 6]);
+
+        let summaries = summaries.summaries();
+        let rows: Vec<_> = summaries.iter().map(|s| s.num_produced_rows).collect();
+        // the scan stops once the limit is reached.
+        assert_eq!(rows, vec![6, 3, 3]);
+        // every executor produces no more rows than its child.
+        assert!(rows.windows(2).all(|w| w[0] >= w[1]));
+        // the last iteration of the limit produces nothing.
+        assert_eq!(summaries[2].num_iterations, 4);
+        assert_eq!(summaries[1].num_iterations, 3);
+        // the time of an executor includes the time of its children.
+        assert!(summaries[2].time_processed_ns >= summaries[1].time_processed_ns);
+        assert!(summaries[1].time_processed_ns >= summaries[0].time_processed_ns);
+    }
+}
@@ -11,121 +11,92 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use kvproto::coprocessor::KeyRange;
 use tipb::executor::TableScan;
 
-use util::collections::HashSet;
 use storage::{SnapshotStore, Statistics};
-use coprocessor::codec::table;
-use coprocessor::endpoint::{is_point, prefix_next};
+use coprocessor::interrupt::Interrupt;
+use coprocessor::key_ranges::KeyRanges;
 use coprocessor::Result;
-use coprocessor::metrics::*;
 
-use super::{Executor, Row};
-use super::scanner::Scanner;
+use super::{Executor, ExecutorMetrics, RangeFeedback, Row, ScannedRange};
+use super::row_scanner::RowScanner;
 
 
 pub struct TableScanExecutor<'a> {
-    desc: bool,
-    col_ids: HashSet<i64>,
-    cursor: usize,
-    key_ranges: Vec<KeyRange>,
-    scanner: Scanner<'a>,
+    scanner: RowScanner<'a>,
+    interrupt: Option<Interrupt>,
+    metrics: ExecutorMetrics,
 }
 
 impl<'a> TableScanExecutor<'a> {
     pub fn new(
         meta: &TableScan,
-        mut key_ranges: Vec<KeyRange>,
+        key_ranges: KeyRanges,
         store: SnapshotStore<'a>,
         statistics: &'a mut Statistics,
     ) -> TableScanExecutor<'a> {
-        let col_ids = meta.get_columns()
-            .iter()
-            .filter(|c| !c.get_pk_handle())
-            .map(|c| c.get_column_id())
-            .collect();
-        let desc = meta.get_desc();
-        if desc {
-            key_ranges.reverse();
-        }
-        let scanner = Scanner::new(store, desc, false, statistics);
-        COPR_EXECUTOR_COUNT.with_label_values(&["tblscan"]).inc();
+        let scanner = RowScanner::new(
+            store,
+            key_ranges,
+            Some(meta.get_columns()),
+            meta.get_desc(),
+            false,
+            statistics,
+        );
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.table_scan = 1;
         TableScanExecutor {
-            desc: desc,
-            col_ids: col_ids,
             scanner: scanner,
-            key_ranges: key_ranges,
-            cursor: Default::default(),
+            interrupt: None,
+            metrics: metrics,
         }
     }
 
-    fn get_row_from_range(&mut self) -> Result<Option<Row>> {
-        let range = &self.key_ranges[self.cursor];
-        let kv = self.scanner.next_row(range)?;
-        let (key, value) = match kv {
-            Some((key, value)) => (key, value),
-            None => return Ok(None),
-        };
-        let h = box_try!(table::decode_handle(&key));
-        let row_data = box_try!(table::cut_row(value, &self.col_ids));
-        let seek_key = if self.desc {
-            box_try!(table::truncate_as_row_key(&key)).to_vec()
-        } else {
-            prefix_next(&key)
-        };
-        self.scanner.set_seek_key(Some(seek_key));
-        Ok(Some(Row::new(h, row_data)))
+    /// Stops the scan once `interrupt` is triggered.
+    pub fn set_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt = Some(interrupt);
     }
 
-    fn get_row_from_point(&mut self) -> Result<Option<Row>> {
-        let key = self.key_ranges[self.cursor].get_start();
-        let value = self.scanner.get_row(key)?;
-        if let Some(value) = value {
-            let values = box_try!(table::cut_row(value, &self.col_ids));
-            let h = box_try!(table::decode_handle(key));
-            return Ok(Some(Row::new(h, values)));
-        }
-        Ok(None)
+    /// Counts the rows scanned in every range into `feedback`.
+    pub fn set_feedback(&mut self, feedback: RangeFeedback) {
+        self.scanner.set_feedback(feedback);
+    }
+
+    /// Records the keys of the rows scanned into `scanned_range`.
+    pub fn set_scanned_range(&mut self, scanned_range: ScannedRange) {
+        self.scanner.set_scanned_range(scanned_range);
     }
 }
 
 impl<'a> Executor for TableScanExecutor<'a> {
     fn next(&mut self) -> Result<Option<Row>> {
-        while self.cursor < self.key_ranges.len() {
-            if is_point(&self.key_ranges[self.cursor]) {
-                CORP_GET_OR_SCAN_COUNT.with_label_values(&["point"]).inc();
-                let data = self.get_row_from_point()?;
-                self.scanner.set_seek_key(None);
-                self.cursor += 1;
-                if data.is_some() {
-                    return Ok(data);
-                }
-                continue;
-            }
-
-            let data = self.get_row_from_range()?;
-            if data.is_none() {
-                CORP_GET_OR_SCAN_COUNT.with_label_values(&["range"]).inc();
-                self.scanner.set_seek_key(None);
-                self.cursor += 1;
-                continue;
-            }
-            return Ok(data);
+        if let Some(ref interrupt) = self.interrupt {
+            interrupt.check()?;
         }
-        Ok(None)
+        let row = self.scanner.next_row()?;
+        Ok(row.map(|row| row.into_row()))
+    }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        let mut metrics = self.metrics.take();
+        metrics.scan_counter.merge(&self.scanner.take_scan_counter());
+        metrics
     }
 }
 
 #[cfg(test)]
 mod test {
     use std::i64;
+    use std::time::Duration;
 
+    use kvproto::coprocessor::KeyRange;
     use kvproto::kvrpcpb::IsolationLevel;
     use protobuf::RepeatedField;
     use tipb::schema::ColumnInfo;
 
+    use coprocessor::Error;
     use storage::{SnapshotStore, Statistics};
+    use util::time::Instant;
 
     use super::*;
     use super::super::scanner::test::{get_point_range, get_range, prepare_table_data, Data,
@@ -183,8 +154,12 @@ mod test {
 
         let (snapshot, start_ts) = wrapper.store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
-        let mut table_scanner =
-            TableScanExecutor::new(&wrapper.table_scan, wrapper.ranges, store, &mut statistics);
+        let mut table_scanner = TableScanExecutor::new(
+            &wrapper.table_scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
 
         let row = table_scanner.next().unwrap().unwrap();
         assert_eq!(row.handle, handle as i64);
@@ -216,8 +191,12 @@ mod test {
 
         let (snapshot, start_ts) = wrapper.store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
-        let mut table_scanner =
-            TableScanExecutor::new(&wrapper.table_scan, wrapper.ranges, store, &mut statistics);
+        let mut table_scanner = TableScanExecutor::new(
+            &wrapper.table_scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
 
         for handle in 0..KEY_NUMBER {
             let row = table_scanner.next().unwrap().unwrap();
@@ -233,6 +212,55 @@ mod test {
         assert!(table_scanner.next().unwrap().is_none());
     }
 
+    #[test]
+    fn test_many_point_ranges() {
+        let mut wrapper = TableScanTestWrapper::default();
+        let mut ranges: Vec<_> = (0..KEY_NUMBER)
+            .map(|handle| wrapper.get_point_range(handle as i64))
+            .collect();
+        // point get on a missing row is skipped
+        ranges.insert(0, wrapper.get_point_range(-1));
+        wrapper.ranges = ranges;
+
+        let mut range_statistics = Statistics::default();
+        let expect_rows = {
+            let (snapshot, start_ts) = wrapper.store.get_snapshot();
+            let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+            let full_range = vec![get_range(TABLE_ID, i64::MIN, i64::MAX)];
+            let mut range_scanner = TableScanExecutor::new(
+                &wrapper.table_scan,
+                full_range.into(),
+                store,
+                &mut range_statistics,
+            );
+            let mut rows = vec![];
+            while let Some(row) = range_scanner.next().unwrap() {
+                rows.push(row);
+            }
+            rows
+        };
+        assert_eq!(expect_rows.len(), KEY_NUMBER);
+
+        let mut statistics = Statistics::default();
+        let (snapshot, start_ts) = wrapper.store.get_snapshot();
+        let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+        let mut point_scanner = TableScanExecutor::new(
+            &wrapper.table_scan,
+            KeyRanges::from_slice(&wrapper.ranges),
+            store,
+            &mut statistics,
+        );
+        for exp in expect_rows {
+            let row = point_scanner.next().unwrap().unwrap();
+            assert_eq!(row.handle, exp.handle);
+            for col in &wrapper.cols {
+                let cid = col.get_column_id();
+                assert_eq!(row.data.get(cid), exp.data.get(cid));
+            }
+        }
+        assert!(point_scanner.next().unwrap().is_none());
+    }
+
     #[test]
     fn test_reverse_scan() {
         let mut statistics = Statistics::default();
@@ -252,8 +280,12 @@ mod test {
 
         let (snapshot, start_ts) = wrapper.store.get_snapshot();
         let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
-        let mut table_scanner =
-            TableScanExecutor::new(&wrapper.table_scan, wrapper.ranges, store, &mut statistics);
+        let mut table_scanner = TableScanExecutor::new(
+            &wrapper.table_scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
 
         for tid in 0..KEY_NUMBER {
             let handle = KEY_NUMBER - tid - 1;
@@ -269,4 +301,27 @@ mod test {
         }
         assert!(table_scanner.next().unwrap().is_none());
     }
+
+    #[test]
+    fn test_interrupted() {
+        let mut statistics = Statistics::default();
+        let wrapper = TableScanTestWrapper::default();
+        let (snapshot, start_ts) = wrapper.store.get_snapshot();
+        let store = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
+        let mut table_scanner = TableScanExecutor::new(
+            &wrapper.table_scan,
+            wrapper.ranges.into(),
+            store,
+            &mut statistics,
+        );
+        let deadline = Instant::now_coarse() + Duration::from_secs(60);
+        let interrupt = Interrupt::new(deadline, "select");
+        table_scanner.set_interrupt(interrupt.clone());
+        assert!(table_scanner.next().unwrap().is_some());
+        interrupt.cancel();
+        match table_scanner.next() {
+            Err(Error::Canceled) => {}
+            res => panic!("expect canceled, got {:?}", res.map(|r| r.map(|r| r.handle))),
+        }
+    }
 }
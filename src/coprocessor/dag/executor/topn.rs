@@ -20,13 +20,12 @@ use tipb::schema::ColumnInfo;
 use tipb::expression::ByItem;
 
 use coprocessor::codec::datum::Datum;
-use coprocessor::Result;
+use coprocessor::{util, BuildError, Result};
 use coprocessor::select::xeval::EvalContext;
 use coprocessor::dag::expr::Expression;
 use coprocessor::select::topn_heap::{SortRow, TopNHeap};
-use coprocessor::metrics::*;
 
-use super::{inflate_with_col_for_dag, Executor, ExprColumnRefVisitor, Row};
+use super::{inflate_with_col_for_dag, Executor, ExecutorMetrics, ExprColumnRefVisitor, Row};
 
 struct OrderBy {
     items: Rc<Vec<ByItem>>,
@@ -35,12 +34,11 @@ struct OrderBy {
 
 impl OrderBy {
     fn new(ctx: &EvalContext, mut order_by: Vec<ByItem>) -> Result<OrderBy> {
-        let exprs: Vec<Expression> = box_try!(
-            order_by
-                .iter_mut()
-                .map(|v| Expression::build(ctx, v.take_expr()))
-                .collect()
-        );
+        let exprs = order_by
+            .iter_mut()
+            .map(|v| Expression::build(ctx, v.take_expr()))
+            .collect::<::std::result::Result<Vec<_>, _>>()
+            .map_err(BuildError::from)?;
         Ok(OrderBy {
             items: Rc::new(order_by),
             exprs: exprs,
@@ -61,6 +59,7 @@ pub struct TopNExecutor<'a> {
     iter: Option<IntoIter<SortRow>>,
     ctx: Rc<EvalContext>,
     src: Box<Executor + 'a>,
+    metrics: ExecutorMetrics,
 }
 
 impl<'a> TopNExecutor<'a> {
@@ -77,15 +76,17 @@ impl<'a> TopNExecutor<'a> {
             visitor.visit(by_item.get_expr())?;
         }
 
-        COPR_EXECUTOR_COUNT.with_label_values(&["topn"]).inc();
+        let mut metrics = ExecutorMetrics::default();
+        metrics.executor_count.topn = 1;
         Ok(TopNExecutor {
             order_by: OrderBy::new(&ctx, order_by)?,
-            heap: Some(TopNHeap::new(meta.get_limit() as usize)?),
+            heap: Some(TopNHeap::new(util::u64_to_usize(meta.get_limit(), "limit")?)?),
             cols: columns_info,
             related_cols_offset: visitor.column_offsets(),
             iter: None,
             ctx: ctx,
             src: src,
+            metrics: metrics,
         })
     }
 
@@ -126,6 +127,11 @@ impl<'a> Executor for TopNExecutor<'a> {
             None => Ok(None),
         }
     }
+
+    fn take_statistics(&mut self) -> ExecutorMetrics {
+        self.metrics.merge(&self.src.take_statistics());
+        self.metrics.take()
+    }
 }
 
 
@@ -401,7 +407,7 @@ pub mod test {
         // prepare range
         let range1 = get_range(tid, 0, 4);
         let range2 = get_range(tid, 5, 10);
-        let key_ranges = vec![range1, range2];
+        let key_ranges = vec![range1, range2].into();
         // init TableScan
         let (snapshot, start_ts) = test_store.get_snapshot();
         let snap = SnapshotStore::new(snapshot, start_ts, IsolationLevel::SI, true);
@@ -155,6 +155,8 @@ impl FnCall {
         let lhs = try_opt!(self.children[0].eval_real(ctx, row));
         let rhs = try_opt!(self.children[1].eval_real(ctx, row));
         if rhs == 0f64 {
+            ctx.handle_division_by_zero()
+                .map_err(|_| Error::DivisionByZero)?;
             return Ok(None);
         }
         let res = lhs / rhs;
@@ -177,7 +179,11 @@ impl FnCall {
                 Res::Ok(v) => Ok(Some(Cow::Owned(v))),
                 Res::Truncated(_) | Res::Overflow(_) => Err(Error::Overflow),
             },
-            None => Ok(None),
+            None => {
+                ctx.handle_division_by_zero()
+                    .map_err(|_| Error::DivisionByZero)?;
+                Ok(None)
+            }
         }
     }
 }
@@ -188,9 +194,14 @@ mod test {
     use tipb::expression::ScalarFuncSig;
     use coprocessor::codec::{mysql, Datum};
     use coprocessor::codec::mysql::types;
-    use coprocessor::dag::expr::{Expression, StatementContext};
+    use coprocessor::dag::expr::{Error, Expression, StatementContext};
     use coprocessor::dag::expr::test::{check_overflow, fncall_expr, str2dec};
     use coprocessor::select::xeval::evaluator::test::datum_expr;
+    use coprocessor::select::xeval::evaluator::{FLAG_DIVIDED_BY_ZERO_AS_WARNING,
+                                                FLAG_IGNORE_TRUNCATE, FLAG_IN_INSERT_STMT,
+                                                FLAG_IN_SELECT_STMT,
+                                                FLAG_IN_UPDATE_OR_DELETE_STMT,
+                                                FLAG_TRUNCATE_AS_WARNING};
 
     #[test]
     fn test_arithmetic_int() {
@@ -493,4 +504,45 @@ mod test {
             assert!(check_overflow(got).is_ok());
         }
     }
+
+    #[test]
+    fn test_divide_by_zero() {
+        let cases = vec![
+            (ScalarFuncSig::DivideReal, Datum::F64(44.3), Datum::F64(0.0)),
+            (ScalarFuncSig::DivideDecimal, str2dec("12.3"), str2dec("0")),
+        ];
+        let flags = vec![
+            (0, true),
+            (FLAG_IN_SELECT_STMT, true),
+            (FLAG_DIVIDED_BY_ZERO_AS_WARNING, true),
+            // it's an error only in the write statements.
+            (FLAG_IN_INSERT_STMT, false),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT, false),
+            (FLAG_IN_INSERT_STMT | FLAG_DIVIDED_BY_ZERO_AS_WARNING, true),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT | FLAG_DIVIDED_BY_ZERO_AS_WARNING, true),
+            // dividing by zero isn't a truncation.
+            (FLAG_IN_INSERT_STMT | FLAG_TRUNCATE_AS_WARNING, false),
+            (FLAG_IN_INSERT_STMT | FLAG_IGNORE_TRUNCATE | FLAG_TRUNCATE_AS_WARNING, false),
+        ];
+        for (flag, is_warning) in flags {
+            let ctx = StatementContext::new(0, flag).unwrap();
+            for &(sig, ref lhs, ref rhs) in &cases {
+                let lhs = datum_expr(lhs.clone());
+                let rhs = datum_expr(rhs.clone());
+                let op = Expression::build(&ctx, fncall_expr(sig, &[lhs, rhs])).unwrap();
+                let got = op.eval(&ctx, &[]);
+                if is_warning {
+                    assert_eq!(got.unwrap(), Datum::Null);
+                } else {
+                    match got {
+                        Err(Error::DivisionByZero) => {}
+                        _ => panic!("{:?} with flags {} should fail, got {:?}", sig, flag, got),
+                    }
+                }
+            }
+            // every NULL comes with a warning.
+            let warning_cnt = if is_warning { cases.len() } else { 0 };
+            assert_eq!(ctx.take_warnings().warning_cnt, warning_cnt, "flags {}", flag);
+        }
+    }
 }
@@ -24,13 +24,26 @@ use super::{FnCall, Result, StatementContext};
 
 impl FnCall {
     pub fn cast_int_as_int(&self, ctx: &StatementContext, row: &[Datum]) -> Result<Option<i64>> {
-        self.children[0].eval_int(ctx, row)
+        let val = try_opt!(self.children[0].eval_int(ctx, row));
+        if mysql::has_unsigned_flag(self.tp.get_flag() as u64) &&
+            !mysql::has_unsigned_flag(self.children[0].get_tp().get_flag() as u64)
+        {
+            let uval = convert::cast_int_as_uint(ctx, val, ctx.in_select_stmt)?;
+            Ok(Some(uval as i64))
+        } else {
+            Ok(Some(val))
+        }
     }
 
     pub fn cast_real_as_int(&self, ctx: &StatementContext, row: &[Datum]) -> Result<Option<i64>> {
         let val = try_opt!(self.children[0].eval_real(ctx, row));
         if mysql::has_unsigned_flag(self.tp.get_flag() as u64) {
-            let uval = convert_float_to_uint(val, u64::MAX, types::DOUBLE)?;
+            let uval = if val < 0f64 {
+                let val = convert_float_to_int(val, i64::MIN, i64::MAX, types::DOUBLE)?;
+                convert::cast_int_as_uint(ctx, val, ctx.in_select_stmt)?
+            } else {
+                convert_float_to_uint(val, u64::MAX, types::DOUBLE)?
+            };
             Ok(Some(uval as i64))
         } else {
             let res = convert_float_to_int(val, i64::MIN, i64::MAX, types::DOUBLE)?;
@@ -46,8 +59,13 @@ impl FnCall {
         let val = try_opt!(self.children[0].eval_decimal(ctx, row));
         let val = val.into_owned().round(0, RoundMode::HalfEven).unwrap();
         if mysql::has_unsigned_flag(self.tp.get_flag() as u64) {
-            let uint = val.as_u64().unwrap();
-            // TODO:handle overflow
+            let ival = val.as_i64().unwrap();
+            let uint = if ival < 0 {
+                convert::cast_int_as_uint(ctx, ival, ctx.in_select_stmt)?
+            } else {
+                // TODO:handle overflow
+                val.as_u64().unwrap()
+            };
             Ok(Some(uint as i64))
         } else {
             let val = val.as_i64().unwrap();
@@ -65,14 +83,31 @@ impl FnCall {
             Some(&b'-') => true,
             _ => false,
         };
+        let is_unsigned = mysql::has_unsigned_flag(self.tp.get_flag() as u64);
         if is_negative {
             // negative
-            let v = convert::bytes_to_int(ctx, &val)?;
+            let v = match convert::bytes_to_int_checked(ctx, &val)? {
+                Some(v) => v,
+                None => {
+                    let v = convert::overflow_from_cast_str_as_int(ctx, &val, true, is_unsigned)?;
+                    return Ok(Some(v));
+                }
+            };
             // TODO: if overflow, don't append this warning
-            Ok(Some(v))
+            if is_unsigned {
+                let uv = convert::cast_int_as_uint(ctx, v, ctx.in_select_stmt)?;
+                Ok(Some(uv as i64))
+            } else {
+                Ok(Some(v))
+            }
         } else {
-            let urs = convert::bytes_to_uint(ctx, &val)?;
-            // TODO: process overflow
+            let urs = match convert::bytes_to_uint_checked(ctx, &val)? {
+                Some(v) => v,
+                None => {
+                    let v = convert::overflow_from_cast_str_as_int(ctx, &val, false, is_unsigned)?;
+                    return Ok(Some(v));
+                }
+            };
             Ok(Some(urs as i64))
         }
     }
@@ -397,7 +432,7 @@ impl FnCall {
         row: &'a [Datum],
     ) -> Result<Option<Cow<'a, Time>>> {
         let val = try_opt!(self.children[0].eval_duration(ctx, row));
-        let mut val = Time::from_duration(&ctx.tz, self.tp.get_tp() as u8, val.as_ref())?;
+        let mut val = Time::from_duration(ctx, self.tp.get_tp() as u8, val.as_ref())?;
         val.round_frac(self.tp.get_decimal() as i8)?;
         Ok(Some(Cow::Owned(val)))
     }
@@ -655,7 +690,7 @@ impl FnCall {
     }
 
     fn produce_time_with_str(&self, ctx: &StatementContext, s: String) -> Result<Cow<Time>> {
-        let mut t = Time::parse_datetime(s.as_ref(), self.tp.get_decimal() as i8, &ctx.tz)?;
+        let mut t = Time::parse_datetime_in_ctx(ctx, s.as_ref(), self.tp.get_decimal() as i8)?;
         t.set_tp(self.tp.get_tp() as u8)?;
         Ok(Cow::Owned(t))
     }
@@ -682,13 +717,14 @@ impl FnCall {
 
 #[cfg(test)]
 mod test {
-    use std::u64;
+    use std::{i64, u64};
 
     use tipb::expression::{Expr, FieldType, ScalarFuncSig};
 
     use chrono::{FixedOffset, Utc};
 
     use coprocessor::codec::{convert, Datum};
+    use coprocessor::codec::chunk::{ArcChunk, Chunk};
     use coprocessor::codec::mysql::{self, charset, types, Decimal, Duration, Json, Time};
     use coprocessor::dag::expr::{Expression, StatementContext};
     use coprocessor::dag::expr::test::fncall_expr;
@@ -813,6 +849,83 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_cast_negative_as_uint() {
+        let cases = vec![
+            (ScalarFuncSig::CastIntAsInt, types::LONG_LONG, Datum::I64(-1), -1),
+            (ScalarFuncSig::CastIntAsInt, types::LONG_LONG, Datum::I64(i64::MIN), i64::MIN),
+            (ScalarFuncSig::CastRealAsInt, types::DOUBLE, Datum::F64(-1f64), -1),
+            (
+                ScalarFuncSig::CastDecimalAsInt,
+                types::NEW_DECIMAL,
+                Datum::Dec(Decimal::from(-1)),
+                -1,
+            ),
+            (
+                ScalarFuncSig::CastStringAsInt,
+                types::STRING,
+                Datum::Bytes(b"-1".to_vec()),
+                -1,
+            ),
+            (
+                ScalarFuncSig::CastStringAsInt,
+                types::STRING,
+                Datum::Bytes(b"-9223372036854775808".to_vec()),
+                i64::MIN,
+            ),
+        ];
+        for &(in_select, overflow_as_warning) in &[(true, true), (true, false), (false, true),
+                                                   (false, false)] {
+            let mut ctx = StatementContext::default();
+            ctx.in_select_stmt = in_select;
+            ctx.overflow_as_warning = overflow_as_warning;
+            for &(sig, tp, ref col, wrapped) in &cases {
+                let mut ex = fncall_expr(sig, &[col_expr(0, tp as i32)]);
+                ex.mut_field_type().set_flag(types::UNSIGNED_FLAG as u32);
+                let e = Expression::build(&ctx, ex).unwrap();
+                let res = e.eval_int(&ctx, &[col.clone()]);
+                if in_select {
+                    // the value is wrapped in SELECT statements.
+                    assert_eq!(res.unwrap(), Some(wrapped), "{:?}", sig);
+                } else if overflow_as_warning {
+                    // the value is clamped with a warning.
+                    assert_eq!(res.unwrap(), Some(0), "{:?}", sig);
+                } else {
+                    assert!(res.is_err(), "{:?}", sig);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cast_as_uint_into_chunk() {
+        let mut ctx = StatementContext::default();
+        ctx.in_select_stmt = true;
+        let mut ex = fncall_expr(
+            ScalarFuncSig::CastIntAsInt,
+            &[col_expr(0, types::LONG_LONG as i32)],
+        );
+        ex.mut_field_type().set_tp(types::LONG_LONG as i32);
+        ex.mut_field_type().set_flag(types::UNSIGNED_FLAG as u32);
+        let ft = ex.get_field_type().clone();
+        let e = Expression::build(&ctx, ex).unwrap();
+        let d = e.eval(&ctx, &[Datum::I64(-1)]).unwrap();
+        assert_eq!(d, Datum::U64(u64::MAX));
+
+        // the casted value is stored in a new unsigned column, and it's never
+        // read back as -1, even with a signed field type.
+        let mut chunk = Chunk::from_field_types(&[ft]);
+        chunk.append_datum(0, &d, e.get_tp()).unwrap();
+        let c = ArcChunk::new(chunk);
+        let tz = FixedOffset::east(0);
+        let row = c.get_row(0);
+        assert_eq!(row.get_u64(0), 18446744073709551615);
+        assert_eq!(row.get_typed_datum(0, &tz).unwrap(), d);
+        let mut signed = FieldType::new();
+        signed.set_tp(types::LONG_LONG as i32);
+        assert_eq!(row.get_datum(0, &signed, &tz).unwrap(), d);
+    }
+
     #[test]
     fn test_cast_as_real() {
         let mut ctx = StatementContext::default();
@@ -0,0 +1,428 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::cmp;
+
+use coprocessor::codec::{convert, mysql, Datum};
+use coprocessor::codec::mysql::{Duration, MAX_DURATION_NANOS, MAX_FSP, NANOS_PER_SEC};
+use super::{FnCall, Result, StatementContext};
+
+const SECS_PER_HOUR: f64 = 3600f64;
+const SECS_PER_MINUTE: f64 = 60f64;
+
+impl FnCall {
+    pub fn add_duration_and_duration<'a, 'b: 'a>(
+        &'b self,
+        ctx: &StatementContext,
+        row: &'a [Datum],
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let d0 = try_opt!(self.children[0].eval_duration(ctx, row));
+        let d1 = try_opt!(self.children[1].eval_duration(ctx, row));
+        self.duration_from_nanos(ctx, d0.to_nanos() + d1.to_nanos())
+    }
+
+    pub fn add_duration_and_string<'a, 'b: 'a>(
+        &'b self,
+        ctx: &StatementContext,
+        row: &'a [Datum],
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let d0 = try_opt!(self.children[0].eval_duration(ctx, row));
+        let d1 = try_opt!(self.eval_string_as_duration(ctx, row, 1));
+        self.duration_from_nanos(ctx, d0.to_nanos() + d1.to_nanos())
+    }
+
+    pub fn sub_duration_and_duration<'a, 'b: 'a>(
+        &'b self,
+        ctx: &StatementContext,
+        row: &'a [Datum],
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let d0 = try_opt!(self.children[0].eval_duration(ctx, row));
+        let d1 = try_opt!(self.children[1].eval_duration(ctx, row));
+        self.duration_from_nanos(ctx, d0.to_nanos() - d1.to_nanos())
+    }
+
+    pub fn sub_duration_and_string<'a, 'b: 'a>(
+        &'b self,
+        ctx: &StatementContext,
+        row: &'a [Datum],
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let d0 = try_opt!(self.children[0].eval_duration(ctx, row));
+        let d1 = try_opt!(self.eval_string_as_duration(ctx, row, 1));
+        self.duration_from_nanos(ctx, d0.to_nanos() - d1.to_nanos())
+    }
+
+    pub fn time_to_sec(&self, ctx: &StatementContext, row: &[Datum]) -> Result<Option<i64>> {
+        let d = try_opt!(self.children[0].eval_duration(ctx, row));
+        // the fractional part is truncated, just like MySQL.
+        Ok(Some(d.to_nanos() / NANOS_PER_SEC))
+    }
+
+    pub fn sec_to_time<'a, 'b: 'a>(
+        &'b self,
+        ctx: &StatementContext,
+        row: &'a [Datum],
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let secs = try_opt!(self.children[0].eval_real(ctx, row));
+        self.duration_from_secs(ctx, secs)
+    }
+
+    pub fn make_time<'a, 'b: 'a>(
+        &'b self,
+        ctx: &StatementContext,
+        row: &'a [Datum],
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let hour = try_opt!(self.children[0].eval_int(ctx, row));
+        let minute = try_opt!(self.children[1].eval_int(ctx, row));
+        let second = try_opt!(self.children[2].eval_real(ctx, row));
+        if minute < 0 || minute >= 60 || second < 0f64 || second >= SECS_PER_MINUTE {
+            return Ok(None);
+        }
+        let hour = if mysql::has_unsigned_flag(self.children[0].get_tp().get_flag() as u64) {
+            hour as u64 as f64
+        } else {
+            hour as f64
+        };
+        let secs = hour.abs() * SECS_PER_HOUR + minute as f64 * SECS_PER_MINUTE + second;
+        self.duration_from_secs(ctx, if hour < 0f64 { -secs } else { secs })
+    }
+
+    // `eval_string_as_duration` returns NULL if the string is not a valid duration.
+    fn eval_string_as_duration(
+        &self,
+        ctx: &StatementContext,
+        row: &[Datum],
+        idx: usize,
+    ) -> Result<Option<Duration>> {
+        let s = try_opt!(self.children[idx].eval_string(ctx, row));
+        Ok(Duration::parse(&s, MAX_FSP).ok())
+    }
+
+    fn duration_from_secs<'a>(
+        &self,
+        ctx: &StatementContext,
+        secs: f64,
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let max = MAX_DURATION_NANOS as f64 / NANOS_PER_SEC as f64;
+        let clamped = secs.max(-max).min(max);
+        convert::handle_truncate(ctx, clamped != secs)?;
+        let nanos = (clamped * NANOS_PER_SEC as f64).round() as i64;
+        self.duration_from_nanos(ctx, nanos)
+    }
+
+    // `duration_from_nanos` clamps `nanos` into the range of mysql time type
+    // and rounds it to the fsp of the function.
+    fn duration_from_nanos<'a>(
+        &self,
+        ctx: &StatementContext,
+        nanos: i64,
+    ) -> Result<Option<Cow<'a, Duration>>> {
+        let clamped = cmp::max(cmp::min(nanos, MAX_DURATION_NANOS), -MAX_DURATION_NANOS);
+        convert::handle_truncate(ctx, clamped != nanos)?;
+        let mut res = Duration::from_nanos(clamped, MAX_FSP)?;
+        res.round_frac(self.tp.get_decimal() as i8)?;
+        Ok(Some(Cow::Owned(res)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{f64, i64};
+
+    use tipb::expression::{Expr, ScalarFuncSig};
+
+    use coprocessor::codec::Datum;
+    use coprocessor::codec::mysql::{types, Duration, MAX_FSP};
+    use coprocessor::dag::expr::{Expression, Result, StatementContext};
+    use coprocessor::dag::expr::test::fncall_expr;
+    use coprocessor::select::xeval::evaluator::test::datum_expr;
+
+    fn dur(s: &str) -> Datum {
+        Datum::Dur(Duration::parse(s.as_bytes(), MAX_FSP).unwrap())
+    }
+
+    fn ignore_truncate_ctx() -> StatementContext {
+        StatementContext {
+            ignore_truncate: true,
+            ..Default::default()
+        }
+    }
+
+    fn eval_fn(
+        ctx: &StatementContext,
+        sig: ScalarFuncSig,
+        args: Vec<Expr>,
+        fsp: i32,
+    ) -> Result<Option<String>> {
+        let mut f = fncall_expr(sig, &args);
+        f.mut_field_type().set_decimal(fsp);
+        let op = Expression::build(ctx, f).unwrap();
+        match op.eval(ctx, &[])? {
+            Datum::Null => Ok(None),
+            Datum::Dur(d) => Ok(Some(format!("{}", d))),
+            Datum::I64(i) => Ok(Some(format!("{}", i))),
+            d => panic!("unexpected result {:?}", d),
+        }
+    }
+
+    #[test]
+    fn test_add_sub_duration() {
+        let cases = vec![
+            (
+                ScalarFuncSig::AddDurationAndDuration,
+                dur("11:30:45.123456"),
+                dur("00:00:14.876544"),
+                6,
+                Some("11:31:00.000000"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndDuration,
+                dur("-01:00:00"),
+                dur("00:30:00"),
+                0,
+                Some("-00:30:00"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndDuration,
+                dur("00:00:01.25"),
+                dur("00:00:01.3"),
+                1,
+                Some("00:00:02.6"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndDuration,
+                dur("00:00:01.25"),
+                dur("00:00:01.3"),
+                0,
+                Some("00:00:03"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndDuration,
+                dur("01:00:00"),
+                Datum::Null,
+                0,
+                None,
+            ),
+            (
+                ScalarFuncSig::SubDurationAndDuration,
+                dur("01:00:00"),
+                dur("01:30:00.5"),
+                1,
+                Some("-00:30:00.5"),
+            ),
+            (
+                ScalarFuncSig::SubDurationAndDuration,
+                dur("-838:59:59"),
+                dur("-838:59:59"),
+                0,
+                Some("00:00:00"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndString,
+                dur("01:00:00"),
+                Datum::Bytes(b"1 01:00:00".to_vec()),
+                0,
+                Some("26:00:00"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndString,
+                dur("01:00:00"),
+                Datum::Bytes(b"00:00:01.55".to_vec()),
+                1,
+                Some("01:00:01.6"),
+            ),
+            (
+                ScalarFuncSig::AddDurationAndString,
+                dur("01:00:00"),
+                Datum::Bytes(b"not a time".to_vec()),
+                0,
+                None,
+            ),
+            (
+                ScalarFuncSig::SubDurationAndString,
+                dur("-01:00:00"),
+                Datum::Bytes(b"-2 00:00:00".to_vec()),
+                0,
+                Some("47:00:00"),
+            ),
+            (
+                ScalarFuncSig::SubDurationAndString,
+                dur("01:00:00"),
+                Datum::Null,
+                0,
+                None,
+            ),
+        ];
+        let ctx = StatementContext::default();
+        for (sig, arg0, arg1, fsp, exp) in cases {
+            let args = vec![datum_expr(arg0), datum_expr(arg1)];
+            let got = eval_fn(&ctx, sig, args, fsp).unwrap();
+            assert_eq!(got, exp.map(|s| s.to_owned()), "{:?}", sig);
+        }
+    }
+
+    #[test]
+    fn test_duration_clamp() {
+        let cases = vec![
+            (
+                ScalarFuncSig::AddDurationAndDuration,
+                dur("838:00:00"),
+                dur("01:00:00"),
+                "838:59:59",
+            ),
+            (
+                ScalarFuncSig::SubDurationAndDuration,
+                dur("-838:00:00"),
+                dur("01:00:00.5"),
+                "-838:59:59",
+            ),
+            (
+                ScalarFuncSig::AddDurationAndString,
+                dur("838:59:59"),
+                Datum::Bytes(b"00:00:00.000001".to_vec()),
+                "838:59:59",
+            ),
+        ];
+        let strict_ctx = StatementContext::default();
+        let ctx = ignore_truncate_ctx();
+        for (sig, arg0, arg1, exp) in cases {
+            let args = vec![datum_expr(arg0), datum_expr(arg1)];
+            assert!(eval_fn(&strict_ctx, sig, args.clone(), 0).is_err());
+            let got = eval_fn(&ctx, sig, args, 0).unwrap();
+            assert_eq!(got, Some(exp.to_owned()), "{:?}", sig);
+        }
+    }
+
+    #[test]
+    fn test_time_to_sec() {
+        let cases = vec![
+            (dur("00:00:00"), Some("0")),
+            (dur("01:00:01.9"), Some("3601")),
+            (dur("-01:00:01.9"), Some("-3601")),
+            (dur("838:59:59"), Some("3020399")),
+            (dur("-838:59:59"), Some("-3020399")),
+            (Datum::Null, None),
+        ];
+        let ctx = StatementContext::default();
+        for (arg, exp) in cases {
+            let got = eval_fn(&ctx, ScalarFuncSig::TimeToSec, vec![datum_expr(arg)], 0).unwrap();
+            assert_eq!(got, exp.map(|s| s.to_owned()));
+        }
+    }
+
+    #[test]
+    fn test_sec_to_time() {
+        let cases = vec![
+            (Datum::F64(0f64), 0, Some("00:00:00")),
+            (Datum::F64(3661.5), 1, Some("01:01:01.5")),
+            (Datum::F64(-3661.56), 1, Some("-01:01:01.6")),
+            (Datum::F64(-3661.56), 0, Some("-01:01:02")),
+            (Datum::F64(0.1234567), 6, Some("00:00:00.123457")),
+            (Datum::F64(3020399f64), 0, Some("838:59:59")),
+            (Datum::Null, 0, None),
+        ];
+        let ctx = StatementContext::default();
+        for (arg, fsp, exp) in cases {
+            let args = vec![datum_expr(arg)];
+            let got = eval_fn(&ctx, ScalarFuncSig::SecToTime, args, fsp).unwrap();
+            assert_eq!(got, exp.map(|s| s.to_owned()));
+        }
+
+        let cases = vec![
+            (3020399.5, "838:59:59"),
+            (1e20, "838:59:59"),
+            (f64::MAX, "838:59:59"),
+            (-1e20, "-838:59:59"),
+        ];
+        let strict_ctx = StatementContext::default();
+        let ctx = ignore_truncate_ctx();
+        for (arg, exp) in cases {
+            let args = vec![datum_expr(Datum::F64(arg))];
+            assert!(eval_fn(&strict_ctx, ScalarFuncSig::SecToTime, args.clone(), 0).is_err());
+            let got = eval_fn(&ctx, ScalarFuncSig::SecToTime, args, 0).unwrap();
+            assert_eq!(got, Some(exp.to_owned()), "{}", arg);
+        }
+    }
+
+    #[test]
+    fn test_make_time() {
+        let cases = vec![
+            (Datum::I64(1), Datum::I64(2), Datum::F64(3.5), 1, Some("01:02:03.5")),
+            (Datum::I64(1), Datum::I64(2), Datum::F64(3.5), 0, Some("01:02:04")),
+            (Datum::I64(-1), Datum::I64(30), Datum::F64(0f64), 0, Some("-01:30:00")),
+            (Datum::I64(838), Datum::I64(59), Datum::F64(59f64), 0, Some("838:59:59")),
+            (Datum::I64(1), Datum::I64(60), Datum::F64(0f64), 0, None),
+            (Datum::I64(1), Datum::I64(-1), Datum::F64(0f64), 0, None),
+            (Datum::I64(1), Datum::I64(0), Datum::F64(60f64), 0, None),
+            (Datum::I64(1), Datum::I64(0), Datum::F64(-0.5), 0, None),
+            (Datum::Null, Datum::I64(0), Datum::F64(0f64), 0, None),
+            (Datum::I64(1), Datum::Null, Datum::F64(0f64), 0, None),
+            (Datum::I64(1), Datum::I64(0), Datum::Null, 0, None),
+        ];
+        let ctx = StatementContext::default();
+        for (hour, minute, second, fsp, exp) in cases {
+            let args = vec![datum_expr(hour), datum_expr(minute), datum_expr(second)];
+            let got = eval_fn(&ctx, ScalarFuncSig::MakeTime, args, fsp).unwrap();
+            assert_eq!(got, exp.map(|s| s.to_owned()));
+        }
+
+        let cases = vec![
+            (Datum::I64(839), "838:59:59"),
+            (Datum::I64(i64::MAX), "838:59:59"),
+            (Datum::I64(-839), "-838:59:59"),
+            (Datum::I64(i64::MIN), "-838:59:59"),
+            // an unsigned hour is never negative.
+            (Datum::U64(i64::MAX as u64 + 1), "838:59:59"),
+        ];
+        let strict_ctx = StatementContext::default();
+        let ctx = ignore_truncate_ctx();
+        for (hour, exp) in cases {
+            let args = vec![
+                datum_expr(hour),
+                datum_expr(Datum::I64(0)),
+                datum_expr(Datum::F64(0f64)),
+            ];
+            assert!(eval_fn(&strict_ctx, ScalarFuncSig::MakeTime, args.clone(), 0).is_err());
+            let got = eval_fn(&ctx, ScalarFuncSig::MakeTime, args, 0).unwrap();
+            assert_eq!(got, Some(exp.to_owned()));
+        }
+    }
+
+    #[test]
+    fn test_compare_duration_with_cast() {
+        // the comparisons between duration and string or int are pushed down
+        // with the other side casted to duration.
+        let cases = vec![
+            (
+                ScalarFuncSig::CastStringAsDuration,
+                Datum::Bytes(b"01:00:00".to_vec()),
+                "1",
+            ),
+            (
+                ScalarFuncSig::CastStringAsDuration,
+                Datum::Bytes(b"1 00:00:00".to_vec()),
+                "0",
+            ),
+            (ScalarFuncSig::CastIntAsDuration, Datum::I64(15959), "1"),
+            (ScalarFuncSig::CastIntAsDuration, Datum::I64(20000), "0"),
+        ];
+        let ctx = StatementContext::default();
+        for (cast, arg, exp) in cases {
+            let mut cast = fncall_expr(cast, &[datum_expr(arg)]);
+            cast.mut_field_type().set_tp(types::DURATION as i32);
+            let args = vec![datum_expr(dur("02:00:00")), cast];
+            let got = eval_fn(&ctx, ScalarFuncSig::GTDuration, args, 0).unwrap();
+            assert_eq!(got, Some(exp.to_owned()));
+        }
+    }
+}
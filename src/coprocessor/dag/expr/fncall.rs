@@ -21,6 +21,12 @@ use coprocessor::codec::mysql::{self, Decimal, Duration, Json, Time};
 use super::{Error, FnCall, Result, StatementContext};
 use super::compare::CmpOp;
 
+// The static costs of the functions, see `FnCall::cost`.
+const COST_COMPARE: u64 = 1;
+const COST_ARITHMETIC: u64 = 2;
+const COST_STRING: u64 = 4;
+const COST_PATTERN: u64 = 16;
+
 impl FnCall {
     pub fn check_args(sig: ScalarFuncSig, args: usize) -> Result<()> {
         let (min_args, max_args) = match sig {
@@ -96,7 +102,11 @@ impl FnCall {
             ScalarFuncSig::DivideReal |
             ScalarFuncSig::BitAndSig |
             ScalarFuncSig::BitOrSig |
-            ScalarFuncSig::BitXorSig => (2, 2),
+            ScalarFuncSig::BitXorSig |
+            ScalarFuncSig::AddDurationAndDuration |
+            ScalarFuncSig::AddDurationAndString |
+            ScalarFuncSig::SubDurationAndDuration |
+            ScalarFuncSig::SubDurationAndString => (2, 2),
 
             ScalarFuncSig::CastIntAsInt |
             ScalarFuncSig::CastIntAsReal |
@@ -180,6 +190,8 @@ impl FnCall {
             ScalarFuncSig::FloorDecToInt |
             ScalarFuncSig::JsonTypeSig |
             ScalarFuncSig::JsonUnquoteSig |
+            ScalarFuncSig::TimeToSec |
+            ScalarFuncSig::SecToTime |
             ScalarFuncSig::BitNegSig => (1, 1),
 
             ScalarFuncSig::IfInt |
@@ -189,6 +201,7 @@ impl FnCall {
             ScalarFuncSig::IfTime |
             ScalarFuncSig::IfDuration |
             ScalarFuncSig::IfJson |
+            ScalarFuncSig::MakeTime |
             ScalarFuncSig::LikeSig => (3, 3),
 
             ScalarFuncSig::JsonArraySig | ScalarFuncSig::JsonObjectSig => (0, usize::MAX),
@@ -231,6 +244,178 @@ impl FnCall {
         }
         Ok(())
     }
+
+    /// `cost` returns the static cost of evaluating the function once, not
+    /// counting its arguments. The comparisons are the cheapest, then the
+    /// arithmetic, the string functions and the pattern matching.
+    pub fn cost(sig: ScalarFuncSig) -> u64 {
+        match sig {
+            ScalarFuncSig::LikeSig => COST_PATTERN,
+
+            ScalarFuncSig::LTString |
+            ScalarFuncSig::LEString |
+            ScalarFuncSig::GTString |
+            ScalarFuncSig::GEString |
+            ScalarFuncSig::EQString |
+            ScalarFuncSig::NEString |
+            ScalarFuncSig::NullEQString |
+            ScalarFuncSig::CastIntAsString |
+            ScalarFuncSig::CastRealAsString |
+            ScalarFuncSig::CastDecimalAsString |
+            ScalarFuncSig::CastStringAsInt |
+            ScalarFuncSig::CastStringAsReal |
+            ScalarFuncSig::CastStringAsString |
+            ScalarFuncSig::CastStringAsDecimal |
+            ScalarFuncSig::CastStringAsTime |
+            ScalarFuncSig::CastStringAsDuration |
+            ScalarFuncSig::CastStringAsJson |
+            ScalarFuncSig::CastTimeAsString |
+            ScalarFuncSig::CastDurationAsString |
+            ScalarFuncSig::CastJsonAsString |
+            ScalarFuncSig::JsonTypeSig |
+            ScalarFuncSig::JsonUnquoteSig |
+            ScalarFuncSig::JsonArraySig |
+            ScalarFuncSig::JsonObjectSig |
+            ScalarFuncSig::JsonExtractSig |
+            ScalarFuncSig::JsonRemoveSig |
+            ScalarFuncSig::JsonMergeSig |
+            ScalarFuncSig::JsonSetSig |
+            ScalarFuncSig::JsonInsertSig |
+            ScalarFuncSig::JsonReplaceSig => COST_STRING,
+
+            ScalarFuncSig::LTInt |
+            ScalarFuncSig::LEInt |
+            ScalarFuncSig::GTInt |
+            ScalarFuncSig::GEInt |
+            ScalarFuncSig::EQInt |
+            ScalarFuncSig::NEInt |
+            ScalarFuncSig::NullEQInt |
+            ScalarFuncSig::LTReal |
+            ScalarFuncSig::LEReal |
+            ScalarFuncSig::GTReal |
+            ScalarFuncSig::GEReal |
+            ScalarFuncSig::EQReal |
+            ScalarFuncSig::NEReal |
+            ScalarFuncSig::NullEQReal |
+            ScalarFuncSig::LTDecimal |
+            ScalarFuncSig::LEDecimal |
+            ScalarFuncSig::GTDecimal |
+            ScalarFuncSig::GEDecimal |
+            ScalarFuncSig::EQDecimal |
+            ScalarFuncSig::NEDecimal |
+            ScalarFuncSig::NullEQDecimal |
+            ScalarFuncSig::LTTime |
+            ScalarFuncSig::LETime |
+            ScalarFuncSig::GTTime |
+            ScalarFuncSig::GETime |
+            ScalarFuncSig::EQTime |
+            ScalarFuncSig::NETime |
+            ScalarFuncSig::NullEQTime |
+            ScalarFuncSig::LTDuration |
+            ScalarFuncSig::LEDuration |
+            ScalarFuncSig::GTDuration |
+            ScalarFuncSig::GEDuration |
+            ScalarFuncSig::EQDuration |
+            ScalarFuncSig::NEDuration |
+            ScalarFuncSig::NullEQDuration |
+            ScalarFuncSig::IntIsTrue |
+            ScalarFuncSig::IntIsFalse |
+            ScalarFuncSig::IntIsNull |
+            ScalarFuncSig::RealIsTrue |
+            ScalarFuncSig::RealIsFalse |
+            ScalarFuncSig::RealIsNull |
+            ScalarFuncSig::DecimalIsTrue |
+            ScalarFuncSig::DecimalIsFalse |
+            ScalarFuncSig::DecimalIsNull |
+            ScalarFuncSig::StringIsNull |
+            ScalarFuncSig::TimeIsNull |
+            ScalarFuncSig::DurationIsNull |
+            ScalarFuncSig::JsonIsNull |
+            ScalarFuncSig::LogicalAnd |
+            ScalarFuncSig::LogicalOr |
+            ScalarFuncSig::LogicalXor |
+            ScalarFuncSig::UnaryNot => COST_COMPARE,
+
+            _ => COST_ARITHMETIC,
+        }
+    }
+
+    /// `is_pure` returns whether the function is deterministic and never
+    /// produces warnings, so the order it's evaluated in among the conjuncts
+    /// of a selection can't be observed. The functions which may truncate or
+    /// overflow, e.g. the casts and the arithmetic, aren't pure.
+    pub fn is_pure(sig: ScalarFuncSig) -> bool {
+        match sig {
+            ScalarFuncSig::LTInt |
+            ScalarFuncSig::LEInt |
+            ScalarFuncSig::GTInt |
+            ScalarFuncSig::GEInt |
+            ScalarFuncSig::EQInt |
+            ScalarFuncSig::NEInt |
+            ScalarFuncSig::NullEQInt |
+            ScalarFuncSig::LTReal |
+            ScalarFuncSig::LEReal |
+            ScalarFuncSig::GTReal |
+            ScalarFuncSig::GEReal |
+            ScalarFuncSig::EQReal |
+            ScalarFuncSig::NEReal |
+            ScalarFuncSig::NullEQReal |
+            ScalarFuncSig::LTDecimal |
+            ScalarFuncSig::LEDecimal |
+            ScalarFuncSig::GTDecimal |
+            ScalarFuncSig::GEDecimal |
+            ScalarFuncSig::EQDecimal |
+            ScalarFuncSig::NEDecimal |
+            ScalarFuncSig::NullEQDecimal |
+            ScalarFuncSig::LTString |
+            ScalarFuncSig::LEString |
+            ScalarFuncSig::GTString |
+            ScalarFuncSig::GEString |
+            ScalarFuncSig::EQString |
+            ScalarFuncSig::NEString |
+            ScalarFuncSig::NullEQString |
+            ScalarFuncSig::LTTime |
+            ScalarFuncSig::LETime |
+            ScalarFuncSig::GTTime |
+            ScalarFuncSig::GETime |
+            ScalarFuncSig::EQTime |
+            ScalarFuncSig::NETime |
+            ScalarFuncSig::NullEQTime |
+            ScalarFuncSig::LTDuration |
+            ScalarFuncSig::LEDuration |
+            ScalarFuncSig::GTDuration |
+            ScalarFuncSig::GEDuration |
+            ScalarFuncSig::EQDuration |
+            ScalarFuncSig::NEDuration |
+            ScalarFuncSig::NullEQDuration |
+            ScalarFuncSig::LTJson |
+            ScalarFuncSig::LEJson |
+            ScalarFuncSig::GTJson |
+            ScalarFuncSig::GEJson |
+            ScalarFuncSig::EQJson |
+            ScalarFuncSig::NEJson |
+            ScalarFuncSig::NullEQJson |
+            ScalarFuncSig::IntIsTrue |
+            ScalarFuncSig::IntIsFalse |
+            ScalarFuncSig::IntIsNull |
+            ScalarFuncSig::RealIsTrue |
+            ScalarFuncSig::RealIsFalse |
+            ScalarFuncSig::RealIsNull |
+            ScalarFuncSig::DecimalIsTrue |
+            ScalarFuncSig::DecimalIsFalse |
+            ScalarFuncSig::DecimalIsNull |
+            ScalarFuncSig::StringIsNull |
+            ScalarFuncSig::TimeIsNull |
+            ScalarFuncSig::DurationIsNull |
+            ScalarFuncSig::JsonIsNull |
+            ScalarFuncSig::LogicalAnd |
+            ScalarFuncSig::LogicalOr |
+            ScalarFuncSig::LogicalXor |
+            ScalarFuncSig::UnaryNot |
+            ScalarFuncSig::LikeSig => true,
+            _ => false,
+        }
+    }
 }
 
 macro_rules! dispatch_call {
@@ -460,6 +645,8 @@ dispatch_call! {
         BitNegSig => bit_neg,
         BitOrSig => bit_or,
         BitXorSig => bit_xor,
+
+        TimeToSec => time_to_sec,
     }
     REAL_CALLS {
         CastIntAsReal => cast_int_as_real,
@@ -559,6 +746,13 @@ dispatch_call! {
 
         CoalesceDuration => coalesce_duration,
         CaseWhenDuration => case_when_duration,
+
+        AddDurationAndDuration => add_duration_and_duration,
+        AddDurationAndString => add_duration_and_string,
+        SubDurationAndDuration => sub_duration_and_duration,
+        SubDurationAndString => sub_duration_and_string,
+        SecToTime => sec_to_time,
+        MakeTime => make_time,
     }
     JSON_CALLS {
         CastIntAsJson => cast_int_as_json,
@@ -17,6 +17,7 @@ mod fncall;
 mod builtin_cast;
 mod builtin_control;
 mod builtin_op;
+mod builtin_time;
 mod compare;
 mod arithmetic;
 mod math;
@@ -67,6 +68,10 @@ quick_error! {
             description("Unknown signature")
             display("Unknown signature: {:?}", sig)
         }
+        UnsupportedExpr(tp: ExprType) {
+            description("Unsupported expression")
+            display("can't handle {:?} expr in DAG mode", tp)
+        }
         Truncated {
             description("Truncated")
             display("error Truncated")
@@ -75,6 +80,10 @@ quick_error! {
             description("Overflow")
             display("error Overflow")
         }
+        DivisionByZero {
+            description("Division by 0")
+            display("[1365] Division by 0")
+        }
         Other(err: Box<error::Error + Send + Sync>) {
             from()
             cause(err.as_ref())
@@ -277,6 +286,28 @@ impl Expression {
 }
 
 impl Expression {
+    /// `cost` returns the static cost of evaluating the expression once, the
+    /// constants and the column references are free.
+    pub fn cost(&self) -> u64 {
+        match *self {
+            Expression::Constant(_) | Expression::ColumnRef(_) => 0,
+            Expression::ScalarFn(ref f) => f.children
+                .iter()
+                .fold(FnCall::cost(f.sig), |cost, child| cost + child.cost()),
+        }
+    }
+
+    /// `is_pure` returns whether all the functions in the expression are pure,
+    /// see `FnCall::is_pure`.
+    pub fn is_pure(&self) -> bool {
+        match *self {
+            Expression::Constant(_) | Expression::ColumnRef(_) => true,
+            Expression::ScalarFn(ref f) => {
+                FnCall::is_pure(f.sig) && f.children.iter().all(|child| child.is_pure())
+            }
+        }
+    }
+
     pub fn eval(&self, ctx: &StatementContext, row: &[Datum]) -> Result<Datum> {
         match *self {
             Expression::Constant(ref constant) => Ok(constant.eval()),
@@ -363,13 +394,13 @@ impl Expression {
                 };
                 Ok(Expression::ColumnRef(column))
             }
-            unhandled => Err(box_err!("can't handle {:?} expr in DAG mode", unhandled)),
+            unhandled => Err(Error::UnsupportedExpr(unhandled)),
         }
     }
 }
 
 #[cfg(test)]
-mod test {
+pub mod test {
     use std::{i64, u64};
     use coprocessor::codec::{convert, Datum};
     use coprocessor::codec::mysql::{types, Decimal, Duration, Json, Time};
@@ -468,7 +499,10 @@ mod test {
             (None, vec![Datum::Null], Datum::Null),
         ];
         for (flag, cols, exp) in cases {
-            let col_expr = col_expr(0);
+            let mut col_expr = col_expr(0);
+            if flag.is_some() {
+                col_expr.mut_field_type().set_flag(flag.unwrap() as u32);
+            }
             let mut ex = fncall_expr(ScalarFuncSig::CastIntAsInt, &[col_expr]);
             if flag.is_some() {
                 ex.mut_field_type().set_flag(flag.unwrap() as u32);
@@ -14,3 +14,17 @@ pub mod executor;
 pub mod dag;
 pub mod expr;
 pub use self::dag::DAGContext;
+
+use coprocessor::BuildError;
+
+// Building an expression only fails because of the request itself.
+impl From<expr::Error> for BuildError {
+    fn from(e: expr::Error) -> BuildError {
+        match e {
+            expr::Error::UnknownSignature(_) | expr::Error::UnsupportedExpr(_) => {
+                BuildError::unsupported(format!("{}", e))
+            }
+            _ => BuildError::invalid_request(format!("{}", e)),
+        }
+    }
+}
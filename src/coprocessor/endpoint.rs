@@ -24,14 +24,15 @@ use tipb::schema::ColumnInfo;
 use protobuf::Message as PbMsg;
 use kvproto::coprocessor::{KeyRange, Request, Response};
 use kvproto::errorpb::{self, ServerIsBusy};
-use kvproto::kvrpcpb::{CommandPri, IsolationLevel};
+use kvproto::kvrpcpb::{self, CommandPri, IsolationLevel};
 
-use util::time::{duration_to_sec, Instant};
+use util::time::{duration_to_ms, duration_to_sec, Instant};
 use util::worker::{BatchRunnable, FutureScheduler, Scheduler};
 use util::collections::HashMap;
 use util::threadpool::{Context, ContextFactory, ThreadPool, ThreadPoolBuilder};
-use server::{Config, OnResponse};
-use storage::{self, engine, Engine, FlowStatistics, Snapshot, Statistics, StatisticsSummary};
+use server::{Config, OnResponse, OnStreamResponse};
+use storage::{self, engine, CFStatistics, Engine, FlowStatistics, Snapshot, Statistics,
+              StatisticsSummary};
 use storage::engine::Error as EngineError;
 use pd::PdTask;
 
@@ -40,6 +41,9 @@ use super::codec::datum::Datum;
 use super::select::select::SelectContext;
 use super::select::xeval::EvalContext;
 use super::dag::DAGContext;
+use super::interrupt::Interrupt;
+use super::limiter::SourceLimiter;
+use super::key_ranges::{KeyRangeRef, KeyRanges};
 use super::statistics::analyze::AnalyzeContext;
 use super::metrics::*;
 use super::{Error, Result};
@@ -62,6 +66,8 @@ pub const SINGLE_GROUP: &'static [u8] = b"SingleGroup";
 
 const OUTDATED_ERROR_MSG: &'static str = "request outdated.";
 
+const CANCELED_ERROR_MSG: &'static str = "request canceled.";
+
 const ENDPOINT_IS_BUSY: &'static str = "endpoint is busy";
 
 pub struct Host {
@@ -73,6 +79,8 @@ pub struct Host {
     low_priority_pool: ThreadPool<CopContext>,
     high_priority_pool: ThreadPool<CopContext>,
     max_running_task_count: usize,
+    stream_batch_row_limit: usize,
+    limiter: SourceLimiter,
 }
 
 pub type CopRequestStatistics = HashMap<u64, FlowStatistics>;
@@ -123,8 +131,8 @@ impl CopContext {
         let flow_stats = self.request_stats
             .entry(region_id)
             .or_insert_with(FlowStatistics::default);
-        flow_stats.add(&stats.write.flow_stats);
-        flow_stats.add(&stats.data.flow_stats);
+        flow_stats.merge(&stats.write.flow_stats);
+        flow_stats.merge(&stats.data.flow_stats);
     }
 }
 
@@ -171,6 +179,8 @@ impl Host {
             reqs: HashMap::default(),
             last_req_id: 0,
             max_running_task_count: cfg.end_point_max_tasks,
+            stream_batch_row_limit: cfg.end_point_stream_batch_row_limit,
+            limiter: SourceLimiter::new(cfg),
             pool: ThreadPoolBuilder::new(
                 thd_name!("endpoint-normal-pool"),
                 CopContextFactory { sender: r.clone() },
@@ -217,7 +227,7 @@ impl Host {
             COPR_PENDING_REQS
                 .with_label_values(&[type_str, pri_str])
                 .add(1.0);
-            let end_point = TiDbEndPoint::new(snap.clone());
+            let end_point = TiDbEndPoint::new(snap.clone(), self.stream_batch_row_limit);
 
             let pool = match pri {
                 CommandPri::Low => &mut self.low_priority_pool,
@@ -262,51 +272,86 @@ enum CopRequest {
 }
 
 pub struct ReqContext {
-    // The deadline before which the task should be responded.
-    pub deadline: Instant,
+    // It tells whether the task should stop, e.g. the deadline before which
+    // the task should be responded is exceeded.
+    pub interrupt: Interrupt,
     pub isolation_level: IsolationLevel,
     pub fill_cache: bool,
     // whether is a table scan request.
     pub table_scan: bool,
+    // whether the rows scanned in every range are counted and responded for
+    // TiDB's statistics feedback.
+    pub collect_feedback: bool,
+    // whether the execution summaries of the executors are collected and
+    // responded for TiDB's `EXPLAIN ANALYZE`.
+    pub collect_summaries: bool,
 }
 
 impl ReqContext {
     #[inline]
     fn get_scan_tag(&self) -> &'static str {
-        if self.table_scan {
-            STR_REQ_TYPE_SELECT
-        } else {
-            STR_REQ_TYPE_INDEX
-        }
+        get_scan_tag(self.table_scan)
     }
+}
 
-    pub fn check_if_outdated(&self) -> Result<()> {
-        let now = Instant::now_coarse();
-        if self.deadline <= now {
-            return Err(Error::Outdated(self.deadline, now, self.get_scan_tag()));
-        }
-        Ok(())
+// TODO: use the source tag in the request context once kvproto carries it,
+// all the requests share the default quota now.
+#[inline]
+fn get_source_tag(_: &kvrpcpb::Context) -> String {
+    String::new()
+}
+
+#[inline]
+fn get_scan_tag(table_scan: bool) -> &'static str {
+    if table_scan {
+        STR_REQ_TYPE_SELECT
+    } else {
+        STR_REQ_TYPE_INDEX
     }
 }
 
+// The callback of the responses of a request, a streaming request is
+// responded with multiple responses.
+enum Responder {
+    Unary(OnResponse),
+    Streaming(OnStreamResponse),
+}
+
 pub struct RequestTask {
     req: Request,
     start_ts: Option<u64>,
     wait_time: Option<f64>,
     timer: Instant,
     statistics: Statistics,
-    on_resp: OnResponse,
+    on_resp: Responder,
     cop_req: Option<Result<CopRequest>>,
     ctx: ReqContext,
+    // the source of the request, which is used to limit its request rate.
+    source: String,
+    // the time spent in encoding the response.
+    encode_time: Duration,
 }
 
 impl RequestTask {
     pub fn new(req: Request, on_resp: OnResponse) -> RequestTask {
+        RequestTask::with_responder(req, Responder::Unary(on_resp))
+    }
+
+    /// Create a task of a streaming request, the rows of a DAG request are
+    /// responded in multiple responses, the other requests are responded in
+    /// one response.
+    pub fn new_streaming(req: Request, on_resp: OnStreamResponse) -> RequestTask {
+        RequestTask::with_responder(req, Responder::Streaming(on_resp))
+    }
+
+    fn with_responder(req: Request, on_resp: Responder) -> RequestTask {
         let timer = Instant::now_coarse();
         let deadline = timer + Duration::from_secs(REQUEST_MAX_HANDLE_SECS);
         let mut start_ts = None;
         let tp = req.get_tp();
         let mut table_scan = false;
+        let mut collect_feedback = false;
+        let mut collect_summaries = false;
         let cop_req = match tp {
             REQ_TYPE_SELECT | REQ_TYPE_INDEX => {
                 if tp == REQ_TYPE_SELECT {
@@ -326,6 +371,8 @@ impl RequestTask {
                     Err(box_err!(e))
                 } else {
                     start_ts = Some(dag.get_start_ts());
+                    collect_feedback = dag.get_collect_range_counts();
+                    collect_summaries = dag.get_collect_execution_summaries();
                     if let Some(scan) = dag.get_executors().iter().next() {
                         if scan.get_tp() == ExecType::TypeTableScan {
                             table_scan = true;
@@ -349,12 +396,18 @@ impl RequestTask {
 
             _ => Err(box_err!("unsupported tp {}", tp)),
         };
+        // TODO: limit the execution time by `Interrupt::with_max_exec_time` once
+        // kvproto carries the max execution time set by TiDB.
+        let interrupt = Interrupt::new(deadline, get_scan_tag(table_scan));
         let req_ctx = ReqContext {
-            deadline: deadline,
+            interrupt: interrupt,
             isolation_level: req.get_context().get_isolation_level(),
             fill_cache: !req.get_context().get_not_fill_cache(),
             table_scan: table_scan,
+            collect_feedback: collect_feedback,
+            collect_summaries: collect_summaries,
         };
+        let source = get_source_tag(req.get_context());
         RequestTask {
             req: req,
             start_ts: start_ts,
@@ -364,12 +417,14 @@ impl RequestTask {
             on_resp: on_resp,
             cop_req: Some(cop_req),
             ctx: req_ctx,
+            source: source,
+            encode_time: Duration::from_secs(0),
         }
     }
 
     #[inline]
-    fn check_outdated(&self) -> Result<()> {
-        self.ctx.check_if_outdated()
+    fn check_interrupted(&self) -> Result<()> {
+        self.ctx.interrupt.check_now()
     }
 
     fn stop_record_waiting(&mut self) {
@@ -400,19 +455,28 @@ impl RequestTask {
         COPR_SCAN_KEYS
             .with_label_values(&[type_str])
             .observe(self.statistics.total_op_count() as f64);
-
+        if let Some(ratio) = self.statistics.write.visible_ratio() {
+            COPR_SCAN_VISIBLE_RATIO
+                .with_label_values(&[type_str])
+                .observe(ratio);
+        }
 
         if handle_time > SLOW_QUERY_LOWER_BOUND {
             info!(
-                "[region {}] handle {:?} [{}] takes {:?} [waiting: {:?}, keys: {}, hit: {}, \
+                "[region {}] handle {:?} [{}] takes {:?} [waiting: {:?}, encoding: {:?}, \
+                 keys: {}, hit: {}, versions: {}, skipped versions: {}, tombstones: {}, \
                  ranges: {} ({:?})]",
                 self.req.get_context().get_region_id(),
                 self.start_ts,
                 type_str,
                 handle_time,
                 wait_time,
+                duration_to_sec(self.encode_time),
                 self.statistics.total_op_count(),
                 self.statistics.total_processed(),
+                self.statistics.write.versions,
+                self.statistics.write.skipped_versions,
+                self.statistics.write.tombstones,
                 self.req.get_ranges().len(),
                 self.req.get_ranges().get(0)
             );
@@ -445,7 +509,11 @@ impl BatchRunnable<Task> for Host {
         for task in tasks.drain(..) {
             match task {
                 Task::Request(req) => {
-                    if let Err(e) = req.check_outdated() {
+                    if let Err(e) = req.check_interrupted() {
+                        on_error(e, req);
+                        continue;
+                    }
+                    if let Err(e) = self.limiter.admit(&req.source) {
                         on_error(e, req);
                         continue;
                     }
@@ -581,6 +649,30 @@ fn err_resp(e: Error) -> Response {
             errorpb.set_server_is_busy(server_is_busy_err);
             resp.set_region_error(errorpb);
         }
+        Error::Canceled => {
+            COPR_REQ_ERROR.with_label_values(&["canceled"]).inc();
+            resp.set_other_error(CANCELED_ERROR_MSG.to_owned());
+        }
+        Error::Throttled(backoff) => {
+            COPR_REQ_ERROR.with_label_values(&["throttled"]).inc();
+            let mut errorpb = errorpb::Error::new();
+            errorpb.set_message(format!(
+                "request quota is exhausted, retry after {}ms",
+                duration_to_ms(backoff)
+            ));
+            let mut server_is_busy_err = ServerIsBusy::new();
+            server_is_busy_err.set_reason(ENDPOINT_IS_BUSY.to_owned());
+            errorpb.set_server_is_busy(server_is_busy_err);
+            resp.set_region_error(errorpb);
+        }
+        Error::MaxExecTime(_) => {
+            COPR_REQ_ERROR.with_label_values(&["max_exec_time"]).inc();
+            resp.set_other_error(format!("{}", e));
+        }
+        Error::Build(e) => {
+            COPR_REQ_ERROR.with_label_values(&[e.tag()]).inc();
+            resp.set_other_error(format!("{}", e));
+        }
         Error::Other(_) => {
             resp.set_other_error(format!("{}", e));
             COPR_REQ_ERROR.with_label_values(&["other"]).inc();
@@ -602,26 +694,64 @@ fn notify_batch_failed<E: Into<Error> + Debug>(e: E, reqs: Vec<RequestTask>) {
     }
 }
 
-fn respond(resp: Response, mut t: RequestTask) -> Statistics {
+fn to_scan_info(stats: &CFStatistics) -> kvrpcpb::ScanInfo {
+    let mut info = kvrpcpb::ScanInfo::new();
+    info.set_total(stats.total_op_count() as i64);
+    info.set_processed(stats.processed as i64);
+    info
+}
+
+// The MVCC versions examined in the write CF, and the visible ones among them.
+fn to_versions_scan_info(stats: &CFStatistics) -> kvrpcpb::ScanInfo {
+    let mut info = kvrpcpb::ScanInfo::new();
+    info.set_total(stats.versions as i64);
+    info.set_processed(stats.versions.saturating_sub(stats.skipped_versions) as i64);
+    info
+}
+
+// The scan details of the request are attached to the response, so TiDB can
+// tell the reads slowed down by the MVCC versions waiting for GC. The write CF
+// reports the versions instead of the ops, the tombstones are only logged.
+fn set_exec_details(resp: &mut Response, statistics: &Statistics) {
+    let mut detail = kvrpcpb::ScanDetail::new();
+    detail.set_write(to_versions_scan_info(&statistics.write));
+    detail.set_lock(to_scan_info(&statistics.lock));
+    detail.set_data(to_scan_info(&statistics.data));
+    resp.mut_exec_details().set_scan_detail(detail);
+}
+
+// The response of a streaming request is the last one of the stream.
+fn respond(mut resp: Response, mut t: RequestTask) -> Statistics {
     t.stop_record_handling();
-    (t.on_resp)(resp);
+    set_exec_details(&mut resp, &t.statistics);
+    match t.on_resp {
+        Responder::Unary(on_resp) => on_resp(resp),
+        Responder::Streaming(mut on_resp) => {
+            on_resp(resp);
+        }
+    }
     t.statistics
 }
 
 pub struct TiDbEndPoint {
     snap: Box<Snapshot>,
+    // the max number of rows in a response of a streaming request.
+    stream_batch_row_limit: usize,
 }
 
 impl TiDbEndPoint {
-    pub fn new(snap: Box<Snapshot>) -> TiDbEndPoint {
-        TiDbEndPoint { snap: snap }
+    pub fn new(snap: Box<Snapshot>, stream_batch_row_limit: usize) -> TiDbEndPoint {
+        TiDbEndPoint {
+            snap: snap,
+            stream_batch_row_limit: stream_batch_row_limit,
+        }
     }
 }
 
 impl TiDbEndPoint {
     fn handle_request(&self, mut t: RequestTask) -> Statistics {
         t.stop_record_waiting();
-        if let Err(e) = t.check_outdated() {
+        if let Err(e) = t.check_interrupted() {
             return on_error(e, t);
         }
         let resp = match t.cop_req.take().unwrap() {
@@ -643,17 +773,30 @@ impl TiDbEndPoint {
     }
 
     pub fn handle_dag(&self, dag: DAGRequest, t: &mut RequestTask) -> Result<Response> {
-        let ranges = t.req.get_ranges().to_vec();
-        let eval_ctx = Rc::new(box_try!(EvalContext::new(
-            dag.get_time_zone_offset(),
-            dag.get_flags()
-        )));
+        let ranges = KeyRanges::from_slice(t.req.get_ranges());
+        let eval_ctx = Rc::new(box_try!(EvalContext::from_request(&dag)));
         let ctx = DAGContext::new(dag, ranges, self.snap.as_ref(), eval_ctx.clone(), &t.ctx);
-        ctx.handle_request(&mut t.statistics)
+        let resp = match t.on_resp {
+            Responder::Unary(_) => ctx.handle_request(&mut t.statistics, &mut t.encode_time),
+            Responder::Streaming(ref mut on_resp) => handle_streaming_dag(
+                ctx,
+                &t.ctx.interrupt,
+                &mut t.statistics,
+                self.stream_batch_row_limit,
+                &mut t.encode_time,
+                on_resp,
+            ),
+        };
+        eval_ctx.take_eval_stats().flush();
+        let resp = resp?;
+        COPR_RESP_ENCODE_DURATION
+            .with_label_values(&[t.ctx.get_scan_tag()])
+            .observe(duration_to_sec(t.encode_time));
+        Ok(resp)
     }
 
     pub fn handle_analyze(&self, analyze: AnalyzeReq, t: &mut RequestTask) -> Result<Response> {
-        let ranges = t.req.get_ranges().to_vec();
+        let ranges = KeyRanges::from_slice(t.req.get_ranges());
         let ctx = AnalyzeContext::new(
             analyze,
             ranges,
@@ -665,6 +808,33 @@ impl TiDbEndPoint {
     }
 }
 
+// Send all the responses of a streaming DAG request but the last one, which is
+// returned and responded like the response of a unary request. The request is
+// canceled once the stream is closed, since nobody waits for the rest, and
+// it's interrupted between the responses once it's out of time.
+fn handle_streaming_dag<'s>(
+    ctx: DAGContext<'s>,
+    interrupt: &Interrupt,
+    statistics: &'s mut Statistics,
+    batch_row_limit: usize,
+    encode_time: &mut Duration,
+    on_resp: &mut OnStreamResponse,
+) -> Result<Response> {
+    let mut last = None;
+    ctx.handle_streaming_request(statistics, batch_row_limit, encode_time, |resp, finished| {
+        if finished {
+            last = Some(resp);
+            Ok(())
+        } else if on_resp(resp) {
+            interrupt.check_now()
+        } else {
+            interrupt.cancel();
+            interrupt.check_now()
+        }
+    })?;
+    Ok(last.unwrap())
+}
+
 pub fn to_pb_error(err: &Error) -> select::Error {
     let mut e = select::Error::new();
     e.set_code(DEFAULT_ERROR_CODE);
@@ -697,7 +867,7 @@ pub fn prefix_next(key: &[u8]) -> Vec<u8> {
 
 /// `is_point` checks if the key range represents a point.
 pub fn is_point(range: &KeyRange) -> bool {
-    range.get_end() == &*prefix_next(range.get_start())
+    KeyRangeRef::from(range).is_point()
 }
 
 #[inline]
@@ -740,12 +910,16 @@ pub fn get_req_pri_str(pri: CommandPri) -> &'static str {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use coprocessor::BuildError;
     use storage::engine::{self, TEMP_DIR};
     use std::sync::*;
     use std::thread;
     use std::time::Duration;
 
     use kvproto::coprocessor::Request;
+    use prometheus::HistogramVec;
+    use prometheus::core::Metric;
+    use tipb::executor::Executor;
 
     use util::worker::{FutureWorker, Worker};
     use util::time::Instant;
@@ -753,16 +927,51 @@ mod tests {
     #[test]
     fn test_get_reg_scan_tag() {
         let mut ctx = ReqContext {
-            deadline: Instant::now_coarse(),
+            interrupt: Interrupt::new(Instant::now_coarse(), STR_REQ_TYPE_SELECT),
             isolation_level: IsolationLevel::RC,
             fill_cache: true,
             table_scan: true,
+            collect_feedback: false,
+            collect_summaries: false,
         };
         assert_eq!(ctx.get_scan_tag(), STR_REQ_TYPE_SELECT);
         ctx.table_scan = false;
         assert_eq!(ctx.get_scan_tag(), STR_REQ_TYPE_INDEX);
     }
 
+    #[test]
+    fn test_build_error_resp() {
+        let cases = vec![
+            (
+                BuildError::unsupported("sig"),
+                "build_unsupported",
+                "[unsupported] sig",
+            ),
+            (
+                BuildError::invalid_request("has no executor"),
+                "build_invalid_request",
+                "[invalid request] has no executor",
+            ),
+            (
+                BuildError::Internal(box_err!("schema is refreshing")),
+                "build_internal",
+                "[internal]",
+            ),
+        ];
+        for (e, tag, msg) in cases {
+            let counter = COPR_REQ_ERROR.with_label_values(&[tag]);
+            let before = counter.get();
+            let resp = err_resp(e.into());
+            assert!(
+                resp.get_other_error().starts_with(msg),
+                "{:?}",
+                resp.get_other_error()
+            );
+            assert!(!resp.has_region_error());
+            assert_eq!(counter.get(), before + 1.0);
+        }
+    }
+
     #[test]
     fn test_req_outdated() {
         let mut worker = Worker::new("test-endpoint");
@@ -774,13 +983,121 @@ mod tests {
         worker.start_batch(end_point, 30).unwrap();
         let (tx, rx) = mpsc::channel();
         let mut task = RequestTask::new(Request::new(), box move |msg| { tx.send(msg).unwrap(); });
-        task.ctx.deadline -= Duration::from_secs(super::REQUEST_MAX_HANDLE_SECS);
+        let deadline = task.ctx.interrupt.deadline();
+        task.ctx.interrupt = Interrupt::new(
+            deadline - Duration::from_secs(super::REQUEST_MAX_HANDLE_SECS),
+            STR_REQ_TYPE_INDEX,
+        );
         worker.schedule(Task::Request(task)).unwrap();
         let resp = rx.recv_timeout(Duration::from_secs(3)).unwrap();
         assert!(!resp.get_other_error().is_empty());
         assert_eq!(resp.get_other_error(), super::OUTDATED_ERROR_MSG);
     }
 
+    #[test]
+    fn test_req_canceled() {
+        let mut worker = Worker::new("test-endpoint");
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let mut cfg = Config::default();
+        cfg.end_point_concurrency = 1;
+        let pd_worker = FutureWorker::new("test-pd-worker");
+        let end_point = Host::new(engine, worker.scheduler(), &cfg, pd_worker.scheduler());
+        worker.start_batch(end_point, 30).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let task = RequestTask::new(Request::new(), box move |msg| { tx.send(msg).unwrap(); });
+        task.ctx.interrupt.cancel();
+        worker.schedule(Task::Request(task)).unwrap();
+        let resp = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+        assert_eq!(resp.get_other_error(), super::CANCELED_ERROR_MSG);
+    }
+
+    #[test]
+    fn test_exec_details() {
+        let mut statistics = Statistics::default();
+        statistics.write.next = 4;
+        statistics.write.seek = 3;
+        statistics.write.processed = 1;
+        statistics.write.versions = 10;
+        statistics.write.skipped_versions = 8;
+        statistics.write.tombstones = 6;
+        statistics.data.get = 2;
+        statistics.data.processed = 2;
+        let mut resp = Response::new();
+        set_exec_details(&mut resp, &statistics);
+
+        let detail = resp.get_exec_details().get_scan_detail();
+        // the write CF reports the versions rather than the ops.
+        assert_eq!(detail.get_write().get_total(), 10);
+        assert_eq!(detail.get_write().get_processed(), 2);
+        assert_eq!(detail.get_lock().get_total(), 0);
+        assert_eq!(detail.get_data().get_total(), 2);
+        assert_eq!(detail.get_data().get_processed(), 2);
+    }
+
+    #[test]
+    fn test_interrupted_error_resp() {
+        let counter = COPR_REQ_ERROR.with_label_values(&["max_exec_time"]);
+        let before = counter.get();
+        let resp = err_resp(Error::MaxExecTime(Duration::from_secs(1)));
+        assert!(!resp.has_region_error());
+        assert!(
+            resp.get_other_error().starts_with("max execution time"),
+            "{:?}",
+            resp.get_other_error()
+        );
+        assert_eq!(counter.get(), before + 1.0);
+
+        let counter = COPR_REQ_ERROR.with_label_values(&["throttled"]);
+        let before = counter.get();
+        let resp = err_resp(Error::Throttled(Duration::from_millis(25)));
+        assert!(resp.get_region_error().has_server_is_busy());
+        assert!(
+            resp.get_region_error()
+                .get_message()
+                .ends_with("retry after 25ms")
+        );
+        assert_eq!(counter.get(), before + 1.0);
+    }
+
+    #[test]
+    fn test_throttled_reqs() {
+        let mut worker = Worker::new("test-endpoint");
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let mut cfg = Config::default();
+        cfg.end_point_concurrency = 1;
+        cfg.end_point_default_quota.rate = 1;
+        cfg.end_point_default_quota.burst = 2;
+        let pd_worker = FutureWorker::new("test-pd-worker");
+        let end_point = Host::new(engine, worker.scheduler(), &cfg, pd_worker.scheduler());
+        worker.start_batch(end_point, 30).unwrap();
+        let timer = Instant::now_coarse();
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..10 {
+            let tx = tx.clone();
+            let task = RequestTask::new(Request::new(), box move |msg| {
+                let _ = tx.send(msg);
+            });
+            worker.schedule(Task::Request(task)).unwrap();
+        }
+        let mut throttled = 0;
+        for _ in 0..10 {
+            let resp = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+            if resp.get_region_error().has_server_is_busy() {
+                throttled += 1;
+            }
+        }
+        // the burst is admitted at once, and a token is filled every second,
+        // the bound tolerates a slow machine.
+        let filled = duration_to_sec(timer.elapsed()).ceil() as usize;
+        let admitted = 10 - throttled;
+        assert!(
+            admitted >= 2 && admitted <= 2 + filled,
+            "admitted {} in {} seconds",
+            admitted,
+            filled
+        );
+    }
+
     #[test]
     fn test_too_many_reqs() {
         let mut worker = Worker::new("test-endpoint");
@@ -818,4 +1135,60 @@ mod tests {
         }
         panic!("suppose to get ServerIsBusy error.");
     }
+
+    #[test]
+    fn test_resp_encode_time() {
+        let mut worker = Worker::new("test-endpoint");
+        let engine = engine::new_local_engine(TEMP_DIR, &[]).unwrap();
+        let mut cfg = Config::default();
+        cfg.end_point_concurrency = 1;
+        let pd_worker = FutureWorker::new("test-pd-worker");
+        let end_point = Host::new(engine, worker.scheduler(), &cfg, pd_worker.scheduler());
+        worker.start_batch(end_point, 30).unwrap();
+
+        let samples = |h: &HistogramVec| {
+            let m = h.with_label_values(&[STR_REQ_TYPE_SELECT]).metric();
+            (m.get_histogram().get_sample_count(), m.get_histogram().get_sample_sum())
+        };
+        let (encode_cnt, encode_sum) = samples(&COPR_RESP_ENCODE_DURATION);
+        let (_, handle_sum) = samples(&COPR_REQ_HANDLE_TIME);
+
+        let reqs = 10;
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..reqs {
+            let mut scan = Executor::new();
+            scan.set_tp(ExecType::TypeTableScan);
+            let mut dag = DAGRequest::new();
+            dag.mut_executors().push(scan);
+            let mut req = Request::new();
+            req.set_tp(REQ_TYPE_DAG);
+            req.set_data(dag.write_to_bytes().unwrap());
+            let tx = tx.clone();
+            let task = RequestTask::new(req, box move |msg| {
+                let _ = tx.send(msg);
+            });
+            worker.schedule(Task::Request(task)).unwrap();
+        }
+        for _ in 0..reqs {
+            let resp = rx.recv_timeout(Duration::from_secs(3)).unwrap();
+            assert!(!resp.has_region_error());
+            assert!(resp.get_other_error().is_empty());
+        }
+
+        let (new_encode_cnt, new_encode_sum) = samples(&COPR_RESP_ENCODE_DURATION);
+        let (_, new_handle_sum) = samples(&COPR_REQ_HANDLE_TIME);
+        assert!(new_encode_cnt >= encode_cnt + reqs);
+        let encode_time = new_encode_sum - encode_sum;
+        let handle_time = new_handle_sum - handle_sum;
+        assert!(encode_time > 0.0);
+        // the encoding is a part of the handling, which is measured by the
+        // coarse clock, so a tolerance of the clock's precision is allowed.
+        let tolerance = 0.01 * reqs as f64;
+        assert!(
+            encode_time <= handle_time + tolerance,
+            "encode {}, handle {}",
+            encode_time,
+            handle_time
+        );
+    }
 }
@@ -0,0 +1,282 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use util::time::Instant;
+
+use super::{Error, Result};
+
+// The clock is read once every `CHECK_TIME_INTERVAL` checks.
+const CHECK_TIME_INTERVAL: usize = 64;
+
+const REASON_NONE: usize = 0;
+const REASON_OUTDATED: usize = 1;
+const REASON_CANCELED: usize = 2;
+const REASON_MAX_EXEC_TIME: usize = 3;
+
+struct State {
+    // the first triggered reason, it never changes once set.
+    reason: AtomicUsize,
+    checks: AtomicUsize,
+}
+
+/// `Interrupt` tells whether a request should stop. It is created by the
+/// endpoint for every request and cloned into the executors, all the clones
+/// share the same state, so a request can be canceled from anywhere, e.g.
+/// once the stream of its responses is closed.
+///
+/// Once triggered, the first reason is kept and returned by all the following
+/// checks.
+#[derive(Clone)]
+pub struct Interrupt {
+    deadline: Instant,
+    // (the max execution time, the instant the execution should stop).
+    max_exec_time: Option<(Duration, Instant)>,
+    scan_tag: &'static str,
+    state: Arc<State>,
+}
+
+impl Interrupt {
+    pub fn new(deadline: Instant, scan_tag: &'static str) -> Interrupt {
+        Interrupt {
+            deadline: deadline,
+            max_exec_time: None,
+            scan_tag: scan_tag,
+            state: Arc::new(State {
+                reason: AtomicUsize::new(REASON_NONE),
+                checks: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Limits the execution time to `limit` since now. It should be called
+    /// before the handle is cloned.
+    pub fn with_max_exec_time(mut self, limit: Duration) -> Interrupt {
+        self.max_exec_time = Some((limit, Instant::now_coarse() + limit));
+        self
+    }
+
+    #[inline]
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn cancel(&self) {
+        self.trigger(REASON_CANCELED);
+    }
+
+    /// `check` returns the error of the first triggered reason if any. The time
+    /// limits are checked only once every `CHECK_TIME_INTERVAL` calls, so it's
+    /// cheap enough to be called for every row.
+    #[inline]
+    pub fn check(&self) -> Result<()> {
+        let checks = self.state.checks.fetch_add(1, Ordering::Relaxed);
+        if checks % CHECK_TIME_INTERVAL == 0 {
+            self.check_time();
+        }
+        self.error()
+    }
+
+    /// `check_now` is like `check`, but always checks the time limits.
+    pub fn check_now(&self) -> Result<()> {
+        self.check_time();
+        self.error()
+    }
+
+    fn check_time(&self) {
+        if self.state.reason.load(Ordering::Acquire) != REASON_NONE {
+            return;
+        }
+        let now = Instant::now_coarse();
+        if self.deadline <= now {
+            self.trigger(REASON_OUTDATED);
+        } else if let Some((_, stop_at)) = self.max_exec_time {
+            if stop_at <= now {
+                self.trigger(REASON_MAX_EXEC_TIME);
+            }
+        }
+    }
+
+    #[inline]
+    fn trigger(&self, reason: usize) {
+        self.state
+            .reason
+            .compare_and_swap(REASON_NONE, reason, Ordering::AcqRel);
+    }
+
+    #[inline]
+    fn error(&self) -> Result<()> {
+        match self.state.reason.load(Ordering::Acquire) {
+            REASON_NONE => Ok(()),
+            REASON_OUTDATED => Err(Error::Outdated(
+                self.deadline,
+                Instant::now_coarse(),
+                self.scan_tag,
+            )),
+            REASON_CANCELED => Err(Error::Canceled),
+            REASON_MAX_EXEC_TIME => Err(Error::MaxExecTime(self.max_exec_time.unwrap().0)),
+            r => panic!("unknown interrupt reason {}", r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use super::*;
+
+    const TAG: &'static str = "select";
+
+    fn new_interrupt() -> Interrupt {
+        Interrupt::new(Instant::now_coarse() + Duration::from_secs(60), TAG)
+    }
+
+    fn outdated_interrupt() -> Interrupt {
+        Interrupt::new(Instant::now_coarse() - Duration::from_secs(1), TAG)
+    }
+
+    fn is_outdated(r: Result<()>) -> bool {
+        match r {
+            Err(Error::Outdated(_, _, tag)) => tag == TAG,
+            _ => false,
+        }
+    }
+
+    fn is_canceled(r: Result<()>) -> bool {
+        match r {
+            Err(Error::Canceled) => true,
+            _ => false,
+        }
+    }
+
+    fn is_max_exec_time(r: Result<()>) -> bool {
+        match r {
+            Err(Error::MaxExecTime(d)) => d == Duration::from_secs(0),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_not_interrupted() {
+        let interrupt = new_interrupt().with_max_exec_time(Duration::from_secs(60));
+        for _ in 0..CHECK_TIME_INTERVAL * 2 {
+            interrupt.check().unwrap();
+        }
+        interrupt.check_now().unwrap();
+    }
+
+    #[test]
+    fn test_outdated() {
+        let interrupt = outdated_interrupt();
+        assert!(is_outdated(interrupt.check_now()));
+        assert!(is_outdated(interrupt.check()));
+
+        // time limits are only checked periodically.
+        let interrupt = outdated_interrupt();
+        interrupt.state.checks.store(1, Ordering::Relaxed);
+        for _ in 1..CHECK_TIME_INTERVAL {
+            interrupt.check().unwrap();
+        }
+        assert!(is_outdated(interrupt.check()));
+    }
+
+    #[test]
+    fn test_canceled() {
+        let interrupt = new_interrupt();
+        let cloned = interrupt.clone();
+        let handle = thread::spawn(move || cloned.cancel());
+        handle.join().unwrap();
+        // no need to wait for the next time check.
+        interrupt.state.checks.store(1, Ordering::Relaxed);
+        assert!(is_canceled(interrupt.check()));
+        assert!(is_canceled(interrupt.check_now()));
+    }
+
+    #[test]
+    fn test_max_exec_time() {
+        let interrupt = new_interrupt().with_max_exec_time(Duration::from_secs(0));
+        assert!(is_max_exec_time(interrupt.check()));
+        assert!(is_max_exec_time(interrupt.check_now()));
+    }
+
+    #[test]
+    fn test_first_reason_wins() {
+        let interrupt = outdated_interrupt().with_max_exec_time(Duration::from_secs(0));
+        interrupt.cancel();
+        assert!(is_canceled(interrupt.check_now()));
+
+        // the deadline is checked before the max execution time.
+        let interrupt = outdated_interrupt().with_max_exec_time(Duration::from_secs(0));
+        assert!(is_outdated(interrupt.check_now()));
+        interrupt.cancel();
+        assert!(is_outdated(interrupt.check()));
+
+        let interrupt = new_interrupt().with_max_exec_time(Duration::from_secs(0));
+        assert!(is_max_exec_time(interrupt.check()));
+        interrupt.cancel();
+        assert!(is_max_exec_time(interrupt.check_now()));
+    }
+
+    // checks `interrupt` from a call that doesn't read the clock until it
+    // fails, the error must satisfy `is_err`. Returns the number of calls.
+    fn check_until<F: Fn(Result<()>) -> bool>(interrupt: &Interrupt, is_err: F) -> usize {
+        interrupt.state.checks.store(1, Ordering::Relaxed);
+        for i in 1..CHECK_TIME_INTERVAL + 1 {
+            match interrupt.check() {
+                Ok(()) => {}
+                r => {
+                    assert!(is_err(r));
+                    return i;
+                }
+            }
+        }
+        panic!("not interrupted in {} checks", CHECK_TIME_INTERVAL);
+    }
+
+    #[test]
+    fn test_check_deadline() {
+        let interrupt = Interrupt::new(Instant::now_coarse() + Duration::from_millis(10), TAG);
+        // the coarse clock must move past the deadline.
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(check_until(&interrupt, is_outdated), CHECK_TIME_INTERVAL);
+        assert!(is_outdated(interrupt.check()));
+    }
+
+    #[test]
+    fn test_check_canceled() {
+        let interrupt = new_interrupt().with_max_exec_time(Duration::from_secs(60));
+        let cloned = interrupt.clone();
+        thread::spawn(move || cloned.cancel()).join().unwrap();
+        // canceling doesn't wait for the clock to be read.
+        assert_eq!(check_until(&interrupt, is_canceled), 1);
+        assert!(is_canceled(interrupt.check()));
+    }
+
+    #[test]
+    fn test_check_max_exec_time() {
+        let limit = Duration::from_millis(10);
+        let interrupt = new_interrupt().with_max_exec_time(limit);
+        // the coarse clock must move past the limit.
+        thread::sleep(Duration::from_millis(50));
+        let is_limited = |r: Result<()>| match r {
+            Err(Error::MaxExecTime(d)) => d == limit,
+            _ => false,
+        };
+        assert_eq!(check_until(&interrupt, &is_limited), CHECK_TIME_INTERVAL);
+        assert!(is_limited(interrupt.check()));
+    }
+}
@@ -0,0 +1,333 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::mem;
+
+use kvproto::coprocessor::KeyRange;
+
+use super::endpoint::prefix_next;
+
+/// `KeyRangeRef` is a borrowed view of a single range stored in `KeyRanges`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyRangeRef<'a> {
+    start: &'a [u8],
+    end: &'a [u8],
+}
+
+impl<'a> KeyRangeRef<'a> {
+    pub fn new(start: &'a [u8], end: &'a [u8]) -> KeyRangeRef<'a> {
+        KeyRangeRef {
+            start: start,
+            end: end,
+        }
+    }
+
+    #[inline]
+    pub fn get_start(&self) -> &'a [u8] {
+        self.start
+    }
+
+    #[inline]
+    pub fn get_end(&self) -> &'a [u8] {
+        self.end
+    }
+
+    /// `is_point` checks if the key range represents a point.
+    pub fn is_point(&self) -> bool {
+        self.end == &*prefix_next(self.start)
+    }
+
+    pub fn to_key_range(&self) -> KeyRange {
+        let mut range = KeyRange::new();
+        range.set_start(self.start.to_vec());
+        range.set_end(self.end.to_vec());
+        range
+    }
+}
+
+impl<'a> From<&'a KeyRange> for KeyRangeRef<'a> {
+    fn from(range: &'a KeyRange) -> KeyRangeRef<'a> {
+        KeyRangeRef::new(range.get_start(), range.get_end())
+    }
+}
+
+/// `KeyRanges` is a compact representation of the key ranges of a request.
+///
+/// All the keys are stored in one contiguous buffer, and the i-th range is
+/// `[buf[offsets[2i]..offsets[2i+1]], buf[offsets[2i+1]..offsets[2i+2]])`,
+/// so a request with a huge amount of point ranges doesn't need two heap
+/// allocations per range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyRanges {
+    buf: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl KeyRanges {
+    pub fn with_capacity(ranges: usize, key_bytes: usize) -> KeyRanges {
+        let mut offsets = Vec::with_capacity(ranges * 2 + 1);
+        offsets.push(0);
+        KeyRanges {
+            buf: Vec::with_capacity(key_bytes),
+            offsets: offsets,
+        }
+    }
+
+    pub fn from_slice(ranges: &[KeyRange]) -> KeyRanges {
+        let key_bytes = ranges
+            .iter()
+            .map(|r| r.get_start().len() + r.get_end().len())
+            .sum();
+        let mut res = KeyRanges::with_capacity(ranges.len(), key_bytes);
+        for r in ranges {
+            res.push(r.get_start(), r.get_end());
+        }
+        res
+    }
+
+    pub fn push(&mut self, start: &[u8], end: &[u8]) {
+        if self.offsets.is_empty() {
+            self.offsets.push(0);
+        }
+        self.buf.extend_from_slice(start);
+        self.offsets.push(self.buf.len());
+        self.buf.extend_from_slice(end);
+        self.offsets.push(self.buf.len());
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1) / 2
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `idx`-th range. Panics if `idx` is out of bound.
+    #[inline]
+    pub fn get(&self, idx: usize) -> KeyRangeRef {
+        let pos = idx * 2;
+        let (s, m, e) = (
+            self.offsets[pos],
+            self.offsets[pos + 1],
+            self.offsets[pos + 2],
+        );
+        KeyRangeRef::new(&self.buf[s..m], &self.buf[m..e])
+    }
+
+    /// Reverses the order of the ranges, which is needed by desc scans.
+    pub fn reverse(&mut self) {
+        let mut res = KeyRanges::with_capacity(self.len(), self.buf.len());
+        for i in (0..self.len()).rev() {
+            let r = self.get(i);
+            res.push(r.get_start(), r.get_end());
+        }
+        *self = res;
+    }
+
+    pub fn iter(&self) -> KeyRangesIter {
+        KeyRangesIter {
+            ranges: self,
+            idx: 0,
+        }
+    }
+
+    /// Searches the range containing `key` by binary search, the ranges are
+    /// expected to be sorted in ascending order and not overlapped.
+    ///
+    /// Returns `Ok(idx)` if `key` is in the `idx`-th range, otherwise returns
+    /// `Err(idx)` where `idx` is the position of the first range after `key`.
+    pub fn binary_search_by_key(&self, key: &[u8]) -> ::std::result::Result<usize, usize> {
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let r = self.get(mid);
+            let ord = if key < r.get_start() {
+                Ordering::Greater
+            } else if key >= r.get_end() {
+                Ordering::Less
+            } else {
+                Ordering::Equal
+            };
+            match ord {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(lo)
+    }
+
+    pub fn to_key_ranges(&self) -> Vec<KeyRange> {
+        self.iter().map(|r| r.to_key_range()).collect()
+    }
+
+    /// Returns the bytes allocated on heap by this struct.
+    pub fn heap_size(&self) -> usize {
+        self.buf.capacity() + self.offsets.capacity() * mem::size_of::<usize>()
+    }
+}
+
+impl From<Vec<KeyRange>> for KeyRanges {
+    fn from(ranges: Vec<KeyRange>) -> KeyRanges {
+        KeyRanges::from_slice(&ranges)
+    }
+}
+
+impl<'a> IntoIterator for &'a KeyRanges {
+    type Item = KeyRangeRef<'a>;
+    type IntoIter = KeyRangesIter<'a>;
+
+    fn into_iter(self) -> KeyRangesIter<'a> {
+        self.iter()
+    }
+}
+
+pub struct KeyRangesIter<'a> {
+    ranges: &'a KeyRanges,
+    idx: usize,
+}
+
+impl<'a> Iterator for KeyRangesIter<'a> {
+    type Item = KeyRangeRef<'a>;
+
+    fn next(&mut self) -> Option<KeyRangeRef<'a>> {
+        if self.idx >= self.ranges.len() {
+            return None;
+        }
+        let r = self.ranges.get(self.idx);
+        self.idx += 1;
+        Some(r)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remain = self.ranges.len() - self.idx;
+        (remain, Some(remain))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem;
+
+    use kvproto::coprocessor::KeyRange;
+
+    use coprocessor::codec::table;
+    use coprocessor::endpoint::prefix_next;
+    use util::codec::number::NumberEncoder;
+
+    use super::*;
+
+    fn new_range(start: &[u8], end: &[u8]) -> KeyRange {
+        let mut range = KeyRange::new();
+        range.set_start(start.to_vec());
+        range.set_end(end.to_vec());
+        range
+    }
+
+    fn point_ranges(n: usize) -> Vec<KeyRange> {
+        (0..n)
+            .map(|i| {
+                let mut handle = Vec::with_capacity(8);
+                handle.encode_i64(i as i64).unwrap();
+                let key = table::encode_row_key(1, &handle);
+                let end = prefix_next(&key);
+                new_range(&key, &end)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_key_ranges() {
+        let ranges = vec![
+            new_range(b"a", b"b"),
+            new_range(b"", b""),
+            new_range(b"c", b"d"),
+            new_range(b"d", b"ff"),
+        ];
+        let key_ranges = KeyRanges::from_slice(&ranges);
+        assert_eq!(key_ranges.len(), ranges.len());
+        for (i, r) in key_ranges.iter().enumerate() {
+            assert_eq!(r.get_start(), ranges[i].get_start());
+            assert_eq!(r.get_end(), ranges[i].get_end());
+            assert_eq!(r, KeyRangeRef::from(&ranges[i]));
+        }
+        assert!(!key_ranges.get(0).is_point());
+        assert!(key_ranges.get(2).is_point());
+        assert_eq!(key_ranges.to_key_ranges(), ranges);
+
+        let mut reversed = key_ranges.clone();
+        reversed.reverse();
+        let mut exp = ranges.clone();
+        exp.reverse();
+        assert_eq!(reversed.to_key_ranges(), exp);
+
+        let empty = KeyRanges::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.iter().count(), 0);
+        assert_eq!(KeyRanges::from(vec![]), KeyRanges::from_slice(&[]));
+    }
+
+    #[test]
+    fn test_binary_search_by_key() {
+        let ranges = vec![
+            new_range(b"b", b"d"),
+            new_range(b"d", b"e"),
+            new_range(b"g", b"h"),
+        ];
+        let key_ranges = KeyRanges::from(ranges);
+        let cases: Vec<(&[u8], Result<usize, usize>)> = vec![
+            (b"a", Err(0)),
+            (b"b", Ok(0)),
+            (b"c", Ok(0)),
+            (b"d", Ok(1)),
+            (b"e", Err(2)),
+            (b"f", Err(2)),
+            (b"g", Ok(2)),
+            (b"h", Err(3)),
+        ];
+        for (key, exp) in cases {
+            assert_eq!(key_ranges.binary_search_by_key(key), exp, "{:?}", key);
+        }
+        assert_eq!(KeyRanges::default().binary_search_by_key(b"a"), Err(0));
+    }
+
+    #[test]
+    fn test_point_ranges_memory() {
+        let count = 50_000;
+        let ranges = point_ranges(count);
+        let pb_size: usize = ranges.capacity() * mem::size_of::<KeyRange>() +
+            ranges
+                .iter()
+                .map(|r| r.get_start().len() + r.get_end().len())
+                .sum::<usize>();
+        let key_ranges = KeyRanges::from_slice(&ranges);
+        assert_eq!(key_ranges.len(), count);
+        // Every `Vec<u8>` in `KeyRange` is a separate allocation with its own
+        // allocator overhead which is not counted above, while `KeyRanges` only
+        // allocates twice, so the real saving is even larger than asserted here.
+        assert!(
+            key_ranges.heap_size() * 3 < pb_size * 2,
+            "{} vs {}",
+            key_ranges.heap_size(),
+            pb_size
+        );
+        for (r, exp) in key_ranges.iter().zip(&ranges) {
+            assert!(r.is_point());
+            assert_eq!(r.to_key_range(), *exp);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use server::{Config, EndPointQuota};
+use util::collections::HashMap;
+use util::time::{duration_to_sec, Instant};
+
+use super::metrics::*;
+use super::{Error, Result};
+
+// The metrics label of the sources sharing the default quota.
+const DEFAULT_SOURCE_LABEL: &'static str = "default";
+
+struct TokenBucket {
+    // tokens filled per second.
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_fill: Instant,
+}
+
+impl TokenBucket {
+    fn new(quota: &EndPointQuota, now: Instant) -> TokenBucket {
+        TokenBucket {
+            rate: quota.rate as f64,
+            burst: quota.burst as f64,
+            tokens: quota.burst as f64,
+            last_fill: now,
+        }
+    }
+
+    /// `try_acquire` takes a token from the bucket. If the bucket is empty,
+    /// the time to wait for the next token is returned.
+    fn try_acquire(&mut self, now: Instant) -> Option<Duration> {
+        if now > self.last_fill {
+            let elapsed = duration_to_sec(now.duration_since(self.last_fill));
+            self.tokens = self.burst.min(self.tokens + elapsed * self.rate);
+            self.last_fill = now;
+        }
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return None;
+        }
+        let wait_ms = ((1.0 - self.tokens) * 1000.0 / self.rate).ceil();
+        Some(Duration::from_millis(wait_ms as u64))
+    }
+}
+
+/// `SourceLimiter` limits the coprocessor requests of every source with a
+/// token bucket, so a single source can't take all the capacity of the store.
+/// The sources without their own quota share the default bucket, and no limit
+/// is applied if the quota is unlimited.
+///
+/// It's only consulted by the endpoint worker before the requests are queued,
+/// so the buckets are owned by the worker and no synchronization is needed.
+pub struct SourceLimiter {
+    buckets: HashMap<String, TokenBucket>,
+    default_bucket: Option<TokenBucket>,
+}
+
+impl SourceLimiter {
+    pub fn new(cfg: &Config) -> SourceLimiter {
+        let now = Instant::now_coarse();
+        let buckets = cfg.end_point_source_quotas
+            .iter()
+            .filter(|&(_, quota)| !quota.is_unlimited())
+            .map(|(source, quota)| (source.clone(), TokenBucket::new(quota, now)))
+            .collect();
+        let default_bucket = if cfg.end_point_default_quota.is_unlimited() {
+            None
+        } else {
+            Some(TokenBucket::new(&cfg.end_point_default_quota, now))
+        };
+        SourceLimiter {
+            buckets: buckets,
+            default_bucket: default_bucket,
+        }
+    }
+
+    /// `admit` checks whether a request from `source` is allowed to run now.
+    #[inline]
+    pub fn admit(&mut self, source: &str) -> Result<()> {
+        if self.buckets.is_empty() && self.default_bucket.is_none() {
+            return Ok(());
+        }
+        self.admit_at(source, Instant::now_coarse())
+    }
+
+    fn admit_at(&mut self, source: &str, now: Instant) -> Result<()> {
+        let (bucket, label) = match self.buckets.get_mut(source) {
+            Some(bucket) => (bucket, source),
+            None => match self.default_bucket {
+                Some(ref mut bucket) => (bucket, DEFAULT_SOURCE_LABEL),
+                None => return Ok(()),
+            },
+        };
+        match bucket.try_acquire(now) {
+            None => {
+                COPR_QUOTA_CONSUMED.with_label_values(&[label]).inc();
+                Ok(())
+            }
+            Some(backoff) => {
+                COPR_QUOTA_REJECTED.with_label_values(&[label]).inc();
+                Err(Error::Throttled(backoff))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_limiter(
+        default_quota: EndPointQuota,
+        source_quotas: Vec<(&str, EndPointQuota)>,
+    ) -> SourceLimiter {
+        let mut cfg = Config::default();
+        cfg.end_point_default_quota = default_quota;
+        for (source, quota) in source_quotas {
+            cfg.end_point_source_quotas.insert(source.to_owned(), quota);
+        }
+        SourceLimiter::new(&cfg)
+    }
+
+    fn quota(rate: u64, burst: u64) -> EndPointQuota {
+        EndPointQuota {
+            rate: rate,
+            burst: burst,
+        }
+    }
+
+    fn counter(metric: &CounterVec, label: &str) -> u64 {
+        metric.with_label_values(&[label]).get() as u64
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now_coarse();
+        let mut bucket = TokenBucket::new(&quota(4, 2), now);
+        // the burst is admitted at once.
+        assert_eq!(bucket.try_acquire(now), None);
+        assert_eq!(bucket.try_acquire(now), None);
+        assert_eq!(bucket.try_acquire(now), Some(Duration::from_millis(250)));
+
+        let now = now + Duration::from_millis(125);
+        assert_eq!(bucket.try_acquire(now), Some(Duration::from_millis(125)));
+        let now = now + Duration::from_millis(125);
+        assert_eq!(bucket.try_acquire(now), None);
+
+        // the tokens never exceed the burst.
+        let now = now + Duration::from_secs(10);
+        assert_eq!(bucket.try_acquire(now), None);
+        assert_eq!(bucket.try_acquire(now), None);
+        assert!(bucket.try_acquire(now).is_some());
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let mut limiter = new_limiter(quota(0, 0), vec![("tidb-1", quota(0, 10))]);
+        assert!(limiter.buckets.is_empty());
+        assert!(limiter.default_bucket.is_none());
+        for _ in 0..1000 {
+            limiter.admit("tidb-1").unwrap();
+            limiter.admit("").unwrap();
+        }
+
+        // only the listed sources are limited if the default quota is unlimited.
+        let mut limiter = new_limiter(quota(0, 0), vec![("tidb-2", quota(1, 1))]);
+        let now = Instant::now_coarse();
+        limiter.admit_at("tidb-2", now).unwrap();
+        assert!(limiter.admit_at("tidb-2", now).is_err());
+        for _ in 0..1000 {
+            limiter.admit_at("tidb-3", now).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_proportional_admission() {
+        let (fast, slow) = ("test-limiter-fast", "test-limiter-slow");
+        let mut limiter = new_limiter(
+            quota(0, 0),
+            vec![(fast, quota(400, 10)), (slow, quota(100, 10))],
+        );
+        let consumed = (
+            counter(&COPR_QUOTA_CONSUMED, fast),
+            counter(&COPR_QUOTA_CONSUMED, slow),
+        );
+        let rejected = (
+            counter(&COPR_QUOTA_REJECTED, fast),
+            counter(&COPR_QUOTA_REJECTED, slow),
+        );
+
+        // both sources send 10 requests every millisecond for 1 second, which
+        // saturates their quotas.
+        let (mut fast_admitted, mut slow_admitted) = (0, 0);
+        let start = Instant::now_coarse();
+        for ms in 0..1000 {
+            let now = start + Duration::from_millis(ms);
+            for _ in 0..10 {
+                match limiter.admit_at(fast, now) {
+                    Ok(()) => fast_admitted += 1,
+                    Err(Error::Throttled(backoff)) => assert!(backoff > Duration::from_millis(0)),
+                    Err(e) => panic!("unexpected error {:?}", e),
+                }
+                if limiter.admit_at(slow, now).is_ok() {
+                    slow_admitted += 1;
+                }
+            }
+        }
+
+        // the initial burst plus the tokens filled in 999ms.
+        assert!(fast_admitted >= 400 && fast_admitted <= 410);
+        assert!(slow_admitted >= 100 && slow_admitted <= 110);
+        let ratio = fast_admitted as f64 / slow_admitted as f64;
+        assert!(ratio > 3.5 && ratio < 4.5, "ratio {}", ratio);
+
+        assert_eq!(
+            counter(&COPR_QUOTA_CONSUMED, fast) - consumed.0,
+            fast_admitted
+        );
+        assert_eq!(
+            counter(&COPR_QUOTA_CONSUMED, slow) - consumed.1,
+            slow_admitted
+        );
+        assert_eq!(
+            counter(&COPR_QUOTA_REJECTED, fast) - rejected.0,
+            10_000 - fast_admitted
+        );
+        assert_eq!(
+            counter(&COPR_QUOTA_REJECTED, slow) - rejected.1,
+            10_000 - slow_admitted
+        );
+    }
+
+    #[test]
+    fn test_shared_default_bucket() {
+        let mut limiter = new_limiter(quota(10, 4), vec![("tidb-1", quota(10, 1))]);
+        let now = Instant::now_coarse();
+        // the unlisted sources share the default bucket.
+        limiter.admit_at("tidb-2", now).unwrap();
+        limiter.admit_at("tidb-3", now).unwrap();
+        limiter.admit_at("", now).unwrap();
+        limiter.admit_at("tidb-2", now).unwrap();
+        assert!(limiter.admit_at("tidb-3", now).is_err());
+
+        // the listed source isn't affected by the default bucket.
+        limiter.admit_at("tidb-1", now).unwrap();
+        match limiter.admit_at("tidb-1", now) {
+            Err(Error::Throttled(backoff)) => assert_eq!(backoff, Duration::from_millis(100)),
+            r => panic!("expect throttled, got {:?}", r),
+        }
+    }
+}
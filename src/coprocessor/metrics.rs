@@ -46,6 +46,14 @@ lazy_static! {
             exponential_buckets(0.0005, 2.0, 20).unwrap()
         ).unwrap();
 
+    pub static ref COPR_RESP_ENCODE_DURATION: HistogramVec =
+        register_histogram_vec!(
+            "tikv_coprocessor_response_encode_seconds",
+            "Bucketed histogram of coprocessor response encoding duration",
+            &["req"],
+            exponential_buckets(0.0001, 2.0, 20).unwrap()
+        ).unwrap();
+
     pub static ref COPR_REQ_ERROR: CounterVec =
         register_counter_vec!(
             "tikv_coprocessor_request_error",
@@ -75,6 +83,14 @@ lazy_static! {
              &["req", "cf", "tag"]
          ).unwrap();
 
+    pub static ref COPR_SCAN_VISIBLE_RATIO: HistogramVec =
+        register_histogram_vec!(
+            "tikv_coprocessor_scan_visible_ratio",
+            "Bucketed histogram of coprocessor per request ratio of visible MVCC versions",
+            &["req"],
+            vec![0.01, 0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 0.95, 1.0]
+        ).unwrap();
+
     pub static ref COPR_EXECUTOR_COUNT: CounterVec =
         register_counter_vec!(
             "tikv_coprocessor_executor_count",
@@ -97,4 +113,31 @@ lazy_static! {
             vec![1.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 14.0, 16.0, 18.0,
             20.0, 24.0, 28.0, 32.0, 48.0, 64.0, 96.0, 128.0, 192.0, 256.0]
         ).unwrap();
+
+    pub static ref COPR_QUOTA_CONSUMED: CounterVec =
+        register_counter_vec!(
+            "tikv_coprocessor_quota_consumed",
+            "Total number of coprocessor request quota consumed by each source",
+            &["source"]
+        ).unwrap();
+
+    pub static ref COPR_QUOTA_REJECTED: CounterVec =
+        register_counter_vec!(
+            "tikv_coprocessor_quota_rejected",
+            "Total number of coprocessor requests rejected by the quota of each source",
+            &["source"]
+        ).unwrap();
+
+    pub static ref COPR_EVAL_WARNINGS: CounterVec =
+        register_counter_vec!(
+            "tikv_coprocessor_eval_warnings",
+            "Total number of errors returned as warnings in evaluations of each class",
+            &["class"]
+        ).unwrap();
+
+    pub static ref COPR_UNKNOWN_FLAGS: Counter =
+        register_counter!(
+            "tikv_coprocessor_unknown_flags_total",
+            "Total number of requests carrying the flags not supported"
+        ).unwrap();
 }
@@ -15,11 +15,16 @@ mod endpoint;
 mod metrics;
 mod dag;
 mod statistics;
+mod key_ranges;
+mod interrupt;
+mod limiter;
+mod util;
 pub mod select;
 pub mod codec;
 
 use std::result;
 use std::error;
+use std::time::Duration;
 
 use kvproto::kvrpcpb::LockInfo;
 use kvproto::errorpb;
@@ -27,6 +32,48 @@ use kvproto::errorpb;
 use storage::{engine, mvcc, txn};
 use util::time::Instant;
 
+// `BuildError` is returned when a request can't be turned into executors.
+// `Unsupported` means the request can never be pushed down, `InvalidRequest`
+// means the request itself is malformed, while `Internal` may be transient
+// and the request can be retried.
+quick_error! {
+    #[derive(Debug)]
+    pub enum BuildError {
+        Unsupported { what: String } {
+            description("unsupported request")
+            display("[unsupported] {}", what)
+        }
+        InvalidRequest { why: String } {
+            description("invalid request")
+            display("[invalid request] {}", why)
+        }
+        Internal(err: Box<error::Error + Send + Sync>) {
+            cause(err.as_ref())
+            description(err.description())
+            display("[internal] {:?}", err)
+        }
+    }
+}
+
+impl BuildError {
+    pub fn unsupported<T: Into<String>>(what: T) -> BuildError {
+        BuildError::Unsupported { what: what.into() }
+    }
+
+    pub fn invalid_request<T: Into<String>>(why: T) -> BuildError {
+        BuildError::InvalidRequest { why: why.into() }
+    }
+
+    /// Get the tag used by metrics.
+    pub fn tag(&self) -> &'static str {
+        match *self {
+            BuildError::Unsupported { .. } => "build_unsupported",
+            BuildError::InvalidRequest { .. } => "build_invalid_request",
+            BuildError::Internal(_) => "build_internal",
+        }
+    }
+}
+
 quick_error! {
     #[derive(Debug)]
     pub enum Error {
@@ -44,6 +91,23 @@ quick_error! {
         Full(allow: usize) {
             description("running queue is full")
         }
+        Canceled {
+            description("request is canceled")
+        }
+        Throttled(backoff: Duration) {
+            description("request quota of the source is exhausted")
+            display("request quota is exhausted, retry after {:?}", backoff)
+        }
+        MaxExecTime(limit: Duration) {
+            description("max execution time is exceeded")
+            display("max execution time {:?} is exceeded", limit)
+        }
+        Build(err: BuildError) {
+            from()
+            cause(err)
+            description(err.description())
+            display("{}", err)
+        }
         Other(err: Box<error::Error + Send + Sync>) {
             from()
             cause(err.as_ref())
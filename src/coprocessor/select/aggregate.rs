@@ -69,16 +69,16 @@ struct First {
 
 impl AggrFunc for First {
     fn update(&mut self, _: &EvalContext, mut args: Vec<Datum>) -> Result<()> {
-        if self.e.is_some() {
-            return Ok(());
-        }
         if args.len() != 1 {
             return Err(box_err!(
                 "Wrong number of args for AggFuncFirstRow: {}",
                 args.len()
             ));
         }
-        self.e = args.pop();
+        // NULL is a legitimate first value, so it's kept.
+        if self.e.is_none() {
+            self.e = args.pop();
+        }
         Ok(())
     }
 
@@ -192,3 +192,78 @@ impl AggrFunc for Extremum {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use coprocessor::codec::mysql::{Duration, Json, Time};
+
+    use super::*;
+
+    fn values_of_types() -> Vec<(Datum, Datum)> {
+        vec![
+            (Datum::I64(-1), Datum::I64(2)),
+            (Datum::U64(1), Datum::U64(2)),
+            (Datum::F64(1.5), Datum::F64(2.5)),
+            (Datum::Bytes(b"abc".to_vec()), Datum::Bytes(b"abd".to_vec())),
+            (
+                Datum::Dec("1.23".parse().unwrap()),
+                Datum::Dec("12.3".parse().unwrap()),
+            ),
+            (
+                Datum::Time(Time::parse_utc_datetime("2012-12-31 11:30:45", 0).unwrap()),
+                Datum::Time(Time::parse_utc_datetime("2013-01-01 00:00:00", 0).unwrap()),
+            ),
+            (
+                Datum::Dur(Duration::parse(b"-10:11:12", 0).unwrap()),
+                Datum::Dur(Duration::parse(b"10:11:12", 0).unwrap()),
+            ),
+            (
+                Datum::Json("1.5".parse::<Json>().unwrap()),
+                Datum::Json("2".parse::<Json>().unwrap()),
+            ),
+        ]
+    }
+
+    fn eval_aggr(tp: ExprType, args: &[Datum]) -> Datum {
+        let ctx = EvalContext::default();
+        let mut aggr = build_aggr_func(tp).unwrap();
+        for arg in args {
+            aggr.update(&ctx, vec![arg.clone()]).unwrap();
+        }
+        let mut res = vec![];
+        aggr.calc(&mut res).unwrap();
+        assert_eq!(res.len(), 1);
+        res.pop().unwrap()
+    }
+
+    #[test]
+    fn test_first() {
+        assert_eq!(eval_aggr(ExprType::First, &[]), Datum::Null);
+        for (small, big) in values_of_types() {
+            let args = vec![big.clone(), Datum::Null, small.clone()];
+            assert_eq!(eval_aggr(ExprType::First, &args), big);
+            // the first NULL must not be skipped.
+            let args = vec![Datum::Null, small.clone(), big.clone()];
+            assert_eq!(eval_aggr(ExprType::First, &args), Datum::Null);
+        }
+
+        let ctx = EvalContext::default();
+        let mut aggr = build_aggr_func(ExprType::First).unwrap();
+        aggr.update(&ctx, vec![Datum::I64(1)]).unwrap();
+        assert!(aggr.update(&ctx, vec![]).is_err());
+        assert!(aggr.update(&ctx, vec![Datum::I64(1), Datum::I64(2)]).is_err());
+    }
+
+    #[test]
+    fn test_extremum() {
+        let ctx = EvalContext::default();
+        for (small, big) in values_of_types() {
+            assert_eq!(small.cmp(&ctx, &big).unwrap(), Ordering::Less);
+            let args = vec![Datum::Null, small.clone(), big.clone(), Datum::Null];
+            assert_eq!(eval_aggr(ExprType::Max, &args), big);
+            assert_eq!(eval_aggr(ExprType::Min, &args), small);
+        }
+        assert_eq!(eval_aggr(ExprType::Max, &[Datum::Null]), Datum::Null);
+        assert_eq!(eval_aggr(ExprType::Min, &[]), Datum::Null);
+    }
+}
@@ -24,7 +24,7 @@ use coprocessor::codec::{datum, mysql, table};
 use coprocessor::codec::table::{RowColsDict, TableDecoder};
 use coprocessor::codec::datum::Datum;
 use coprocessor::metrics::*;
-use coprocessor::{Error, Result};
+use coprocessor::{util, Error, Result};
 use coprocessor::endpoint::{get_chunk, get_pk, is_point, prefix_next, to_pb_error, ReqContext,
                             BATCH_ROW_COUNT, SINGLE_GROUP};
 use util::{escape, Either};
@@ -76,6 +76,7 @@ impl<'a> SelectContext<'a> {
         } else {
             self.get_rows_from_idx(ranges)
         };
+        self.core.ctx.take_eval_stats().flush();
         let mut resp = Response::new();
         let mut sel_resp = SelectResponse::new();
         match res {
@@ -110,7 +111,7 @@ impl<'a> SelectContext<'a> {
                 duration_to_ms(timer.elapsed())
             );
             collected += row_cnt;
-            self.req_ctx.check_if_outdated()?;
+            self.req_ctx.interrupt.check()?;
         }
         if self.core.topn {
             self.core.collect_topn_rows()
@@ -168,7 +169,7 @@ impl<'a> SelectContext<'a> {
             )?;
             while self.core.limit > row_count {
                 if row_count & REQUEST_CHECKPOINT == 0 {
-                    self.req_ctx.check_if_outdated()?;
+                    self.req_ctx.interrupt.check()?;
                 }
                 let kv = if self.core.desc_scan {
                     scanner.reverse_seek(Key::from_raw(&seek_key))?
@@ -211,7 +212,7 @@ impl<'a> SelectContext<'a> {
                 break;
             }
             collected += self.get_idx_row_from_range(r)?;
-            self.req_ctx.check_if_outdated()?;
+            self.req_ctx.interrupt.check()?;
         }
         if self.core.topn {
             self.core.collect_topn_rows()
@@ -247,7 +248,7 @@ impl<'a> SelectContext<'a> {
         )?;
         while row_cnt < self.core.limit {
             if row_cnt & REQUEST_CHECKPOINT == 0 {
-                self.req_ctx.check_if_outdated()?;
+                self.req_ctx.interrupt.check()?;
             }
             let nk = if self.core.desc_scan {
                 scanner.reverse_seek(Key::from_raw(&seek_key))?
@@ -368,7 +369,7 @@ impl SelectContextCore {
 
         let limit = if sel.has_limit() {
             COPR_EXECUTOR_COUNT.with_label_values(&["limit"]).inc();
-            sel.get_limit() as usize
+            util::i64_to_usize(sel.get_limit(), "limit")?
         } else {
             usize::MAX
         };
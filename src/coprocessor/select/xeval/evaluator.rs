@@ -11,12 +11,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
 use std::ascii::AsciiExt;
-use std::result;
+use std::cell::{Cell, RefCell};
+use std::{mem, result};
+use std::sync::atomic::{self, AtomicUsize, ATOMIC_USIZE_INIT};
 
-use chrono::FixedOffset;
+use chrono::{DateTime, FixedOffset, Utc};
 use tipb::expression::{Expr, ExprType, ScalarFuncSig};
+use tipb::select::{self, DAGRequest};
 
 use util::is_even;
 use util::codec::number::NumberDecoder;
@@ -26,6 +29,7 @@ use coprocessor::codec;
 use coprocessor::codec::datum::{Datum, DatumDecoder};
 use coprocessor::codec::mysql::{DecimalDecoder, Duration, ModifyType, Time, MAX_FSP};
 use coprocessor::codec::mysql::json::{json_array, json_object};
+use coprocessor::metrics::*;
 use super::{Error, Result};
 
 /// Flags are used by `SelectRequest.flags` to handle execution mode, like how to handle
@@ -38,30 +42,263 @@ pub const FLAG_IGNORE_TRUNCATE: u64 = 1;
 /// This flag only matters if `FLAG_IGNORE_TRUNCATE` is not set, in strict sql mode, truncate error
 /// should be returned as error, in non-strict sql mode, truncate error should be saved as warning.
 pub const FLAG_TRUNCATE_AS_WARNING: u64 = 1 << 1;
+/// `FLAG_PAD_CHAR_TO_FULL_LENGTH` indicates if the CHAR values should be padded to their full
+/// length with spaces when they are read, which is the `PAD_CHAR_TO_FULL_LENGTH` sql mode.
+pub const FLAG_PAD_CHAR_TO_FULL_LENGTH: u64 = 1 << 2;
+/// `FLAG_IN_INSERT_STMT` indicates if this is a INSERT statement.
+pub const FLAG_IN_INSERT_STMT: u64 = 1 << 3;
+/// `FLAG_IN_UPDATE_OR_DELETE_STMT` indicates if this is a UPDATE statement or a DELETE statement.
+pub const FLAG_IN_UPDATE_OR_DELETE_STMT: u64 = 1 << 4;
+/// `FLAG_IN_SELECT_STMT` indicates if this is a SELECT statement.
+pub const FLAG_IN_SELECT_STMT: u64 = 1 << 5;
+/// `FLAG_OVERFLOW_AS_WARNING` indicates if overflow error should be returned as warning.
+/// In strict sql mode, overflow error should be returned as error,
+/// in non-strict sql mode, overflow error should be saved as warning.
+pub const FLAG_OVERFLOW_AS_WARNING: u64 = 1 << 6;
+/// `FLAG_IGNORE_ZERO_IN_DATE` indicates if the dates with zero month or day like '2017-00-10'
+/// should be regarded as the zero date with a warning even in strict sql mode, it's set for
+/// read-only statements.
+pub const FLAG_IGNORE_ZERO_IN_DATE: u64 = 1 << 7;
+/// `FLAG_DIVIDED_BY_ZERO_AS_WARNING` indicates if divided by zero error should be returned as
+/// warning. Dividing by zero is always a warning in SELECT statements, in other statements it's
+/// an error unless this flag is set.
+pub const FLAG_DIVIDED_BY_ZERO_AS_WARNING: u64 = 1 << 8;
+
+/// All the flags supported, the other bits are unknown and ignored.
+pub const KNOWN_FLAGS: u64 = FLAG_IGNORE_TRUNCATE | FLAG_TRUNCATE_AS_WARNING |
+    FLAG_PAD_CHAR_TO_FULL_LENGTH | FLAG_IN_INSERT_STMT |
+    FLAG_IN_UPDATE_OR_DELETE_STMT | FLAG_IN_SELECT_STMT |
+    FLAG_OVERFLOW_AS_WARNING | FLAG_IGNORE_ZERO_IN_DATE |
+    FLAG_DIVIDED_BY_ZERO_AS_WARNING;
+
+/// The bits of TiDB's sql_mode which change how the errors are handled.
+pub const MODE_STRICT_TRANS_TABLES: u64 = 1 << 21;
+pub const MODE_STRICT_ALL_TABLES: u64 = 1 << 22;
+pub const MODE_NO_ZERO_IN_DATE: u64 = 1 << 23;
+pub const MODE_NO_ZERO_DATE: u64 = 1 << 24;
+pub const MODE_ERROR_FOR_DIVISION_BY_ZERO: u64 = 1 << 26;
+
+/// `EvalStats` counts the errors returned as warnings or ignored in an
+/// evaluation, so the regions producing lots of them can be found.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EvalStats {
+    pub truncate_as_warning_cnt: u64,
+    pub overflow_as_warning_cnt: u64,
+    pub div_by_zero_cnt: u64,
+    pub zero_in_date_cnt: u64,
+}
+
+impl EvalStats {
+    /// Add the counts to `COPR_EVAL_WARNINGS`.
+    pub fn flush(&self) {
+        let classes = [
+            ("truncate", self.truncate_as_warning_cnt),
+            ("overflow", self.overflow_as_warning_cnt),
+            ("div_by_zero", self.div_by_zero_cnt),
+            ("zero_in_date", self.zero_in_date_cnt),
+        ];
+        for &(class, cnt) in &classes {
+            if cnt > 0 {
+                COPR_EVAL_WARNINGS
+                    .with_label_values(&[class])
+                    .inc_by(cnt as f64)
+                    .unwrap();
+            }
+        }
+    }
+}
+
+/// The max number of warnings kept by `EvalWarnings` by default, the others
+/// are only counted.
+pub const DEFAULT_MAX_WARNING_CNT: usize = 64;
+/// The upper bound of the max number of warnings requested, which is the
+/// largest `max_error_count` of MySQL.
+pub const MAX_WARNING_CNT_LIMIT: usize = 65535;
+
+/// The MySQL error codes of the errors returned as warnings.
+pub const ERR_WARN_DATA_OUT_OF_RANGE: i32 = 1264;
+pub const ERR_TRUNCATED: i32 = 1265;
+pub const ERR_TRUNCATED_WRONG_VALUE: i32 = 1292;
+pub const ERR_DIVISION_BY_ZERO: i32 = 1365;
+pub const ERR_DATA_OUT_OF_RANGE: i32 = 1690;
+
+// The sequence numbers of the warnings kept, in the order they are generated.
+static WARNING_SEQ: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// `EvalWarnings` collects the errors returned as warnings in an evaluation,
+/// which are responded to TiDB for `SHOW WARNINGS`. All the warnings are
+/// counted, but only the first `max_warning_cnt` generated ones are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalWarnings {
+    max_warning_cnt: usize,
+    pub warning_cnt: usize,
+    pub warnings: Vec<select::Error>,
+    // the sequence numbers of `warnings`, they are ascending.
+    seqs: Vec<usize>,
+}
+
+impl EvalWarnings {
+    /// Create the warnings keeping at most `max_warning_cnt` ones, 0 means
+    /// they are only counted. The count is clamped to `MAX_WARNING_CNT_LIMIT`,
+    /// and no more than `DEFAULT_MAX_WARNING_CNT` ones are preallocated, since
+    /// most requests get few warnings.
+    pub fn new(max_warning_cnt: usize) -> EvalWarnings {
+        let max_warning_cnt = cmp::min(max_warning_cnt, MAX_WARNING_CNT_LIMIT);
+        EvalWarnings {
+            max_warning_cnt: max_warning_cnt,
+            warning_cnt: 0,
+            warnings: Vec::with_capacity(cmp::min(max_warning_cnt, DEFAULT_MAX_WARNING_CNT)),
+            seqs: Vec::with_capacity(cmp::min(max_warning_cnt, DEFAULT_MAX_WARNING_CNT)),
+        }
+    }
+
+    pub fn append_warning(&mut self, code: i32, msg: String) {
+        self.warning_cnt += 1;
+        if self.warnings.len() < self.max_warning_cnt {
+            let mut warning = select::Error::new();
+            warning.set_code(code);
+            warning.set_msg(msg);
+            self.warnings.push(warning);
+            self.seqs.push(WARNING_SEQ.fetch_add(1, atomic::Ordering::Relaxed));
+        }
+    }
+
+    /// Merge the warnings of `other` into `self`, and drain `other`. The first
+    /// `max_warning_cnt` generated warnings of both are kept, so the result
+    /// doesn't depend on the order of the merges, as long as no set keeps
+    /// fewer warnings than `self`.
+    pub fn merge(&mut self, other: &mut EvalWarnings) {
+        if other.warning_cnt == 0 {
+            return;
+        }
+        self.warning_cnt += other.warning_cnt;
+        other.warning_cnt = 0;
+        let kept = cmp::min(
+            self.warnings.len() + other.warnings.len(),
+            self.max_warning_cnt,
+        );
+        let mut mine = mem::replace(&mut self.warnings, Vec::with_capacity(kept))
+            .into_iter()
+            .zip(mem::replace(&mut self.seqs, Vec::with_capacity(kept)))
+            .peekable();
+        let mut theirs = other
+            .warnings
+            .drain(..)
+            .zip(other.seqs.drain(..))
+            .peekable();
+        while self.warnings.len() < kept {
+            let take_mine = match (mine.peek(), theirs.peek()) {
+                (Some(&(_, a)), Some(&(_, b))) => a < b,
+                (Some(_), None) => true,
+                _ => false,
+            };
+            let (warning, seq) = if take_mine {
+                mine.next().unwrap()
+            } else {
+                theirs.next().unwrap()
+            };
+            self.warnings.push(warning);
+            self.seqs.push(seq);
+        }
+    }
+}
 
 #[derive(Debug)]
 /// Some global variables needed in an evaluation.
 pub struct EvalContext {
     /// timezone to use when parse/calculate time.
     pub tz: FixedOffset,
+    /// the raw flags of the request, including the unknown ones.
+    pub flags: u64,
     pub ignore_truncate: bool,
     pub truncate_as_warning: bool,
+    pub pad_char_to_full_length: bool,
+    pub in_insert_stmt: bool,
+    pub in_update_or_delete_stmt: bool,
+    pub in_select_stmt: bool,
+    pub overflow_as_warning: bool,
+    pub ignore_zero_in_date: bool,
+    pub div_by_zero_as_warning: bool,
+    /// the sql_mode of the session, `None` if the request doesn't carry it,
+    /// the flags alone decide how the errors are handled then.
+    pub sql_mode: Option<u64>,
+    /// the errors returned as warnings so far, the context is shared by the
+    /// executors, so they are counted in a `Cell`.
+    pub stats: Cell<EvalStats>,
+    /// the errors returned as warnings so far, they are kept for the response.
+    pub warnings: RefCell<EvalWarnings>,
+    /// the current time in `tz`, it's fixed when it's used the first time,
+    /// so all the evaluations in a statement see the same time.
+    pub cur_time: Cell<Option<DateTime<FixedOffset>>>,
 }
 
 impl Default for EvalContext {
     fn default() -> EvalContext {
         EvalContext {
             tz: FixedOffset::east(0),
+            flags: 0,
             ignore_truncate: false,
             truncate_as_warning: false,
+            pad_char_to_full_length: false,
+            in_insert_stmt: false,
+            in_update_or_delete_stmt: false,
+            in_select_stmt: false,
+            overflow_as_warning: false,
+            ignore_zero_in_date: false,
+            div_by_zero_as_warning: false,
+            sql_mode: None,
+            stats: Cell::new(EvalStats::default()),
+            warnings: RefCell::new(EvalWarnings::new(DEFAULT_MAX_WARNING_CNT)),
+            cur_time: Cell::new(None),
         }
     }
 }
 
 const ONE_DAY: i64 = 3600 * 24;
 
-impl EvalContext {
-    pub fn new(tz_offset: i64, flags: u64) -> Result<EvalContext> {
+/// `EvalContextBuilder` builds an `EvalContext` and validates it, e.g.
+/// `EvalContext::builder().tz_offset(3600).flag(FLAG_IN_SELECT_STMT).build()`.
+#[derive(Default)]
+pub struct EvalContextBuilder {
+    tz_offset: i64,
+    flags: u64,
+    sql_mode: Option<u64>,
+    strict_flags: bool,
+}
+
+impl EvalContextBuilder {
+    /// Set the offset of the timezone in seconds.
+    pub fn tz_offset(mut self, tz_offset: i64) -> EvalContextBuilder {
+        self.tz_offset = tz_offset;
+        self
+    }
+
+    /// Set all the flags, the ones set before are replaced.
+    pub fn flags(mut self, flags: u64) -> EvalContextBuilder {
+        self.flags = flags;
+        self
+    }
+
+    /// Add a flag to the ones set before.
+    pub fn flag(mut self, flag: u64) -> EvalContextBuilder {
+        self.flags |= flag;
+        self
+    }
+
+    pub fn sql_mode(mut self, sql_mode: u64) -> EvalContextBuilder {
+        self.sql_mode = Some(sql_mode);
+        self
+    }
+
+    /// Reject the unknown flags instead of ignoring them with a warning, so
+    /// the tests catch the flags not supported yet when tipb is upgraded.
+    pub fn strict_flags(mut self, strict_flags: bool) -> EvalContextBuilder {
+        self.strict_flags = strict_flags;
+        self
+    }
+
+    pub fn build(self) -> Result<EvalContext> {
+        let tz_offset = self.tz_offset;
         if tz_offset <= -ONE_DAY || tz_offset >= ONE_DAY {
             return Err(Error::Eval(format!("invalid tz offset {}", tz_offset)));
         }
@@ -69,17 +306,193 @@ impl EvalContext {
             None => return Err(Error::Eval(format!("invalid tz offset {}", tz_offset))),
             Some(tz) => tz,
         };
+        let flags = self.flags;
+        let stmt_flags = flags & (FLAG_IN_INSERT_STMT | FLAG_IN_UPDATE_OR_DELETE_STMT |
+            FLAG_IN_SELECT_STMT);
+        if stmt_flags.count_ones() > 1 {
+            return Err(Error::Eval(format!(
+                "conflicting statement flags {:#x}",
+                stmt_flags
+            )));
+        }
+        let unknown_flags = flags & !KNOWN_FLAGS;
+        if unknown_flags != 0 {
+            if self.strict_flags {
+                return Err(Error::Eval(format!("unknown flags {:#x}", unknown_flags)));
+            }
+            warn!("unknown flags {:#x} are ignored", unknown_flags);
+            COPR_UNKNOWN_FLAGS.inc();
+        }
 
         let e = EvalContext {
             tz: tz,
+            flags: flags,
             ignore_truncate: (flags & FLAG_IGNORE_TRUNCATE) > 0,
             truncate_as_warning: (flags & FLAG_TRUNCATE_AS_WARNING) > 0,
+            pad_char_to_full_length: (flags & FLAG_PAD_CHAR_TO_FULL_LENGTH) > 0,
+            in_insert_stmt: (flags & FLAG_IN_INSERT_STMT) > 0,
+            in_update_or_delete_stmt: (flags & FLAG_IN_UPDATE_OR_DELETE_STMT) > 0,
+            in_select_stmt: (flags & FLAG_IN_SELECT_STMT) > 0,
+            overflow_as_warning: (flags & FLAG_OVERFLOW_AS_WARNING) > 0,
+            ignore_zero_in_date: (flags & FLAG_IGNORE_ZERO_IN_DATE) > 0,
+            div_by_zero_as_warning: (flags & FLAG_DIVIDED_BY_ZERO_AS_WARNING) > 0,
+            sql_mode: self.sql_mode,
+            stats: Cell::new(EvalStats::default()),
+            warnings: RefCell::new(EvalWarnings::new(DEFAULT_MAX_WARNING_CNT)),
+            cur_time: Cell::new(None),
         };
 
         Ok(e)
     }
 }
 
+impl EvalContext {
+    pub fn new(tz_offset: i64, flags: u64) -> Result<EvalContext> {
+        EvalContext::builder().tz_offset(tz_offset).flags(flags).build()
+    }
+
+    pub fn builder() -> EvalContextBuilder {
+        EvalContextBuilder::default()
+    }
+
+    /// Build the context from the time zone and the flags of a DAG request.
+    pub fn from_request(req: &DAGRequest) -> Result<EvalContext> {
+        // TODO: set the sql mode once tipb carries it.
+        EvalContext::builder()
+            .tz_offset(req.get_time_zone_offset())
+            .flags(req.get_flags())
+            .build()
+    }
+
+    /// Get the current time in the timezone of the context, it's the same in
+    /// all the calls.
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        if let Some(t) = self.cur_time.get() {
+            return t;
+        }
+        let t = Utc::now().with_timezone(&self.tz);
+        self.cur_time.set(Some(t));
+        t
+    }
+
+    #[inline]
+    fn update_stats<F: FnOnce(&mut EvalStats)>(&self, f: F) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    /// Record a truncation returned as warning or ignored, only the former is
+    /// kept as a warning.
+    #[inline]
+    pub fn on_truncate_as_warning(&self) {
+        self.update_stats(|s| s.truncate_as_warning_cnt += 1);
+        if !self.ignore_truncate {
+            self.append_warning(ERR_TRUNCATED, "Data Truncated".to_owned());
+        }
+    }
+
+    /// Record an overflow returned as warning with its MySQL error code.
+    #[inline]
+    pub fn on_overflow_as_warning(&self, code: i32, msg: String) {
+        self.update_stats(|s| s.overflow_as_warning_cnt += 1);
+        self.append_warning(code, msg);
+    }
+
+    /// Take the counts of the errors returned as warnings so far, they are
+    /// reset in the context.
+    pub fn take_eval_stats(&self) -> EvalStats {
+        let stats = self.stats.get();
+        self.stats.set(EvalStats::default());
+        stats
+    }
+
+    /// Append an error returned as warning with its MySQL error code.
+    pub fn append_warning(&self, code: i32, msg: String) {
+        self.warnings.borrow_mut().append_warning(code, msg);
+    }
+
+    /// Set the max number of warnings kept, the warnings collected so far are
+    /// dropped.
+    pub fn set_max_warning_cnt(&self, max_warning_cnt: usize) {
+        *self.warnings.borrow_mut() = EvalWarnings::new(max_warning_cnt);
+    }
+
+    /// Take the warnings collected so far, they are reset in the context.
+    pub fn take_warnings(&self) -> EvalWarnings {
+        let mut warnings = self.warnings.borrow_mut();
+        let max_warning_cnt = warnings.max_warning_cnt;
+        mem::replace(&mut *warnings, EvalWarnings::new(max_warning_cnt))
+    }
+
+    /// Whether the statement writes the values, i.e. it's an INSERT, UPDATE
+    /// or DELETE statement.
+    #[inline]
+    pub fn in_write_stmt(&self) -> bool {
+        self.in_insert_stmt || self.in_update_or_delete_stmt
+    }
+
+    #[inline]
+    fn has_sql_mode(&self, mode: u64, default: bool) -> bool {
+        self.sql_mode.map_or(default, |m| m & mode != 0)
+    }
+
+    /// Whether the sql mode is strict. Without the sql mode, it's regarded as
+    /// strict, so the errors are returned unless the flags say otherwise.
+    #[inline]
+    pub fn strict_sql_mode(&self) -> bool {
+        self.has_sql_mode(MODE_STRICT_TRANS_TABLES | MODE_STRICT_ALL_TABLES, true)
+    }
+
+    /// Whether dividing by zero may be an error, it's true without the sql
+    /// mode like `strict_sql_mode`.
+    #[inline]
+    pub fn error_for_division_by_zero(&self) -> bool {
+        self.has_sql_mode(MODE_ERROR_FOR_DIVISION_BY_ZERO, true)
+    }
+
+    /// Whether the dates with zero parts like '2017-00-01' are rejected.
+    #[inline]
+    pub fn no_zero_in_date(&self) -> bool {
+        self.has_sql_mode(MODE_NO_ZERO_IN_DATE, false)
+    }
+
+    /// Whether the zero date '0000-00-00' is rejected.
+    #[inline]
+    pub fn no_zero_date(&self) -> bool {
+        self.has_sql_mode(MODE_NO_ZERO_DATE, false)
+    }
+
+    /// `handle_division_by_zero` decides what dividing by zero results in. It's
+    /// an error only in INSERT, UPDATE or DELETE statements with the strict sql
+    /// mode erroring for division by zero, unless `FLAG_DIVIDED_BY_ZERO_AS_WARNING`
+    /// is set. Otherwise it's a warning and the caller should return NULL.
+    pub fn handle_division_by_zero(&self) -> codec::Result<()> {
+        if !self.in_write_stmt() || self.div_by_zero_as_warning || !self.strict_sql_mode() ||
+            !self.error_for_division_by_zero()
+        {
+            self.update_stats(|s| s.div_by_zero_cnt += 1);
+            self.append_warning(ERR_DIVISION_BY_ZERO, "Division by 0".to_owned());
+            return Ok(());
+        }
+        Err(box_err!("[{}] Division by 0", ERR_DIVISION_BY_ZERO))
+    }
+
+    /// `handle_zero_in_date` decides what a date with zero month or day like
+    /// '2017-00-10' results in. It's a warning and the caller should use the
+    /// zero date if `FLAG_IGNORE_ZERO_IN_DATE` is set, or if the sql mode isn't
+    /// strict or doesn't reject the zero parts, otherwise it's an error.
+    pub fn handle_zero_in_date(&self, s: &str) -> codec::Result<()> {
+        let msg = format!("Incorrect datetime value: '{}'", s);
+        if self.ignore_zero_in_date || !self.strict_sql_mode() || !self.no_zero_in_date() {
+            self.update_stats(|s| s.zero_in_date_cnt += 1);
+            self.append_warning(ERR_TRUNCATED_WRONG_VALUE, msg);
+            return Ok(());
+        }
+        Err(box_err!("[{}] {}", ERR_TRUNCATED_WRONG_VALUE, msg))
+    }
+}
+
 // `Evaluator` evaluates `tipb::Expr`.
 // TODO(performance) Evaluator should not contain any data member,
 // because its responsibility is calculating, not managing data.
@@ -684,10 +1097,13 @@ pub mod test {
     use coprocessor::codec::mysql::json::JsonEncoder;
     use tipb::expression::FieldType;
 
-    use std::i32;
+    use std::{i32, usize};
+    use std::thread;
+    use std::time::Duration as StdDuration;
 
     use tipb::expression::{Expr, ExprType};
-    use tipb::select::SelectRequest;
+    use tipb::select::{DAGRequest, SelectRequest};
+    use chrono::FixedOffset;
     use protobuf::RepeatedField;
 
     pub fn datum_expr(datum: Datum) -> Expr {
@@ -818,8 +1234,10 @@ pub mod test {
 
                 let mut xevaluator = Evaluator::default();
                 xevaluator.row.insert(1, Datum::I64(100));
+                // dividing by zero returns NULL in SELECT statements.
+                let ctx = EvalContext::new(0, FLAG_IN_SELECT_STMT).unwrap();
                 for (expr, exp) in cases {
-                    let res = xevaluator.eval(&Default::default(), &expr);
+                    let res = xevaluator.eval(&ctx, &expr);
                     if res.is_err() {
                         panic!("failed to eval {:?}: {:?}", expr, res);
                     }
@@ -1981,6 +2399,214 @@ pub mod test {
         EvalContext::new(req.get_time_zone_offset(), req.get_flags()).unwrap();
     }
 
+    #[test]
+    fn test_context_builder() {
+        for &offset in &[ONE_DAY, -ONE_DAY, i32::MAX as i64 + 1] {
+            assert!(EvalContext::builder().tz_offset(offset).build().is_err());
+        }
+        let ctx = EvalContext::builder()
+            .tz_offset(3600)
+            .flag(FLAG_IN_INSERT_STMT)
+            .flag(FLAG_TRUNCATE_AS_WARNING)
+            .sql_mode(MODE_STRICT_ALL_TABLES)
+            .build()
+            .unwrap();
+        assert_eq!(ctx.tz, FixedOffset::east(3600));
+        assert!(ctx.in_insert_stmt && ctx.truncate_as_warning);
+        assert!(!ctx.in_select_stmt && !ctx.ignore_truncate);
+        assert_eq!(ctx.sql_mode, Some(MODE_STRICT_ALL_TABLES));
+
+        // `flags` replaces the flags set before.
+        let ctx = EvalContext::builder()
+            .flag(FLAG_IGNORE_TRUNCATE)
+            .flags(FLAG_OVERFLOW_AS_WARNING)
+            .build()
+            .unwrap();
+        assert!(!ctx.ignore_truncate && ctx.overflow_as_warning);
+        assert_eq!(ctx.sql_mode, None);
+
+        // a statement can't be of more than one kind.
+        let conflicts = vec![
+            FLAG_IN_SELECT_STMT | FLAG_IN_INSERT_STMT,
+            FLAG_IN_SELECT_STMT | FLAG_IN_UPDATE_OR_DELETE_STMT,
+            FLAG_IN_INSERT_STMT | FLAG_IN_UPDATE_OR_DELETE_STMT,
+        ];
+        for flags in conflicts {
+            assert!(EvalContext::builder().flags(flags).build().is_err());
+            assert!(EvalContext::new(0, flags).is_err());
+        }
+        let builder = EvalContext::builder().flag(FLAG_IN_SELECT_STMT);
+        assert!(builder.flag(FLAG_IN_INSERT_STMT).build().is_err());
+
+        let mut req = DAGRequest::new();
+        req.set_time_zone_offset(-3600);
+        req.set_flags(FLAG_IN_SELECT_STMT | FLAG_IGNORE_TRUNCATE);
+        let ctx = EvalContext::from_request(&req).unwrap();
+        assert_eq!(ctx.tz, FixedOffset::east(-3600));
+        assert!(ctx.in_select_stmt && ctx.ignore_truncate);
+        assert_eq!(ctx.sql_mode, None);
+        req.set_time_zone_offset(ONE_DAY);
+        assert!(EvalContext::from_request(&req).is_err());
+    }
+
+    #[test]
+    fn test_max_warning_cnt() {
+        let cases = vec![
+            // requested, kept, max capacity
+            (0, 0, 0),
+            (1, 1, 1),
+            (DEFAULT_MAX_WARNING_CNT + 1, 10, DEFAULT_MAX_WARNING_CNT),
+            (usize::MAX, 10, DEFAULT_MAX_WARNING_CNT),
+        ];
+        for (requested, kept, capacity) in cases {
+            let ctx = EvalContext::default();
+            ctx.set_max_warning_cnt(requested);
+            // the huge counts aren't preallocated.
+            assert!(ctx.warnings.borrow().warnings.capacity() <= capacity);
+            for i in 0..10 {
+                ctx.append_warning(ERR_TRUNCATED, format!("{}", i));
+            }
+            let warnings = ctx.take_warnings();
+            // all the warnings are counted even if none is kept.
+            assert_eq!(warnings.warning_cnt, 10);
+            assert_eq!(warnings.warnings.len(), kept);
+            if kept > 0 {
+                assert_eq!(warnings.warnings[0].get_msg(), "0");
+            }
+        }
+        assert_eq!(
+            EvalWarnings::new(usize::MAX).max_warning_cnt,
+            MAX_WARNING_CNT_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_merge_warnings() {
+        let mut sets = vec![EvalWarnings::new(3); 3];
+        // the warnings are generated in turn: set 0 gets 0 and 3, set 1 gets
+        // 1 and 4, and set 2 gets 2 and 5.
+        for i in 0..6 {
+            sets[i % 3].append_warning(ERR_TRUNCATED, format!("{}", i));
+        }
+        let orders = vec![[0, 1, 2], [2, 1, 0], [1, 2, 0], [2, 0, 1]];
+        for order in orders {
+            let mut sets = sets.clone();
+            let mut merged = EvalWarnings::new(3);
+            for &i in &order {
+                merged.merge(&mut sets[i]);
+                // the merged set is drained.
+                assert_eq!(sets[i].warning_cnt, 0);
+                assert!(sets[i].warnings.is_empty());
+            }
+            assert_eq!(merged.warning_cnt, 6);
+            let msgs: Vec<_> = merged.warnings.iter().map(|w| w.get_msg()).collect();
+            assert_eq!(msgs, vec!["0", "1", "2"], "{:?}", order);
+
+            // merging nothing changes nothing.
+            let before = merged.clone();
+            merged.merge(&mut EvalWarnings::new(3));
+            assert_eq!(merged, before);
+        }
+
+        // the warnings beyond the max count are still counted.
+        let mut merged = EvalWarnings::new(0);
+        merged.merge(&mut sets[0].clone());
+        assert_eq!(merged.warning_cnt, 2);
+        assert!(merged.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_flags() {
+        let unknown = 1 << 9 | 1 << 20;
+        let cases = vec![
+            // flags, has unknown flags
+            (FLAG_IN_SELECT_STMT | FLAG_OVERFLOW_AS_WARNING, false),
+            (KNOWN_FLAGS & !(FLAG_IN_INSERT_STMT | FLAG_IN_UPDATE_OR_DELETE_STMT), false),
+            (FLAG_IGNORE_TRUNCATE | unknown, true),
+            (unknown, true),
+        ];
+        for (flags, has_unknown) in cases {
+            let before = COPR_UNKNOWN_FLAGS.get();
+            let ctx = EvalContext::builder().flags(flags).build().unwrap();
+            // the raw flags are kept for the executors.
+            assert_eq!(ctx.flags, flags);
+            assert_eq!(ctx.ignore_truncate, flags & FLAG_IGNORE_TRUNCATE > 0);
+            if has_unknown {
+                assert!(COPR_UNKNOWN_FLAGS.get() > before);
+            }
+
+            let res = EvalContext::builder()
+                .flags(flags)
+                .strict_flags(true)
+                .build();
+            assert_eq!(res.is_err(), has_unknown, "flags {:#x}", flags);
+        }
+    }
+
+    #[test]
+    fn test_now() {
+        let ctx = EvalContext::new(3600, 0).unwrap();
+        let now = ctx.now();
+        assert_eq!(now.offset(), &FixedOffset::east(3600));
+        thread::sleep(StdDuration::from_millis(10));
+        // the time doesn't change in a statement.
+        assert_eq!(ctx.now(), now);
+        assert!(EvalContext::default().now() > now);
+    }
+
+    #[test]
+    fn test_flush_eval_stats() {
+        let counter = |class| COPR_EVAL_WARNINGS.with_label_values(&[class]).get() as u64;
+        let before = (counter("truncate"), counter("overflow"), counter("div_by_zero"));
+        let stats = EvalStats {
+            truncate_as_warning_cnt: 3,
+            overflow_as_warning_cnt: 0,
+            div_by_zero_cnt: 5,
+            zero_in_date_cnt: 0,
+        };
+        stats.flush();
+        assert_eq!(counter("truncate") - before.0, 3);
+        assert_eq!(counter("overflow") - before.1, 0);
+        assert_eq!(counter("div_by_zero") - before.2, 5);
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let cases = vec![
+            bin_expr(Datum::F64(1.0), Datum::F64(0.0), ExprType::Div),
+            bin_expr(Datum::I64(1), Datum::I64(0), ExprType::Div),
+            bin_expr(Datum::I64(1), Datum::I64(0), ExprType::IntDiv),
+            bin_expr(Datum::I64(1), Datum::U64(0), ExprType::Mod),
+            bin_expr(Datum::F64(1.0), Datum::F64(0.0), ExprType::Mod),
+        ];
+        let flags = vec![
+            (0, true),
+            (FLAG_IN_SELECT_STMT, true),
+            (FLAG_IN_INSERT_STMT, false),
+            (FLAG_IN_UPDATE_OR_DELETE_STMT, false),
+            (FLAG_DIVIDED_BY_ZERO_AS_WARNING, true),
+            (FLAG_IN_INSERT_STMT | FLAG_DIVIDED_BY_ZERO_AS_WARNING, true),
+            // truncating doesn't decide how dividing by zero is handled.
+            (
+                FLAG_IN_INSERT_STMT | FLAG_IGNORE_TRUNCATE | FLAG_TRUNCATE_AS_WARNING,
+                false,
+            ),
+        ];
+        let mut xevaluator = Evaluator::default();
+        for (flag, is_warning) in flags {
+            let ctx = EvalContext::new(0, flag).unwrap();
+            assert_eq!(ctx.handle_division_by_zero().is_ok(), is_warning);
+            for expr in &cases {
+                let res = xevaluator.eval(&ctx, expr);
+                if is_warning {
+                    assert_eq!(res.unwrap(), Datum::Null);
+                } else {
+                    assert!(res.is_err(), "{:?} with flags {} should fail", expr, flag);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_where_in() {
         let cases = vec![
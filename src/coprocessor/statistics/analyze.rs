@@ -13,15 +13,16 @@
 
 use rand::{thread_rng, Rng, ThreadRng};
 use protobuf::{Message, RepeatedField};
-use kvproto::coprocessor::{KeyRange, Response};
+use kvproto::coprocessor::Response;
 use tipb::analyze::{self, AnalyzeColumnsReq, AnalyzeReq, AnalyzeType};
 use tipb::schema::ColumnInfo;
-use tipb::executor::TableScan;
 
-use coprocessor::dag::executor::{Executor, IndexScanExecutor, TableScanExecutor};
+use coprocessor::dag::executor::{Executor, ExecutorMetrics, IndexScanExecutor, RowScanner};
 use coprocessor::endpoint::ReqContext;
+use coprocessor::interrupt::Interrupt;
+use coprocessor::key_ranges::KeyRanges;
 use coprocessor::codec::datum;
-use coprocessor::{Error, Result};
+use coprocessor::{util, Error, Result};
 use storage::{Snapshot, SnapshotStore, Statistics};
 use super::fmsketch::FMSketch;
 use super::histogram::Histogram;
@@ -31,13 +32,14 @@ pub struct AnalyzeContext<'a> {
     req: AnalyzeReq,
     snap: SnapshotStore<'a>,
     statistics: &'a mut Statistics,
-    ranges: Vec<KeyRange>,
+    ranges: KeyRanges,
+    interrupt: Interrupt,
 }
 
 impl<'a> AnalyzeContext<'a> {
     pub fn new(
         req: AnalyzeReq,
-        ranges: Vec<KeyRange>,
+        ranges: KeyRanges,
         snap: &'a Snapshot,
         statistics: &'a mut Statistics,
         req_ctx: &'a ReqContext,
@@ -53,6 +55,7 @@ impl<'a> AnalyzeContext<'a> {
             snap: snap,
             statistics: statistics,
             ranges: ranges,
+            interrupt: req_ctx.interrupt.clone(),
         }
     }
 
@@ -86,11 +89,13 @@ impl<'a> AnalyzeContext<'a> {
             self.snap,
             self.statistics,
         );
-        let mut hist = Histogram::new(req.get_bucket_size() as usize);
+        scanner.set_interrupt(self.interrupt);
+        let mut hist = Histogram::new(util::i64_to_usize(req.get_bucket_size(), "bucket size")?);
         while let Some(row) = scanner.next()? {
             let bytes = row.data.get_column_values();
             hist.append(bytes);
         }
+        scanner.take_statistics().flush();
         let mut res = analyze::AnalyzeIndexResp::new();
         res.set_hist(hist.into_proto());
         let dt = box_try!(res.write_to_bytes());
@@ -102,7 +107,9 @@ impl<'a> AnalyzeContext<'a> {
     // collectors for each column value.
     fn handle_column(mut self) -> Result<Vec<u8>> {
         let col_req = self.req.take_col_req();
-        let builder = SampleBuilder::new(col_req, self.snap, self.ranges, &mut self.statistics)?;
+        let mut builder =
+            SampleBuilder::new(col_req, self.snap, self.ranges, &mut self.statistics)?;
+        builder.interrupt = Some(self.interrupt);
 
         let (collectors, pk_builder) = builder.collect_samples_and_estimate_ndvs()?;
         let pk_hist = pk_builder.into_proto();
@@ -120,7 +127,8 @@ impl<'a> AnalyzeContext<'a> {
 }
 
 struct SampleBuilder<'a> {
-    data: TableScanExecutor<'a>,
+    data: RowScanner<'a>,
+    interrupt: Option<Interrupt>,
     cols: Vec<ColumnInfo>,
     // the number of columns need to be sampled. It equals to cols.len()
     // if cols[0] is not pk handle, or it should be cols.len() - 1.
@@ -137,7 +145,7 @@ impl<'a> SampleBuilder<'a> {
     fn new(
         mut req: AnalyzeColumnsReq,
         snap: SnapshotStore<'a>,
-        ranges: Vec<KeyRange>,
+        ranges: KeyRanges,
         statistics: &'a mut Statistics,
     ) -> Result<SampleBuilder<'a>> {
         let cols_info = req.take_columns_info();
@@ -150,16 +158,15 @@ impl<'a> SampleBuilder<'a> {
             col_len -= 1;
         }
 
-        let mut meta = TableScan::new();
-        meta.set_columns(cols_info);
-        let table_scanner = TableScanExecutor::new(&meta, ranges, snap, statistics);
+        let scanner = RowScanner::new(snap, ranges, Some(&cols_info[..]), false, false, statistics);
         Ok(SampleBuilder {
-            data: table_scanner,
-            cols: meta.take_columns().to_vec(),
+            data: scanner,
+            interrupt: None,
+            cols: cols_info.into_vec(),
             col_len: col_len,
-            max_bucket_size: req.get_bucket_size() as usize,
-            max_sketch_size: req.get_sketch_size() as usize,
-            max_sample_size: req.get_sample_size() as usize,
+            max_bucket_size: util::i64_to_usize(req.get_bucket_size(), "bucket size")?,
+            max_sketch_size: util::i64_to_usize(req.get_sketch_size(), "sketch size")?,
+            max_sample_size: util::i64_to_usize(req.get_sample_size(), "sample size")?,
         })
     }
 
@@ -170,8 +177,11 @@ impl<'a> SampleBuilder<'a> {
         let mut pk_builder = Histogram::new(self.max_bucket_size);
         let mut collectors =
             vec![SampleCollector::new(self.max_sample_size, self.max_sketch_size); self.col_len];
-        while let Some(row) = self.data.next()? {
-            let cols = row.get_binary_cols(&self.cols)?;
+        while let Some(row) = self.data.next_row()? {
+            if let Some(ref interrupt) = self.interrupt {
+                interrupt.check()?;
+            }
+            let cols = row.into_row().get_binary_cols(&self.cols)?;
             let retreive_len = cols.len();
             let mut cols_iter = cols.into_iter();
             if self.col_len != retreive_len {
@@ -183,6 +193,9 @@ impl<'a> SampleBuilder<'a> {
                 collector.collect(val);
             }
         }
+        let mut metrics = ExecutorMetrics::default();
+        metrics.scan_counter = self.data.take_scan_counter();
+        metrics.flush();
         Ok((collectors, pk_builder))
     }
 }
@@ -16,6 +16,8 @@ use byteorder::{ByteOrder, LittleEndian};
 use murmur3::murmur3_x64_128;
 use tipb::analyze;
 
+use coprocessor::util;
+
 /// `FMSketch` is used to count the approximate number of distinct
 /// elements in multiset.
 /// Refer:[Flajolet-Martin](https://en.wikipedia.org/wiki/Flajolet%E2%80%93Martin_algorithm)
@@ -31,7 +33,7 @@ impl FMSketch {
         FMSketch {
             mask: 0,
             max_size: max_size,
-            hash_set: HashSet::with_capacity(max_size + 1),
+            hash_set: HashSet::with_capacity(util::capped_capacity(max_size) + 1),
         }
     }
 
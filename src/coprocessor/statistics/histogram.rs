@@ -140,7 +140,8 @@ impl Histogram {
 
     // It merges every two neighbor buckets.
     fn merge_buckets(&mut self) {
-        let bucket_num = (self.buckets_num + 1) / 2;
+        // rounds up without overflowing when buckets_num is usize::MAX.
+        let bucket_num = self.buckets_num / 2 + self.buckets_num % 2;
         if self.buckets_num > 1 {
             let (left, right) = self.buckets.split_at_mut(1);
             mem::swap(&mut left[0].upper_bound, &mut right[0].upper_bound);
@@ -0,0 +1,118 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cmp, error, result, usize};
+
+use super::{BuildError, Result};
+
+/// The max capacity reserved in advance according to a size hint from the request,
+/// containers grow on demand if the hint is larger.
+pub const MAX_RESERVED_CAPACITY: usize = 1024;
+
+/// `capped_capacity` limits a capacity hint provided by the request, so an
+/// adversarial hint can't lead to a huge allocation or an overflow.
+#[inline]
+pub fn capped_capacity(hint: usize) -> usize {
+    cmp::min(hint, MAX_RESERVED_CAPACITY)
+}
+
+/// `u64_to_usize` converts an unsigned integer from the request to `usize`,
+/// `what` describes the value in the error message.
+pub fn u64_to_usize(v: u64, what: &str) -> Result<usize> {
+    if v > usize::MAX as u64 {
+        return Err(BuildError::invalid_request(format!("{} {} is too large", what, v)).into());
+    }
+    Ok(v as usize)
+}
+
+/// `i64_to_usize` converts a signed integer from the request to `usize`,
+/// `what` describes the value in the error message.
+pub fn i64_to_usize(v: i64, what: &str) -> Result<usize> {
+    if v < 0 {
+        return Err(BuildError::invalid_request(format!("{} {} is negative", what, v)).into());
+    }
+    u64_to_usize(v as u64, what)
+}
+
+/// `checked_mul_add` returns `a * b + c`, or an error mentioning `what` if it
+/// overflows. The error is boxed so that it can be converted by `?` into both
+/// the coprocessor and the codec errors.
+pub fn checked_mul_add(
+    a: usize,
+    b: usize,
+    c: usize,
+    what: &str,
+) -> result::Result<usize, Box<error::Error + Send + Sync>> {
+    match a.checked_mul(b).and_then(|v| v.checked_add(c)) {
+        Some(v) => Ok(v),
+        None => Err(box_err!("{} overflows: {} * {} + {}", what, a, b, c)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{i64, u64, usize};
+
+    use coprocessor::{BuildError, Error};
+
+    use super::*;
+
+    fn is_invalid_request<T>(res: Result<T>) -> bool {
+        match res {
+            Err(Error::Build(BuildError::InvalidRequest { .. })) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_capped_capacity() {
+        assert_eq!(capped_capacity(0), 0);
+        assert_eq!(capped_capacity(10), 10);
+        assert_eq!(capped_capacity(usize::MAX), MAX_RESERVED_CAPACITY);
+    }
+
+    #[test]
+    fn test_to_usize() {
+        assert_eq!(u64_to_usize(0, "limit").unwrap(), 0);
+        assert_eq!(u64_to_usize(100, "limit").unwrap(), 100);
+        if usize::MAX as u64 == u64::MAX {
+            assert_eq!(u64_to_usize(u64::MAX, "limit").unwrap(), usize::MAX);
+        } else {
+            assert!(is_invalid_request(u64_to_usize(u64::MAX, "limit")));
+        }
+
+        assert_eq!(i64_to_usize(0, "size").unwrap(), 0);
+        assert_eq!(i64_to_usize(i64::MAX, "size").unwrap() as u64, i64::MAX as u64);
+        assert!(is_invalid_request(i64_to_usize(-1, "size")));
+        assert!(is_invalid_request(i64_to_usize(i64::MIN, "size")));
+    }
+
+    #[test]
+    fn test_checked_mul_add() {
+        let cases = vec![
+            (3, 4, 5, Some(17)),
+            (0, usize::MAX, usize::MAX, Some(usize::MAX)),
+            (usize::MAX, 1, 0, Some(usize::MAX)),
+            (usize::MAX, 2, 0, None),
+            (usize::MAX, 1, 1, None),
+            (usize::MAX / 2 + 1, 2, 0, None),
+        ];
+        for (a, b, c, exp) in cases {
+            match (checked_mul_add(a, b, c, "offset"), exp) {
+                (Ok(v), Some(e)) => assert_eq!(v, e),
+                (Err(e), None) => assert!(format!("{}", e).contains("offset")),
+                (res, exp) => panic!("{} * {} + {}: expect {:?}, got {:?}", a, b, c, exp, res),
+            }
+        }
+    }
+}
@@ -39,6 +39,34 @@ const DEFAULT_MESSAGES_PER_TICK: usize = 4096;
 // larger latency.
 pub const DEFAULT_MAX_RUNNING_TASK_COUNT: usize = 2 as usize * 1000;
 
+// The max number of rows in a response of a streaming coprocessor request.
+pub const DEFAULT_ENDPOINT_STREAM_BATCH_ROW_LIMIT: usize = 128;
+
+/// The request quota of a coprocessor request source, `rate` requests are
+/// admitted per second on average and at most `burst` at once. A zero `rate`
+/// means unlimited.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+#[serde(rename_all = "kebab-case")]
+pub struct EndPointQuota {
+    pub rate: u64,
+    pub burst: u64,
+}
+
+impl EndPointQuota {
+    #[inline]
+    pub fn is_unlimited(&self) -> bool {
+        self.rate == 0
+    }
+
+    fn validate(&self, name: &str) -> Result<()> {
+        if !self.is_unlimited() && self.burst == 0 {
+            return Err(box_err!("{}.burst should not be 0 when rate is set.", name));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 #[serde(rename_all = "kebab-case")]
@@ -60,6 +88,12 @@ pub struct Config {
     pub grpc_stream_initial_window_size: ReadableSize,
     pub end_point_concurrency: usize,
     pub end_point_max_tasks: usize,
+    pub end_point_stream_batch_row_limit: usize,
+    // The quota shared by the coprocessor requests whose source isn't listed
+    // in `end_point_source_quotas`.
+    pub end_point_default_quota: EndPointQuota,
+    #[serde(with = "config::order_map_serde")]
+    pub end_point_source_quotas: HashMap<String, EndPointQuota>,
     // Server labels to specify some attributes about this server.
     #[serde(with = "config::order_map_serde")]
     pub labels: HashMap<String, String>,
@@ -86,6 +120,9 @@ impl Default for Config {
             grpc_stream_initial_window_size: ReadableSize(DEFAULT_GRPC_STREAM_INITIAL_WINDOW_SIZE),
             end_point_concurrency: concurrency,
             end_point_max_tasks: DEFAULT_MAX_RUNNING_TASK_COUNT,
+            end_point_stream_batch_row_limit: DEFAULT_ENDPOINT_STREAM_BATCH_ROW_LIMIT,
+            end_point_default_quota: EndPointQuota::default(),
+            end_point_source_quotas: HashMap::default(),
         }
     }
 }
@@ -114,6 +151,16 @@ impl Config {
             return Err(box_err!("server.end-point-max-tasks should not be 0."));
         }
 
+        if self.end_point_stream_batch_row_limit == 0 {
+            return Err(box_err!("server.end-point-stream-batch-row-limit should not be 0."));
+        }
+
+        self.end_point_default_quota
+            .validate("server.end-point-default-quota")?;
+        for (source, quota) in &self.end_point_source_quotas {
+            quota.validate(&format!("server.end-point-source-quotas.{}", source))?;
+        }
+
         for (k, v) in &self.labels {
             validate_label(k, "key")?;
             validate_label(v, "value")?;
@@ -173,6 +220,23 @@ mod tests {
         invalid_cfg.end_point_max_tasks = 0;
         assert!(invalid_cfg.validate().is_err());
 
+        let mut invalid_cfg = cfg.clone();
+        invalid_cfg.end_point_stream_batch_row_limit = 0;
+        assert!(invalid_cfg.validate().is_err());
+
+        let mut invalid_cfg = cfg.clone();
+        invalid_cfg.end_point_default_quota.rate = 100;
+        assert!(invalid_cfg.validate().is_err());
+        invalid_cfg.end_point_default_quota.burst = 10;
+        invalid_cfg.validate().unwrap();
+
+        let mut invalid_cfg = cfg.clone();
+        let quota = EndPointQuota { rate: 100, burst: 0 };
+        invalid_cfg
+            .end_point_source_quotas
+            .insert("tidb-1".to_owned(), quota);
+        assert!(invalid_cfg.validate().is_err());
+
         invalid_cfg = Config::default();
         invalid_cfg.addr = "0.0.0.0:1000".to_owned();
         assert!(invalid_cfg.validate().is_err());
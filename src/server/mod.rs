@@ -25,7 +25,7 @@ pub mod node;
 pub mod resolve;
 pub mod snap;
 
-pub use self::config::{Config, DEFAULT_CLUSTER_ID, DEFAULT_LISTENING_ADDR};
+pub use self::config::{Config, EndPointQuota, DEFAULT_CLUSTER_ID, DEFAULT_LISTENING_ADDR};
 pub use self::errors::{Error, Result};
 pub use self::server::Server;
 pub use self::transport::{ServerRaftStoreRouter, ServerTransport};
@@ -34,3 +34,7 @@ pub use self::resolve::{PdStoreAddrResolver, StoreAddrResolver};
 pub use self::raft_client::RaftClient;
 
 pub type OnResponse = Box<FnBox(Response) + Send>;
+// It's called with every response of a streaming request, and returns false
+// once the stream is closed, e.g. the client has gone. It's called in the
+// thread pool of the endpoint, so it must not block waiting for the client.
+pub type OnStreamResponse = Box<FnMut(Response) -> bool + Send>;
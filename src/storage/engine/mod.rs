@@ -42,6 +42,9 @@ const STAT_PREV: &'static str = "prev";
 const STAT_SEEK: &'static str = "seek";
 const STAT_SEEK_FOR_PREV: &'static str = "seek_for_prev";
 const STAT_OVER_SEEK_BOUND: &'static str = "over_seek_bound";
+const STAT_VERSIONS: &'static str = "versions";
+const STAT_SKIPPED_VERSIONS: &'static str = "skipped_versions";
+const STAT_TOMBSTONES: &'static str = "tombstones";
 
 pub type Callback<T> = Box<FnBox((CbContext, Result<T>)) + Send>;
 pub type BatchResults<T> = Vec<Option<(CbContext, Result<T>)>>;
@@ -176,7 +179,7 @@ pub enum ScanMode {
 }
 
 /// Statistics collects the ops taken when fetching data.
-#[derive(Default)]
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct CFStatistics {
     // How many keys that's effective to user. This counter should be increased
     // by the caller.
@@ -187,18 +190,26 @@ pub struct CFStatistics {
     pub seek: usize,
     pub seek_for_prev: usize,
     pub over_seek_bound: usize,
+    // How many MVCC versions are examined when reading the keys, and how many
+    // of them aren't visible, i.e. the deletes, locks, rollbacks and the
+    // versions newer than the read ts met by the scanners. Only the write CF
+    // counts them.
+    pub versions: usize,
+    pub skipped_versions: usize,
+    // How many of the skipped versions are deletes.
+    pub tombstones: usize,
     pub flow_stats: FlowStatistics,
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct FlowStatistics {
     pub read_keys: usize,
     pub read_bytes: usize,
 }
 
 impl FlowStatistics {
-    pub fn add(&mut self, other: &Self) {
-        self.read_bytes = self.read_keys.saturating_add(other.read_bytes);
+    pub fn merge(&mut self, other: &Self) {
+        self.read_bytes = self.read_bytes.saturating_add(other.read_bytes);
         self.read_keys = self.read_keys.saturating_add(other.read_keys);
     }
 }
@@ -209,6 +220,17 @@ impl CFStatistics {
         self.get + self.next + self.prev + self.seek + self.seek_for_prev
     }
 
+    /// `visible_ratio` returns the ratio of the visible versions in all the
+    /// MVCC versions examined, or `None` if no version is examined. A low ratio
+    /// means the reads are slowed down by the versions waiting for GC.
+    pub fn visible_ratio(&self) -> Option<f64> {
+        if self.versions == 0 {
+            return None;
+        }
+        let visible = self.versions.saturating_sub(self.skipped_versions);
+        Some(visible as f64 / self.versions as f64)
+    }
+
     pub fn details(&self) -> Vec<(&str, usize)> {
         vec![
             (STAT_TOTAL, self.total_op_count()),
@@ -219,10 +241,13 @@ impl CFStatistics {
             (STAT_SEEK, self.seek),
             (STAT_SEEK_FOR_PREV, self.seek_for_prev),
             (STAT_OVER_SEEK_BOUND, self.over_seek_bound),
+            (STAT_VERSIONS, self.versions),
+            (STAT_SKIPPED_VERSIONS, self.skipped_versions),
+            (STAT_TOMBSTONES, self.tombstones),
         ]
     }
 
-    pub fn add(&mut self, other: &Self) {
+    pub fn merge(&mut self, other: &Self) {
         self.processed = self.processed.saturating_add(other.processed);
         self.get = self.get.saturating_add(other.get);
         self.next = self.next.saturating_add(other.next);
@@ -230,11 +255,14 @@ impl CFStatistics {
         self.seek = self.seek.saturating_add(other.seek);
         self.seek_for_prev = self.seek_for_prev.saturating_add(other.seek_for_prev);
         self.over_seek_bound = self.over_seek_bound.saturating_add(other.over_seek_bound);
-        self.flow_stats.add(&other.flow_stats);
+        self.versions = self.versions.saturating_add(other.versions);
+        self.skipped_versions = self.skipped_versions.saturating_add(other.skipped_versions);
+        self.tombstones = self.tombstones.saturating_add(other.tombstones);
+        self.flow_stats.merge(&other.flow_stats);
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct Statistics {
     pub lock: CFStatistics,
     pub write: CFStatistics,
@@ -258,10 +286,10 @@ impl Statistics {
         ]
     }
 
-    pub fn add(&mut self, other: &Self) {
-        self.lock.add(&other.lock);
-        self.write.add(&other.write);
-        self.data.add(&other.data);
+    pub fn merge(&mut self, other: &Self) {
+        self.lock.merge(&other.lock);
+        self.write.merge(&other.write);
+        self.data.merge(&other.data);
     }
 }
 
@@ -273,7 +301,7 @@ pub struct StatisticsSummary {
 
 impl StatisticsSummary {
     pub fn add_statistics(&mut self, v: &Statistics) {
-        self.stat.add(v);
+        self.stat.merge(v);
         self.count += 1;
     }
 }
@@ -964,4 +992,82 @@ mod tests {
     fn test_empty_write(engine: &Engine) {
         engine.write(&Context::new(), vec![]).unwrap();
     }
+
+    fn new_cf_statistics(base: usize) -> CFStatistics {
+        CFStatistics {
+            processed: base,
+            get: base + 1,
+            next: base + 2,
+            prev: base + 3,
+            seek: base + 4,
+            seek_for_prev: base + 5,
+            over_seek_bound: base + 6,
+            versions: base + 9,
+            skipped_versions: base + 10,
+            tombstones: base + 11,
+            flow_stats: FlowStatistics {
+                read_keys: base + 7,
+                read_bytes: base + 8,
+            },
+        }
+    }
+
+    #[test]
+    fn test_statistics_merge() {
+        let mut flow = FlowStatistics {
+            read_keys: 1,
+            read_bytes: 10,
+        };
+        flow.merge(&FlowStatistics {
+            read_keys: 2,
+            read_bytes: 20,
+        });
+        assert_eq!(flow.read_keys, 3);
+        assert_eq!(flow.read_bytes, 30);
+
+        let mut cf = new_cf_statistics(0);
+        cf.merge(&new_cf_statistics(100));
+        assert_eq!(cf.processed, 100);
+        assert_eq!(cf.get, 102);
+        assert_eq!(cf.next, 104);
+        assert_eq!(cf.prev, 106);
+        assert_eq!(cf.seek, 108);
+        assert_eq!(cf.seek_for_prev, 110);
+        assert_eq!(cf.over_seek_bound, 112);
+        assert_eq!(cf.flow_stats.read_keys, 114);
+        assert_eq!(cf.flow_stats.read_bytes, 116);
+        assert_eq!(cf.versions, 118);
+        assert_eq!(cf.skipped_versions, 120);
+        assert_eq!(cf.tombstones, 122);
+
+        let mut stats = Statistics {
+            lock: new_cf_statistics(0),
+            write: new_cf_statistics(10),
+            data: new_cf_statistics(20),
+        };
+        let other = Statistics {
+            lock: new_cf_statistics(1),
+            write: new_cf_statistics(2),
+            data: new_cf_statistics(3),
+        };
+        let mut exp = stats.clone();
+        exp.lock.merge(&other.lock);
+        exp.write.merge(&other.write);
+        exp.data.merge(&other.data);
+        stats.merge(&other);
+        assert_eq!(stats, exp);
+        assert_eq!(stats.data.flow_stats.read_bytes, 20 + 8 + 3 + 8);
+
+        // merging the default statistics changes nothing.
+        stats.merge(&Statistics::default());
+        assert_eq!(stats, exp);
+
+        let mut summary = StatisticsSummary::default();
+        summary.add_statistics(&other);
+        summary.add_statistics(&other);
+        let mut exp = other.clone();
+        exp.merge(&other);
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.stat, exp);
+    }
 }
@@ -220,23 +220,30 @@ impl<'a> MvccReader<'a> {
             IsolationLevel::RC => {}
         }
         loop {
-            match self.seek_write(key, ts)? {
-                Some((commit_ts, mut write)) => match write.write_type {
-                    WriteType::Put => {
-                        if write.short_value.is_some() {
-                            if self.key_only {
-                                return Ok(Some(vec![]));
-                            }
-                            return Ok(write.short_value.take());
+            let (commit_ts, mut write) = match self.seek_write(key, ts)? {
+                Some(res) => res,
+                None => return Ok(None),
+            };
+            self.statistics.write.versions += 1;
+            match write.write_type {
+                WriteType::Put => {
+                    if write.short_value.is_some() {
+                        if self.key_only {
+                            return Ok(Some(vec![]));
                         }
-                        return self.load_data(key, write.start_ts).map(Some);
+                        return Ok(write.short_value.take());
                     }
-                    WriteType::Delete => {
-                        return Ok(None);
-                    }
-                    WriteType::Lock | WriteType::Rollback => ts = commit_ts - 1,
-                },
-                None => return Ok(None),
+                    return self.load_data(key, write.start_ts).map(Some);
+                }
+                WriteType::Delete => {
+                    self.statistics.write.skipped_versions += 1;
+                    self.statistics.write.tombstones += 1;
+                    return Ok(None);
+                }
+                WriteType::Lock | WriteType::Rollback => {
+                    self.statistics.write.skipped_versions += 1;
+                    ts = commit_ts - 1;
+                }
             }
         }
     }
@@ -313,7 +320,7 @@ impl<'a> MvccReader<'a> {
         let (mut write_valid, mut lock_valid) = (true, true);
 
         loop {
-            key = {
+            let (user_key, write_key) = {
                 let w_cur = self.write_cursor.as_mut().unwrap();
                 let l_cur = self.lock_cursor.as_mut().unwrap();
                 let (mut w_key, mut l_key) = (None, None);
@@ -335,15 +342,19 @@ impl<'a> MvccReader<'a> {
                 }
                 match (w_key, l_key) {
                     (None, None) => return Ok(None),
-                    (None, Some(k)) => Key::from_encoded(k.to_vec()),
-                    (Some(k), None) => Key::from_encoded(k.to_vec()).truncate_ts()?,
+                    (None, Some(k)) => (Key::from_encoded(k.to_vec()), None),
+                    (Some(k), None) => split_write_key(k)?,
                     (Some(wk), Some(lk)) => if wk < lk {
-                        Key::from_encoded(wk.to_vec()).truncate_ts()?
+                        split_write_key(wk)?
                     } else {
-                        Key::from_encoded(lk.to_vec())
+                        (Key::from_encoded(lk.to_vec()), None)
                     },
                 }
             };
+            key = user_key;
+            if let Some(write_key) = write_key {
+                self.on_scanned_version(&write_key, ts)?;
+            }
             if let Some(v) = self.get(&key, ts)? {
                 return Ok(Some((key, v)));
             }
@@ -359,7 +370,7 @@ impl<'a> MvccReader<'a> {
         let (mut write_valid, mut lock_valid) = (true, true);
 
         loop {
-            key = {
+            let (user_key, write_key) = {
                 let w_cur = self.write_cursor.as_mut().unwrap();
                 let l_cur = self.lock_cursor.as_mut().unwrap();
                 let (mut w_key, mut l_key) = (None, None);
@@ -381,21 +392,35 @@ impl<'a> MvccReader<'a> {
                 }
                 match (w_key, l_key) {
                     (None, None) => return Ok(None),
-                    (None, Some(k)) => Key::from_encoded(k.to_vec()),
-                    (Some(k), None) => Key::from_encoded(k.to_vec()).truncate_ts()?,
+                    (None, Some(k)) => (Key::from_encoded(k.to_vec()), None),
+                    (Some(k), None) => split_write_key(k)?,
                     (Some(wk), Some(lk)) => if wk < lk {
-                        Key::from_encoded(lk.to_vec())
+                        (Key::from_encoded(lk.to_vec()), None)
                     } else {
-                        Key::from_encoded(wk.to_vec()).truncate_ts()?
+                        split_write_key(wk)?
                     },
                 }
             };
+            key = user_key;
+            if let Some(write_key) = write_key {
+                self.on_scanned_version(&write_key, ts)?;
+            }
             if let Some(v) = self.get(&key, ts)? {
                 return Ok(Some((key, v)));
             }
         }
     }
 
+    // The version the scanners land on is examined even if it's newer than
+    // `ts`, which `get` skips by seeking to `ts` directly.
+    fn on_scanned_version(&mut self, write_key: &Key, ts: u64) -> Result<()> {
+        if write_key.decode_ts()? > ts {
+            self.statistics.write.versions += 1;
+            self.statistics.write.skipped_versions += 1;
+        }
+        Ok(())
+    }
+
     #[allow(type_complexity)]
     pub fn scan_lock<F>(
         &mut self,
@@ -542,6 +567,12 @@ impl<'a> MvccReader<'a> {
     }
 }
 
+// Split a key of the write CF into the user key and itself.
+fn split_write_key(k: &[u8]) -> Result<(Key, Option<Key>)> {
+    let write_key = Key::from_encoded(k.to_vec());
+    Ok((write_key.truncate_ts()?, Some(write_key)))
+}
+
 #[cfg(test)]
 mod tests {
     use std::u64;
@@ -17,7 +17,7 @@ use std::fs::File;
 
 use log::LogLevelFilter;
 use rocksdb::{CompactionPriority, DBCompressionType, DBRecoveryMode};
-use tikv::server::Config as ServerConfig;
+use tikv::server::{Config as ServerConfig, EndPointQuota};
 use tikv::raftstore::store::Config as RaftstoreConfig;
 use tikv::config::*;
 use tikv::storage::Config as StorageConfig;
@@ -63,6 +63,14 @@ fn test_serde_custom_tikv_config() {
         grpc_stream_initial_window_size: ReadableSize(12_345),
         end_point_concurrency: 12,
         end_point_max_tasks: 12,
+        end_point_stream_batch_row_limit: 32,
+        end_point_default_quota: EndPointQuota {
+            rate: 1_000,
+            burst: 100,
+        },
+        end_point_source_quotas: map!{
+            "tidb-1".to_owned() => EndPointQuota { rate: 200, burst: 20 }
+        },
     };
     value.metric = MetricConfig {
         interval: ReadableDuration::secs(12),
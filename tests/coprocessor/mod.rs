@@ -13,3 +13,5 @@
 
 mod test_select;
 mod test_analyze;
+mod test_codec_prelude;
+mod test_codec_golden;
@@ -11,6 +11,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::i64;
+
 use kvproto::coprocessor::{KeyRange, Request};
 use kvproto::kvrpcpb::{Context, IsolationLevel};
 use protobuf::{Message, RepeatedField};
@@ -187,3 +189,38 @@ fn test_analyze_index() {
     assert_eq!(hist.get_buckets().len(), 2);
     end_point.stop().unwrap().join().unwrap();
 }
+
+#[test]
+fn test_analyze_invalid_sizes() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_data_with_commit(&product, &data, true);
+
+    // huge sizes must not be used to reserve memory in advance.
+    let req = new_analyze_column_req(&product.table, i64::MAX, i64::MAX, i64::MAX);
+    let resp = handle_request(&end_point, req);
+    let mut analyze_resp = AnalyzeColumnsResp::new();
+    analyze_resp.merge_from_bytes(resp.get_data()).unwrap();
+    assert_eq!(analyze_resp.get_pk_hist().get_ndv(), 2);
+
+    let reqs = vec![
+        new_analyze_column_req(&product.table, -1, 3, 3),
+        new_analyze_column_req(&product.table, 3, i64::MIN, 3),
+        new_analyze_column_req(&product.table, 3, 3, -1),
+        new_analyze_index_req(&product.table, -1, product.name.index),
+    ];
+    for req in reqs {
+        let resp = handle_request(&end_point, req);
+        assert!(resp.get_data().is_empty(), "{:?}", resp);
+        assert!(
+            resp.get_other_error().starts_with("[invalid request]"),
+            "{:?}",
+            resp
+        );
+    }
+    end_point.stop().unwrap().join().unwrap();
+}
@@ -0,0 +1,141 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The golden file in `tests/coprocessor/golden` holds encoded datums, one
+// case per line. The tests check that they are decoded to the expected values,
+// and that we encode the same values into the same bytes. If an encoding isn't
+// canonical, e.g. a decimal is encoded with the precision of its column, the
+// values are compared instead.
+//
+// The goldens were NOT captured from TiDB. They are assembled by hand from the
+// encoders in TiDB's `util/codec`, so they guard against accidental changes of
+// our encoding rather than prove the compatibility with a TiDB build. The
+// inputs of every case are the expected values in this file.
+//
+// There are no chunk goldens, chunks assembled by hand would only repeat our
+// own encoder. They should be bytes encoded by TiDB's `chunk.Codec`, with the
+// TiDB version and commit that produced them noted in the golden file.
+//
+// When the format changes on purpose:
+// 1. work out the new bytes of the changed cases from TiDB's encoders, or
+//    better, capture them from the TiDB version with the new format;
+// 2. update the expected values here only if the decoded values change;
+// 3. describe the format change in the commit message, a golden should never
+//    be changed just to make the tests pass.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::{f64, i64, u64};
+
+use rustc_serialize::hex::FromHex;
+
+use tikv::coprocessor::codec::prelude::*;
+
+const JSON_DOC: &'static str = r#"{"a": [1, "中", true, null], "b": 1.5}"#;
+const NEG_MAX_PREC_DEC: &'static str =
+    "-12345678901234567890123456789012345.123456789012345678901234567890";
+
+fn read_golden(name: &str) -> Vec<Vec<String>> {
+    let mut p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    p.push("tests/coprocessor/golden");
+    p.push(name);
+    let mut f = File::open(p).unwrap();
+    let mut buffer = String::new();
+    f.read_to_string(&mut buffer).unwrap();
+    buffer
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.split_whitespace().map(|s| s.to_owned()).collect())
+        .collect()
+}
+
+fn dur(s: &str) -> Duration {
+    Duration::parse(s.as_bytes(), 6).unwrap()
+}
+
+fn dec(s: &str) -> Decimal {
+    s.parse().unwrap()
+}
+
+fn datetime(s: &str, fsp: i8) -> Time {
+    Time::parse_utc_datetime(s, fsp).unwrap()
+}
+
+// Returns the expected datum and whether the encoding is canonical.
+fn expected_datum(case: &str) -> (Datum, bool) {
+    match case {
+        "int_zero" => (Datum::I64(0), true),
+        "int_neg_one" => (Datum::I64(-1), true),
+        "int_min" => (Datum::I64(i64::MIN), true),
+        "int_max" => (Datum::I64(i64::MAX), true),
+        "uint_max" => (Datum::U64(u64::MAX), true),
+        "float_neg" => (Datum::F64(-2.25), true),
+        "float_max" => (Datum::F64(f64::MAX), true),
+        "str_empty" => (Datum::Bytes(vec![]), true),
+        "str_multibyte" => (Datum::Bytes("中文".as_bytes().to_vec()), true),
+        "str_emoji" => (Datum::Bytes("héllo🙂".as_bytes().to_vec()), true),
+        "dec_neg_max_prec" => (Datum::Dec(dec(NEG_MAX_PREC_DEC)), true),
+        "dec_zero" => (Datum::Dec(dec("0")), true),
+        // encoded as DECIMAL(10, 4).
+        "dec_declared_frac" => (Datum::Dec(dec("1.5")), false),
+        // times are decoded as their packed u64.
+        "time_fsp6" => {
+            let t = datetime("2017-10-16 12:34:56.789012", 6);
+            (Datum::U64(t.to_packed_u64()), true)
+        }
+        "dur_neg_max" => (Datum::Dur(dur("-838:59:59")), true),
+        "dur_fsp3" => (Datum::Dur(dur("12:34:56.789")), true),
+        "json_doc" => (Datum::Json(JSON_DOC.parse().unwrap()), true),
+        "null" => (Datum::Null, true),
+        _ => panic!("unknown case {}", case),
+    }
+}
+
+fn encode(d: &Datum, key: bool) -> Vec<u8> {
+    let datums = [d.clone()];
+    if key {
+        encode_key(&datums).unwrap()
+    } else {
+        encode_value(&datums).unwrap()
+    }
+}
+
+#[test]
+fn test_datum_golden() {
+    let cases = read_golden("datum.golden");
+    assert!(!cases.is_empty());
+    for case in cases {
+        let (name, kind, golden) = (&case[0], &case[1], case[2].from_hex().unwrap());
+        let key = match kind.as_str() {
+            "key" => true,
+            "value" => false,
+            _ => panic!("unknown kind {} of {}", kind, name),
+        };
+        let (expected, canonical) = expected_datum(name);
+
+        let mut buf = golden.as_slice();
+        let decoded = buf.decode_datum().unwrap();
+        assert!(buf.is_empty(), "{}: {} bytes left", name, buf.len());
+        assert_eq!(decoded, expected, "{}", name);
+
+        // the decoded datum keeps what's needed to reproduce the bytes.
+        assert_eq!(encode(&decoded, key), golden, "{}", name);
+        let ours = encode(&expected, key);
+        if canonical {
+            assert_eq!(ours, golden, "{}", name);
+        } else {
+            assert_eq!(ours.as_slice().decode_datum().unwrap(), expected, "{}", name);
+        }
+    }
+}
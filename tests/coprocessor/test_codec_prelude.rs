@@ -0,0 +1,89 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// This test plays the role of a consumer outside of the coprocessor, so it
+// must only use the items of the codec prelude.
+use tikv::coprocessor::codec::prelude::*;
+
+#[test]
+fn test_datum_codec() {
+    let dur = Duration::parse(b"10:11:12.123", 3).unwrap();
+    let dec: Decimal = "3.1415".parse().unwrap();
+    let json: Json = r#"{"a": [1, "2"]}"#.parse().unwrap();
+    let datums = vec![
+        Datum::I64(-1),
+        Datum::U64(1),
+        Datum::F64(1.5),
+        Datum::Bytes(b"abc".to_vec()),
+        Datum::Null,
+        Datum::Dur(dur),
+        Datum::Dec(dec),
+        Datum::Json(json),
+    ];
+    let value = encode_value(&datums).unwrap();
+    assert_eq!(value.as_slice().decode().unwrap(), datums);
+
+    let key = encode_key(&datums[..4]).unwrap();
+    let mut buf = key.as_slice();
+    for d in &datums[..4] {
+        assert_eq!(&buf.decode_datum().unwrap(), d);
+    }
+    assert!(buf.is_empty());
+
+    let mut buf = vec![];
+    buf.encode(&[Datum::I64(1)], false).unwrap();
+    assert_eq!(buf, encode_value(&[Datum::I64(1)]).unwrap());
+}
+
+#[test]
+fn test_chunk() {
+    let tps = [types::LONG_LONG as i32, types::VARCHAR as i32];
+    let mut chunk = Chunk::new_chunk(&tps);
+    for i in 0..3 {
+        chunk.append_i64(0, i).unwrap();
+        if i == 1 {
+            chunk.append_null(1);
+        } else {
+            chunk.append_bytes(1, format!("{}", i).as_bytes()).unwrap();
+        }
+    }
+    assert_eq!(chunk.num_rows(), 3);
+
+    let mut buf = vec![];
+    let mut encoder = ChunkEncoder::new(&chunk, &tps).unwrap();
+    assert_eq!(encoder.encode_next(10, &mut buf).unwrap(), 3);
+    assert!(!buf.is_empty());
+
+    let chunk = ArcChunk::new(chunk);
+    let mut row: Row = chunk.begin();
+    let end = chunk.end();
+    while row.idx() != end.idx() {
+        assert_eq!(row.len(), 2);
+        assert_eq!(row.get_i64(0), row.idx() as i64);
+        if row.idx() == 1 {
+            assert!(row.is_null(1));
+        } else {
+            assert_eq!(row.get_bytes(1), format!("{}", row.idx()).as_bytes());
+        }
+        row = row.next();
+    }
+    assert_eq!(row.idx(), 3);
+}
+
+#[test]
+fn test_error() {
+    let res: Result<Duration> = Duration::parse(b"not a time", 0);
+    let err: Error = res.unwrap_err();
+    assert!(!format!("{}", err).is_empty());
+    assert!(Time::parse_utc_datetime("2017-01-01 00:00:00", 0).is_ok());
+}
@@ -15,7 +15,7 @@ use std::collections::{BTreeMap, HashMap};
 use std::mem;
 use std::sync::mpsc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::i64;
+use std::{i64, u32, u64};
 use std::thread;
 use std::time::Duration;
 
@@ -39,7 +39,8 @@ use protobuf::{Message, RepeatedField};
 use raftstore::util::MAX_LEADER_LEASE;
 use storage::sync_storage::SyncStorage;
 use storage::util::new_raft_engine;
-use tikv::coprocessor::select::xeval::evaluator::FLAG_IGNORE_TRUNCATE;
+use tikv::coprocessor::select::xeval::evaluator::{DEFAULT_MAX_WARNING_CNT, FLAG_IGNORE_TRUNCATE,
+                                                   FLAG_TRUNCATE_AS_WARNING};
 
 static ID_GENERATOR: AtomicUsize = AtomicUsize::new(1);
 
@@ -729,6 +730,9 @@ struct DAGSelect {
     group_by: Vec<Expr>,
     key_range: KeyRange,
     output_offsets: Option<Vec<u32>>,
+    collect_range_counts: bool,
+    collect_execution_summaries: bool,
+    max_warning_count: u64,
 }
 
 impl DAGSelect {
@@ -759,6 +763,9 @@ impl DAGSelect {
             group_by: vec![],
             key_range: range,
             output_offsets: None,
+            collect_range_counts: false,
+            collect_execution_summaries: false,
+            max_warning_count: DEFAULT_MAX_WARNING_CNT as u64,
         }
     }
 
@@ -785,6 +792,9 @@ impl DAGSelect {
             group_by: vec![],
             key_range: range,
             output_offsets: None,
+            collect_range_counts: false,
+            collect_execution_summaries: false,
+            max_warning_count: DEFAULT_MAX_WARNING_CNT as u64,
         }
     }
 
@@ -860,6 +870,21 @@ impl DAGSelect {
         self
     }
 
+    fn collect_range_counts(mut self) -> DAGSelect {
+        self.collect_range_counts = true;
+        self
+    }
+
+    fn collect_execution_summaries(mut self) -> DAGSelect {
+        self.collect_execution_summaries = true;
+        self
+    }
+
+    fn max_warning_count(mut self, max_warning_count: u64) -> DAGSelect {
+        self.max_warning_count = max_warning_count;
+        self
+    }
+
     fn where_expr(mut self, expr: Expr) -> DAGSelect {
         let mut exec = Executor::new();
         exec.set_tp(ExecType::TypeSelection);
@@ -915,6 +940,9 @@ impl DAGSelect {
         dag.set_executors(RepeatedField::from_vec(self.execs));
         dag.set_start_ts(next_id() as u64);
         dag.set_flags(flags.iter().fold(0, |acc, f| acc | *f));
+        dag.set_collect_range_counts(self.collect_range_counts);
+        dag.set_collect_execution_summaries(self.collect_execution_summaries);
+        dag.set_max_warning_count(self.max_warning_count);
 
         let output_offsets = if self.output_offsets.is_some() {
             self.output_offsets.take().unwrap()
@@ -1057,6 +1085,90 @@ fn test_group_by() {
     end_point.stop().unwrap().join().unwrap();
 }
 
+#[test]
+fn test_string_bytes_kept() {
+    // the strings are stored and returned as they are, the trailing spaces
+    // and the embedded NULs are never trimmed. All the collations are binary
+    // in this version, so such strings are distinct and ordered by bytes.
+    let data = vec![
+        (1, Some("a"), 1),
+        (2, Some("a "), 2),
+        (3, Some(" a"), 3),
+        (4, Some("a\0"), 4),
+        (5, Some("a"), 5),
+        (6, Some("a\0 "), 6),
+        (7, Some("a  "), 7),
+        (8, Some("a "), 100),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+    let cols = product.table.get_table_columns();
+    // `count < 100`, which filters out the last row.
+    let cond = || {
+        let mut col = Expr::new();
+        col.set_tp(ExprType::ColumnRef);
+        let count_offset = offset_for_column(&cols, product.count.id);
+        col.mut_val().encode_i64(count_offset).unwrap();
+
+        let mut value = Expr::new();
+        value.set_tp(ExprType::Int64);
+        value.mut_val().encode_i64(100).unwrap();
+
+        let mut cond = Expr::new();
+        cond.set_tp(ExprType::ScalarFunc);
+        cond.set_sig(ScalarFuncSig::LTInt);
+        cond.mut_children().push(col);
+        cond.mut_children().push(value);
+        cond
+    };
+
+    let req = DAGSelect::from(&product.table)
+        .where_expr(cond())
+        .count()
+        .group_by(&[product.name])
+        .build();
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 2);
+    let rows: Vec<_> = spliter.collect();
+    let exp: Vec<(&[u8], u64)> = vec![
+        (&b"a"[..], 2),
+        (&b"a "[..], 1),
+        (&b" a"[..], 1),
+        (&b"a\0"[..], 1),
+        (&b"a\0 "[..], 1),
+        (&b"a  "[..], 1),
+    ];
+    assert_eq!(rows.len(), exp.len());
+    for (row, (name, cnt)) in rows.into_iter().zip(exp) {
+        assert_eq!(row, vec![Datum::U64(cnt), Datum::Bytes(name.to_vec())]);
+    }
+
+    let req = DAGSelect::from(&product.table)
+        .where_expr(cond())
+        .order_by(product.name, false)
+        .limit(10)
+        .build();
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 3);
+    let rows: Vec<_> = spliter.collect();
+    let exp = vec![" a", "a", "a", "a\0", "a\0 ", "a ", "a  "];
+    assert_eq!(rows.len(), exp.len());
+    for (row, name) in rows.into_iter().zip(exp) {
+        assert_eq!(row[1], Datum::Bytes(name.as_bytes().to_vec()));
+        // the other columns of the row are returned along with the string.
+        let id = match row[0] {
+            Datum::I64(id) => id,
+            ref d => panic!("unexpected id {:?}", d),
+        };
+        let &(_, expected_name, cnt) = data.iter().find(|&&(i, _, _)| i == id).unwrap();
+        assert_eq!(expected_name, Some(name));
+        assert_eq!(row[2], Datum::I64(cnt));
+    }
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
 #[test]
 fn test_aggr_count() {
     let data = vec![
@@ -2346,6 +2458,210 @@ fn test_where_for_dag() {
     end_point.stop().unwrap().join().unwrap();
 }
 
+#[test]
+fn test_output_counts() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+        (4, Some("name:3"), 1),
+        (5, Some("name:1"), 4),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+    let cols = product.table.get_table_columns();
+    // count < 2
+    let cond = {
+        let mut col = Expr::new();
+        col.set_tp(ExprType::ColumnRef);
+        let count_offset = offset_for_column(&cols, product.count.id);
+        col.mut_val().encode_i64(count_offset).unwrap();
+        let mut value = Expr::new();
+        value.set_tp(ExprType::Int64);
+        value.mut_val().encode_i64(2).unwrap();
+        let mut cond = Expr::new();
+        cond.set_tp(ExprType::ScalarFunc);
+        cond.set_sig(ScalarFuncSig::LTInt);
+        cond.mut_children().push(col);
+        cond.mut_children().push(value);
+        cond
+    };
+
+    let req = DAGSelect::from(&product.table)
+        .where_expr(cond.clone())
+        .collect_range_counts()
+        .build();
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 3);
+    assert_eq!(spliter.count(), 1);
+    // the rows scanned in the range are counted, not the rows matched.
+    assert_eq!(resp.get_output_counts(), &[data.len() as i64]);
+
+    // nothing is counted unless the request asks for it.
+    let req = DAGSelect::from(&product.table).where_expr(cond).build();
+    let resp = handle_select(&end_point, req);
+    assert!(resp.get_output_counts().is_empty());
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn test_execution_summaries() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+        (4, Some("name:3"), 1),
+        (5, Some("name:1"), 4),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+    let req = DAGSelect::from(&product.table)
+        .limit(2)
+        .collect_execution_summaries()
+        .build();
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 3);
+    assert_eq!(spliter.count(), 2);
+    // the summaries are in the order of the executors: scan, limit.
+    let summaries = resp.get_execution_summaries();
+    assert_eq!(summaries.len(), 2);
+    let rows: Vec<_> = summaries.iter().map(|s| s.get_num_produced_rows()).collect();
+    assert_eq!(rows, vec![2, 2]);
+    // the limit stops calling the scan once it's reached.
+    let iterations: Vec<_> = summaries.iter().map(|s| s.get_num_iterations()).collect();
+    assert_eq!(iterations, vec![2, 3]);
+    assert!(summaries[1].get_time_processed_ns() >= summaries[0].get_time_processed_ns());
+
+    // nothing is collected unless the request asks for it.
+    let req = DAGSelect::from(&product.table).limit(2).build();
+    let resp = handle_select(&end_point, req);
+    assert!(resp.get_execution_summaries().is_empty());
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn test_truncate_warnings() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+        (4, Some("name:3"), 1),
+        (5, Some("name:1"), 4),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+    let cols = product.table.get_table_columns();
+    // cast(name as signed) > 0, every name is truncated to 0.
+    let cond = {
+        let mut col = Expr::new();
+        col.set_tp(ExprType::ColumnRef);
+        let name_offset = offset_for_column(&cols, product.name.id);
+        col.mut_val().encode_i64(name_offset).unwrap();
+        let mut cast = Expr::new();
+        cast.set_tp(ExprType::ScalarFunc);
+        cast.set_sig(ScalarFuncSig::CastStringAsInt);
+        cast.mut_children().push(col);
+
+        let mut value = Expr::new();
+        value.set_tp(ExprType::Int64);
+        value.mut_val().encode_i64(0).unwrap();
+        let mut cond = Expr::new();
+        cond.set_tp(ExprType::ScalarFunc);
+        cond.set_sig(ScalarFuncSig::GTInt);
+        cond.mut_children().push(cast);
+        cond.mut_children().push(value);
+        cond
+    };
+
+    let req = DAGSelect::from(&product.table)
+        .where_expr(cond.clone())
+        .build_with(&[FLAG_TRUNCATE_AS_WARNING]);
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 3);
+    assert_eq!(spliter.count(), 0);
+    assert_eq!(resp.get_warning_count(), data.len() as i64);
+    assert_eq!(resp.get_warnings().len(), data.len());
+    for warning in resp.get_warnings() {
+        assert_eq!(warning.get_code(), 1265);
+    }
+
+    // the warnings beyond the max count are only counted.
+    let req = DAGSelect::from(&product.table)
+        .where_expr(cond.clone())
+        .max_warning_count(1)
+        .build_with(&[FLAG_TRUNCATE_AS_WARNING]);
+    let resp = handle_select(&end_point, req);
+    assert_eq!(resp.get_warning_count(), data.len() as i64);
+    assert_eq!(resp.get_warnings().len(), 1);
+
+    // the ignored truncations aren't warnings.
+    let req = DAGSelect::from(&product.table)
+        .where_expr(cond)
+        .build_with(&[FLAG_IGNORE_TRUNCATE]);
+    let resp = handle_select(&end_point, req);
+    assert_eq!(resp.get_warning_count(), 0);
+    assert!(resp.get_warnings().is_empty());
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn test_streaming() {
+    let batch_row_limit = Config::default().end_point_stream_batch_row_limit;
+    let data: Vec<_> = (0..batch_row_limit as i64 * 2 + 3)
+        .map(|i| (i, Some("name"), i))
+        .collect();
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+    let req = DAGSelect::from(&product.table).build();
+    let (tx, rx) = mpsc::channel();
+    let req = RequestTask::new_streaming(req, box move |r| tx.send(r).is_ok());
+    end_point.schedule(EndPointTask::Request(req)).unwrap();
+    // the callback is dropped with the task once the last response is sent.
+    let resps: Vec<Response> = rx.iter().collect();
+    let counts: Vec<_> = resps
+        .into_iter()
+        .map(|resp| {
+            assert!(!resp.has_region_error() && resp.get_other_error().is_empty());
+            let mut sel_resp = SelectResponse::new();
+            sel_resp.merge_from_bytes(resp.get_data()).unwrap();
+            DAGChunkSpliter::new(sel_resp.take_chunks().into_vec(), 3).count()
+        })
+        .collect();
+    assert_eq!(counts, vec![batch_row_limit, batch_row_limit, 3]);
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn test_streaming_closed() {
+    let batch_row_limit = Config::default().end_point_stream_batch_row_limit;
+    let data: Vec<_> = (0..batch_row_limit as i64 * 2 + 3)
+        .map(|i| (i, Some("name"), i))
+        .collect();
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+    let req = DAGSelect::from(&product.table).build();
+    let (tx, rx) = mpsc::channel();
+    // the stream is closed right after the first response.
+    let req = RequestTask::new_streaming(req, box move |r| {
+        tx.send(r).unwrap();
+        false
+    });
+    end_point.schedule(EndPointTask::Request(req)).unwrap();
+    let resps: Vec<Response> = rx.iter().collect();
+    // the request stops once the stream is closed, the error is responded last.
+    assert_eq!(resps.len(), 2);
+    assert!(resps[0].get_other_error().is_empty());
+    assert_eq!(resps[1].get_other_error(), "request canceled.");
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
 #[test]
 fn test_handle_truncate() {
     let data = vec![
@@ -2625,6 +2941,131 @@ fn test_output_offsets() {
     end_point.stop().unwrap().join().unwrap();
 }
 
+#[test]
+fn test_duplicate_output_offsets() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+        (4, Some("name:3"), 1),
+        (5, Some("name:1"), 4),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+
+    let req = DAGSelect::from(&product.table)
+        .output_offsets(Some(vec![2, 0, 2, 2]))
+        .build();
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 4);
+    let mut row_count = 0;
+    for (row, (id, _, cnt)) in spliter.zip(data) {
+        let cnt_datum = Datum::I64(cnt);
+        let expected_encoded = datum::encode_value(&[
+            cnt_datum.clone(),
+            Datum::I64(id),
+            cnt_datum.clone(),
+            cnt_datum,
+        ]).unwrap();
+        let result_encoded = datum::encode_value(&row).unwrap();
+        assert_eq!(&*result_encoded, &*expected_encoded);
+        row_count += 1;
+    }
+    assert_eq!(row_count, 4);
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn test_build_error() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+
+    // no executor
+    let mut req = DAGSelect::from(&product.table).build();
+    let mut dag = DAGRequest::new();
+    dag.set_start_ts(next_id() as u64);
+    req.set_data(dag.write_to_bytes().unwrap());
+    let resp = handle_request(&end_point, req);
+    assert!(
+        resp.get_other_error().starts_with("[invalid request]"),
+        "{:?}",
+        resp
+    );
+
+    // the first executor is not a scan
+    let mut req = DAGSelect::from(&product.table).build();
+    let mut dag = DAGRequest::new();
+    let mut exec = Executor::new();
+    exec.set_tp(ExecType::TypeLimit);
+    dag.mut_executors().push(exec);
+    dag.set_start_ts(next_id() as u64);
+    req.set_data(dag.write_to_bytes().unwrap());
+    let resp = handle_request(&end_point, req);
+    assert!(
+        resp.get_other_error().starts_with("[invalid request]"),
+        "{:?}",
+        resp
+    );
+
+    // unsupported expression
+    let mut cond = Expr::new();
+    cond.set_tp(ExprType::Count);
+    let req = DAGSelect::from(&product.table).where_expr(cond).build();
+    let resp = handle_request(&end_point, req);
+    assert!(
+        resp.get_other_error().starts_with("[unsupported]"),
+        "{:?}",
+        resp
+    );
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn test_overflow_boundaries() {
+    let data = vec![
+        (1, Some("name:0"), 2),
+        (2, Some("name:4"), 3),
+    ];
+
+    let product = ProductTable::new();
+    let (_, mut end_point) = init_with_data(&product, &data);
+
+    // the largest limit returns all rows.
+    let req = DAGSelect::from(&product.table).limit(u64::MAX).build();
+    let mut resp = handle_select(&end_point, req);
+    let spliter = DAGChunkSpliter::new(resp.take_chunks().into_vec(), 3);
+    assert_eq!(spliter.count(), data.len());
+
+    // output offset out of range
+    let req = DAGSelect::from(&product.table)
+        .output_offsets(Some(vec![0, u32::MAX]))
+        .build();
+    let resp = handle_request(&end_point, req);
+    assert!(
+        resp.get_other_error().starts_with("[invalid request]"),
+        "{:?}",
+        resp
+    );
+
+    // negative limit
+    let req = Select::from(&product.table).limit(-1).build();
+    let resp = handle_request(&end_point, req);
+    assert!(
+        resp.get_other_error().starts_with("[invalid request]"),
+        "{:?}",
+        resp
+    );
+
+    end_point.stop().unwrap().join().unwrap();
+}
+
 #[test]
 fn test_key_is_locked_for_primary() {
     let data = vec![